@@ -128,6 +128,8 @@ pub async fn demonstrate_config_hot_reload() -> Result<()> {
         recursive: true,
         extensions: None,
         exclude_patterns: Some(vec!["*.log".to_string()]),
+        display_name: None,
+        custom_mime_types: None,
     });
 
     // Save directly to file to simulate external modification
@@ -149,8 +151,6 @@ pub async fn demonstrate_config_hot_reload() -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::sync::Arc;
-    use tokio::sync::RwLock;
 
     #[tokio::test]
     async fn test_example_service() -> Result<()> {
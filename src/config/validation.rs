@@ -1,11 +1,14 @@
 use anyhow::{anyhow, Context, Result};
 use std::{
     net::{IpAddr, SocketAddr},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
-use super::{AppConfig, MonitoredDirectoryConfig, NetworkInterfaceConfig};
+use super::{AppConfig, AuthMode, DatabaseBackend, MonitoredDirectoryConfig, NetworkInterfaceConfig};
 use crate::platform::config::PlatformConfig;
+use crate::platform::network::parse_client_subnet;
+use crate::web::icon::DeviceIcon;
+use crate::web::throttle::MIN_STREAM_BANDWIDTH_KBPS;
 
 /// Configuration validator for ensuring configuration integrity
 pub struct ConfigValidator;
@@ -44,6 +47,83 @@ impl ConfigValidator {
             return Err(anyhow!("Invalid UUID format: {}", config.server.uuid));
         }
 
+        // At least one media class must be advertised
+        if config.server.advertised_content.is_empty() {
+            return Err(anyhow!("At least one media class must be advertised"));
+        }
+
+        // Validate device description fields
+        let description_fields = [
+            ("manufacturer", &config.server.manufacturer),
+            ("manufacturer_url", &config.server.manufacturer_url),
+            ("model_name", &config.server.model_name),
+            ("model_number", &config.server.model_number),
+            ("model_url", &config.server.model_url),
+            ("serial_number", &config.server.serial_number),
+        ];
+        for (field, value) in description_fields {
+            if value.trim().is_empty() {
+                return Err(anyhow!("Server {} cannot be empty", field));
+            }
+        }
+        for (field, value) in [
+            ("manufacturer_url", &config.server.manufacturer_url),
+            ("model_url", &config.server.model_url),
+        ] {
+            reqwest::Url::parse(value)
+                .with_context(|| format!("Invalid server {}: {}", field, value))?;
+        }
+
+        // API token labels identify callers in the audit log, so they must be unique
+        let mut labels = std::collections::HashSet::new();
+        for token in &config.server.api_tokens {
+            if token.label.trim().is_empty() {
+                return Err(anyhow!("API token label cannot be empty"));
+            }
+            if !labels.insert(token.label.as_str()) {
+                return Err(anyhow!("Duplicate API token label: {}", token.label));
+            }
+            if token.token_sha256.len() != 64 || !token.token_sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(anyhow!("API token '{}' must be a SHA-256 hex digest", token.label));
+            }
+        }
+        if config.server.api_token.as_deref().is_some_and(|t| t.trim().is_empty()) {
+            return Err(anyhow!("API token cannot be empty"));
+        }
+
+        if let Some(icon_path) = &config.server.icon_path {
+            DeviceIcon::load(Path::new(icon_path))
+                .with_context(|| format!("Invalid server icon: {}", icon_path))?;
+        }
+
+        let bandwidth = config.server.max_stream_bandwidth_kbps;
+        if bandwidth > 0 && bandwidth < MIN_STREAM_BANDWIDTH_KBPS {
+            return Err(anyhow!(
+                "Stream bandwidth cap of {} kbps is below the minimum of {} kbps",
+                bandwidth,
+                MIN_STREAM_BANDWIDTH_KBPS
+            ));
+        }
+
+        let auth = &config.server.auth;
+        let is_blank = |value: &Option<String>| value.as_deref().is_none_or(|v| v.is_empty());
+        match auth.mode {
+            AuthMode::Disabled => {}
+            AuthMode::Basic => {
+                if is_blank(&auth.username) || is_blank(&auth.password) {
+                    return Err(anyhow!("Basic authentication requires a username and password"));
+                }
+                if auth.username.as_deref().is_some_and(|u| u.contains(':')) {
+                    return Err(anyhow!("Basic authentication username cannot contain ':'"));
+                }
+            }
+            AuthMode::Token => {
+                if is_blank(&auth.token) {
+                    return Err(anyhow!("Token authentication requires a token"));
+                }
+            }
+        }
+
         Ok(())
     }
 
@@ -69,6 +149,42 @@ impl ConfigValidator {
             return Err(anyhow!("Announce interval must be greater than 0 seconds"));
         }
 
+        // Validate announce jitter
+        if config.network.announce_jitter_percent > 50 {
+            return Err(anyhow!("Announce jitter cannot exceed 50 percent"));
+        }
+
+        // Validate advertised max-age, which must outlive at least two announcements
+        if let Some(max_age) = config.network.ssdp_max_age_seconds {
+            if max_age < config.network.announce_interval_seconds.saturating_mul(2) {
+                return Err(anyhow!(
+                    "SSDP max-age ({}s) must be at least twice the announce interval ({}s)",
+                    max_age,
+                    config.network.announce_interval_seconds
+                ));
+            }
+        }
+
+        // Validate startup announcement burst
+        if config.network.initial_announce_count > 10 {
+            return Err(anyhow!("Initial announce count cannot exceed 10"));
+        }
+
+        // Validate SSDP multicast groups
+        if config.network.multicast_groups.is_empty() {
+            return Err(anyhow!("At least one SSDP multicast group must be configured"));
+        }
+        if let Some(group) = config.network.multicast_groups.iter().find(|group| !group.is_multicast()) {
+            return Err(anyhow!("SSDP multicast group {} is not a multicast address", group));
+        }
+
+        // Validate client subnet filters
+        for subnet in config.network.allowed_client_subnets.iter().chain(&config.network.blocked_client_subnets) {
+            if parse_client_subnet(subnet).is_err() {
+                return Err(anyhow!("Invalid client subnet '{}': expected CIDR notation such as 192.168.1.0/24", subnet));
+            }
+        }
+
         // Validate interface selection
         match &config.network.interface_selection {
             NetworkInterfaceConfig::Specific(interface) => {
@@ -96,6 +212,15 @@ impl ConfigValidator {
             Self::validate_monitored_directory(dir, index)?;
         }
 
+        // Loading drops these; warn about configurations assembled some other way
+        for (redundant, covering) in Self::redundant_directories(&config.media.directories) {
+            tracing::warn!(
+                "Monitored directory {} is already scanned through {}; its files would be listed twice",
+                config.media.directories[redundant].path,
+                config.media.directories[covering].path
+            );
+        }
+
         // Validate supported extensions
         if config.media.supported_extensions.is_empty() {
             return Err(anyhow!("At least one supported file extension must be configured"));
@@ -109,6 +234,56 @@ impl ConfigValidator {
             return Err(anyhow!("Duplicate file extensions found in supported_extensions"));
         }
 
+        if config.media.scan_concurrency == 0 {
+            return Err(anyhow!("media.scan_concurrency must be at least 1"));
+        }
+
+        if config.media.transcoding.max_concurrent == 0 {
+            return Err(anyhow!("media.transcoding.max_concurrent must be at least 1"));
+        }
+
+        if let Some(schedule) = &config.media.scan_schedule {
+            crate::media::parse_scan_schedule(schedule)?;
+        }
+
+        // A week is far beyond any useful safety-net interval
+        if config.media.rescan_interval_minutes > 7 * 24 * 60 {
+            return Err(anyhow!("media.rescan_interval_minutes cannot exceed 10080 (one week)"));
+        }
+
+        let directory_mime_types = config.media.directories.iter().flat_map(|dir| dir.custom_mime_types.iter().flatten());
+        for (extension, mime_type) in config.media.custom_mime_types.iter().flatten().chain(directory_mime_types) {
+            if extension.trim_start_matches('.').is_empty() || extension.contains(['/', '\\']) {
+                return Err(anyhow!("Invalid extension in custom_mime_types: '{}'", extension));
+            }
+            if !is_valid_mime_type(mime_type) {
+                return Err(anyhow!(
+                    "Invalid MIME type '{}' for extension '{}': expected type/subtype",
+                    mime_type,
+                    extension
+                ));
+            }
+        }
+
+        for extension in &config.media.sniff_extensions {
+            if extension.trim_start_matches('.').is_empty() || extension.contains(['/', '\\']) {
+                return Err(anyhow!("Invalid extension in sniff_extensions: '{}'", extension));
+            }
+        }
+
+        for (mime_type, profile) in config.media.dlna_profiles.iter().flatten() {
+            if !is_valid_mime_type(mime_type) {
+                return Err(anyhow!("Invalid MIME type in dlna_profiles: '{}'", mime_type));
+            }
+            if !profile.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(anyhow!(
+                    "Invalid DLNA profile '{}' for '{}': expected letters, digits and underscores",
+                    profile,
+                    mime_type
+                ));
+            }
+        }
+
         Ok(())
     }
 
@@ -168,8 +343,64 @@ impl ConfigValidator {
         Ok(())
     }
 
+    /// Monitored directories whose files another entry already scans, each paired
+    /// with the entry covering it: directories inside a recursive one, and repeats
+    /// of the same directory (the recursive or first entry is kept). Paths are
+    /// compared after canonicalization, ignoring case where the platform does.
+    pub fn redundant_directories(directories: &[MonitoredDirectoryConfig]) -> Vec<(usize, usize)> {
+        Self::redundant_directories_with_case(directories, PlatformConfig::for_current_platform().is_case_sensitive())
+    }
+
+    fn redundant_directories_with_case(directories: &[MonitoredDirectoryConfig], case_sensitive: bool) -> Vec<(usize, usize)> {
+        let paths: Vec<PathBuf> = directories
+            .iter()
+            .map(|dir| {
+                let path = Path::new(&dir.path);
+                let canonical = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+                if case_sensitive {
+                    canonical
+                } else {
+                    PathBuf::from(canonical.to_string_lossy().to_lowercase())
+                }
+            })
+            .collect();
+
+        let covers = |outer: usize, inner: usize| {
+            if paths[outer] == paths[inner] {
+                // Keep the recursive entry, or the first of equals
+                match (directories[outer].recursive, directories[inner].recursive) {
+                    (true, false) => true,
+                    (false, true) => false,
+                    _ => outer < inner,
+                }
+            } else {
+                directories[outer].recursive && paths[inner].starts_with(&paths[outer])
+            }
+        };
+        (0..directories.len())
+            .filter_map(|inner| {
+                (0..directories.len())
+                    .find(|&outer| outer != inner && covers(outer, inner))
+                    .map(|outer| (inner, outer))
+            })
+            .collect()
+    }
+
     /// Validate database configuration
     fn validate_database_config(config: &AppConfig) -> Result<()> {
+        if config.database.backend == DatabaseBackend::Postgres {
+            if !cfg!(feature = "postgres") {
+                return Err(anyhow!("Database backend 'postgres' requires a build with the 'postgres' feature"));
+            }
+            match config.database.url.as_deref().map(str::trim) {
+                None | Some("") => return Err(anyhow!("Database backend 'postgres' requires database.url")),
+                Some(url) if !url.starts_with("postgres://") && !url.starts_with("postgresql://") => {
+                    return Err(anyhow!("Database url must start with postgres:// or postgresql://"));
+                }
+                Some(_) => {}
+            }
+        }
+
         // Validate database path if specified
         if let Some(db_path) = &config.database.path {
             if db_path.trim().is_empty() {
@@ -188,6 +419,28 @@ impl ConfigValidator {
             }
         }
 
+        let backup = &config.database.backup;
+        if backup.max_backups < 1 {
+            return Err(anyhow!("Database backup max_backups must be at least 1"));
+        }
+        if backup.directory.as_deref().is_some_and(|d| d.trim().is_empty()) {
+            return Err(anyhow!("Database backup directory cannot be empty if specified"));
+        }
+        if backup.interval_hours > 24 * 365 {
+            return Err(anyhow!("Database backup interval_hours cannot exceed 8760 (one year)"));
+        }
+        // Old backups are pruned from the backup directory, which must not hold the live database
+        let backup_dir = config.get_backup_directory();
+        if let Some(database_dir) = config.get_database_path().parent() {
+            let canonical = |path: &std::path::Path| std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+            if canonical(&backup_dir) == canonical(database_dir) {
+                return Err(anyhow!(
+                    "Database backup directory {} cannot be the database's own directory",
+                    backup_dir.display()
+                ));
+            }
+        }
+
         Ok(())
     }
 
@@ -206,7 +459,7 @@ impl ConfigValidator {
         let db_path = config.get_database_path();
         if let Some(parent) = db_path.parent() {
             // Only validate the parent directory, not the database file itself
-            platform_config.validate_path(&parent.to_path_buf())
+            platform_config.validate_path(parent)
                 .with_context(|| format!("Database directory failed platform validation: {}", parent.display()))?;
         }
         
@@ -298,6 +551,14 @@ impl ConfigValidator {
     }
 }
 
+/// Whether `value` looks like `type/subtype`, e.g. `video/mp2t` or `image/svg+xml`
+fn is_valid_mime_type(value: &str) -> bool {
+    let token = |part: &str| {
+        !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || "!#$&-^_.+".contains(c))
+    };
+    matches!(value.split_once('/'), Some((kind, subtype)) if token(kind) && token(subtype))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -318,6 +579,8 @@ mod tests {
                 recursive: true,
                 extensions: None,
                 exclude_patterns: None,
+                display_name: None,
+                custom_mime_types: None,
             }
         ];
         
@@ -341,6 +604,76 @@ mod tests {
         config.server.name = "Test Server".to_string();
         config.server.uuid = "invalid-uuid".to_string();
         assert!(ConfigValidator::validate(&config).is_err());
+
+        // Caps too low to pace even one slice a second are rejected; 0 means unthrottled
+        config.server.uuid = uuid::Uuid::new_v4().to_string();
+        config.server.max_stream_bandwidth_kbps = 8;
+        assert!(ConfigValidator::validate(&config).is_err());
+        config.server.max_stream_bandwidth_kbps = MIN_STREAM_BANDWIDTH_KBPS;
+        assert!(ConfigValidator::validate(&config).is_ok());
+        config.server.max_stream_bandwidth_kbps = 0;
+        assert!(ConfigValidator::validate(&config).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_device_description_fields() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = AppConfig::default_for_platform();
+        config.media.directories = vec![
+            super::MonitoredDirectoryConfig {
+                path: temp_dir.path().to_string_lossy().to_string(),
+                recursive: true,
+                extensions: None,
+                exclude_patterns: None,
+                display_name: None,
+                custom_mime_types: None,
+            }
+        ];
+        assert!(ConfigValidator::validate(&config).is_ok());
+
+        let mut empty_model = config.clone();
+        empty_model.server.model_name = "  ".to_string();
+        assert!(ConfigValidator::validate(&empty_model).is_err());
+
+        let mut bad_url = config.clone();
+        bad_url.server.manufacturer_url = "not a url".to_string();
+        assert!(ConfigValidator::validate(&bad_url).is_err());
+
+        let token = |label: &str, digest: &str| crate::config::ApiToken {
+            label: label.to_string(),
+            token_sha256: digest.to_string(),
+        };
+        let digest = "a".repeat(64);
+        let mut tokens = config.clone();
+        tokens.server.api_tokens = vec![token("ci", &digest), token("backup", &digest)];
+        assert!(ConfigValidator::validate(&tokens).is_ok());
+        tokens.server.api_tokens.push(token("ci", &digest));
+        assert!(ConfigValidator::validate(&tokens).is_err());
+        tokens.server.api_tokens = vec![token("ci", "not-a-digest")];
+        assert!(ConfigValidator::validate(&tokens).is_err());
+
+        let mut auth = config.clone();
+        auth.server.auth.mode = crate::config::AuthMode::Basic;
+        auth.server.auth.username = Some("admin".to_string());
+        assert!(ConfigValidator::validate(&auth).is_err());
+        auth.server.auth.password = Some("secret".to_string());
+        assert!(ConfigValidator::validate(&auth).is_ok());
+        auth.server.auth.mode = crate::config::AuthMode::Token;
+        assert!(ConfigValidator::validate(&auth).is_err());
+
+        let mut icon = config.clone();
+        icon.server.icon_path = Some(temp_dir.path().join("missing.png").to_string_lossy().to_string());
+        assert!(ConfigValidator::validate(&icon).is_err());
+        let not_an_image = temp_dir.path().join("icon.png");
+        std::fs::write(&not_an_image, "not an image").unwrap();
+        icon.server.icon_path = Some(not_an_image.to_string_lossy().to_string());
+        assert!(ConfigValidator::validate(&icon).is_err());
+        std::fs::write(&not_an_image, include_bytes!("../web/icon.png")).unwrap();
+        assert!(ConfigValidator::validate(&icon).is_ok());
+        let mut oversized = include_bytes!("../web/icon.png").to_vec();
+        oversized.resize(crate::web::icon::MAX_ICON_BYTES + 1, 0);
+        std::fs::write(&not_an_image, oversized).unwrap();
+        assert!(ConfigValidator::validate(&icon).is_err());
     }
 
     #[test]
@@ -353,6 +686,8 @@ mod tests {
                 recursive: true,
                 extensions: None,
                 exclude_patterns: None,
+                display_name: None,
+                custom_mime_types: None,
             }
         ];
         
@@ -368,6 +703,24 @@ mod tests {
         config.network.ssdp_port = 1900;
         config.network.multicast_ttl = 0;
         assert!(ConfigValidator::validate(&config).is_err());
+
+        // Test SSDP multicast groups
+        config.network.multicast_ttl = 4;
+        config.network.multicast_groups = vec![];
+        assert!(ConfigValidator::validate_network_config(&config).is_err());
+        config.network.multicast_groups = vec!["192.168.1.10".parse().unwrap()];
+        assert!(ConfigValidator::validate_network_config(&config).is_err());
+        config.network.multicast_groups = vec!["ff02::c".parse().unwrap()];
+        assert!(ConfigValidator::validate_network_config(&config).is_ok());
+
+        // Test client subnet filters
+        config.network.allowed_client_subnets = vec!["192.168.1.0/24".to_string(), "fd00::/8".to_string()];
+        config.network.blocked_client_subnets = vec!["192.168.1.77".to_string()];
+        assert!(ConfigValidator::validate_network_config(&config).is_ok());
+        config.network.blocked_client_subnets = vec!["192.168.1.0/33".to_string()];
+        assert!(ConfigValidator::validate_network_config(&config).is_err());
+        config.network.blocked_client_subnets = vec!["guest-network".to_string()];
+        assert!(ConfigValidator::validate_network_config(&config).is_err());
     }
 
     #[test]
@@ -385,10 +738,22 @@ mod tests {
                 recursive: true,
                 extensions: None,
                 exclude_patterns: None,
+                display_name: None,
+                custom_mime_types: None,
             }
         ];
         config.media.supported_extensions = vec![];
         assert!(ConfigValidator::validate(&config).is_err());
+
+        // Test a zero scan concurrency
+        let mut config = AppConfig::default_for_platform();
+        config.media.scan_concurrency = 0;
+        assert!(ConfigValidator::validate(&config).is_err());
+
+        // Test a zero transcode limit
+        let mut config = AppConfig::default_for_platform();
+        config.media.transcoding.max_concurrent = 0;
+        assert!(ConfigValidator::validate(&config).is_err());
     }
 
     #[test]
@@ -401,6 +766,8 @@ mod tests {
             recursive: true,
             extensions: Some(vec!["mp4".to_string()]),
             exclude_patterns: Some(vec!["*.tmp".to_string()]),
+            display_name: None,
+            custom_mime_types: None,
         };
         assert!(ConfigValidator::validate_monitored_directory(&valid_dir, 0).is_ok());
         
@@ -410,6 +777,8 @@ mod tests {
             recursive: true,
             extensions: None,
             exclude_patterns: None,
+            display_name: None,
+            custom_mime_types: None,
         };
         assert!(ConfigValidator::validate_monitored_directory(&invalid_dir, 0).is_err());
         
@@ -419,7 +788,112 @@ mod tests {
             recursive: true,
             extensions: None,
             exclude_patterns: None,
+            display_name: None,
+            custom_mime_types: None,
         };
         assert!(ConfigValidator::validate_monitored_directory(&empty_path_dir, 0).is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_nested_monitored_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let media = temp_dir.path().join("media");
+        let movies = media.join("movies");
+        let music = temp_dir.path().join("music");
+        std::fs::create_dir_all(&movies).unwrap();
+        std::fs::create_dir_all(&music).unwrap();
+        let dir = |path: &Path, recursive: bool| super::MonitoredDirectoryConfig {
+            path: path.to_string_lossy().to_string(),
+            recursive,
+            extensions: None,
+            exclude_patterns: None,
+            display_name: None,
+            custom_mime_types: None,
+        };
+
+        // A recursive parent already covers the nested directory, whichever is listed first
+        let nested = [dir(&movies, true), dir(&media, true), dir(&music, true)];
+        assert_eq!(ConfigValidator::redundant_directories(&nested), vec![(0, 1)]);
+        let mut config = AppConfig::default_for_platform();
+        config.media.directories = nested.to_vec();
+        let effective = config.media.effective_directories();
+        assert_eq!(effective.len(), 2);
+        assert!(effective.iter().all(|dir| dir.path != movies.to_string_lossy()));
+        assert_eq!(config.media.directories.len(), 3, "the configured list is kept as written");
+        assert!(ConfigValidator::validate(&config).is_ok());
+
+        // A non-recursive parent only scans its own files
+        assert!(ConfigValidator::redundant_directories(&[dir(&media, false), dir(&movies, true)]).is_empty());
+
+        // The same directory twice, spelled differently, keeps the recursive entry
+        let dotted = media.join("movies").join("..");
+        assert_eq!(ConfigValidator::redundant_directories(&[dir(&media, false), dir(&dotted, true)]), vec![(0, 1)]);
+
+        // Unrelated siblings, including one sharing a name prefix
+        let media_extra = temp_dir.path().join("media-extra");
+        std::fs::create_dir_all(&media_extra).unwrap();
+        assert!(ConfigValidator::redundant_directories(&[dir(&media, true), dir(&music, true), dir(&media_extra, true)]).is_empty());
+
+        // Case only matters on case-sensitive platforms
+        let upper = [dir(Path::new("/Media"), true), dir(Path::new("/media/Movies"), true)];
+        assert!(ConfigValidator::redundant_directories_with_case(&upper, true).is_empty());
+        assert_eq!(ConfigValidator::redundant_directories_with_case(&upper, false), vec![(1, 0)]);
+    }
+
+    #[test]
+    fn test_custom_mime_types_validation() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = AppConfig::default_for_platform();
+        config.media.directories[0].path = temp_dir.path().to_string_lossy().to_string();
+
+        config.media.custom_mime_types = Some([("ts".to_string(), "video/mp2t".to_string())].into());
+        assert!(ConfigValidator::validate_media_config(&config).is_ok());
+
+        for mime in ["video", "video/", "/mp2t", "video/mp2t/extra", "video/mp 2t"] {
+            config.media.custom_mime_types = Some([("ts".to_string(), mime.to_string())].into());
+            assert!(ConfigValidator::validate_media_config(&config).is_err(), "{} accepted", mime);
+        }
+
+        config.media.custom_mime_types = Some([(".".to_string(), "video/mp2t".to_string())].into());
+        assert!(ConfigValidator::validate_media_config(&config).is_err());
+        config.media.custom_mime_types = None;
+
+        // Per-directory mappings are held to the same rules
+        config.media.directories[0].custom_mime_types = Some([("ts".to_string(), "video".to_string())].into());
+        assert!(ConfigValidator::validate_media_config(&config).is_err());
+        config.media.directories[0].custom_mime_types = Some([("ts".to_string(), "video/mp2t".to_string())].into());
+        assert!(ConfigValidator::validate_media_config(&config).is_ok());
+
+        config.media.sniff_extensions = vec![".ts".to_string(), "bin".to_string()];
+        assert!(ConfigValidator::validate_media_config(&config).is_ok());
+        config.media.sniff_extensions = vec!["media/ts".to_string()];
+        assert!(ConfigValidator::validate_media_config(&config).is_err());
+        config.media.sniff_extensions = Vec::new();
+
+        for (mime, profile, valid) in [
+            ("video/x-matroska", "AVC_MKV_HP_HD_AAC_MULT5", true),
+            ("audio/mpeg", "", true),
+            ("matroska", "AVC_MKV_HP_HD_AAC_MULT5", false),
+            ("video/mp4", "AVC_MP4;DLNA.ORG_OP=11", false),
+        ] {
+            config.media.dlna_profiles = Some([(mime.to_string(), profile.to_string())].into());
+            assert_eq!(ConfigValidator::validate_media_config(&config).is_ok(), valid, "{} = {:?}", mime, profile);
+        }
+    }
+
+    #[test]
+    fn test_postgres_backend_validation() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = AppConfig::default_for_platform();
+        config.database.path = Some(temp_dir.path().join("media.db").to_string_lossy().to_string());
+        assert!(ConfigValidator::validate_database_config(&config).is_ok());
+
+        config.database.backend = DatabaseBackend::Postgres;
+        assert!(ConfigValidator::validate_database_config(&config).is_err());
+        config.database.url = Some("mysql://localhost/vuio".to_string());
+        assert!(ConfigValidator::validate_database_config(&config).is_err());
+
+        config.database.url = Some("postgres://vuio@localhost/vuio".to_string());
+        assert_eq!(ConfigValidator::validate_database_config(&config).is_ok(), cfg!(feature = "postgres"));
+    }
+}
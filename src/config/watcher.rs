@@ -1,9 +1,10 @@
 use anyhow::Result;
 use std::{
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::Arc,
+    time::SystemTime,
 };
-use tokio::sync::{broadcast, RwLock};
+use tokio::sync::broadcast;
 use tracing::{info, warn};
 
 use super::{AppConfig, ConfigChangeEvent, ConfigManager};
@@ -195,11 +196,43 @@ impl Default for ConfigChangeRegistry {
     }
 }
 
+/// Remembers the modification time of the configuration file as it was last
+/// loaded, so periodic checks only reload after the file actually changed
+pub struct ConfigFileTracker {
+    path: PathBuf,
+    last_loaded: Option<SystemTime>,
+}
+
+impl ConfigFileTracker {
+    /// Track `path`, treating its current contents as already loaded
+    pub fn new(path: PathBuf) -> Self {
+        let last_loaded = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+        Self { path, last_loaded }
+    }
+
+    /// The tracked configuration file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Modification time of the file if it is newer than the last loaded one
+    pub async fn modified_since_load(&self) -> Option<SystemTime> {
+        let modified = tokio::fs::metadata(&self.path).await.and_then(|m| m.modified()).ok()?;
+        self.last_loaded.is_none_or(|last| modified > last).then_some(modified)
+    }
+
+    /// Record that the file as of `modified` was loaded. Not calling this after
+    /// a failed load makes the next check retry it.
+    pub fn mark_loaded(&mut self, modified: SystemTime) {
+        self.last_loaded = Some(modified);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use tempfile::NamedTempFile;
-    use tokio::time::{sleep, Duration};
+    use tokio::sync::RwLock;
 
     #[tokio::test]
     async fn test_config_watcher_service_creation() -> Result<()> {
@@ -261,7 +294,7 @@ mod tests {
         registry.register_handler(handler);
 
         let config = AppConfig::default_for_platform();
-        let event = ConfigChangeEvent::Reloaded(config);
+        let event = ConfigChangeEvent::Reloaded(Box::new(config));
         
         registry.notify_handlers(&event).await?;
         
@@ -270,4 +303,27 @@ mod tests {
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_config_file_tracker_reloads_only_after_mtime_bump() -> Result<()> {
+        let temp_file = NamedTempFile::new()?;
+        let mut tracker = ConfigFileTracker::new(temp_file.path().to_path_buf());
+
+        // Tick without a change: nothing to reload
+        assert_eq!(tracker.modified_since_load().await, None);
+
+        let bumped = SystemTime::now() + std::time::Duration::from_secs(5);
+        temp_file.as_file().set_modified(bumped)?;
+        assert_eq!(tracker.modified_since_load().await, Some(bumped));
+
+        // A failed reload leaves the mtime unrecorded, so the next tick retries
+        assert_eq!(tracker.modified_since_load().await, Some(bumped));
+        tracker.mark_loaded(bumped);
+        assert_eq!(tracker.modified_since_load().await, None);
+
+        // An older mtime, e.g. a restored backup, is not newer than what was loaded
+        temp_file.as_file().set_modified(bumped - std::time::Duration::from_secs(60))?;
+        assert_eq!(tracker.modified_since_load().await, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+//! Database backups taken on a schedule and at shutdown.
+//!
+//! Each backup is a standalone copy of the database written with `VACUUM INTO`
+//! to the configured backup directory. After every backup the directory is
+//! pruned to the newest `max_backups` backups; other files there are left alone.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tracing::{info, warn};
+
+use super::DatabaseManager;
+
+/// Write a backup named after `kind` (e.g. `scheduled`, `shutdown`) to
+/// `backup_dir`, then remove all but the newest `max_backups` backups
+pub async fn create_backup(
+    database: &dyn DatabaseManager,
+    backup_dir: &Path,
+    kind: &str,
+    max_backups: usize,
+) -> Result<PathBuf> {
+    tokio::fs::create_dir_all(backup_dir)
+        .await
+        .with_context(|| format!("Failed to create backup directory {}", backup_dir.display()))?;
+
+    // Milliseconds keep names unique; VACUUM INTO refuses to overwrite a file
+    let timestamp = chrono::Utc::now().format("%Y%m%d_%H%M%S_%3f");
+    let backup_path = backup_dir.join(format!("vuio_{}_backup_{}.db", kind, timestamp));
+    database
+        .create_backup(&backup_path)
+        .await
+        .with_context(|| format!("Failed to create {} backup", kind))?;
+    info!("Database backup created at: {}", backup_path.display());
+
+    if let Err(e) = cleanup_old_backups(backup_dir, max_backups).await {
+        warn!("Failed to clean up old backups: {}", e);
+    }
+    Ok(backup_path)
+}
+
+/// Whether `name` is one written by [`create_backup`], `vuio_<kind>_backup_<timestamp>.db`
+fn is_backup_file_name(name: &str) -> bool {
+    name.strip_prefix("vuio_")
+        .and_then(|rest| rest.strip_suffix(".db"))
+        .is_some_and(|stem| stem.contains("_backup_"))
+}
+
+/// Remove all but the `max_backups` most recently modified backups in
+/// `backup_dir`, returning how many were removed
+pub async fn cleanup_old_backups(backup_dir: &Path, max_backups: usize) -> Result<usize> {
+    let mut entries = tokio::fs::read_dir(backup_dir).await?;
+    let mut backup_files = Vec::new();
+
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        let is_backup = path.file_name().and_then(|name| name.to_str()).is_some_and(is_backup_file_name);
+        if is_backup && path.is_file() {
+            if let Ok(metadata) = entry.metadata().await {
+                backup_files.push((path, metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH)));
+            }
+        }
+    }
+
+    // Newest first
+    backup_files.sort_by_key(|b| std::cmp::Reverse(b.1));
+
+    let mut removed = 0;
+    for (old_backup, _) in backup_files.iter().skip(max_backups) {
+        if let Err(e) = tokio::fs::remove_file(old_backup).await {
+            warn!("Failed to remove old backup {}: {}", old_backup.display(), e);
+        } else {
+            info!("Removed old backup: {}", old_backup.display());
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::SqliteDatabase;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_cleanup_keeps_newest_backups() {
+        let temp_dir = tempdir().unwrap();
+        let now = SystemTime::now();
+        for (name, age_hours) in [
+            ("vuio_scheduled_backup_a.db", 3),
+            ("vuio_shutdown_backup_b.db", 1),
+            ("vuio_scheduled_backup_c.db", 2),
+            ("vuio_scheduled_backup_d.db", 4),
+            // Databases that are not ours survive however old they are
+            ("media.db", 10),
+            ("vuio_media.db", 10),
+        ] {
+            let path = temp_dir.path().join(name);
+            let file = std::fs::File::create(&path).unwrap();
+            file.set_modified(now - Duration::from_secs(age_hours * 3600)).unwrap();
+        }
+        std::fs::write(temp_dir.path().join("notes.txt"), "kept").unwrap();
+
+        assert_eq!(cleanup_old_backups(temp_dir.path(), 2).await.unwrap(), 2);
+
+        let mut remaining: Vec<String> = std::fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        remaining.sort();
+        assert_eq!(
+            remaining,
+            vec!["media.db", "notes.txt", "vuio_media.db", "vuio_scheduled_backup_c.db", "vuio_shutdown_backup_b.db"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_backup_prunes_to_configured_limit() {
+        let temp_dir = tempdir().unwrap();
+        let db = SqliteDatabase::new(temp_dir.path().join("media.db")).await.unwrap();
+        db.initialize().await.unwrap();
+        let backup_dir = temp_dir.path().join("backups");
+
+        let mut created = Vec::new();
+        for _ in 0..3 {
+            created.push(create_backup(&db, &backup_dir, "scheduled", 2).await.unwrap());
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        assert_eq!(std::fs::read_dir(&backup_dir).unwrap().count(), 2);
+        assert!(!created[0].exists());
+        assert!(created[1].exists() && created[2].exists());
+    }
+}
@@ -0,0 +1,302 @@
+//! Versioned schema migrations for the SQLite database.
+//!
+//! `create_tables` lays down the version 1 schema. Every later change is a
+//! migration that upgrades the schema by one version; pending migrations run
+//! in order when the database is initialized, each in its own transaction
+//! together with the `schema_version` update, so a failed migration leaves
+//! the database at the last version that applied cleanly.
+
+use anyhow::{bail, Context, Result};
+use futures_util::future::{BoxFuture, FutureExt};
+use sqlx::{SqliteConnection, SqlitePool};
+use std::time::SystemTime;
+use tracing::info;
+
+/// A schema change upgrading the database to `version`
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub apply: for<'c> fn(&'c mut SqliteConnection) -> BoxFuture<'c, Result<()>>,
+}
+
+/// All migrations, in version order
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 2,
+        description: "full-text search index",
+        apply: create_text_search_index,
+    },
+    Migration {
+        version: 3,
+        description: "subtitle sidecars",
+        apply: create_subtitles_table,
+    },
+    Migration {
+        version: 4,
+        description: "picture resolution",
+        apply: add_resolution_columns,
+    },
+    Migration {
+        version: 5,
+        description: "playlists",
+        apply: create_playlist_tables,
+    },
+];
+
+/// Version of the schema this build creates
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(1, |m| m.version)
+}
+
+/// Current `schema_version` recorded in `database_metadata`
+pub async fn schema_version(pool: &SqlitePool) -> Result<u32> {
+    let version: Option<String> = sqlx::query_scalar("SELECT value FROM database_metadata WHERE key = 'schema_version'")
+        .fetch_optional(pool)
+        .await?;
+    version
+        .as_deref()
+        .unwrap_or("1")
+        .parse()
+        .with_context(|| format!("invalid schema_version {:?}", version))
+}
+
+/// Apply all pending migrations, returning the resulting schema version
+pub async fn run_migrations(pool: &SqlitePool) -> Result<u32> {
+    apply_migrations(pool, MIGRATIONS).await
+}
+
+async fn apply_migrations(pool: &SqlitePool, migrations: &[Migration]) -> Result<u32> {
+    let mut version = schema_version(pool).await?;
+    let latest = migrations.last().map_or(1, |m| m.version);
+    if version > latest {
+        bail!(
+            "database schema version {} is newer than this version of vuio supports ({})",
+            version,
+            latest
+        );
+    }
+
+    let current = version;
+    for migration in migrations.iter().filter(|m| m.version > current) {
+        info!("Migrating database to schema version {}: {}", migration.version, migration.description);
+        let mut tx = pool.begin().await?;
+        (migration.apply)(&mut tx)
+            .await
+            .with_context(|| format!("migration to schema version {} failed", migration.version))?;
+
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+        sqlx::query(
+            "INSERT INTO database_metadata (key, value, updated_at) VALUES ('schema_version', ?, ?)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+        )
+        .bind(migration.version.to_string())
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+        // Dropping the transaction on an earlier error rolls the migration back
+        tx.commit().await?;
+        version = migration.version;
+    }
+
+    Ok(version)
+}
+
+/// Version 2: FTS5 index over titles, artists, albums and filenames, kept in
+/// sync with `media_files` by triggers and filled from the existing rows
+fn create_text_search_index(conn: &mut SqliteConnection) -> BoxFuture<'_, Result<()>> {
+    async move {
+        for statement in [
+            r#"
+            CREATE VIRTUAL TABLE IF NOT EXISTS media_fts USING fts5(
+                title, artist, album, filename,
+                content = 'media_files', content_rowid = 'id'
+            )
+            "#,
+            r#"
+            CREATE TRIGGER IF NOT EXISTS media_files_fts_insert AFTER INSERT ON media_files BEGIN
+                INSERT INTO media_fts(rowid, title, artist, album, filename)
+                VALUES (new.id, new.title, new.artist, new.album, new.filename);
+            END
+            "#,
+            r#"
+            CREATE TRIGGER IF NOT EXISTS media_files_fts_delete AFTER DELETE ON media_files BEGIN
+                INSERT INTO media_fts(media_fts, rowid, title, artist, album, filename)
+                VALUES ('delete', old.id, old.title, old.artist, old.album, old.filename);
+            END
+            "#,
+            r#"
+            CREATE TRIGGER IF NOT EXISTS media_files_fts_update AFTER UPDATE ON media_files BEGIN
+                INSERT INTO media_fts(media_fts, rowid, title, artist, album, filename)
+                VALUES ('delete', old.id, old.title, old.artist, old.album, old.filename);
+                INSERT INTO media_fts(rowid, title, artist, album, filename)
+                VALUES (new.id, new.title, new.artist, new.album, new.filename);
+            END
+            "#,
+            "INSERT INTO media_fts(media_fts) VALUES ('rebuild')",
+        ] {
+            sqlx::query(statement).execute(&mut *conn).await?;
+        }
+        Ok(())
+    }
+    .boxed()
+}
+
+/// Version 3: subtitle files found next to videos, removed with their video
+fn create_subtitles_table(conn: &mut SqliteConnection) -> BoxFuture<'_, Result<()>> {
+    async move {
+        for statement in [
+            r#"
+            CREATE TABLE IF NOT EXISTS subtitles (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                media_file_id INTEGER NOT NULL,
+                path TEXT NOT NULL,
+                language TEXT,
+                mime_type TEXT NOT NULL,
+                UNIQUE(media_file_id, path)
+            )
+            "#,
+            "CREATE INDEX IF NOT EXISTS idx_subtitles_media_file ON subtitles(media_file_id)",
+            r#"
+            CREATE TRIGGER IF NOT EXISTS media_files_subtitles_delete AFTER DELETE ON media_files BEGIN
+                DELETE FROM subtitles WHERE media_file_id = old.id;
+            END
+            "#,
+        ] {
+            sqlx::query(statement).execute(&mut *conn).await?;
+        }
+        Ok(())
+    }
+    .boxed()
+}
+
+/// Version 4: width and height of videos and images, in pixels
+fn add_resolution_columns(conn: &mut SqliteConnection) -> BoxFuture<'_, Result<()>> {
+    async move {
+        for statement in [
+            "ALTER TABLE media_files ADD COLUMN width INTEGER",
+            "ALTER TABLE media_files ADD COLUMN height INTEGER",
+        ] {
+            sqlx::query(statement).execute(&mut *conn).await?;
+        }
+        Ok(())
+    }
+    .boxed()
+}
+
+/// Version 5: M3U and PLS playlists with their entry paths in order
+fn create_playlist_tables(conn: &mut SqliteConnection) -> BoxFuture<'_, Result<()>> {
+    async move {
+        for statement in [
+            r#"
+            CREATE TABLE IF NOT EXISTS playlists (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                path TEXT NOT NULL UNIQUE,
+                title TEXT NOT NULL
+            )
+            "#,
+            r#"
+            CREATE TABLE IF NOT EXISTS playlist_entries (
+                playlist_id INTEGER NOT NULL REFERENCES playlists(id) ON DELETE CASCADE,
+                position INTEGER NOT NULL,
+                path TEXT NOT NULL,
+                PRIMARY KEY (playlist_id, position)
+            )
+            "#,
+        ] {
+            sqlx::query(statement).execute(&mut *conn).await?;
+        }
+        Ok(())
+    }
+    .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{DatabaseManager, MediaFile, SqliteDatabase};
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    /// Roll a freshly initialized database back to the version 1 schema
+    async fn downgrade_to_v1(pool: &SqlitePool) {
+        for statement in [
+            "DROP TRIGGER media_files_fts_insert",
+            "DROP TRIGGER media_files_fts_delete",
+            "DROP TRIGGER media_files_fts_update",
+            "DROP TABLE media_fts",
+            "DROP TRIGGER media_files_subtitles_delete",
+            "DROP TABLE subtitles",
+            "ALTER TABLE media_files DROP COLUMN width",
+            "ALTER TABLE media_files DROP COLUMN height",
+            "DROP TABLE playlist_entries",
+            "DROP TABLE playlists",
+            "UPDATE database_metadata SET value = '1' WHERE key = 'schema_version'",
+        ] {
+            sqlx::query(statement).execute(pool).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_v1_database_upgrades_and_reruns_cleanly() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let db = SqliteDatabase::new(db_path.clone()).await.unwrap();
+        db.initialize().await.unwrap();
+        let mut file = MediaFile::new(PathBuf::from("/music/song.flac"), 1024, "audio/flac".to_string());
+        file.artist = Some("Portishead".to_string());
+        db.store_media_file(&file).await.unwrap();
+        downgrade_to_v1(&db.pool()).await;
+        assert_eq!(schema_version(&db.pool()).await.unwrap(), 1);
+        drop(db);
+
+        let db = SqliteDatabase::new(db_path).await.unwrap();
+        db.initialize().await.unwrap();
+        assert_eq!(schema_version(&db.pool()).await.unwrap(), latest_version());
+        assert_eq!(db.get_all_media_files().await.unwrap().len(), 1);
+        assert_eq!(db.search_text("portis", 10).await.unwrap()[0].filename, "song.flac");
+
+        // Nothing is pending on a second run
+        assert_eq!(run_migrations(&db.pool()).await.unwrap(), latest_version());
+        db.initialize().await.unwrap();
+        assert_eq!(db.search_text("portis", 10).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failed_migration_rolls_back() {
+        fn broken(conn: &mut SqliteConnection) -> BoxFuture<'_, Result<()>> {
+            async move {
+                sqlx::query("CREATE TABLE half_done (id INTEGER)").execute(&mut *conn).await?;
+                sqlx::query("SELECT * FROM no_such_table").execute(&mut *conn).await?;
+                Ok(())
+            }
+            .boxed()
+        }
+
+        let temp_dir = tempdir().unwrap();
+        let db = SqliteDatabase::new(temp_dir.path().join("test.db")).await.unwrap();
+        db.initialize().await.unwrap();
+        downgrade_to_v1(&db.pool()).await;
+
+        let migrations = [
+            Migration { version: 2, description: "full-text search index", apply: create_text_search_index },
+            Migration { version: 3, description: "broken", apply: broken },
+        ];
+        let error = apply_migrations(&db.pool(), &migrations).await.unwrap_err();
+        assert!(format!("{:#}", error).contains("schema version 3"));
+
+        // Version 2 applied; version 3 left no trace
+        assert_eq!(schema_version(&db.pool()).await.unwrap(), 2);
+        let half_done: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE name = 'half_done')")
+            .fetch_one(&db.pool())
+            .await
+            .unwrap();
+        assert!(!half_done);
+
+        // A database from a newer build is refused
+        assert!(apply_migrations(&db.pool(), &migrations[..0]).await.is_err());
+    }
+}
@@ -1,8 +1,31 @@
-use anyhow::Result;
+pub mod backup;
+pub mod migrations;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+
+use anyhow::{Context, Result};
 use async_trait::async_trait;
-use sqlx::{Row, SqlitePool};
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{QueryBuilder, Row, Sqlite, SqlitePool};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::future::Future;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::time::{Duration, SystemTime};
+use tracing::warn;
+
+use crate::platform::filesystem::strip_extended_length_prefix;
+use crate::tvshows::EpisodeInfo;
+
+/// Default time SQLite waits on a locked database before reporting SQLITE_BUSY
+pub const DEFAULT_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Rows per multi-row insert in `batch_store_media_files`, keeping the 13
+/// bound columns of each row under SQLite's historical limit of 999 parameters
+const BATCH_INSERT_ROWS: usize = 999 / 13;
+
+/// Values per `IN (...)` list of the batched lookups, within the same limit
+const BATCH_LOOKUP_PARAMS: usize = 999;
 
 /// Enhanced MediaFile structure for database storage
 #[derive(Clone, Debug)]
@@ -17,6 +40,9 @@ pub struct MediaFile {
     pub title: Option<String>,
     pub artist: Option<String>,
     pub album: Option<String>,
+    /// Picture size in pixels of a video or image, when it could be read
+    pub width: Option<u32>,
+    pub height: Option<u32>,
     pub created_at: SystemTime,
     pub updated_at: SystemTime,
 }
@@ -42,13 +68,20 @@ impl MediaFile {
             title: None,
             artist: None,
             album: None,
+            width: None,
+            height: None,
             created_at: now,
             updated_at: now,
         }
     }
 
-    /// Create MediaFile from database row
-    fn from_row(row: &sqlx::sqlite::SqliteRow) -> Result<Self> {
+    /// Create MediaFile from a row of any backend that stores integers as 64-bit values
+    fn from_row<R: Row>(row: &R) -> Result<Self>
+    where
+        for<'a> &'a str: sqlx::ColumnIndex<R>,
+        for<'r> String: sqlx::Decode<'r, R::Database> + sqlx::Type<R::Database>,
+        for<'r> i64: sqlx::Decode<'r, R::Database> + sqlx::Type<R::Database>,
+    {
         let path_str: String = row.try_get("path")?;
         let modified_timestamp: i64 = row.try_get("modified")?;
         let created_timestamp: i64 = row.try_get("created_at")?;
@@ -68,12 +101,375 @@ impl MediaFile {
             title: row.try_get("title")?,
             artist: row.try_get("artist")?,
             album: row.try_get("album")?,
+            width: row.try_get::<Option<i64>, _>("width")?.map(|w| w as u32),
+            height: row.try_get::<Option<i64>, _>("height")?.map(|h| h as u32),
             created_at: SystemTime::UNIX_EPOCH + Duration::from_secs(created_timestamp as u64),
             updated_at: SystemTime::UNIX_EPOCH + Duration::from_secs(updated_timestamp as u64),
         })
     }
 }
 
+/// An indexed video file grouped under a TV show
+#[derive(Clone, Debug)]
+pub struct TvEpisode {
+    /// Stable id of the show, kept while the show has episodes
+    pub show_id: i64,
+    pub info: EpisodeInfo,
+    pub file: MediaFile,
+}
+
+/// External subtitle file stored next to a video, such as `movie.en.srt`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subtitle {
+    pub id: Option<i64>,
+    pub media_file_id: i64,
+    pub path: PathBuf,
+    /// Language suffix of the filename, e.g. `en` for `movie.en.srt`
+    pub language: Option<String>,
+    pub mime_type: String,
+}
+
+/// An M3U or PLS playlist file and the media paths it lists, in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Playlist {
+    pub id: Option<i64>,
+    pub path: PathBuf,
+    pub title: String,
+    /// Absolute paths of the entries; they need not be indexed files
+    pub entries: Vec<PathBuf>,
+}
+
+/// An artist in the music library; `None` groups audio files without an artist tag
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtistSummary {
+    pub name: Option<String>,
+    pub album_count: usize,
+}
+
+/// An album of one artist; `None` groups tracks without an album tag
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlbumSummary {
+    pub name: Option<String>,
+    pub track_count: usize,
+}
+
+/// Text columns of `media_files` a [`MediaFilter`] can test
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaField {
+    Filename,
+    Title,
+    Artist,
+    Album,
+    MimeType,
+}
+
+impl MediaField {
+    fn column(self) -> &'static str {
+        match self {
+            MediaField::Filename => "filename",
+            MediaField::Title => "title",
+            MediaField::Artist => "artist",
+            MediaField::Album => "album",
+            MediaField::MimeType => "mime_type",
+        }
+    }
+
+    fn value(self, file: &MediaFile) -> &str {
+        match self {
+            MediaField::Filename => &file.filename,
+            MediaField::Title => file.title.as_deref().unwrap_or_default(),
+            MediaField::Artist => file.artist.as_deref().unwrap_or_default(),
+            MediaField::Album => file.album.as_deref().unwrap_or_default(),
+            MediaField::MimeType => &file.mime_type,
+        }
+    }
+}
+
+/// Column a paged listing is sorted by. Ties are broken by filename and then
+/// id, so consecutive pages never skip or repeat a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MediaOrder {
+    #[default]
+    Filename,
+    /// Tagged titles first, untagged files last
+    Title,
+    Modified,
+    Size,
+}
+
+impl MediaOrder {
+    fn sql(self) -> &'static str {
+        match self {
+            MediaOrder::Filename => "filename, id",
+            MediaOrder::Title => "title IS NULL, title, filename, id",
+            MediaOrder::Modified => "modified, filename, id",
+            MediaOrder::Size => "size, filename, id",
+        }
+    }
+
+    fn compare(self, a: &MediaFile, b: &MediaFile) -> std::cmp::Ordering {
+        let primary = match self {
+            MediaOrder::Filename => std::cmp::Ordering::Equal,
+            MediaOrder::Title => (a.title.is_none(), &a.title).cmp(&(b.title.is_none(), &b.title)),
+            MediaOrder::Modified => a.modified.cmp(&b.modified),
+            MediaOrder::Size => a.size.cmp(&b.size),
+        };
+        primary.then_with(|| a.filename.cmp(&b.filename)).then_with(|| a.id.cmp(&b.id))
+    }
+}
+
+/// A `MediaFile` field a Browse result can be sorted by, as offered through `GetSortCapabilities`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Title,
+    Date,
+    Album,
+    Size,
+}
+
+/// One entry of a `SortCriteria` argument such as `-dc:date`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortCriterion {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+/// Order two files by the given criteria, most significant first
+pub fn compare_by_criteria(a: &MediaFile, b: &MediaFile, criteria: &[SortCriterion]) -> std::cmp::Ordering {
+    criteria
+        .iter()
+        .map(|criterion| {
+            let ordering = match criterion.key {
+                SortKey::Title => a.filename.to_lowercase().cmp(&b.filename.to_lowercase()),
+                SortKey::Date => a.modified.cmp(&b.modified),
+                SortKey::Album => a.album.cmp(&b.album),
+                SortKey::Size => a.size.cmp(&b.size),
+            };
+            if criterion.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(std::cmp::Ordering::Equal)
+}
+
+/// Whether containers, which only have a title, should be listed in reverse
+pub fn containers_descending(criteria: &[SortCriterion]) -> bool {
+    criteria
+        .iter()
+        .find(|c| c.key == SortKey::Title)
+        .is_some_and(|c| c.descending)
+}
+
+/// `ORDER BY` terms listing files by `criteria` in `dialect`. Ties are broken
+/// by the filename ignoring case and then the id, as [`DirectoryPage::from_listing`] does.
+fn criteria_order_sql(criteria: &[SortCriterion], dialect: SqlDialect) -> String {
+    let name = match dialect {
+        SqlDialect::Sqlite => "filename COLLATE NOCASE",
+        SqlDialect::Postgres => "LOWER(filename)",
+    };
+    let mut terms: Vec<String> = criteria
+        .iter()
+        .map(|criterion| {
+            let direction = if criterion.descending { " DESC" } else { "" };
+            match criterion.key {
+                SortKey::Title => format!("{}{}", name, direction),
+                SortKey::Date => format!("modified{}", direction),
+                SortKey::Size => format!("size{}", direction),
+                // Files without an album come first, as `None` does
+                SortKey::Album => format!("album IS NOT NULL{0}, album{0}", direction),
+            }
+        })
+        .collect();
+    terms.push(name.to_string());
+    terms.push("id".to_string());
+    terms.join(", ")
+}
+
+/// One page of a directory listing: its subdirectories, then the files directly inside it
+#[derive(Debug, Clone, Default)]
+pub struct DirectoryPage {
+    /// Names of the subdirectories on the page
+    pub subdirectories: Vec<String>,
+    /// Files on the page
+    pub files: Vec<MediaFile>,
+    /// Subdirectories and files in the whole listing
+    pub total: usize,
+}
+
+impl DirectoryPage {
+    /// Sort a whole listing by `criteria` and keep the page of `limit` entries
+    /// after `offset`. Subdirectories sort by name ignoring case and come first.
+    pub fn from_listing(
+        mut subdirectories: Vec<String>,
+        mut files: Vec<&MediaFile>,
+        criteria: &[SortCriterion],
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Self {
+        sort_subdirectories(&mut subdirectories, criteria);
+        files.sort_by(|a, b| {
+            compare_by_criteria(a, b, criteria)
+                .then_with(|| a.filename.to_lowercase().cmp(&b.filename.to_lowercase()))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        let total = subdirectories.len() + files.len();
+        let (subdirectory_offset, file_offset, file_limit) = Self::split_window(subdirectories.len(), offset, limit);
+        Self {
+            subdirectories: subdirectories.into_iter().skip(subdirectory_offset).take(limit.unwrap_or(usize::MAX)).collect(),
+            files: files.into_iter().skip(file_offset).take(file_limit.unwrap_or(usize::MAX)).cloned().collect(),
+            total,
+        }
+    }
+
+    /// Split the window of `limit` entries after `offset` over a listing starting
+    /// with `subdirectory_count` subdirectories: where the subdirectories on the
+    /// page start, and the offset and limit left for the files
+    fn split_window(subdirectory_count: usize, offset: usize, limit: Option<usize>) -> (usize, usize, Option<usize>) {
+        let subdirectory_offset = offset.min(subdirectory_count);
+        let subdirectories_on_page = (subdirectory_count - subdirectory_offset).min(limit.unwrap_or(usize::MAX));
+        let file_limit = limit.map(|limit| limit - subdirectories_on_page);
+        (subdirectory_offset, offset - subdirectory_offset, file_limit)
+    }
+}
+
+/// Sort subdirectory names ignoring case, in reverse for a descending title sort
+fn sort_subdirectories(names: &mut [String], criteria: &[SortCriterion]) {
+    names.sort_by_key(|name| name.to_lowercase());
+    if containers_descending(criteria) {
+        names.reverse();
+    }
+}
+
+/// Condition on indexed media files. Text comparisons ignore ASCII case and
+/// treat missing tags as empty strings. SQLite's `LIKE` and `NOCASE` only fold
+/// ASCII letters, so "ÉTÉ" does not match "été" there or in [`Self::matches`];
+/// PostgreSQL's `ILIKE` and `LOWER` fold non-ASCII letters as well.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MediaFilter {
+    All,
+    Nothing,
+    Contains(MediaField, String),
+    Equals(MediaField, String),
+    StartsWith(MediaField, String),
+    Not(Box<MediaFilter>),
+    And(Box<MediaFilter>, Box<MediaFilter>),
+    Or(Box<MediaFilter>, Box<MediaFilter>),
+}
+
+impl MediaFilter {
+    /// Whether `file` satisfies the filter
+    pub fn matches(&self, file: &MediaFile) -> bool {
+        match self {
+            MediaFilter::All => true,
+            MediaFilter::Nothing => false,
+            MediaFilter::Contains(field, text) => field
+                .value(file)
+                .to_ascii_lowercase()
+                .contains(&text.to_ascii_lowercase()),
+            MediaFilter::Equals(field, text) => field.value(file).eq_ignore_ascii_case(text),
+            MediaFilter::StartsWith(field, text) => field
+                .value(file)
+                .to_ascii_lowercase()
+                .starts_with(&text.to_ascii_lowercase()),
+            MediaFilter::Not(inner) => !inner.matches(file),
+            MediaFilter::And(a, b) => a.matches(file) && b.matches(file),
+            MediaFilter::Or(a, b) => a.matches(file) || b.matches(file),
+        }
+    }
+
+    /// SQL condition for the filter in `dialect`, pushing the values it binds onto `params`
+    fn to_sql(&self, dialect: SqlDialect, params: &mut Vec<String>) -> String {
+        let column = |field: MediaField| format!("COALESCE({}, '')", field.column());
+        let like = match dialect {
+            SqlDialect::Sqlite => "LIKE",
+            SqlDialect::Postgres => "ILIKE",
+        };
+        match self {
+            MediaFilter::All => "TRUE".to_string(),
+            MediaFilter::Nothing => "FALSE".to_string(),
+            MediaFilter::Contains(field, text) => {
+                let pattern = dialect.bind(params, format!("%{}%", escape_like(text)));
+                format!("{} {} {} ESCAPE '\\'", column(*field), like, pattern)
+            }
+            MediaFilter::Equals(field, text) => {
+                let value = dialect.bind(params, text.clone());
+                match dialect {
+                    SqlDialect::Sqlite => format!("{} = {} COLLATE NOCASE", column(*field), value),
+                    SqlDialect::Postgres => format!("LOWER({}) = LOWER({})", column(*field), value),
+                }
+            }
+            MediaFilter::StartsWith(field, text) => {
+                let pattern = dialect.bind(params, format!("{}%", escape_like(text)));
+                format!("{} {} {} ESCAPE '\\'", column(*field), like, pattern)
+            }
+            MediaFilter::Not(inner) => format!("NOT ({})", inner.to_sql(dialect, params)),
+            MediaFilter::And(a, b) => {
+                let a = a.to_sql(dialect, params);
+                format!("({}) AND ({})", a, b.to_sql(dialect, params))
+            }
+            MediaFilter::Or(a, b) => {
+                let a = a.to_sql(dialect, params);
+                format!("({}) OR ({})", a, b.to_sql(dialect, params))
+            }
+        }
+    }
+}
+
+/// SQL flavour a [`MediaFilter`] is rendered in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SqlDialect {
+    Sqlite,
+    #[cfg_attr(not(feature = "postgres"), allow(dead_code))]
+    Postgres,
+}
+
+impl SqlDialect {
+    /// Push `value` onto `params`, returning its placeholder
+    fn bind(self, params: &mut Vec<String>, value: String) -> String {
+        params.push(value);
+        match self {
+            SqlDialect::Sqlite => "?".to_string(),
+            SqlDialect::Postgres => format!("${}", params.len()),
+        }
+    }
+}
+
+/// Escape `%`, `_` and the escape character itself for a `LIKE ... ESCAPE '\'` pattern
+pub fn escape_like(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Smallest string greater than every string starting with `prefix`, or
+/// `None` when no such bound exists (an empty prefix)
+fn prefix_upper_bound(prefix: &str) -> Option<String> {
+    let mut chars: Vec<char> = prefix.chars().collect();
+    while let Some(last) = chars.pop() {
+        if let Some(next) = (last as u32 + 1..=char::MAX as u32).find_map(char::from_u32) {
+            chars.push(next);
+            return Some(chars.into_iter().collect());
+        }
+    }
+    None
+}
+
+/// Words of a free-text query, split the way the FTS5 `unicode61` tokenizer splits text
+fn text_search_terms(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
 /// Database manager trait for media file operations
 #[async_trait]
 pub trait DatabaseManager: Send + Sync {
@@ -83,30 +479,255 @@ pub trait DatabaseManager: Send + Sync {
     /// Store a new media file record
     async fn store_media_file(&self, file: &MediaFile) -> Result<i64>;
 
+    /// Store many media files at once, updating the records already stored
+    /// under their paths in place. Returns the id of each file, in order.
+    async fn batch_store_media_files(&self, files: &[MediaFile]) -> Result<Vec<i64>> {
+        let mut ids = Vec::with_capacity(files.len());
+        for file in files {
+            match self.get_file_by_path(&file.path).await?.and_then(|existing| existing.id) {
+                Some(id) => {
+                    self.update_media_file(file).await?;
+                    ids.push(id);
+                }
+                None => ids.push(self.store_media_file(file).await?),
+            }
+        }
+        Ok(ids)
+    }
+
     /// Get all media files from the database
     async fn get_all_media_files(&self) -> Result<Vec<MediaFile>>;
 
     /// Remove a media file record by path
     async fn remove_media_file(&self, path: &Path) -> Result<bool>;
 
-    /// Update an existing media file record
+    /// Update the media file record at `file.path`, inserting it when there is
+    /// none, such as for a modify event that arrived before its create event
     async fn update_media_file(&self, file: &MediaFile) -> Result<()>;
 
     /// Get all files in a specific directory
     async fn get_files_in_directory(&self, dir: &Path) -> Result<Vec<MediaFile>>;
 
+    /// Remove every media file record below `dir`, returning how many were removed
+    async fn remove_files_in_directory(&self, dir: &Path) -> Result<usize> {
+        let mut removed = 0;
+        for file in self.get_files_in_directory(dir).await? {
+            if self.remove_media_file(&file.path).await? {
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
     /// Remove media files that no longer exist on disk
     async fn cleanup_missing_files(&self, existing_paths: &[PathBuf]) -> Result<usize>;
 
     /// Get a specific file by path
     async fn get_file_by_path(&self, path: &Path) -> Result<Option<MediaFile>>;
 
+    /// Move a media file record to a new path and filename in place, keeping its
+    /// id stable. Returns whether a record was at `from`.
+    async fn rename_media_file(&self, from: &Path, to: &Path) -> Result<bool>;
+
+    /// Files whose MIME type starts with `prefix`, such as `"video/"`, ordered by filename
+    async fn get_files_by_mime_prefix(&self, prefix: &str) -> Result<Vec<MediaFile>> {
+        let mut files: Vec<MediaFile> = self
+            .get_all_media_files()
+            .await?
+            .into_iter()
+            .filter(|f| f.mime_type.starts_with(prefix))
+            .collect();
+        files.sort_by(|a, b| a.filename.cmp(&b.filename));
+        Ok(files)
+    }
+
+    /// Get a specific file by its database id
+    async fn get_file_by_id(&self, id: i64) -> Result<Option<MediaFile>> {
+        Ok(self
+            .get_all_media_files()
+            .await?
+            .into_iter()
+            .find(|f| f.id == Some(id)))
+    }
+
+    /// The indexed files among `ids`, in no particular order
+    async fn get_files_by_ids(&self, ids: &[i64]) -> Result<Vec<MediaFile>> {
+        let mut files = Vec::with_capacity(ids.len());
+        for id in ids {
+            files.extend(self.get_file_by_id(*id).await?);
+        }
+        Ok(files)
+    }
+
+    /// Ids of the indexed files among `paths`, keyed by the requested path
+    async fn get_file_ids_by_paths(&self, paths: &[PathBuf]) -> Result<HashMap<PathBuf, i64>> {
+        let mut ids = HashMap::with_capacity(paths.len());
+        for path in paths {
+            if let Some(id) = self.get_file_by_path(path).await?.and_then(|file| file.id) {
+                ids.insert(path.clone(), id);
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Files matching `filter` ordered by filename, skipping `offset` and returning
+    /// at most `limit` of them, together with the total number of matches
+    async fn search_media_files(
+        &self,
+        filter: &MediaFilter,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Result<(Vec<MediaFile>, usize)> {
+        let mut matches: Vec<MediaFile> = self
+            .get_all_media_files()
+            .await?
+            .into_iter()
+            .filter(|f| filter.matches(f))
+            .collect();
+        matches.sort_by(|a, b| a.filename.cmp(&b.filename));
+        let total = matches.len();
+        let page = matches.into_iter().skip(offset).take(limit.unwrap_or(usize::MAX)).collect();
+        Ok((page, total))
+    }
+
+    /// One page of the library sorted by `order`: at most `limit` files after
+    /// skipping `offset`, together with the total number of files
+    async fn get_media_files_paged(
+        &self,
+        offset: usize,
+        limit: usize,
+        order: MediaOrder,
+    ) -> Result<(Vec<MediaFile>, usize)> {
+        let mut files = self.get_all_media_files().await?;
+        files.sort_by(|a, b| order.compare(a, b));
+        let total = files.len();
+        let page = files.into_iter().skip(offset).take(limit).collect();
+        Ok((page, total))
+    }
+
+    /// One page of the listing of `dir` limited to MIME types starting with
+    /// `mime_prefix`: the subdirectories holding such files, then the files
+    /// directly inside, see [`DirectoryPage::from_listing`] for the order
+    async fn browse_directory(
+        &self,
+        dir: &Path,
+        mime_prefix: &str,
+        criteria: &[SortCriterion],
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Result<DirectoryPage> {
+        let files = self.get_files_in_directory(dir).await?;
+        let mut subdirectories = BTreeSet::new();
+        let mut direct = Vec::new();
+        for file in files.iter().filter(|f| f.mime_type.starts_with(mime_prefix)) {
+            let Ok(relative) = file.path.strip_prefix(dir) else {
+                continue;
+            };
+            let mut components = relative.components();
+            let first = components.next();
+            if components.next().is_none() {
+                direct.push(file);
+            } else if let Some(first) = first {
+                subdirectories.insert(first.as_os_str().to_string_lossy().to_string());
+            }
+        }
+        Ok(DirectoryPage::from_listing(subdirectories.into_iter().collect(), direct, criteria, offset, limit))
+    }
+
+    /// Files whose title, artist, album or filename contain words starting with
+    /// every word of `query`, best matches first where the database can rank them
+    async fn search_text(&self, query: &str, limit: usize) -> Result<Vec<MediaFile>> {
+        let terms = text_search_terms(query);
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut matches: Vec<MediaFile> = self
+            .get_all_media_files()
+            .await?
+            .into_iter()
+            .filter(|file| {
+                let words: Vec<String> = [&file.title, &file.artist, &file.album]
+                    .into_iter()
+                    .flatten()
+                    .chain(std::iter::once(&file.filename))
+                    .flat_map(|field| text_search_terms(field))
+                    .collect();
+                terms.iter().all(|term| words.iter().any(|word| word.starts_with(term.as_str())))
+            })
+            .collect();
+        matches.sort_by(|a, b| a.filename.cmp(&b.filename));
+        matches.truncate(limit);
+        Ok(matches)
+    }
+
+    /// Artists of the indexed audio files with their album counts, ordered by name
+    async fn get_music_artists(&self) -> Result<Vec<ArtistSummary>> {
+        let mut albums: BTreeMap<Option<String>, BTreeSet<Option<String>>> = BTreeMap::new();
+        for file in self.get_all_media_files().await? {
+            if file.mime_type.starts_with("audio/") {
+                albums.entry(file.artist).or_default().insert(file.album);
+            }
+        }
+        let mut artists: Vec<ArtistSummary> = albums
+            .into_iter()
+            .map(|(name, albums)| ArtistSummary { name, album_count: albums.len() })
+            .collect();
+        // Untagged groups come last, as in the SQL ordering
+        artists.sort_by_key(|a| (a.name.is_none(), a.name.as_ref().map(|n| n.to_lowercase())));
+        Ok(artists)
+    }
+
+    /// Albums of `artist` with their track counts, ordered by name
+    async fn get_artist_albums(&self, artist: Option<&str>) -> Result<Vec<AlbumSummary>> {
+        let mut tracks: BTreeMap<Option<String>, usize> = BTreeMap::new();
+        for file in self.get_all_media_files().await? {
+            if file.mime_type.starts_with("audio/") && file.artist.as_deref() == artist {
+                *tracks.entry(file.album).or_default() += 1;
+            }
+        }
+        let mut albums: Vec<AlbumSummary> = tracks
+            .into_iter()
+            .map(|(name, track_count)| AlbumSummary { name, track_count })
+            .collect();
+        // Untagged groups come last, as in the SQL ordering
+        albums.sort_by_key(|a| (a.name.is_none(), a.name.as_ref().map(|n| n.to_lowercase())));
+        Ok(albums)
+    }
+
+    /// Audio files tagged with `artist` and `album`, ordered by filename
+    async fn get_album_tracks(&self, artist: Option<&str>, album: Option<&str>) -> Result<Vec<MediaFile>> {
+        let mut tracks: Vec<MediaFile> = self
+            .get_all_media_files()
+            .await?
+            .into_iter()
+            .filter(|f| f.mime_type.starts_with("audio/") && f.artist.as_deref() == artist && f.album.as_deref() == album)
+            .collect();
+        tracks.sort_by(|a, b| a.filename.cmp(&b.filename));
+        Ok(tracks)
+    }
+
     /// Get database statistics
     async fn get_stats(&self) -> Result<DatabaseStats>;
 
+    /// Get database statistics broken down by media type
+    async fn get_stats_detailed(&self) -> Result<DetailedDatabaseStats> {
+        let mut detailed = DetailedDatabaseStats::new(self.get_stats().await?);
+        for file in self.get_all_media_files().await? {
+            detailed.record(&file.mime_type, 1, file.size);
+            detailed.oldest_modified = Some(detailed.oldest_modified.map_or(file.modified, |t| t.min(file.modified)));
+            detailed.newest_modified = Some(detailed.newest_modified.map_or(file.modified, |t| t.max(file.modified)));
+        }
+        Ok(detailed)
+    }
+
     /// Check database integrity and repair if needed
     async fn check_and_repair(&self) -> Result<DatabaseHealth>;
 
+    /// Result of the most recent `check_and_repair`, if one has run
+    fn last_health(&self) -> Option<DatabaseHealth> {
+        None
+    }
+
     /// Create a backup of the database
     async fn create_backup(&self, backup_path: &Path) -> Result<()>;
 
@@ -115,15 +736,127 @@ pub trait DatabaseManager: Send + Sync {
 
     /// Vacuum the database to reclaim space and optimize performance
     async fn vacuum(&self) -> Result<()>;
+
+    /// Remove media file records with an empty path or filename, or a negative
+    /// size, returning how many were removed
+    async fn cleanup_invalid_records(&self) -> Result<usize>;
+
+    /// Remove duplicate file entries, keeping the most recent, returning how
+    /// many were removed
+    async fn remove_duplicates(&self) -> Result<usize>;
+
+    /// Replace the TV show grouping with `episodes`, keyed by media file id
+    async fn replace_tv_episodes(&self, episodes: &[(i64, EpisodeInfo)]) -> Result<()>;
+
+    /// All grouped episodes with their media files
+    async fn get_tv_episodes(&self) -> Result<Vec<TvEpisode>>;
+
+    /// Replace the subtitle sidecars recorded for the video `media_file_id`
+    async fn replace_subtitles(&self, media_file_id: i64, subtitles: &[Subtitle]) -> Result<()>;
+
+    /// Subtitle sidecars of every indexed video
+    async fn get_all_subtitles(&self) -> Result<Vec<Subtitle>>;
+
+    /// Subtitle sidecars of the video `media_file_id`
+    async fn get_subtitles(&self, media_file_id: i64) -> Result<Vec<Subtitle>> {
+        Ok(self
+            .get_all_subtitles()
+            .await?
+            .into_iter()
+            .filter(|s| s.media_file_id == media_file_id)
+            .collect())
+    }
+
+    /// Get a specific subtitle sidecar by its database id
+    async fn get_subtitle_by_id(&self, id: i64) -> Result<Option<Subtitle>> {
+        Ok(self.get_all_subtitles().await?.into_iter().find(|s| s.id == Some(id)))
+    }
+
+    /// Record `playlist`, replacing the entries of a playlist already stored
+    /// under its path while keeping its id, and return that id
+    async fn store_playlist(&self, playlist: &Playlist) -> Result<i64>;
+
+    /// Forget the playlist stored under `path`, returning whether one was
+    async fn remove_playlist(&self, path: &Path) -> Result<bool>;
+
+    /// All recorded playlists with their entries in order
+    async fn get_playlists(&self) -> Result<Vec<Playlist>>;
+
+    /// SystemUpdateID recorded by `set_system_update_id`, if any
+    async fn get_system_update_id(&self) -> Result<Option<u32>>;
+
+    /// Record the SystemUpdateID to resume from after a restart
+    async fn set_system_update_id(&self, update_id: u32) -> Result<()>;
+
+    /// Advance the UPnP BOOTID recorded for this database and return it; called
+    /// once per server start so control points notice the restart
+    async fn next_boot_id(&self) -> Result<u32>;
+}
+
+/// BOOTID following `previous`, which stays within the 31 bits UPnP allows
+pub fn following_boot_id(previous: Option<u32>) -> u32 {
+    previous
+        .and_then(|id| id.checked_add(1))
+        .filter(|id| *id <= i32::MAX as u32)
+        .unwrap_or(1)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DatabaseStats {
     pub total_files: usize,
     pub total_size: u64,
     pub database_size: u64,
 }
 
+/// File count and byte total for a single media category
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MediaTypeStats {
+    pub file_count: usize,
+    pub total_size: u64,
+}
+
+/// Database statistics with a per-category breakdown
+#[derive(Debug, Clone)]
+pub struct DetailedDatabaseStats {
+    pub totals: DatabaseStats,
+    pub video: MediaTypeStats,
+    pub audio: MediaTypeStats,
+    pub image: MediaTypeStats,
+    pub other: MediaTypeStats,
+    pub oldest_modified: Option<SystemTime>,
+    pub newest_modified: Option<SystemTime>,
+}
+
+impl DetailedDatabaseStats {
+    /// Create an empty breakdown for the given totals
+    pub fn new(totals: DatabaseStats) -> Self {
+        Self {
+            totals,
+            video: MediaTypeStats::default(),
+            audio: MediaTypeStats::default(),
+            image: MediaTypeStats::default(),
+            other: MediaTypeStats::default(),
+            oldest_modified: None,
+            newest_modified: None,
+        }
+    }
+
+    /// Add files of the given MIME type to the matching category
+    fn record(&mut self, mime_type: &str, file_count: usize, total_size: u64) {
+        let category = if mime_type.starts_with("video/") {
+            &mut self.video
+        } else if mime_type.starts_with("audio/") {
+            &mut self.audio
+        } else if mime_type.starts_with("image/") {
+            &mut self.image
+        } else {
+            &mut self.other
+        };
+        category.file_count += file_count;
+        category.total_size += total_size;
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DatabaseHealth {
     pub is_healthy: bool,
@@ -134,12 +867,21 @@ pub struct DatabaseHealth {
     pub repair_successful: bool,
 }
 
+impl DatabaseHealth {
+    /// Total number of rows removed or changed by repair actions
+    pub fn rows_repaired(&self) -> usize {
+        self.issues.iter().filter_map(|issue| issue.rows_affected).sum()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DatabaseIssue {
     pub severity: IssueSeverity,
     pub description: String,
     pub table_affected: Option<String>,
     pub suggested_action: String,
+    /// Number of rows changed when the issue records a repair action
+    pub rows_affected: Option<usize>,
 }
 
 #[derive(Debug, Clone)]
@@ -150,24 +892,116 @@ pub enum IssueSeverity {
     Critical,
 }
 
+/// Rows removed by a repair pass
+struct RepairReport {
+    orphaned_removed: usize,
+    duplicates_removed: usize,
+    integrity_ok: bool,
+}
+
+/// Retry behaviour for writes that fail with transient busy/locked errors
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Number of retries after the first failed attempt
+    pub max_retries: u32,
+    /// Delay before the first retry; doubled on each subsequent retry
+    pub initial_backoff: Duration,
+    /// Longest delay between two attempts, however many retries came before
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
 /// SQLite implementation of DatabaseManager
 pub struct SqliteDatabase {
-    pool: SqlitePool,
+    /// Swapped for a fresh pool when a backup is restored
+    pool: std::sync::RwLock<SqlitePool>,
+    connect_options: SqliteConnectOptions,
     db_path: PathBuf,
+    retry_policy: RetryPolicy,
+    last_health: std::sync::RwLock<Option<DatabaseHealth>>,
 }
 
 impl SqliteDatabase {
     /// Create a new SQLite database manager
     pub async fn new(db_path: PathBuf) -> Result<Self> {
+        Self::with_options(db_path, DEFAULT_BUSY_TIMEOUT, RetryPolicy::default()).await
+    }
+
+    /// Create a new SQLite database manager with explicit lock handling settings
+    pub async fn with_options(
+        db_path: PathBuf,
+        busy_timeout: Duration,
+        retry_policy: RetryPolicy,
+    ) -> Result<Self> {
         // Ensure parent directory exists
         if let Some(parent) = db_path.parent() {
             tokio::fs::create_dir_all(parent).await?;
         }
 
         let database_url = format!("sqlite://{}?mode=rwc", db_path.display());
-        let pool = SqlitePool::connect(&database_url).await?;
+        let connect_options = SqliteConnectOptions::from_str(&database_url)?.busy_timeout(busy_timeout);
+        let pool = SqlitePool::connect_with(connect_options.clone()).await?;
+
+        Ok(Self {
+            pool: std::sync::RwLock::new(pool),
+            connect_options,
+            db_path,
+            retry_policy,
+            last_health: std::sync::RwLock::new(None),
+        })
+    }
+
+    /// The live connection pool
+    fn pool(&self) -> SqlitePool {
+        self.pool.read().unwrap().clone()
+    }
+
+    /// Check whether an error is caused by transient lock contention
+    fn is_transient_error(error: &sqlx::Error) -> bool {
+        match error {
+            sqlx::Error::Database(db_err) => {
+                // Primary result codes SQLITE_BUSY (5) and SQLITE_LOCKED (6); extended
+                // codes keep the primary code in the low byte.
+                db_err
+                    .code()
+                    .and_then(|code| code.parse::<i32>().ok())
+                    .is_some_and(|code| matches!(code & 0xff, 5 | 6))
+            }
+            _ => false,
+        }
+    }
 
-        Ok(Self { pool, db_path })
+    /// Run a write operation, retrying with exponential backoff on busy/locked errors
+    async fn with_retry<T, F, Fut>(&self, operation: &str, mut op: F) -> Result<T, sqlx::Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, sqlx::Error>>,
+    {
+        let mut attempt = 0;
+        let mut backoff = self.retry_policy.initial_backoff;
+        loop {
+            match op().await {
+                Err(e) if attempt < self.retry_policy.max_retries && Self::is_transient_error(&e) => {
+                    attempt += 1;
+                    warn!(
+                        "Database busy during {} (retry {}/{} in {:?}): {}",
+                        operation, attempt, self.retry_policy.max_retries, backoff, e
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(self.retry_policy.max_backoff);
+                }
+                result => return result,
+            }
+        }
     }
 
     /// Create database tables
@@ -190,24 +1024,56 @@ impl SqliteDatabase {
             )
             "#,
         )
-        .execute(&self.pool)
+        .execute(&self.pool())
         .await?;
 
         // Create indexes for better query performance
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_media_files_path ON media_files(path)")
-            .execute(&self.pool)
+            .execute(&self.pool())
             .await?;
 
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_media_files_modified ON media_files(modified)")
-            .execute(&self.pool)
+            .execute(&self.pool())
             .await?;
 
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_media_files_mime_type ON media_files(mime_type)")
-            .execute(&self.pool)
+            .execute(&self.pool())
             .await?;
 
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_media_files_filename ON media_files(filename)")
-            .execute(&self.pool)
+            .execute(&self.pool())
+            .await?;
+
+        // TV show grouping built from .nfo files and SxxExx filenames
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS tv_shows (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                title TEXT NOT NULL,
+                path TEXT NOT NULL,
+                UNIQUE(path, title)
+            )
+            "#,
+        )
+        .execute(&self.pool())
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS tv_episodes (
+                media_file_id INTEGER PRIMARY KEY REFERENCES media_files(id) ON DELETE CASCADE,
+                show_id INTEGER NOT NULL REFERENCES tv_shows(id) ON DELETE CASCADE,
+                season INTEGER NOT NULL,
+                episode INTEGER NOT NULL,
+                title TEXT
+            )
+            "#,
+        )
+        .execute(&self.pool())
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_tv_episodes_show ON tv_episodes(show_id, season)")
+            .execute(&self.pool())
             .await?;
 
         // Create database metadata table for migrations
@@ -220,10 +1086,10 @@ impl SqliteDatabase {
             )
             "#,
         )
-        .execute(&self.pool)
+        .execute(&self.pool())
         .await?;
 
-        // Set initial schema version
+        // The tables above make up schema version 1; later changes are migrations
         let now = SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
             .unwrap()
@@ -235,12 +1101,46 @@ impl SqliteDatabase {
         .bind("schema_version")
         .bind("1")
         .bind(now)
-        .execute(&self.pool)
+        .execute(&self.pool())
         .await?;
 
         Ok(())
     }
 
+    /// Schema version recorded in the database
+    pub async fn schema_version(&self) -> Result<u32> {
+        migrations::schema_version(&self.pool()).await
+    }
+
+    /// Convert a path to the form stored in the database, so extended-length
+    /// (`\\?\`) and plain spellings of the same Windows path match
+    fn path_to_db_string(path: &Path) -> String {
+        strip_extended_length_prefix(path).to_string_lossy().to_string()
+    }
+
+    /// Stored form of `dir` ending in a separator, so it prefixes only paths
+    /// inside the directory and not siblings such as `MovieExtras` for `Movie`.
+    /// The separator already used in the path is kept, so UNC paths stay consistent.
+    fn child_prefix(dir: &Path) -> String {
+        let mut prefix = Self::path_to_db_string(dir);
+        if !prefix.ends_with(['/', '\\']) {
+            let separator = prefix
+                .rfind(['/', '\\'])
+                .map_or(std::path::MAIN_SEPARATOR, |i| prefix.as_bytes()[i] as char);
+            prefix.push(separator);
+        }
+        prefix
+    }
+
+    /// Bounds of the stored paths inside `dir`. Unlike SQLite's `LIKE`, the
+    /// range compares case-sensitively, so `/media/show` is not inside `/media/Show`.
+    fn child_range(dir: &Path) -> (String, String) {
+        let prefix = Self::child_prefix(dir);
+        // A prefix ending in a separator always has an upper bound
+        let upper = prefix_upper_bound(&prefix).unwrap_or_default();
+        (prefix, upper)
+    }
+
     /// Convert SystemTime to Unix timestamp
     fn system_time_to_timestamp(time: SystemTime) -> i64 {
         time.duration_since(SystemTime::UNIX_EPOCH)
@@ -254,61 +1154,86 @@ impl DatabaseManager for SqliteDatabase {
     async fn initialize(&self) -> Result<()> {
         // Configure SQLite for better performance
         sqlx::query("PRAGMA journal_mode = WAL")
-            .execute(&self.pool)
+            .execute(&self.pool())
             .await?;
         sqlx::query("PRAGMA synchronous = NORMAL")
-            .execute(&self.pool)
+            .execute(&self.pool())
             .await?;
         sqlx::query("PRAGMA temp_store = MEMORY")
-            .execute(&self.pool)
+            .execute(&self.pool())
             .await?;
         sqlx::query("PRAGMA foreign_keys = ON")
-            .execute(&self.pool)
+            .execute(&self.pool())
             .await?;
         sqlx::query("PRAGMA cache_size = -10000") // 10MB cache
-            .execute(&self.pool)
+            .execute(&self.pool())
             .await?;
 
         self.create_tables().await?;
+        migrations::run_migrations(&self.pool()).await?;
         Ok(())
     }
 
     async fn store_media_file(&self, file: &MediaFile) -> Result<i64> {
-        let path_str = file.path.to_string_lossy().to_string();
+        let path_str = Self::path_to_db_string(&file.path);
         let modified_timestamp = Self::system_time_to_timestamp(file.modified);
         let created_timestamp = Self::system_time_to_timestamp(file.created_at);
         let updated_timestamp = Self::system_time_to_timestamp(file.updated_at);
         let duration_ms = file.duration.map(|d| d.as_millis() as i64);
 
-        let result = sqlx::query(
-            r#"
-            INSERT INTO media_files 
-            (path, filename, size, modified, mime_type, duration, title, artist, album, created_at, updated_at)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-            "#,
-        )
-        .bind(&path_str)
-        .bind(&file.filename)
-        .bind(file.size as i64)
-        .bind(modified_timestamp)
-        .bind(&file.mime_type)
-        .bind(duration_ms)
-        .bind(&file.title)
-        .bind(&file.artist)
-        .bind(&file.album)
-        .bind(created_timestamp)
-        .bind(updated_timestamp)
-        .execute(&self.pool)
-        .await?;
+        let pool = self.pool();
+        let result = self
+            .with_retry("store_media_file", || {
+                sqlx::query(
+                    r#"
+                    INSERT INTO media_files 
+                    (path, filename, size, modified, mime_type, duration, title, artist, album, width, height, created_at, updated_at)
+                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                    "#,
+                )
+                .bind(&path_str)
+                .bind(&file.filename)
+                .bind(file.size as i64)
+                .bind(modified_timestamp)
+                .bind(&file.mime_type)
+                .bind(duration_ms)
+                .bind(&file.title)
+                .bind(&file.artist)
+                .bind(&file.album)
+                .bind(file.width.map(i64::from))
+                .bind(file.height.map(i64::from))
+                .bind(created_timestamp)
+                .bind(updated_timestamp)
+                .execute(&pool)
+            })
+            .await?;
 
         Ok(result.last_insert_rowid())
     }
 
+    async fn batch_store_media_files(&self, files: &[MediaFile]) -> Result<Vec<i64>> {
+        // The whole transaction is retried, so a busy database never leaves half a batch stored
+        let ids_by_path = self
+            .with_retry("batch_store_media_files", || self.insert_media_batch(files))
+            .await?;
+
+        files
+            .iter()
+            .map(|file| {
+                let path = Self::path_to_db_string(&file.path);
+                ids_by_path
+                    .get(&path)
+                    .copied()
+                    .ok_or_else(|| anyhow::anyhow!("No id returned for {}", path))
+            })
+            .collect()
+    }
+
     async fn get_all_media_files(&self) -> Result<Vec<MediaFile>> {
         let rows = sqlx::query(
-            "SELECT id, path, filename, size, modified, mime_type, duration, title, artist, album, created_at, updated_at FROM media_files ORDER BY filename"
+            "SELECT id, path, filename, size, modified, mime_type, duration, title, artist, album, width, height, created_at, updated_at FROM media_files ORDER BY filename"
         )
-        .fetch_all(&self.pool)
+        .fetch_all(&self.pool())
         .await?;
 
         let mut files = Vec::new();
@@ -320,59 +1245,112 @@ impl DatabaseManager for SqliteDatabase {
     }
 
     async fn remove_media_file(&self, path: &Path) -> Result<bool> {
-        let path_str = path.to_string_lossy().to_string();
-
-        let result = sqlx::query("DELETE FROM media_files WHERE path = ?")
-            .bind(&path_str)
-            .execute(&self.pool)
+        let path_str = Self::path_to_db_string(path);
+
+        let pool = self.pool();
+        let result = self
+            .with_retry("remove_media_file", || {
+                sqlx::query("DELETE FROM media_files WHERE path = ?")
+                    .bind(&path_str)
+                    .execute(&pool)
+            })
             .await?;
 
         Ok(result.rows_affected() > 0)
     }
 
-    async fn update_media_file(&self, file: &MediaFile) -> Result<()> {
-        let path_str = file.path.to_string_lossy().to_string();
-        let modified_timestamp = Self::system_time_to_timestamp(file.modified);
-        let updated_timestamp = Self::system_time_to_timestamp(SystemTime::now());
-        let duration_ms = file.duration.map(|d| d.as_millis() as i64);
+    async fn remove_files_in_directory(&self, dir: &Path) -> Result<usize> {
+        let (lower, upper) = Self::child_range(dir);
+
+        let pool = self.pool();
+        let result = self
+            .with_retry("remove_files_in_directory", || {
+                sqlx::query("DELETE FROM media_files WHERE path >= ? AND path < ?")
+                    .bind(&lower)
+                    .bind(&upper)
+                    .execute(&pool)
+            })
+            .await?;
 
-        sqlx::query(
-            r#"
-            UPDATE media_files 
-            SET filename = ?, size = ?, modified = ?, mime_type = ?, duration = ?, 
-                title = ?, artist = ?, album = ?, updated_at = ?
-            WHERE path = ?
-            "#,
-        )
-        .bind(&file.filename)
-        .bind(file.size as i64)
-        .bind(modified_timestamp)
-        .bind(&file.mime_type)
-        .bind(duration_ms)
-        .bind(&file.title)
-        .bind(&file.artist)
-        .bind(&file.album)
-        .bind(updated_timestamp)
-        .bind(&path_str)
-        .execute(&self.pool)
+        Ok(result.rows_affected() as usize)
+    }
+
+    async fn update_media_file(&self, file: &MediaFile) -> Result<()> {
+        let path_str = Self::path_to_db_string(&file.path);
+        let modified_timestamp = Self::system_time_to_timestamp(file.modified);
+        let created_timestamp = Self::system_time_to_timestamp(file.created_at);
+        let updated_timestamp = Self::system_time_to_timestamp(SystemTime::now());
+        let duration_ms = file.duration.map(|d| d.as_millis() as i64);
+
+        let pool = self.pool();
+        self.with_retry("update_media_file", || {
+            sqlx::query(
+                r#"
+                INSERT INTO media_files
+                (path, filename, size, modified, mime_type, duration, title, artist, album, width, height, created_at, updated_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT(path) DO UPDATE SET
+                    filename = excluded.filename, size = excluded.size, modified = excluded.modified,
+                    mime_type = excluded.mime_type, duration = excluded.duration, title = excluded.title,
+                    artist = excluded.artist, album = excluded.album, width = excluded.width,
+                    height = excluded.height, updated_at = excluded.updated_at
+                "#,
+            )
+            .bind(&path_str)
+            .bind(&file.filename)
+            .bind(file.size as i64)
+            .bind(modified_timestamp)
+            .bind(&file.mime_type)
+            .bind(duration_ms)
+            .bind(&file.title)
+            .bind(&file.artist)
+            .bind(&file.album)
+            .bind(file.width.map(i64::from))
+            .bind(file.height.map(i64::from))
+            .bind(created_timestamp)
+            .bind(updated_timestamp)
+            .execute(&pool)
+        })
         .await?;
 
         Ok(())
     }
 
+    async fn rename_media_file(&self, from: &Path, to: &Path) -> Result<bool> {
+        let from_str = Self::path_to_db_string(from);
+        let to_str = Self::path_to_db_string(to);
+        let filename = to.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let updated_timestamp = Self::system_time_to_timestamp(SystemTime::now());
+
+        let pool = self.pool();
+        let result = self
+            .with_retry("rename_media_file", || {
+                sqlx::query("UPDATE media_files SET path = ?, filename = ?, updated_at = ? WHERE path = ?")
+                    .bind(&to_str)
+                    .bind(&filename)
+                    .bind(updated_timestamp)
+                    .bind(&from_str)
+                    .execute(&pool)
+            })
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
     async fn get_files_in_directory(&self, dir: &Path) -> Result<Vec<MediaFile>> {
-        let dir_str = format!("{}%", dir.to_string_lossy());
+        let (lower, upper) = Self::child_range(dir);
 
         let rows = sqlx::query(
             r#"
-            SELECT id, path, filename, size, modified, mime_type, duration, title, artist, album, created_at, updated_at 
+            SELECT id, path, filename, size, modified, mime_type, duration, title, artist, album, width, height, created_at, updated_at 
             FROM media_files 
-            WHERE path LIKE ?
+            WHERE path >= ? AND path < ?
             ORDER BY filename
             "#,
         )
-        .bind(&dir_str)
-        .fetch_all(&self.pool)
+        .bind(&lower)
+        .bind(&upper)
+        .fetch_all(&self.pool())
         .await?;
 
         let mut files = Vec::new();
@@ -383,6 +1361,29 @@ impl DatabaseManager for SqliteDatabase {
         Ok(files)
     }
 
+    async fn get_files_by_mime_prefix(&self, prefix: &str) -> Result<Vec<MediaFile>> {
+        // LIKE is case-insensitive, so the range lets SQLite seek the mime_type index
+        // while LIKE keeps the match exact for prefixes ending in any character
+        let Some(upper_bound) = prefix_upper_bound(prefix) else {
+            return self.get_all_media_files().await;
+        };
+        let rows = sqlx::query(
+            r#"
+            SELECT id, path, filename, size, modified, mime_type, duration, title, artist, album, width, height, created_at, updated_at
+            FROM media_files
+            WHERE mime_type >= ? AND mime_type < ? AND mime_type LIKE ? ESCAPE '\'
+            ORDER BY filename
+            "#,
+        )
+        .bind(prefix)
+        .bind(&upper_bound)
+        .bind(format!("{}%", escape_like(prefix)))
+        .fetch_all(&self.pool())
+        .await?;
+
+        rows.iter().map(MediaFile::from_row).collect()
+    }
+
     async fn cleanup_missing_files(&self, existing_paths: &[PathBuf]) -> Result<usize> {
         if existing_paths.is_empty() {
             // If no existing paths provided, don't remove anything
@@ -391,7 +1392,7 @@ impl DatabaseManager for SqliteDatabase {
 
         let existing_paths: Vec<String> = existing_paths
             .iter()
-            .map(|p| p.to_string_lossy().to_string())
+            .map(|p| Self::path_to_db_string(p))
             .collect();
 
         // Create placeholders for the IN clause
@@ -407,23 +1408,41 @@ impl DatabaseManager for SqliteDatabase {
             query_builder = query_builder.bind(path);
         }
 
-        let result = query_builder.execute(&self.pool).await?;
+        let result = query_builder.execute(&self.pool()).await?;
 
         Ok(result.rows_affected() as usize)
     }
 
     async fn get_file_by_path(&self, path: &Path) -> Result<Option<MediaFile>> {
-        let path_str = path.to_string_lossy().to_string();
+        let path_str = Self::path_to_db_string(path);
 
         let row = sqlx::query(
             r#"
-            SELECT id, path, filename, size, modified, mime_type, duration, title, artist, album, created_at, updated_at 
+            SELECT id, path, filename, size, modified, mime_type, duration, title, artist, album, width, height, created_at, updated_at 
             FROM media_files 
             WHERE path = ?
             "#,
         )
         .bind(&path_str)
-        .fetch_optional(&self.pool)
+        .fetch_optional(&self.pool())
+        .await?;
+
+        match row {
+            Some(row) => Ok(Some(MediaFile::from_row(&row)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn get_file_by_id(&self, id: i64) -> Result<Option<MediaFile>> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, path, filename, size, modified, mime_type, duration, title, artist, album, width, height, created_at, updated_at 
+            FROM media_files 
+            WHERE id = ?
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool())
         .await?;
 
         match row {
@@ -432,20 +1451,308 @@ impl DatabaseManager for SqliteDatabase {
         }
     }
 
+    async fn get_files_by_ids(&self, ids: &[i64]) -> Result<Vec<MediaFile>> {
+        let mut files = Vec::with_capacity(ids.len());
+        for chunk in ids.chunks(BATCH_LOOKUP_PARAMS) {
+            let mut query = QueryBuilder::<Sqlite>::new(
+                "SELECT id, path, filename, size, modified, mime_type, duration, title, artist, album, width, height, created_at, updated_at \
+                 FROM media_files WHERE id IN (",
+            );
+            let mut values = query.separated(", ");
+            for id in chunk {
+                values.push_bind(*id);
+            }
+            query.push(")");
+            for row in query.build().fetch_all(&self.pool()).await? {
+                files.push(MediaFile::from_row(&row)?);
+            }
+        }
+        Ok(files)
+    }
+
+    async fn get_file_ids_by_paths(&self, paths: &[PathBuf]) -> Result<HashMap<PathBuf, i64>> {
+        let requested: HashMap<String, &PathBuf> =
+            paths.iter().map(|path| (Self::path_to_db_string(path), path)).collect();
+        let stored: Vec<&String> = requested.keys().collect();
+        let mut ids = HashMap::with_capacity(paths.len());
+        for chunk in stored.chunks(BATCH_LOOKUP_PARAMS) {
+            let mut query = QueryBuilder::<Sqlite>::new("SELECT id, path FROM media_files WHERE path IN (");
+            let mut values = query.separated(", ");
+            for path in chunk {
+                values.push_bind(path.as_str());
+            }
+            query.push(")");
+            for row in query.build().fetch_all(&self.pool()).await? {
+                let path: String = row.try_get("path")?;
+                if let Some(requested) = requested.get(&path) {
+                    ids.insert((*requested).clone(), row.try_get("id")?);
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    async fn search_media_files(
+        &self,
+        filter: &MediaFilter,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Result<(Vec<MediaFile>, usize)> {
+        let mut params = Vec::new();
+        let condition = filter.to_sql(SqlDialect::Sqlite, &mut params);
+
+        let count_sql = format!("SELECT COUNT(*) FROM media_files WHERE {}", condition);
+        let mut count = sqlx::query_scalar::<_, i64>(&count_sql);
+        for param in &params {
+            count = count.bind(param);
+        }
+        let total = count.fetch_one(&self.pool()).await? as usize;
+
+        let sql = format!(
+            r#"
+            SELECT id, path, filename, size, modified, mime_type, duration, title, artist, album, width, height, created_at, updated_at
+            FROM media_files
+            WHERE {}
+            ORDER BY filename
+            LIMIT ? OFFSET ?
+            "#,
+            condition
+        );
+        let mut query = sqlx::query(&sql);
+        for param in &params {
+            query = query.bind(param);
+        }
+        // A negative LIMIT means no limit in SQLite
+        let rows = query
+            .bind(limit.map_or(-1, |l| l as i64))
+            .bind(offset as i64)
+            .fetch_all(&self.pool())
+            .await?;
+
+        let files = rows.iter().map(MediaFile::from_row).collect::<Result<Vec<_>>>()?;
+        Ok((files, total))
+    }
+
+    async fn get_media_files_paged(
+        &self,
+        offset: usize,
+        limit: usize,
+        order: MediaOrder,
+    ) -> Result<(Vec<MediaFile>, usize)> {
+        let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM media_files")
+            .fetch_one(&self.pool())
+            .await?;
+
+        let sql = format!(
+            r#"
+            SELECT id, path, filename, size, modified, mime_type, duration, title, artist, album, width, height, created_at, updated_at
+            FROM media_files
+            ORDER BY {}
+            LIMIT ? OFFSET ?
+            "#,
+            order.sql()
+        );
+        let rows = sqlx::query(&sql)
+            .bind(limit.min(i64::MAX as usize) as i64)
+            .bind(offset as i64)
+            .fetch_all(&self.pool())
+            .await?;
+
+        let files = rows.iter().map(MediaFile::from_row).collect::<Result<Vec<_>>>()?;
+        Ok((files, total as usize))
+    }
+
+    async fn browse_directory(
+        &self,
+        dir: &Path,
+        mime_prefix: &str,
+        criteria: &[SortCriterion],
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Result<DirectoryPage> {
+        let (lower, upper) = Self::child_range(dir);
+        // SQLite measures and slices strings in characters
+        let start = lower.chars().count() as i64 + 1;
+        let separator = lower.chars().last().unwrap_or(std::path::MAIN_SEPARATOR).to_string();
+        let mime_pattern = format!("{}%", escape_like(mime_prefix));
+        let in_directory = "path >= ? AND path < ? AND mime_type LIKE ? ESCAPE '\\'";
+
+        let mut subdirectories: Vec<String> = sqlx::query_scalar(&format!(
+            "SELECT DISTINCT substr(path, ?, instr(substr(path, ?), ?) - 1) FROM media_files \
+             WHERE {} AND instr(substr(path, ?), ?) > 0",
+            in_directory
+        ))
+        .bind(start)
+        .bind(start)
+        .bind(&separator)
+        .bind(&lower)
+        .bind(&upper)
+        .bind(&mime_pattern)
+        .bind(start)
+        .bind(&separator)
+        .fetch_all(&self.pool())
+        .await?;
+        sort_subdirectories(&mut subdirectories, criteria);
+
+        let direct_files = format!("{} AND instr(substr(path, ?), ?) = 0", in_directory);
+        let file_count: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM media_files WHERE {}", direct_files))
+            .bind(&lower)
+            .bind(&upper)
+            .bind(&mime_pattern)
+            .bind(start)
+            .bind(&separator)
+            .fetch_one(&self.pool())
+            .await?;
+
+        let total = subdirectories.len() + file_count as usize;
+        let (subdirectory_offset, file_offset, file_limit) = DirectoryPage::split_window(subdirectories.len(), offset, limit);
+        let files = if file_limit == Some(0) {
+            Vec::new()
+        } else {
+            let sql = format!(
+                r#"
+                SELECT id, path, filename, size, modified, mime_type, duration, title, artist, album, width, height, created_at, updated_at
+                FROM media_files
+                WHERE {}
+                ORDER BY {}
+                LIMIT ? OFFSET ?
+                "#,
+                direct_files,
+                criteria_order_sql(criteria, SqlDialect::Sqlite)
+            );
+            // A negative LIMIT means no limit in SQLite
+            let rows = sqlx::query(&sql)
+                .bind(&lower)
+                .bind(&upper)
+                .bind(&mime_pattern)
+                .bind(start)
+                .bind(&separator)
+                .bind(file_limit.map_or(-1, |l| l.min(i64::MAX as usize) as i64))
+                .bind(file_offset as i64)
+                .fetch_all(&self.pool())
+                .await?;
+            rows.iter().map(MediaFile::from_row).collect::<Result<Vec<_>>>()?
+        };
+
+        Ok(DirectoryPage {
+            subdirectories: subdirectories
+                .into_iter()
+                .skip(subdirectory_offset)
+                .take(limit.unwrap_or(usize::MAX))
+                .collect(),
+            files,
+            total,
+        })
+    }
+
+    async fn search_text(&self, query: &str, limit: usize) -> Result<Vec<MediaFile>> {
+        // Every word must match as a prefix; quoting keeps FTS5 syntax out of user input
+        let terms = text_search_terms(query);
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+        let pattern = terms.iter().map(|term| format!("\"{}\"*", term)).collect::<Vec<_>>().join(" ");
+
+        let rows = sqlx::query(
+            r#"
+            SELECT m.id, m.path, m.filename, m.size, m.modified, m.mime_type, m.duration, m.title, m.artist, m.album,
+                   m.width, m.height, m.created_at, m.updated_at
+            FROM media_fts
+            JOIN media_files m ON m.id = media_fts.rowid
+            WHERE media_fts MATCH ?
+            ORDER BY media_fts.rank
+            LIMIT ?
+            "#,
+        )
+        .bind(pattern)
+        .bind(limit as i64)
+        .fetch_all(&self.pool())
+        .await?;
+
+        rows.iter().map(MediaFile::from_row).collect()
+    }
+
+    async fn get_music_artists(&self) -> Result<Vec<ArtistSummary>> {
+        let rows = sqlx::query(
+            r#"
+            -- Untagged tracks form one more album besides the distinct album names
+            SELECT artist, COUNT(DISTINCT album) + MAX(album IS NULL) AS album_count
+            FROM media_files
+            WHERE mime_type LIKE 'audio/%'
+            GROUP BY artist
+            ORDER BY artist IS NULL, LOWER(artist)
+            "#,
+        )
+        .fetch_all(&self.pool())
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(ArtistSummary {
+                    name: row.try_get("artist")?,
+                    album_count: row.try_get::<i64, _>("album_count")? as usize,
+                })
+            })
+            .collect()
+    }
+
+    async fn get_artist_albums(&self, artist: Option<&str>) -> Result<Vec<AlbumSummary>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT album, COUNT(*) AS track_count
+            FROM media_files
+            WHERE mime_type LIKE 'audio/%' AND artist IS ?
+            GROUP BY album
+            ORDER BY album IS NULL, LOWER(album)
+            "#,
+        )
+        .bind(artist)
+        .fetch_all(&self.pool())
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(AlbumSummary {
+                    name: row.try_get("album")?,
+                    track_count: row.try_get::<i64, _>("track_count")? as usize,
+                })
+            })
+            .collect()
+    }
+
+    async fn get_album_tracks(&self, artist: Option<&str>, album: Option<&str>) -> Result<Vec<MediaFile>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, path, filename, size, modified, mime_type, duration, title, artist, album, width, height, created_at, updated_at
+            FROM media_files
+            WHERE mime_type LIKE 'audio/%' AND artist IS ? AND album IS ?
+            ORDER BY filename
+            "#,
+        )
+        .bind(artist)
+        .bind(album)
+        .fetch_all(&self.pool())
+        .await?;
+
+        rows.iter().map(MediaFile::from_row).collect()
+    }
+
     async fn get_stats(&self) -> Result<DatabaseStats> {
         // Get total files and size
         let row = sqlx::query("SELECT COUNT(*), COALESCE(SUM(size), 0) FROM media_files")
-            .fetch_one(&self.pool)
+            .fetch_one(&self.pool())
             .await?;
 
         let total_files: i64 = row.try_get(0)?;
         let total_size: i64 = row.try_get(1)?;
 
-        // Get database file size
-        let database_size = tokio::fs::metadata(&self.db_path)
-            .await
-            .map(|m| m.len())
-            .unwrap_or(0);
+        // Get database file size, counting changes not yet checkpointed from the WAL
+        let mut wal_path = self.db_path.clone().into_os_string();
+        wal_path.push("-wal");
+        let mut database_size = 0;
+        for path in [self.db_path.as_os_str(), wal_path.as_os_str()] {
+            database_size += tokio::fs::metadata(path).await.map(|m| m.len()).unwrap_or(0);
+        }
 
         Ok(DatabaseStats {
             total_files: total_files as usize,
@@ -454,6 +1761,39 @@ impl DatabaseManager for SqliteDatabase {
         })
     }
 
+    async fn get_stats_detailed(&self) -> Result<DetailedDatabaseStats> {
+        let mut detailed = DetailedDatabaseStats::new(self.get_stats().await?);
+
+        // Group by top-level MIME type so the category split happens in SQLite
+        let rows = sqlx::query(
+            r#"
+            SELECT substr(mime_type, 1, instr(mime_type, '/')) AS category,
+                   COUNT(*), COALESCE(SUM(size), 0)
+            FROM media_files
+            GROUP BY category
+            "#,
+        )
+        .fetch_all(&self.pool())
+        .await?;
+
+        for row in rows {
+            let category: String = row.try_get(0)?;
+            let file_count: i64 = row.try_get(1)?;
+            let total_size: i64 = row.try_get(2)?;
+            detailed.record(&category, file_count as usize, total_size as u64);
+        }
+
+        let row = sqlx::query("SELECT MIN(modified), MAX(modified) FROM media_files")
+            .fetch_one(&self.pool())
+            .await?;
+        let oldest: Option<i64> = row.try_get(0)?;
+        let newest: Option<i64> = row.try_get(1)?;
+        detailed.oldest_modified = oldest.map(|ts| SystemTime::UNIX_EPOCH + Duration::from_secs(ts as u64));
+        detailed.newest_modified = newest.map(|ts| SystemTime::UNIX_EPOCH + Duration::from_secs(ts as u64));
+
+        Ok(detailed)
+    }
+
     async fn check_and_repair(&self) -> Result<DatabaseHealth> {
         let mut health = DatabaseHealth {
             is_healthy: true,
@@ -477,6 +1817,7 @@ impl DatabaseManager for SqliteDatabase {
                         table_affected: None,
                         suggested_action: "Attempt database repair or restore from backup"
                             .to_string(),
+                        rows_affected: None,
                     });
                 }
             }
@@ -487,27 +1828,52 @@ impl DatabaseManager for SqliteDatabase {
                     description: format!("Failed to run integrity check: {}", e),
                     table_affected: None,
                     suggested_action: "Check database file permissions and disk space".to_string(),
+                    rows_affected: None,
                 });
             }
         }
 
         // Check for common issues
-        if let Err(e) = self.check_common_issues(&mut health).await {
-            health.issues.push(DatabaseIssue {
-                severity: IssueSeverity::Warning,
-                description: format!("Error during common issues check: {}", e),
-                table_affected: None,
-                suggested_action: "Review database configuration".to_string(),
-            });
+        let mut repairable_rows_found = false;
+        match self.check_common_issues(&mut health).await {
+            Ok(found) => repairable_rows_found = found,
+            Err(e) => {
+                health.issues.push(DatabaseIssue {
+                    severity: IssueSeverity::Warning,
+                    description: format!("Error during common issues check: {}", e),
+                    table_affected: None,
+                    suggested_action: "Review database configuration".to_string(),
+                    rows_affected: None,
+                });
+            }
         }
 
-        // Attempt repair if corruption detected
-        if health.corruption_detected {
+        // Attempt repair if corruption or invalid rows were detected
+        if health.corruption_detected || repairable_rows_found {
             health.repair_attempted = true;
             match self.attempt_repair().await {
-                Ok(success) => {
-                    health.repair_successful = success;
-                    if success {
+                Ok(report) => {
+                    if report.orphaned_removed > 0 {
+                        health.issues.push(DatabaseIssue {
+                            severity: IssueSeverity::Info,
+                            description: format!("Removed {} orphaned records", report.orphaned_removed),
+                            table_affected: Some("media_files".to_string()),
+                            suggested_action: "No action needed".to_string(),
+                            rows_affected: Some(report.orphaned_removed),
+                        });
+                    }
+                    if report.duplicates_removed > 0 {
+                        health.issues.push(DatabaseIssue {
+                            severity: IssueSeverity::Info,
+                            description: format!("Removed {} duplicate entries", report.duplicates_removed),
+                            table_affected: Some("media_files".to_string()),
+                            suggested_action: "No action needed".to_string(),
+                            rows_affected: Some(report.duplicates_removed),
+                        });
+                    }
+
+                    health.repair_successful = report.integrity_ok;
+                    if report.integrity_ok && health.corruption_detected {
                         health.is_healthy = true;
                         health.corruption_detected = false;
                         health.issues.push(DatabaseIssue {
@@ -515,6 +1881,7 @@ impl DatabaseManager for SqliteDatabase {
                             description: "Database successfully repaired".to_string(),
                             table_affected: None,
                             suggested_action: "Consider creating a backup".to_string(),
+                            rows_affected: None,
                         });
                     }
                 }
@@ -524,14 +1891,20 @@ impl DatabaseManager for SqliteDatabase {
                         description: format!("Database repair failed: {}", e),
                         table_affected: None,
                         suggested_action: "Restore from backup or recreate database".to_string(),
+                        rows_affected: None,
                     });
                 }
             }
         }
 
+        *self.last_health.write().unwrap() = Some(health.clone());
         Ok(health)
     }
 
+    fn last_health(&self) -> Option<DatabaseHealth> {
+        self.last_health.read().unwrap().clone()
+    }
+
     async fn create_backup(&self, backup_path: &Path) -> Result<()> {
         // Ensure backup directory exists
         if let Some(parent) = backup_path.parent() {
@@ -542,7 +1915,7 @@ impl DatabaseManager for SqliteDatabase {
         let backup_path_str = backup_path.to_string_lossy().to_string();
 
         sqlx::query(&format!("VACUUM INTO '{}'", backup_path_str))
-            .execute(&self.pool)
+            .execute(&self.pool())
             .await?;
 
         // Verify backup was created successfully
@@ -596,147 +1969,51 @@ impl DatabaseManager for SqliteDatabase {
             ));
         }
 
-        // Close current connection
-        self.pool.close().await;
+        // Close the live pool; this waits for connections in use to be returned
+        self.pool().close().await;
+
+        // A leftover write-ahead log belongs to the old database and must not be
+        // replayed onto the restored one
+        for suffix in ["-wal", "-shm"] {
+            let mut sidecar = self.db_path.clone().into_os_string();
+            sidecar.push(suffix);
+            match tokio::fs::remove_file(&sidecar).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
 
         // Replace current database with backup
-        tokio::fs::copy(backup_path, &self.db_path).await?;
-
-        // Reconnect to restored database
-        let database_url = format!("sqlite://{}?mode=rwc", self.db_path.display());
-        let new_pool = SqlitePool::connect(&database_url).await?;
-
-        // Configure SQLite for better performance
-        sqlx::query("PRAGMA foreign_keys = ON")
-            .execute(&new_pool)
-            .await?;
-        sqlx::query("PRAGMA journal_mode = WAL")
-            .execute(&new_pool)
-            .await?;
-        sqlx::query("PRAGMA synchronous = NORMAL")
-            .execute(&new_pool)
-            .await?;
-        sqlx::query("PRAGMA cache_size = 10000")
-            .execute(&new_pool)
-            .await?;
-        sqlx::query("PRAGMA temp_store = MEMORY")
-            .execute(&new_pool)
-            .await?;
+        let copied = tokio::fs::copy(backup_path, &self.db_path).await;
 
-        // Note: We can't replace self.pool here due to borrowing rules
-        // In a real implementation, this would require restructuring or using Arc<Mutex<>>
+        // Reconnect even if the copy failed, so the database stays usable, and
+        // bring the restored schema up to date
+        let new_pool = SqlitePool::connect_with(self.connect_options.clone()).await?;
+        *self.pool.write().unwrap() = new_pool;
+        copied.with_context(|| format!("Failed to copy backup {}", backup_path.display()))?;
+        self.initialize().await?;
 
         Ok(())
     }
 
     async fn vacuum(&self) -> Result<()> {
-        sqlx::query("VACUUM").execute(&self.pool).await?;
-
-        Ok(())
-    }
-}
-
-impl SqliteDatabase {
-    /// Run SQLite integrity check
-    async fn run_integrity_check(&self) -> Result<bool> {
-        let result = sqlx::query_scalar::<_, String>("PRAGMA integrity_check")
-            .fetch_one(&self.pool)
-            .await?;
-
-        Ok(result == "ok")
-    }
-
-    /// Check for common database issues
-    async fn check_common_issues(&self, health: &mut DatabaseHealth) -> Result<()> {
-        // Check for orphaned records or inconsistencies
-        let orphaned_count: i64 =
-            sqlx::query_scalar("SELECT COUNT(*) FROM media_files WHERE path = '' OR filename = ''")
-                .fetch_one(&self.pool)
-                .await?;
-
-        if orphaned_count > 0 {
-            health.issues.push(DatabaseIssue {
-                severity: IssueSeverity::Warning,
-                description: format!("Found {} records with empty path or filename", orphaned_count),
-                table_affected: Some("media_files".to_string()),
-                suggested_action: "Clean up orphaned records".to_string(),
-            });
-        }
-
-        // Check for duplicate paths
-        let duplicate_count: i64 = sqlx::query_scalar(
-            "SELECT COUNT(*) FROM (SELECT path FROM media_files GROUP BY path HAVING COUNT(*) > 1)",
-        )
-        .fetch_one(&self.pool)
-        .await?;
-
-        if duplicate_count > 0 {
-            health.issues.push(DatabaseIssue {
-                severity: IssueSeverity::Warning,
-                description: format!("Found {} duplicate file paths", duplicate_count),
-                table_affected: Some("media_files".to_string()),
-                suggested_action: "Remove duplicate entries".to_string(),
-            });
-        }
-
-        // Check database size vs file count ratio
-        let stats = self.get_stats().await?;
-        if stats.total_files > 0 {
-            let avg_db_size_per_file = stats.database_size / stats.total_files as u64;
-            if avg_db_size_per_file > 10000 {
-                // More than 10KB per file record seems excessive
-                health.issues.push(DatabaseIssue {
-                    severity: IssueSeverity::Info,
-                    description: "Database size seems large relative to file count".to_string(),
-                    table_affected: None,
-                    suggested_action: "Consider running VACUUM to optimize database".to_string(),
-                });
-            }
-        }
+        sqlx::query("VACUUM").execute(&self.pool()).await?;
+        // The compacted database is written through the WAL; checkpoint it so the file shrinks now
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)").execute(&self.pool()).await?;
 
         Ok(())
     }
 
-    /// Attempt to repair database corruption
-    async fn attempt_repair(&self) -> Result<bool> {
-        // Try to clean up orphaned records
-        sqlx::query("DELETE FROM media_files WHERE path = '' OR filename = ''")
-            .execute(&self.pool)
-            .await?;
-
-        // Remove duplicates, keeping the most recent
-        sqlx::query(
-            r#"
-            DELETE FROM media_files 
-            WHERE id NOT IN (
-                SELECT MAX(id) 
-                FROM media_files 
-                GROUP BY path
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await?;
-
-        // Try to rebuild indexes
-        sqlx::query("REINDEX").execute(&self.pool).await?;
-
-        // Run integrity check again
-        self.run_integrity_check().await
-    }
-
-    /// Clean up orphaned and invalid records
-    pub async fn cleanup_invalid_records(&self) -> Result<usize> {
+    async fn cleanup_invalid_records(&self) -> Result<usize> {
         let result =
             sqlx::query("DELETE FROM media_files WHERE path = '' OR filename = '' OR size < 0")
-                .execute(&self.pool)
+                .execute(&self.pool())
                 .await?;
 
         Ok(result.rows_affected() as usize)
     }
 
-    /// Remove duplicate file entries, keeping the most recent
-    pub async fn remove_duplicates(&self) -> Result<usize> {
+    async fn remove_duplicates(&self) -> Result<usize> {
         let result = sqlx::query(
             r#"
             DELETE FROM media_files 
@@ -747,14 +2024,388 @@ impl SqliteDatabase {
             )
             "#,
         )
-        .execute(&self.pool)
+        .execute(&self.pool())
         .await?;
 
         Ok(result.rows_affected() as usize)
     }
-}
 
-#[cfg(test)]
+    async fn replace_tv_episodes(&self, episodes: &[(i64, EpisodeInfo)]) -> Result<()> {
+        let mut tx = self.pool().begin().await?;
+
+        // Upsert shows rather than recreating them so their ids, and the
+        // ObjectIDs clients hold, survive a refresh
+        let mut show_ids: HashMap<(PathBuf, String), i64> = HashMap::new();
+        for (_, info) in episodes {
+            let key = (info.show_path.clone(), info.show_title.clone());
+            if show_ids.contains_key(&key) {
+                continue;
+            }
+            let path_str = Self::path_to_db_string(&info.show_path);
+            sqlx::query("INSERT INTO tv_shows (title, path) VALUES (?, ?) ON CONFLICT(path, title) DO NOTHING")
+                .bind(&info.show_title)
+                .bind(&path_str)
+                .execute(&mut *tx)
+                .await?;
+            let show_id: i64 = sqlx::query_scalar("SELECT id FROM tv_shows WHERE path = ? AND title = ?")
+                .bind(&path_str)
+                .bind(&info.show_title)
+                .fetch_one(&mut *tx)
+                .await?;
+            show_ids.insert(key, show_id);
+        }
+
+        sqlx::query("DELETE FROM tv_episodes").execute(&mut *tx).await?;
+        for (media_file_id, info) in episodes {
+            let show_id = show_ids[&(info.show_path.clone(), info.show_title.clone())];
+            // Files removed since detection ran are skipped rather than failing the refresh
+            sqlx::query(
+                r#"
+                INSERT INTO tv_episodes (media_file_id, show_id, season, episode, title)
+                SELECT ?, ?, ?, ?, ? WHERE EXISTS (SELECT 1 FROM media_files WHERE id = ?)
+                "#,
+            )
+            .bind(media_file_id)
+            .bind(show_id)
+            .bind(info.season as i64)
+            .bind(info.episode as i64)
+            .bind(&info.title)
+            .bind(media_file_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        sqlx::query("DELETE FROM tv_shows WHERE id NOT IN (SELECT show_id FROM tv_episodes)")
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_tv_episodes(&self) -> Result<Vec<TvEpisode>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT s.id AS show_id, s.title AS show_title, s.path AS show_path,
+                   e.season, e.episode, e.title AS episode_title,
+                   m.id, m.path, m.filename, m.size, m.modified, m.mime_type, m.duration,
+                   m.title, m.artist, m.album, m.width, m.height, m.created_at, m.updated_at
+            FROM tv_episodes e
+            JOIN tv_shows s ON s.id = e.show_id
+            JOIN media_files m ON m.id = e.media_file_id
+            ORDER BY s.title, e.season, e.episode
+            "#,
+        )
+        .fetch_all(&self.pool())
+        .await?;
+
+        let mut episodes = Vec::new();
+        for row in rows {
+            let show_path: String = row.try_get("show_path")?;
+            episodes.push(TvEpisode {
+                show_id: row.try_get("show_id")?,
+                info: EpisodeInfo {
+                    show_title: row.try_get("show_title")?,
+                    show_path: PathBuf::from(show_path),
+                    season: row.try_get::<i64, _>("season")? as u32,
+                    episode: row.try_get::<i64, _>("episode")? as u32,
+                    title: row.try_get("episode_title")?,
+                },
+                file: MediaFile::from_row(&row)?,
+            });
+        }
+
+        Ok(episodes)
+    }
+
+    async fn replace_subtitles(&self, media_file_id: i64, subtitles: &[Subtitle]) -> Result<()> {
+        let mut tx = self.pool().begin().await?;
+        sqlx::query("DELETE FROM subtitles WHERE media_file_id = ?")
+            .bind(media_file_id)
+            .execute(&mut *tx)
+            .await?;
+        for subtitle in subtitles {
+            sqlx::query("INSERT INTO subtitles (media_file_id, path, language, mime_type) VALUES (?, ?, ?, ?)")
+                .bind(media_file_id)
+                .bind(Self::path_to_db_string(&subtitle.path))
+                .bind(&subtitle.language)
+                .bind(&subtitle.mime_type)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_all_subtitles(&self) -> Result<Vec<Subtitle>> {
+        let rows = sqlx::query("SELECT id, media_file_id, path, language, mime_type FROM subtitles ORDER BY media_file_id, path")
+            .fetch_all(&self.pool())
+            .await?;
+        rows.iter().map(Self::subtitle_from_row).collect()
+    }
+
+    async fn get_subtitles(&self, media_file_id: i64) -> Result<Vec<Subtitle>> {
+        let rows = sqlx::query("SELECT id, media_file_id, path, language, mime_type FROM subtitles WHERE media_file_id = ? ORDER BY path")
+            .bind(media_file_id)
+            .fetch_all(&self.pool())
+            .await?;
+        rows.iter().map(Self::subtitle_from_row).collect()
+    }
+
+    async fn get_subtitle_by_id(&self, id: i64) -> Result<Option<Subtitle>> {
+        let row = sqlx::query("SELECT id, media_file_id, path, language, mime_type FROM subtitles WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool())
+            .await?;
+        row.as_ref().map(Self::subtitle_from_row).transpose()
+    }
+
+    async fn store_playlist(&self, playlist: &Playlist) -> Result<i64> {
+        let mut tx = self.pool().begin().await?;
+        let id: i64 = sqlx::query_scalar(
+            "INSERT INTO playlists (path, title) VALUES (?, ?)
+             ON CONFLICT(path) DO UPDATE SET title = excluded.title
+             RETURNING id",
+        )
+        .bind(Self::path_to_db_string(&playlist.path))
+        .bind(&playlist.title)
+        .fetch_one(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM playlist_entries WHERE playlist_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        for (position, entry) in playlist.entries.iter().enumerate() {
+            sqlx::query("INSERT INTO playlist_entries (playlist_id, position, path) VALUES (?, ?, ?)")
+                .bind(id)
+                .bind(position as i64)
+                .bind(Self::path_to_db_string(entry))
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    async fn remove_playlist(&self, path: &Path) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM playlists WHERE path = ?")
+            .bind(Self::path_to_db_string(path))
+            .execute(&self.pool())
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_playlists(&self) -> Result<Vec<Playlist>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT p.id, p.path, p.title, e.path AS entry
+            FROM playlists p
+            LEFT JOIN playlist_entries e ON e.playlist_id = p.id
+            ORDER BY p.id, e.position
+            "#,
+        )
+        .fetch_all(&self.pool())
+        .await?;
+
+        let mut playlists: Vec<Playlist> = Vec::new();
+        for row in rows {
+            let id: i64 = row.try_get("id")?;
+            if playlists.last().is_none_or(|playlist| playlist.id != Some(id)) {
+                let path: String = row.try_get("path")?;
+                playlists.push(Playlist {
+                    id: Some(id),
+                    path: PathBuf::from(path),
+                    title: row.try_get("title")?,
+                    entries: Vec::new(),
+                });
+            }
+            if let Some(entry) = row.try_get::<Option<String>, _>("entry")? {
+                playlists.last_mut().unwrap().entries.push(PathBuf::from(entry));
+            }
+        }
+        Ok(playlists)
+    }
+
+    async fn get_system_update_id(&self) -> Result<Option<u32>> {
+        let value: Option<String> =
+            sqlx::query_scalar("SELECT value FROM database_metadata WHERE key = 'system_update_id'")
+                .fetch_optional(&self.pool())
+                .await?;
+        value
+            .map(|value| value.parse().with_context(|| format!("invalid system_update_id {:?}", value)))
+            .transpose()
+    }
+
+    async fn set_system_update_id(&self, update_id: u32) -> Result<()> {
+        let updated_timestamp = Self::system_time_to_timestamp(SystemTime::now());
+        sqlx::query(
+            "INSERT INTO database_metadata (key, value, updated_at) VALUES ('system_update_id', ?, ?)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+        )
+        .bind(update_id.to_string())
+        .bind(updated_timestamp)
+        .execute(&self.pool())
+        .await?;
+        Ok(())
+    }
+
+    async fn next_boot_id(&self) -> Result<u32> {
+        let previous: Option<String> = sqlx::query_scalar("SELECT value FROM database_metadata WHERE key = 'boot_id'")
+            .fetch_optional(&self.pool())
+            .await?;
+        let boot_id = following_boot_id(previous.and_then(|value| value.parse().ok()));
+        sqlx::query(
+            "INSERT INTO database_metadata (key, value, updated_at) VALUES ('boot_id', ?, ?)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+        )
+        .bind(boot_id.to_string())
+        .bind(Self::system_time_to_timestamp(SystemTime::now()))
+        .execute(&self.pool())
+        .await?;
+        Ok(boot_id)
+    }
+}
+
+impl SqliteDatabase {
+    /// Upsert `files` in one transaction, returning the id stored under each path
+    async fn insert_media_batch(&self, files: &[MediaFile]) -> Result<HashMap<String, i64>, sqlx::Error> {
+        let mut ids_by_path = HashMap::with_capacity(files.len());
+        let mut tx = self.pool().begin().await?;
+        for chunk in files.chunks(BATCH_INSERT_ROWS) {
+            let mut query = QueryBuilder::<Sqlite>::new(
+                "INSERT INTO media_files \
+                 (path, filename, size, modified, mime_type, duration, title, artist, album, width, height, created_at, updated_at) ",
+            );
+            query.push_values(chunk, |mut row, file| {
+                row.push_bind(Self::path_to_db_string(&file.path))
+                    .push_bind(&file.filename)
+                    .push_bind(file.size as i64)
+                    .push_bind(Self::system_time_to_timestamp(file.modified))
+                    .push_bind(&file.mime_type)
+                    .push_bind(file.duration.map(|d| d.as_millis() as i64))
+                    .push_bind(&file.title)
+                    .push_bind(&file.artist)
+                    .push_bind(&file.album)
+                    .push_bind(file.width.map(i64::from))
+                    .push_bind(file.height.map(i64::from))
+                    .push_bind(Self::system_time_to_timestamp(file.created_at))
+                    .push_bind(Self::system_time_to_timestamp(file.updated_at));
+            });
+            // Re-scanned files keep their id and creation time
+            query.push(
+                " ON CONFLICT(path) DO UPDATE SET \
+                 filename = excluded.filename, size = excluded.size, modified = excluded.modified, \
+                 mime_type = excluded.mime_type, duration = excluded.duration, title = excluded.title, \
+                 artist = excluded.artist, album = excluded.album, width = excluded.width, \
+                 height = excluded.height, updated_at = excluded.updated_at \
+                 RETURNING id, path",
+            );
+            // RETURNING rows come back in no particular order, so match them up by path
+            for row in query.build().fetch_all(&mut *tx).await? {
+                let path: String = row.try_get("path")?;
+                ids_by_path.insert(path, row.try_get::<i64, _>("id")?);
+            }
+        }
+        tx.commit().await?;
+        Ok(ids_by_path)
+    }
+
+    fn subtitle_from_row(row: &sqlx::sqlite::SqliteRow) -> Result<Subtitle> {
+        let path: String = row.try_get("path")?;
+        Ok(Subtitle {
+            id: Some(row.try_get("id")?),
+            media_file_id: row.try_get("media_file_id")?,
+            path: PathBuf::from(path),
+            language: row.try_get("language")?,
+            mime_type: row.try_get("mime_type")?,
+        })
+    }
+
+    /// Run SQLite integrity check
+    async fn run_integrity_check(&self) -> Result<bool> {
+        let result = sqlx::query_scalar::<_, String>("PRAGMA integrity_check")
+            .fetch_one(&self.pool())
+            .await?;
+
+        Ok(result == "ok")
+    }
+
+    /// Check for common database issues, returning whether rows that repair can fix were found
+    async fn check_common_issues(&self, health: &mut DatabaseHealth) -> Result<bool> {
+        // Check for orphaned records or inconsistencies
+        let orphaned_count: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM media_files WHERE path = '' OR filename = ''")
+                .fetch_one(&self.pool())
+                .await?;
+
+        if orphaned_count > 0 {
+            health.issues.push(DatabaseIssue {
+                severity: IssueSeverity::Warning,
+                description: format!("Found {} records with empty path or filename", orphaned_count),
+                table_affected: Some("media_files".to_string()),
+                suggested_action: "Clean up orphaned records".to_string(),
+                rows_affected: None,
+            });
+        }
+
+        // Check for duplicate paths
+        let duplicate_count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM (SELECT path FROM media_files GROUP BY path HAVING COUNT(*) > 1)",
+        )
+        .fetch_one(&self.pool())
+        .await?;
+
+        if duplicate_count > 0 {
+            health.issues.push(DatabaseIssue {
+                severity: IssueSeverity::Warning,
+                description: format!("Found {} duplicate file paths", duplicate_count),
+                table_affected: Some("media_files".to_string()),
+                suggested_action: "Remove duplicate entries".to_string(),
+                rows_affected: None,
+            });
+        }
+
+        // Check database size vs file count ratio
+        let stats = self.get_stats().await?;
+        if stats.total_files > 0 {
+            let avg_db_size_per_file = stats.database_size / stats.total_files as u64;
+            if avg_db_size_per_file > 10000 {
+                // More than 10KB per file record seems excessive
+                health.issues.push(DatabaseIssue {
+                    severity: IssueSeverity::Info,
+                    description: "Database size seems large relative to file count".to_string(),
+                    table_affected: None,
+                    suggested_action: "Consider running VACUUM to optimize database".to_string(),
+                    rows_affected: None,
+                });
+            }
+        }
+
+        Ok(orphaned_count > 0 || duplicate_count > 0)
+    }
+
+    /// Attempt to repair database corruption
+    async fn attempt_repair(&self) -> Result<RepairReport> {
+        // Try to clean up orphaned records
+        let orphaned = sqlx::query("DELETE FROM media_files WHERE path = '' OR filename = ''")
+            .execute(&self.pool())
+            .await?;
+
+        // Remove duplicates, keeping the most recent
+        let duplicates_removed = self.remove_duplicates().await?;
+
+        // Try to rebuild indexes
+        sqlx::query("REINDEX").execute(&self.pool()).await?;
+
+        // Run integrity check again
+        Ok(RepairReport {
+            orphaned_removed: orphaned.rows_affected() as usize,
+            duplicates_removed,
+            integrity_ok: self.run_integrity_check().await?,
+        })
+    }
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
     use tempfile::tempdir;
@@ -828,6 +2479,82 @@ mod tests {
         assert!(not_found.is_none());
     }
 
+    #[tokio::test]
+    async fn test_update_media_file_inserts_missing_rows() {
+        let temp_dir = tempdir().unwrap();
+        let db = SqliteDatabase::new(temp_dir.path().join("test.db")).await.unwrap();
+        db.initialize().await.unwrap();
+
+        // Updating an existing row keeps its id
+        let id = db
+            .store_media_file(&MediaFile::new(PathBuf::from("/test/existing.mp4"), 1024, "video/mp4".to_string()))
+            .await
+            .unwrap();
+        let mut existing = db.get_file_by_id(id).await.unwrap().unwrap();
+        existing.size = 2048;
+        db.update_media_file(&existing).await.unwrap();
+        let updated = db.get_file_by_path(Path::new("/test/existing.mp4")).await.unwrap().unwrap();
+        assert_eq!((updated.id, updated.size), (Some(id), 2048));
+
+        // A row that is not there yet is inserted
+        let mut missing = MediaFile::new(PathBuf::from("/test/missing.mkv"), 4096, "video/x-matroska".to_string());
+        missing.title = Some("Late Arrival".to_string());
+        db.update_media_file(&missing).await.unwrap();
+        let inserted = db.get_file_by_path(Path::new("/test/missing.mkv")).await.unwrap().unwrap();
+        assert!(inserted.id.is_some_and(|inserted_id| inserted_id != id));
+        assert_eq!((inserted.size, inserted.title.as_deref()), (4096, Some("Late Arrival")));
+        assert_eq!(db.get_all_media_files().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_boot_id_advances_across_restarts() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+
+        let mut boot_ids = Vec::new();
+        for _ in 0..3 {
+            let db = SqliteDatabase::new(db_path.clone()).await.unwrap();
+            db.initialize().await.unwrap();
+            boot_ids.push(db.next_boot_id().await.unwrap());
+        }
+        assert_eq!(boot_ids, vec![1, 2, 3]);
+
+        assert_eq!(following_boot_id(Some(i32::MAX as u32)), 1);
+    }
+
+    #[tokio::test]
+    async fn test_batch_store_media_files_upserts_by_path() {
+        let temp_dir = tempdir().unwrap();
+        let db = SqliteDatabase::new(temp_dir.path().join("test.db")).await.unwrap();
+        db.initialize().await.unwrap();
+
+        // More rows than fit in one multi-row insert
+        let files: Vec<MediaFile> = (0..BATCH_INSERT_ROWS * 2 + 5)
+            .map(|i| MediaFile::new(PathBuf::from(format!("/music/track{:03}.mp3", i)), i as u64, "audio/mpeg".to_string()))
+            .collect();
+        let ids = db.batch_store_media_files(&files).await.unwrap();
+        assert_eq!(ids.len(), files.len());
+        assert_eq!(db.get_stats().await.unwrap().total_files, files.len());
+        let stored = db.get_file_by_path(Path::new("/music/track007.mp3")).await.unwrap().unwrap();
+        assert_eq!(stored.id, Some(ids[7]));
+        assert_eq!(stored.size, 7);
+
+        // A re-scan updates rows in place and keeps their ids
+        let mut rescanned = vec![files[7].clone(), MediaFile::new(PathBuf::from("/music/new.mp3"), 1, "audio/mpeg".to_string())];
+        rescanned[0].size = 4096;
+        rescanned[0].title = Some("Seven".to_string());
+        let new_ids = db.batch_store_media_files(&rescanned).await.unwrap();
+        assert_eq!(new_ids[0], ids[7]);
+        assert!(!ids.contains(&new_ids[1]));
+        assert_eq!(db.get_stats().await.unwrap().total_files, files.len() + 1);
+        let updated = db.get_file_by_id(ids[7]).await.unwrap().unwrap();
+        assert_eq!(updated.size, 4096);
+        assert_eq!(updated.title.as_deref(), Some("Seven"));
+        assert_eq!(db.search_text("Seven", 10).await.unwrap().len(), 1);
+
+        assert!(db.batch_store_media_files(&[]).await.unwrap().is_empty());
+    }
+
     #[tokio::test]
     async fn test_database_health_check() {
         let temp_dir = tempdir().unwrap();
@@ -888,12 +2615,12 @@ mod tests {
 
         // Manually insert invalid records
         sqlx::query("INSERT INTO media_files (path, filename, size, modified, mime_type, created_at, updated_at) VALUES ('', 'empty.mp4', 1024, 0, 'video/mp4', 0, 0)")
-            .execute(&db.pool)
+            .execute(&db.pool())
             .await
             .unwrap();
 
         sqlx::query("INSERT INTO media_files (path, filename, size, modified, mime_type, created_at, updated_at) VALUES ('/test/valid.mp4', '', 1024, 0, 'video/mp4', 0, 0)")
-            .execute(&db.pool)
+            .execute(&db.pool())
             .await
             .unwrap();
 
@@ -936,7 +2663,7 @@ mod tests {
 
         // Verify we have 2 unique records
         let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM media_files")
-            .fetch_one(&db.pool)
+            .fetch_one(&db.pool())
             .await
             .unwrap();
         assert_eq!(count, 2);
@@ -947,9 +2674,610 @@ mod tests {
 
         // Verify count is still 2
         let count_after: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM media_files")
-            .fetch_one(&db.pool)
+            .fetch_one(&db.pool())
             .await
             .unwrap();
         assert_eq!(count_after, 2);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_get_stats_detailed() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+
+        let db = SqliteDatabase::new(db_path).await.unwrap();
+        db.initialize().await.unwrap();
+
+        let files = [
+            ("/test/movie.mp4", 1000, "video/mp4", 100),
+            ("/test/show.mkv", 2000, "video/x-matroska", 300),
+            ("/test/song.mp3", 300, "audio/mpeg", 200),
+            ("/test/photo.jpg", 50, "image/jpeg", 150),
+            ("/test/notes.txt", 7, "text/plain", 250),
+        ];
+        for (path, size, mime, modified) in files {
+            let mut file = MediaFile::new(PathBuf::from(path), size, mime.to_string());
+            file.modified = SystemTime::UNIX_EPOCH + Duration::from_secs(modified);
+            db.store_media_file(&file).await.unwrap();
+        }
+
+        let stats = db.get_stats_detailed().await.unwrap();
+        assert_eq!(stats.totals.total_files, 5);
+        assert_eq!(stats.totals.total_size, 3357);
+        assert_eq!(stats.video, MediaTypeStats { file_count: 2, total_size: 3000 });
+        assert_eq!(stats.audio, MediaTypeStats { file_count: 1, total_size: 300 });
+        assert_eq!(stats.image, MediaTypeStats { file_count: 1, total_size: 50 });
+        assert_eq!(stats.other, MediaTypeStats { file_count: 1, total_size: 7 });
+        assert_eq!(stats.oldest_modified, Some(SystemTime::UNIX_EPOCH + Duration::from_secs(100)));
+        assert_eq!(stats.newest_modified, Some(SystemTime::UNIX_EPOCH + Duration::from_secs(300)));
+    }
+
+    #[tokio::test]
+    async fn test_write_retries_on_contention() {
+        use sqlx::{Connection, Executor, SqliteConnection};
+
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+
+        // No busy timeout so lock contention surfaces immediately as SQLITE_BUSY
+        let retry_policy = RetryPolicy {
+            max_retries: 10,
+            initial_backoff: Duration::from_millis(20),
+            max_backoff: Duration::from_millis(80),
+        };
+        let db = SqliteDatabase::with_options(db_path.clone(), Duration::ZERO, retry_policy)
+            .await
+            .unwrap();
+        db.initialize().await.unwrap();
+
+        // Hold a write lock from a separate connection for a short while
+        let mut locker = SqliteConnection::connect(&format!("sqlite://{}", db_path.display()))
+            .await
+            .unwrap();
+        locker.execute("BEGIN IMMEDIATE").await.unwrap();
+        let release = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            locker.execute("COMMIT").await.unwrap();
+            locker
+        });
+
+        let file = MediaFile::new(PathBuf::from("/test/contended.mp4"), 1024, "video/mp4".to_string());
+        let id = db.store_media_file(&file).await.unwrap();
+        assert!(id > 0);
+
+        let mut locker = release.await.unwrap();
+        assert!(db.get_file_by_path(Path::new("/test/contended.mp4")).await.unwrap().is_some());
+
+        // A batch insert retries its whole transaction
+        locker.execute("BEGIN IMMEDIATE").await.unwrap();
+        let release = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            locker.execute("COMMIT").await.unwrap();
+        });
+        let files: Vec<MediaFile> = (0..3)
+            .map(|i| MediaFile::new(PathBuf::from(format!("/test/batch{}.mp4", i)), 1, "video/mp4".to_string()))
+            .collect();
+        assert_eq!(db.batch_store_media_files(&files).await.unwrap().len(), 3);
+        release.await.unwrap();
+        assert_eq!(db.get_stats().await.unwrap().total_files, 4);
+    }
+
+    #[cfg(target_os = "windows")]
+    #[tokio::test]
+    async fn test_unc_and_extended_length_paths() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+
+        let db = SqliteDatabase::new(db_path).await.unwrap();
+        db.initialize().await.unwrap();
+
+        // Indexed through a canonicalized (extended-length) UNC path
+        let file = MediaFile::new(
+            PathBuf::from(r"\\?\UNC\nas\share\Movies\movie.mp4"),
+            1024,
+            "video/mp4".to_string(),
+        );
+        db.store_media_file(&file).await.unwrap();
+
+        // Looked up and listed through the plain UNC spelling
+        let plain = Path::new(r"\\nas\share\Movies\movie.mp4");
+        let stored = db.get_file_by_path(plain).await.unwrap().unwrap();
+        assert_eq!(stored.path, PathBuf::from(r"\\nas\share\Movies\movie.mp4"));
+
+        let in_dir = db.get_files_in_directory(Path::new(r"\\?\UNC\nas\share\Movies")).await.unwrap();
+        assert_eq!(in_dir.len(), 1);
+
+        // Cleaned up through the plain spelling as reported by the watcher
+        assert!(db.remove_media_file(plain).await.unwrap());
+        assert!(db.get_all_media_files().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_batched_lookups_by_id_and_path() {
+        let temp_dir = tempdir().unwrap();
+        let db = SqliteDatabase::new(temp_dir.path().join("test.db")).await.unwrap();
+        db.initialize().await.unwrap();
+
+        // More than one IN list's worth of values
+        let files: Vec<MediaFile> = (0..BATCH_LOOKUP_PARAMS + 5)
+            .map(|i| MediaFile::new(PathBuf::from(format!("/media/{}.mp3", i)), 1, "audio/mpeg".to_string()))
+            .collect();
+        db.batch_store_media_files(&files).await.unwrap();
+
+        let mut paths: Vec<PathBuf> = files.iter().map(|file| file.path.clone()).collect();
+        paths.push(PathBuf::from("/media/missing.mp3"));
+        let ids = db.get_file_ids_by_paths(&paths).await.unwrap();
+        assert_eq!(ids.len(), files.len());
+        assert!(!ids.contains_key(Path::new("/media/missing.mp3")));
+
+        let wanted: Vec<i64> = ids.values().copied().chain([-1]).collect();
+        let found = db.get_files_by_ids(&wanted).await.unwrap();
+        assert_eq!(found.len(), files.len());
+        let seventh = found.iter().find(|file| file.id == ids.get(Path::new("/media/7.mp3")).copied()).unwrap();
+        assert_eq!(seventh.path, PathBuf::from("/media/7.mp3"));
+    }
+
+    #[tokio::test]
+    async fn test_files_in_directory_excludes_prefix_siblings_and_wildcards() {
+        let temp_dir = tempdir().unwrap();
+        let db = SqliteDatabase::new(temp_dir.path().join("test.db")).await.unwrap();
+        db.initialize().await.unwrap();
+
+        for path in [
+            "/media/Movie/a.mp4",
+            "/media/Movie/sub/b.mp4",
+            "/media/MovieExtras/c.mp4",
+            "/media/TV_Shows/d.mp4",
+            "/media/TVxShows/e.mp4",
+            "/media/100%/f.mp4",
+            "/media/1000/g.mp4",
+        ] {
+            db.store_media_file(&MediaFile::new(PathBuf::from(path), 1, "video/mp4".to_string())).await.unwrap();
+        }
+        let names = |files: Vec<MediaFile>| files.into_iter().map(|f| f.filename).collect::<Vec<_>>();
+
+        let movie = db.get_files_in_directory(Path::new("/media/Movie")).await.unwrap();
+        assert_eq!(names(movie), vec!["a.mp4", "b.mp4"]);
+        let trailing = db.get_files_in_directory(Path::new("/media/Movie/")).await.unwrap();
+        assert_eq!(names(trailing), vec!["a.mp4", "b.mp4"]);
+
+        let shows = db.get_files_in_directory(Path::new("/media/TV_Shows")).await.unwrap();
+        assert_eq!(names(shows), vec!["d.mp4"]);
+        let percent = db.get_files_in_directory(Path::new("/media/100%")).await.unwrap();
+        assert_eq!(names(percent), vec!["f.mp4"]);
+    }
+
+    #[tokio::test]
+    async fn test_directory_queries_match_case_exactly() {
+        let temp_dir = tempdir().unwrap();
+        let db = SqliteDatabase::new(temp_dir.path().join("test.db")).await.unwrap();
+        db.initialize().await.unwrap();
+
+        for path in ["/media/Show/a.mp4", "/media/Show/Season 1/b.mp4", "/media/show/c.mp4", "/media/SHOW/d.mp4"] {
+            db.store_media_file(&MediaFile::new(PathBuf::from(path), 1, "video/mp4".to_string())).await.unwrap();
+        }
+        let names = |files: Vec<MediaFile>| files.into_iter().map(|f| f.filename).collect::<Vec<_>>();
+
+        assert_eq!(names(db.get_files_in_directory(Path::new("/media/show")).await.unwrap()), vec!["c.mp4"]);
+        assert_eq!(db.remove_files_in_directory(Path::new("/media/Show")).await.unwrap(), 2);
+        let mut remaining = names(db.get_all_media_files().await.unwrap());
+        remaining.sort();
+        assert_eq!(remaining, vec!["c.mp4", "d.mp4"]);
+    }
+
+    #[tokio::test]
+    async fn test_search_folds_ascii_case_only() {
+        let temp_dir = tempdir().unwrap();
+        let db = SqliteDatabase::new(temp_dir.path().join("test.db")).await.unwrap();
+        db.initialize().await.unwrap();
+
+        let mut file = MediaFile::new(PathBuf::from("/music/été.mp3"), 1, "audio/mpeg".to_string());
+        file.artist = Some("Édith Piaf".to_string());
+        db.store_media_file(&file).await.unwrap();
+
+        for (filter, expected) in [
+            (MediaFilter::Contains(MediaField::Artist, "PIAF".to_string()), true),
+            (MediaFilter::Contains(MediaField::Artist, "édith".to_string()), false),
+            (MediaFilter::Equals(MediaField::Artist, "édith piaf".to_string()), false),
+            (MediaFilter::Equals(MediaField::Artist, "ÉDITH PIAF".to_string()), true),
+            (MediaFilter::StartsWith(MediaField::Filename, "ÉTÉ".to_string()), false),
+            (MediaFilter::StartsWith(MediaField::Filename, "été.MP3".to_string()), true),
+        ] {
+            let total = db.search_media_files(&filter, 0, None).await.unwrap().1;
+            assert_eq!((total == 1, filter.matches(&file)), (expected, expected), "{:?}", filter);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remove_files_in_directory_in_one_call() {
+        let temp_dir = tempdir().unwrap();
+        let db = SqliteDatabase::new(temp_dir.path().join("test.db")).await.unwrap();
+        db.initialize().await.unwrap();
+
+        for path in [
+            "/media/TV_Shows/a.mp4",
+            "/media/TV_Shows/b.mp4",
+            "/media/TV_Shows/Season 1/c.mp4",
+            "/media/TV_Shows/Season 1/d.mp4",
+            "/media/TVxShows/e.mp4",
+            "/media/TV_ShowsExtra/f.mp4",
+            "/media/TV_Shows.mp4",
+        ] {
+            db.store_media_file(&MediaFile::new(PathBuf::from(path), 1, "video/mp4".to_string())).await.unwrap();
+        }
+
+        assert_eq!(db.remove_files_in_directory(Path::new("/media/TV_Shows")).await.unwrap(), 4);
+        let mut remaining: Vec<_> = db.get_all_media_files().await.unwrap().into_iter().map(|f| f.path).collect();
+        remaining.sort();
+        assert_eq!(
+            remaining,
+            vec![
+                PathBuf::from("/media/TV_Shows.mp4"),
+                PathBuf::from("/media/TV_ShowsExtra/f.mp4"),
+                PathBuf::from("/media/TVxShows/e.mp4"),
+            ]
+        );
+        assert_eq!(db.remove_files_in_directory(Path::new("/media/TV_Shows/")).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_files_by_mime_prefix_return_one_category() {
+        let temp_dir = tempdir().unwrap();
+        let db = SqliteDatabase::new(temp_dir.path().join("test.db")).await.unwrap();
+        db.initialize().await.unwrap();
+
+        for (path, mime) in [
+            ("/media/b.mp4", "video/mp4"),
+            ("/media/a.mkv", "video/x-matroska"),
+            ("/media/song.mp3", "audio/mpeg"),
+            ("/media/photo.jpg", "image/jpeg"),
+            ("/media/video_notes.txt", "videox/plain"),
+            ("/media/odd.bin", "vid_o/weird"),
+        ] {
+            db.store_media_file(&MediaFile::new(PathBuf::from(path), 1, mime.to_string())).await.unwrap();
+        }
+        let names = |files: Vec<MediaFile>| files.into_iter().map(|f| f.filename).collect::<Vec<_>>();
+
+        assert_eq!(names(db.get_files_by_mime_prefix("video/").await.unwrap()), vec!["a.mkv", "b.mp4"]);
+        assert_eq!(names(db.get_files_by_mime_prefix("audio/").await.unwrap()), vec!["song.mp3"]);
+        assert_eq!(names(db.get_files_by_mime_prefix("image/").await.unwrap()), vec!["photo.jpg"]);
+        // Wildcards in the prefix match literally
+        assert_eq!(names(db.get_files_by_mime_prefix("vid_o/").await.unwrap()), vec!["odd.bin"]);
+        assert!(db.get_files_by_mime_prefix("text/").await.unwrap().is_empty());
+        assert_eq!(db.get_files_by_mime_prefix("").await.unwrap().len(), 6);
+
+        // The range predicate lets SQLite search the mime_type index
+        let plan = sqlx::query(
+            "EXPLAIN QUERY PLAN SELECT id FROM media_files \
+             WHERE mime_type >= 'video/' AND mime_type < 'video0' AND mime_type LIKE 'video/%' ESCAPE '\\'",
+        )
+        .fetch_all(&db.pool())
+        .await
+        .unwrap();
+        let details: Vec<String> = plan.iter().map(|row| row.get("detail")).collect();
+        assert!(details.iter().any(|step| step.contains("idx_media_files_mime_type")), "{:?}", details);
+    }
+
+    #[tokio::test]
+    async fn test_rename_keeps_id_stable() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+
+        let db = SqliteDatabase::new(db_path).await.unwrap();
+        db.initialize().await.unwrap();
+
+        let file = MediaFile::new(PathBuf::from("/test/old.mp4"), 1024, "video/mp4".to_string());
+        let id = db.store_media_file(&file).await.unwrap();
+
+        assert!(db.rename_media_file(Path::new("/test/old.mp4"), Path::new("/test/sub/new.mp4")).await.unwrap());
+        assert!(!db.rename_media_file(Path::new("/test/missing.mp4"), Path::new("/test/x.mp4")).await.unwrap());
+
+        let renamed = db.get_file_by_id(id).await.unwrap().unwrap();
+        assert_eq!(renamed.path, PathBuf::from("/test/sub/new.mp4"));
+        assert_eq!(renamed.filename, "new.mp4");
+        assert!(db.get_file_by_path(Path::new("/test/old.mp4")).await.unwrap().is_none());
+        assert!(db.get_file_by_id(id + 100).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tv_episodes_keep_show_ids_across_refreshes() {
+        let temp_dir = tempdir().unwrap();
+        let db = SqliteDatabase::new(temp_dir.path().join("test.db")).await.unwrap();
+        db.initialize().await.unwrap();
+
+        let episode = |title: &str, season, episode| EpisodeInfo {
+            show_title: title.to_string(),
+            show_path: PathBuf::from(format!("/tv/{}", title)),
+            season,
+            episode,
+            title: None,
+        };
+        let mut ids = Vec::new();
+        for name in ["a.mkv", "b.mkv", "c.mkv"] {
+            let file = MediaFile::new(PathBuf::from("/tv").join(name), 1024, "video/x-matroska".to_string());
+            ids.push(db.store_media_file(&file).await.unwrap());
+        }
+
+        db.replace_tv_episodes(&[(ids[0], episode("Alpha", 1, 1)), (ids[1], episode("Beta", 2, 3))])
+            .await
+            .unwrap();
+        let first = db.get_tv_episodes().await.unwrap();
+        assert_eq!(first.len(), 2);
+        let alpha_id = first.iter().find(|e| e.info.show_title == "Alpha").unwrap().show_id;
+        assert_eq!(first[1].info.season, 2);
+        assert_eq!(first[1].file.filename, "b.mkv");
+
+        // Beta disappears, Alpha gains an episode and a vanished file is skipped
+        db.replace_tv_episodes(&[
+            (ids[0], episode("Alpha", 1, 1)),
+            (ids[2], episode("Alpha", 1, 2)),
+            (ids[2] + 100, episode("Gamma", 1, 1)),
+        ])
+        .await
+        .unwrap();
+        let second = db.get_tv_episodes().await.unwrap();
+        assert_eq!(second.len(), 2);
+        assert!(second.iter().all(|e| e.show_id == alpha_id && e.info.show_title == "Alpha"));
+
+        // Removing a media file drops its episode
+        db.remove_media_file(Path::new("/tv/c.mkv")).await.unwrap();
+        assert_eq!(db.get_tv_episodes().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_check_and_repair_reports_removed_rows() {
+        let temp_dir = tempdir().unwrap();
+        let db = SqliteDatabase::new(temp_dir.path().join("repair.db")).await.unwrap();
+        db.initialize().await.unwrap();
+
+        // Recreate the table without the UNIQUE constraint, as in databases from older releases
+        sqlx::query("DROP TABLE media_files").execute(&db.pool()).await.unwrap();
+        sqlx::query(
+            r#"
+            CREATE TABLE media_files (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                path TEXT NOT NULL,
+                filename TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                modified INTEGER NOT NULL,
+                mime_type TEXT NOT NULL,
+                duration INTEGER,
+                title TEXT,
+                artist TEXT,
+                album TEXT,
+                width INTEGER,
+                height INTEGER,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL
+            )
+            "#,
+        )
+        .execute(&db.pool())
+        .await
+        .unwrap();
+
+        let rows = [
+            ("/media/a.mp4", "a.mp4"),
+            ("/media/a.mp4", "a.mp4"),
+            ("/media/a.mp4", "a.mp4"),
+            ("/media/b.mp4", "b.mp4"),
+            ("/media/b.mp4", "b.mp4"),
+            ("/media/c.mp4", "c.mp4"),
+            ("", "orphan.mp4"),
+            ("/media/d.mp4", ""),
+        ];
+        for (path, filename) in rows {
+            sqlx::query(
+                "INSERT INTO media_files (path, filename, size, modified, mime_type, created_at, updated_at) \
+                 VALUES (?, ?, 1, 0, 'video/mp4', 0, 0)",
+            )
+            .bind(path)
+            .bind(filename)
+            .execute(&db.pool())
+            .await
+            .unwrap();
+        }
+
+        let health = db.check_and_repair().await.unwrap();
+        assert!(health.repair_attempted);
+        assert!(health.repair_successful);
+
+        let removed = |description: &str| {
+            health
+                .issues
+                .iter()
+                .find(|issue| issue.description.contains(description))
+                .and_then(|issue| issue.rows_affected)
+        };
+        assert_eq!(removed("orphaned records"), Some(2));
+        assert_eq!(removed("duplicate entries"), Some(3));
+        assert_eq!(health.rows_repaired(), 5);
+
+        let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM media_files")
+            .fetch_one(&db.pool())
+            .await
+            .unwrap();
+        assert_eq!(remaining, 3);
+    }
+
+    #[tokio::test]
+    async fn test_text_search_matches_partial_words() {
+        let temp_dir = tempdir().unwrap();
+        let db = SqliteDatabase::new(temp_dir.path().join("test.db")).await.unwrap();
+        db.initialize().await.unwrap();
+
+        let track = |name: &str, title: &str, artist: &str, album: &str| {
+            let mut file = MediaFile::new(PathBuf::from(format!("/music/{}", name)), 1024, "audio/mpeg".to_string());
+            file.title = Some(title.to_string());
+            file.artist = Some(artist.to_string());
+            file.album = Some(album.to_string());
+            file
+        };
+        db.store_media_file(&track("01.mp3", "Yesterday", "The Beatles", "Help!")).await.unwrap();
+        db.store_media_file(&track("02.mp3", "Help!", "The Beatles", "Help!")).await.unwrap();
+        db.store_media_file(&track("03.mp3", "Angie", "The Rolling Stones", "Goats Head Soup")).await.unwrap();
+        db.store_media_file(&MediaFile::new(PathBuf::from("/videos/holiday_beach.mp4"), 1024, "video/mp4".to_string()))
+            .await
+            .unwrap();
+
+        let titles = |files: Vec<MediaFile>| files.into_iter().map(|f| f.filename).collect::<Vec<_>>();
+        assert_eq!(titles(db.search_text("beat", 10).await.unwrap()).len(), 2);
+        // Matching the title and the album ranks above matching the album alone
+        assert_eq!(titles(db.search_text("help", 10).await.unwrap()), vec!["02.mp3", "01.mp3"]);
+        assert_eq!(titles(db.search_text("roll ston", 10).await.unwrap()), vec!["03.mp3"]);
+        assert_eq!(titles(db.search_text("BEACH", 10).await.unwrap()), vec!["holiday_beach.mp4"]);
+        assert!(db.search_text("beatles angie", 10).await.unwrap().is_empty());
+        assert_eq!(db.search_text("the", 1).await.unwrap().len(), 1);
+        // FTS5 syntax in the query is treated as plain words
+        assert!(db.search_text("\"", 10).await.unwrap().is_empty());
+        assert_eq!(titles(db.search_text("goat* -soup\"", 10).await.unwrap()), vec!["03.mp3"]);
+
+        // The index follows updates and deletes
+        let mut angie = db.get_file_by_path(Path::new("/music/03.mp3")).await.unwrap().unwrap();
+        angie.title = Some("Wild Horses".to_string());
+        db.update_media_file(&angie).await.unwrap();
+        assert!(db.search_text("angie", 10).await.unwrap().is_empty());
+        assert_eq!(titles(db.search_text("horse", 10).await.unwrap()), vec!["03.mp3"]);
+        db.remove_media_file(Path::new("/music/03.mp3")).await.unwrap();
+        assert!(db.search_text("horse", 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_restore_from_backup_replaces_live_data() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let backup_path = temp_dir.path().join("backup.db");
+        let db = SqliteDatabase::new(db_path).await.unwrap();
+        db.initialize().await.unwrap();
+
+        for name in ["a.mp4", "b.mp3"] {
+            let mut file = MediaFile::new(PathBuf::from(format!("/media/{}", name)), 1024, "video/mp4".to_string());
+            file.title = Some(format!("Title {}", name));
+            db.store_media_file(&file).await.unwrap();
+        }
+        db.create_backup(&backup_path).await.unwrap();
+
+        // Wipe the live database
+        for file in db.get_all_media_files().await.unwrap() {
+            db.remove_media_file(&file.path).await.unwrap();
+        }
+        db.store_media_file(&MediaFile::new(PathBuf::from("/media/new.mkv"), 1, "video/x-matroska".to_string()))
+            .await
+            .unwrap();
+
+        db.restore_from_backup(&backup_path).await.unwrap();
+        let mut restored: Vec<String> = db.get_all_media_files().await.unwrap().into_iter().map(|f| f.filename).collect();
+        restored.sort();
+        assert_eq!(restored, vec!["a.mp4", "b.mp3"]);
+        assert_eq!(db.search_text("title", 10).await.unwrap().len(), 2);
+
+        // The restored database accepts writes through the new pool
+        db.store_media_file(&MediaFile::new(PathBuf::from("/media/c.mp4"), 1, "video/mp4".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(db.get_stats().await.unwrap().total_files, 3);
+
+        assert!(db.restore_from_backup(&temp_dir.path().join("missing.db")).await.is_err());
+        assert_eq!(db.get_stats().await.unwrap().total_files, 3);
+    }
+
+    #[tokio::test]
+    async fn test_paged_listing_has_stable_boundaries() {
+        let temp_dir = tempdir().unwrap();
+        let db = SqliteDatabase::new(temp_dir.path().join("paged.db")).await.unwrap();
+        db.initialize().await.unwrap();
+
+        // Several files share a name, so paging relies on the id tie-break
+        let names = ["b.mp3", "cover.jpg", "a.mp3", "cover.jpg", "cover.jpg", "c.mp3", "cover.jpg"];
+        for (i, name) in names.iter().enumerate() {
+            let mut file = MediaFile::new(PathBuf::from(format!("/music/{}/{}", i, name)), 100 - i as u64, "audio/mpeg".to_string());
+            file.title = (i % 2 == 0).then(|| format!("Track {}", i));
+            db.store_media_file(&file).await.unwrap();
+        }
+
+        let mut seen = Vec::new();
+        for offset in (0..names.len()).step_by(3) {
+            let (page, total) = db.get_media_files_paged(offset, 3, MediaOrder::Filename).await.unwrap();
+            assert_eq!(total, names.len());
+            assert_eq!(page.len(), 3.min(names.len() - offset));
+            seen.extend(page);
+        }
+        let filenames: Vec<&str> = seen.iter().map(|f| f.filename.as_str()).collect();
+        assert_eq!(filenames, ["a.mp3", "b.mp3", "c.mp3", "cover.jpg", "cover.jpg", "cover.jpg", "cover.jpg"]);
+        let cover_ids: Vec<i64> = seen[3..].iter().map(|f| f.id.unwrap()).collect();
+        assert!(cover_ids.windows(2).all(|w| w[0] < w[1]));
+
+        let (page, total) = db.get_media_files_paged(names.len(), 3, MediaOrder::Filename).await.unwrap();
+        assert!(page.is_empty());
+        assert_eq!(total, names.len());
+
+        // SQL ordering agrees with the in-memory ordering for every column
+        for order in [MediaOrder::Filename, MediaOrder::Title, MediaOrder::Modified, MediaOrder::Size] {
+            let (page, _) = db.get_media_files_paged(0, usize::MAX, order).await.unwrap();
+            let mut expected = db.get_all_media_files().await.unwrap();
+            expected.sort_by(|a, b| order.compare(a, b));
+            let ids = |files: &[MediaFile]| files.iter().map(|f| f.id).collect::<Vec<_>>();
+            assert_eq!(ids(&page), ids(&expected), "{:?}", order);
+        }
+        let (page, _) = db.get_media_files_paged(0, 2, MediaOrder::Size).await.unwrap();
+        assert_eq!(page[0].size, 94);
+        let (page, _) = db.get_media_files_paged(names.len() - 3, 3, MediaOrder::Title).await.unwrap();
+        assert!(page.iter().all(|f| f.title.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_browse_directory_pages_subdirectories_then_files() {
+        let temp_dir = tempdir().unwrap();
+        let db = SqliteDatabase::new(temp_dir.path().join("browse.db")).await.unwrap();
+        db.initialize().await.unwrap();
+
+        let entries = [
+            ("/media/b.mp4", "video/mp4", 30),
+            ("/media/A.mkv", "video/x-matroska", 10),
+            ("/media/c.mp4", "video/mp4", 20),
+            ("/media/Shows/s1/e1.mp4", "video/mp4", 5),
+            ("/media/extras/clip.mp4", "video/mp4", 5),
+            ("/media/music/song.mp3", "audio/mpeg", 5),
+            ("/media/song.mp3", "audio/mpeg", 5),
+            ("/media-other/d.mp4", "video/mp4", 5),
+        ];
+        for (path, mime, size) in entries {
+            db.store_media_file(&MediaFile::new(PathBuf::from(path), size, mime.to_string())).await.unwrap();
+        }
+        let dir = Path::new("/media");
+        let names = |page: &DirectoryPage| {
+            let mut names = page.subdirectories.clone();
+            names.extend(page.files.iter().map(|f| f.filename.clone()));
+            names
+        };
+
+        let all = db.browse_directory(dir, "video/", &[], 0, None).await.unwrap();
+        assert_eq!(all.total, 5);
+        assert_eq!(names(&all), ["extras", "Shows", "A.mkv", "b.mp4", "c.mp4"]);
+
+        // Pages split across subdirectories and files without gaps or repeats
+        let mut paged = Vec::new();
+        for offset in (0..all.total).step_by(2) {
+            let page = db.browse_directory(dir, "video/", &[], offset, Some(2)).await.unwrap();
+            assert_eq!(page.total, 5);
+            paged.extend(names(&page));
+        }
+        assert_eq!(paged, names(&all));
+        assert!(names(&db.browse_directory(dir, "video/", &[], 5, Some(2)).await.unwrap()).is_empty());
+
+        // SQL ordering agrees with sorting the listing in memory
+        let criteria = [SortCriterion { key: SortKey::Size, descending: true }];
+        let sorted = db.browse_directory(dir, "video/", &criteria, 1, Some(3)).await.unwrap();
+        let files = db.get_files_in_directory(dir).await.unwrap();
+        let direct: Vec<&MediaFile> = files
+            .iter()
+            .filter(|f| f.mime_type.starts_with("video/") && f.path.parent() == Some(dir))
+            .collect();
+        let expected = DirectoryPage::from_listing(vec!["Shows".to_string(), "extras".to_string()], direct, &criteria, 1, Some(3));
+        assert_eq!(names(&sorted), names(&expected));
+        assert_eq!(names(&sorted), ["Shows", "b.mp4", "c.mp4"]);
+
+        let audio = db.browse_directory(dir, "audio/", &[], 0, None).await.unwrap();
+        assert_eq!(names(&audio), ["music", "song.mp3"]);
+    }
+}
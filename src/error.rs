@@ -92,15 +92,15 @@ impl AppError {
             AppError::Internal(_) => false,
             AppError::Io(io_err) => {
                 // Some I/O errors are recoverable (temporary network issues, etc.)
-                match io_err.kind() {
-                    std::io::ErrorKind::TimedOut => true,
-                    std::io::ErrorKind::Interrupted => true,
-                    std::io::ErrorKind::WouldBlock => true,
-                    std::io::ErrorKind::ConnectionRefused => true,
-                    std::io::ErrorKind::ConnectionAborted => true,
-                    std::io::ErrorKind::NotConnected => true,
-                    _ => false,
-                }
+                matches!(
+                    io_err.kind(),
+                    std::io::ErrorKind::TimedOut
+                        | std::io::ErrorKind::Interrupted
+                        | std::io::ErrorKind::WouldBlock
+                        | std::io::ErrorKind::ConnectionRefused
+                        | std::io::ErrorKind::ConnectionAborted
+                        | std::io::ErrorKind::NotConnected
+                )
             }
             AppError::Http(_) => false,
             AppError::Watcher(_) => true, // File watcher can often be restarted
@@ -253,6 +253,7 @@ impl AppError {
 }
 
 /// Trait for implementing automatic error recovery
+#[allow(async_fn_in_trait)]
 pub trait ErrorRecovery {
     type Output;
     type Error;
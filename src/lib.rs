@@ -3,8 +3,11 @@ pub mod database;
 pub mod error;
 pub mod logging;
 pub mod media;
+pub mod metadata;
 pub mod platform;
+pub mod shutdown;
 pub mod ssdp;
+pub mod tvshows;
 pub mod watcher;
 pub mod web;
 
@@ -12,17 +15,361 @@ pub mod state {
     use crate::{
         config::AppConfig,
         database::{DatabaseManager, MediaFile},
-        platform::PlatformInfo,
+        media::{
+            album_art::AlbumArtCache, playlist::PlaylistIndex, subtitles::SubtitleIndex, thumbnail::ThumbnailCache,
+            transcode::Transcoder,
+            MediaScanner, ScanError, ScanResult,
+        },
+        platform::{filesystem::create_platform_filesystem_manager, network::ClientFilter, PlatformInfo},
+        shutdown::Shutdown,
+        web::{
+            auth::ApiTokenStore,
+            didl_cache::DidlCache,
+            eventing::{scan_changes, ContentUpdateNotifier, EventSubscriptions},
+            file_cache::SmallFileCache,
+            icon::DeviceIcon,
+            metrics::ServerMetrics,
+        },
     };
+    use futures_util::future::{BoxFuture, FutureExt, Shared};
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
     use std::sync::Arc;
-    use tokio::sync::RwLock;
+    use tokio::sync::{Mutex, RwLock};
+
+    type SharedLookup = Shared<BoxFuture<'static, Result<Option<MediaFile>, Arc<anyhow::Error>>>>;
+
+    /// Database lookups by object id that are currently in progress, so concurrent
+    /// requests for the same id share a single round-trip
+    #[derive(Clone, Default)]
+    pub struct InFlightLookups {
+        lookups: Arc<std::sync::Mutex<HashMap<i64, SharedLookup>>>,
+    }
+
+    impl InFlightLookups {
+        /// Join the lookup for `id` already in progress, or start one with `database`
+        pub async fn get_or_fetch(
+            &self,
+            id: i64,
+            database: &Arc<dyn DatabaseManager>,
+        ) -> anyhow::Result<Option<MediaFile>> {
+            let lookup = {
+                let mut lookups = self.lookups.lock().unwrap();
+                lookups
+                    .entry(id)
+                    .or_insert_with(|| {
+                        let database = database.clone();
+                        let in_flight = self.lookups.clone();
+                        async move {
+                            let result = database.get_file_by_id(id).await.map_err(Arc::new);
+                            // Later requests must see fresh data, not this result
+                            in_flight.lock().unwrap().remove(&id);
+                            result
+                        }
+                        .boxed()
+                        .shared()
+                    })
+                    .clone()
+            };
+
+            lookup.await.map_err(|e| anyhow::anyhow!("{:#}", e))
+        }
+    }
+
+    /// Stable copy of the library served to clients while a full rescan runs
+    #[derive(Clone, Default)]
+    pub struct LibrarySnapshot {
+        files: Arc<std::sync::RwLock<Option<Arc<Vec<MediaFile>>>>>,
+    }
+
+    impl LibrarySnapshot {
+        /// Start serving `files` until the snapshot is released
+        pub fn freeze(&self, files: Vec<MediaFile>) {
+            *self.files.write().unwrap() = Some(Arc::new(files));
+        }
+
+        /// Stop serving the snapshot so readers see the live library again
+        pub fn release(&self) {
+            *self.files.write().unwrap() = None;
+        }
+
+        /// The frozen library, if a snapshot is active
+        pub fn get(&self) -> Option<Arc<Vec<MediaFile>>> {
+            self.files.read().unwrap().clone()
+        }
+
+        pub fn is_active(&self) -> bool {
+            self.files.read().unwrap().is_some()
+        }
+    }
+
+    /// Configuration last applied by a hot reload, starting as the startup
+    /// configuration. Settings that need a restart still come from `AppState::config`.
+    #[derive(Clone)]
+    pub struct SharedConfig {
+        config: Arc<std::sync::RwLock<Arc<AppConfig>>>,
+    }
+
+    impl SharedConfig {
+        pub fn new(config: Arc<AppConfig>) -> Self {
+            Self { config: Arc::new(std::sync::RwLock::new(config)) }
+        }
+
+        /// The configuration currently applied
+        pub fn get(&self) -> Arc<AppConfig> {
+            self.config.read().unwrap().clone()
+        }
+
+        /// Record a newly applied configuration
+        pub fn set(&self, config: Arc<AppConfig>) {
+            *self.config.write().unwrap() = config;
+        }
+    }
+
+    /// Platform information shared by the HTTP and SSDP sides, replaced when
+    /// the network interfaces change
+    #[derive(Clone)]
+    pub struct SharedPlatformInfo {
+        info: Arc<std::sync::RwLock<Arc<PlatformInfo>>>,
+    }
+
+    impl SharedPlatformInfo {
+        pub fn new(info: PlatformInfo) -> Self {
+            Self { info: Arc::new(std::sync::RwLock::new(Arc::new(info))) }
+        }
+
+        /// The current platform information
+        pub fn get(&self) -> Arc<PlatformInfo> {
+            self.info.read().unwrap().clone()
+        }
+
+        /// Replace the platform information for every holder of this handle
+        pub fn set(&self, info: PlatformInfo) {
+            *self.info.write().unwrap() = Arc::new(info);
+        }
+    }
 
     #[derive(Clone)]
     pub struct AppState {
         pub config: Arc<AppConfig>,
+        /// Configuration as last reloaded, which the management API compares against
+        pub applied_config: SharedConfig,
+        /// In-memory media cache; left empty when `server.in_memory_cache` is disabled
         pub media_files: Arc<RwLock<Vec<MediaFile>>>,
         pub database: Arc<dyn DatabaseManager>,
-        pub platform_info: Arc<PlatformInfo>,
+        pub platform_info: SharedPlatformInfo,
         pub content_update_id: Arc<std::sync::atomic::AtomicU32>,
+        /// Batches content changes into debounced `content_update_id` bumps
+        pub content_updates: ContentUpdateNotifier,
+        /// Held for the duration of a full rescan so scheduled and manual scans never overlap
+        pub scan_lock: Arc<Mutex<()>>,
+        /// Coalesces concurrent database lookups for the same media id
+        pub media_lookups: InFlightLookups,
+        /// Library served to Browse requests while a rescan runs in maintenance mode
+        pub library_snapshot: LibrarySnapshot,
+        /// Small files such as photos and album art served without touching disk
+        pub small_files: SmallFileCache,
+        /// Rendered Browse responses, dropped when the SystemUpdateID changes
+        pub didl_cache: DidlCache,
+        /// Tokens accepted by the management API, reloaded with the configuration
+        pub api_tokens: ApiTokenStore,
+        /// Clients answered over HTTP and SSDP, from the `network` client subnet lists
+        pub client_filter: Arc<ClientFilter>,
+        /// Cached thumbnails for images and videos
+        pub thumbnails: ThumbnailCache,
+        /// Cover art extracted from audio files, shared by the tracks of an album
+        pub album_art: AlbumArtCache,
+        /// GENA subscribers notified when the SystemUpdateID changes
+        pub event_subscriptions: EventSubscriptions,
+        /// GENA subscribers to the ConnectionManager, which only get the initial event
+        pub connection_manager_subscriptions: EventSubscriptions,
+        /// Set when transcoding is enabled and `ffmpeg` was found
+        pub transcoder: Option<Transcoder>,
+        /// Subtitle sidecars of the indexed videos, advertised in DIDL-Lite
+        pub subtitles: SubtitleIndex,
+        /// M3U and PLS playlists, browsable as containers of their media files
+        pub playlists: PlaylistIndex,
+        /// Icon listed in the device description and served at its URL
+        pub icon: DeviceIcon,
+        /// Counters exposed at `GET /metrics`
+        pub metrics: ServerMetrics,
+        /// When the server started, reported as uptime by `GET /health`
+        pub started_at: std::time::Instant,
+        /// UPnP `BOOTID.UPNP.ORG`, advanced in the database at every start
+        pub boot_id: u32,
+        /// UPnP `CONFIGID.UPNP.ORG` of the descriptions served, set by `update_config_id`
+        pub config_id: u32,
+        /// Errors of the latest full scan, listed at `GET /api/scan/errors`
+        pub scan_errors: Arc<std::sync::RwLock<Vec<ScanError>>>,
+    }
+
+    impl AppState {
+        /// Hash the device and service descriptions into `config_id`; called
+        /// once the state is built and whenever its configuration is replaced
+        pub fn update_config_id(&mut self) {
+            self.config_id = crate::web::xml::description_config_id(self);
+        }
+
+        /// Whether media lookups are served from the in-memory cache
+        pub fn uses_media_cache(&self) -> bool {
+            self.config.server.in_memory_cache
+        }
+
+        /// Look up a media file by id from the cache or the database
+        pub async fn get_media_file_by_id(&self, id: i64) -> anyhow::Result<Option<MediaFile>> {
+            if self.uses_media_cache() {
+                let files = self.media_files.read().await;
+                Ok(files.iter().find(|f| f.id == Some(id)).cloned())
+            } else {
+                self.media_lookups.get_or_fetch(id, &self.database).await
+            }
+        }
+
+        /// Rescan all configured directories, refreshing the cache and notifying
+        /// clients when anything changed. Waits for any scan already in progress.
+        pub async fn rescan_all_directories(&self) -> anyhow::Result<ScanResult> {
+            let _guard = self.scan_lock.lock().await;
+            // Scan the directories of the configuration last applied, not the one at startup
+            let config = self.applied_config.get();
+
+            let maintenance_mode = config.media.maintenance_mode;
+            if maintenance_mode {
+                let files = if self.uses_media_cache() {
+                    self.media_files.read().await.clone()
+                } else {
+                    self.database.get_all_media_files().await?
+                };
+                tracing::info!("Maintenance mode: serving a snapshot of {} files during rescan", files.len());
+                self.library_snapshot.freeze(files);
+            }
+
+            let result = self.scan_and_refresh(&config).await;
+
+            // The refreshed library replaces the snapshot in a single step
+            if maintenance_mode {
+                self.library_snapshot.release();
+            }
+            result
+        }
+
+        /// Rescan all directories every `period` until shutdown, a safety net for
+        /// changes the file watcher misses, such as those on network shares
+        pub fn spawn_periodic_rescan(&self, period: std::time::Duration, shutdown: Shutdown) -> tokio::task::JoinHandle<()> {
+            let state = self.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+                // A scan outlasting the period is followed by a full period of rest
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                loop {
+                    tokio::select! {
+                        _ = interval.tick() => {}
+                        _ = shutdown.wait() => break,
+                    }
+                    match state.rescan_all_directories().await {
+                        Ok(result) if result.has_changes() => {
+                            tracing::info!("Periodic rescan found changes: {}", result.summary())
+                        }
+                        Ok(_) => tracing::debug!("Periodic rescan found no changes"),
+                        Err(e) => tracing::warn!("Periodic rescan failed: {}", e),
+                    }
+                }
+            })
+        }
+
+        /// Rebuild the TV show grouping from the indexed video files, returning
+        /// the number of episodes found
+        pub async fn refresh_tv_library(&self) -> anyhow::Result<usize> {
+            let files = self.database.get_all_media_files().await?;
+            let episodes =
+                tokio::task::spawn_blocking(move || crate::tvshows::detect_episodes(&files)).await?;
+            self.database.replace_tv_episodes(&episodes).await?;
+            Ok(episodes.len())
+        }
+
+        /// Reload the subtitle sidecars advertised to clients from the database
+        pub async fn refresh_subtitles(&self) -> anyhow::Result<()> {
+            self.subtitles.replace(self.database.get_all_subtitles().await?);
+            Ok(())
+        }
+
+        /// Match the recorded playlists to the indexed media files again,
+        /// forgetting playlists whose file is gone. Returns whether any
+        /// playlist served to clients changed.
+        pub async fn refresh_playlists(&self) -> anyhow::Result<bool> {
+            let mut playlists = Vec::new();
+            for playlist in self.database.get_playlists().await? {
+                if tokio::fs::try_exists(&playlist.path).await.unwrap_or(false) {
+                    playlists.push(playlist);
+                } else {
+                    self.database.remove_playlist(&playlist.path).await?;
+                }
+            }
+            if playlists.is_empty() && self.playlists.is_empty() {
+                return Ok(false);
+            }
+
+            let entries: Vec<PathBuf> = playlists.iter().flat_map(|playlist| playlist.entries.iter().cloned()).collect();
+            let ids = self.database.get_file_ids_by_paths(&entries).await?;
+            Ok(self.playlists.replace(&playlists, &ids))
+        }
+
+        /// Record the playlist file at `path` again, or forget it when it is
+        /// gone. Returns whether what clients see of it changed.
+        pub async fn refresh_playlist(&self, path: &Path) -> anyhow::Result<bool> {
+            let filesystem = create_platform_filesystem_manager();
+            let path = filesystem.normalize_path(path);
+            if !tokio::fs::metadata(&path).await.is_ok_and(|metadata| metadata.is_file()) {
+                self.database.remove_playlist(&path).await?;
+                return Ok(self.playlists.remove(&path));
+            }
+
+            let mut playlist = crate::media::playlist::read_playlist(&path, filesystem.as_ref()).await?;
+            playlist.id = Some(self.database.store_playlist(&playlist).await?);
+            let ids = self.database.get_file_ids_by_paths(&playlist.entries).await?;
+            Ok(self.playlists.update(&playlist, &ids))
+        }
+
+        /// Match the playlists listing any of the media files at `paths`,
+        /// which were just added or removed, to the index again. Returns
+        /// whether any playlist served to clients changed.
+        pub async fn refresh_playlists_listing(&self, paths: &[PathBuf]) -> anyhow::Result<bool> {
+            let filesystem = create_platform_filesystem_manager();
+            let paths: Vec<PathBuf> = paths.iter().map(|path| filesystem.normalize_path(path)).collect();
+            let mut changed = false;
+            for playlist in self.playlists.listing(&paths) {
+                let ids = self.database.get_file_ids_by_paths(&playlist.entries).await?;
+                changed |= self.playlists.update(&playlist, &ids);
+            }
+            Ok(changed)
+        }
+
+        async fn scan_and_refresh(&self, config: &AppConfig) -> anyhow::Result<ScanResult> {
+            let started = std::time::Instant::now();
+            let scanner = MediaScanner::with_database(self.database.clone())
+                .with_hidden_files(config.media.include_hidden)
+                .with_follow_symlinks(config.media.follow_symlinks)
+                .with_min_file_size(config.media.min_file_size_bytes)
+                .with_scan_concurrency(config.media.scan_concurrency)
+                .with_media_types(config.media.media_types())
+                .with_metadata_extraction(config.media.extract_metadata)
+                .with_album_art(self.album_art.clone());
+            let result = scanner.scan_all_directories(&config.media.effective_directories()).await;
+            self.metrics.record_scan(started.elapsed());
+            *self.scan_errors.write().unwrap() = result.errors.clone();
+
+            if result.has_changes() {
+                if self.uses_media_cache() {
+                    let all_files = self.database.get_all_media_files().await?;
+                    *self.media_files.write().await = all_files;
+                }
+                self.refresh_subtitles().await?;
+            }
+            let playlists_changed = self.refresh_playlists().await?;
+            if result.has_changes() || playlists_changed {
+                self.content_updates.record_object_changes(scan_changes(&result));
+            }
+
+            Ok(result)
+        }
     }
-}
\ No newline at end of file
+}
@@ -1,21 +1,32 @@
 use anyhow::Context;
 use vuio::{
-    config::AppConfig,
-    database::{self, DatabaseManager, SqliteDatabase},
-    logging, media,
-    platform::{self, PlatformInfo},
-    ssdp,
-    state::AppState,
-    watcher::{CrossPlatformWatcher, FileSystemEvent, FileSystemWatcher},
-    web,
+    config::{watcher::ConfigFileTracker, AppConfig, ConfigSource, DatabaseBackend},
+    database::{self, DatabaseManager, RetryPolicy, SqliteDatabase},
+    logging,
+    media::{self, album_art::AlbumArtCache, thumbnail::ThumbnailCache, transcode::Transcoder},
+    metadata,
+    platform::{self, filesystem::create_platform_filesystem_manager, network::{ClientFilter, PlatformNetworkManager}, PlatformInfo},
+    shutdown::Shutdown,
+    ssdp::{SsdpAdaptation, SsdpController},
+    state::{AppState, SharedConfig, SharedPlatformInfo},
+    watcher::{apply_directory_changes, exclude_patterns, watched_directories, CrossPlatformWatcher, FileSystemEvent, FileSystemWatcher, RecursiveMode},
+    web::{
+        self,
+        auth::ApiTokenStore,
+        didl_cache::DidlCache,
+        eventing::{restore_system_update_id, scan_changes, ContentChange, ContentUpdateNotifier, EventSubscriptions},
+        file_cache::SmallFileCache,
+        icon::DeviceIcon,
+        metrics::ServerMetrics,
+    },
 };
 use std::{net::SocketAddr, sync::Arc};
 use tokio::sync::RwLock;
 use tracing::{debug, error, info, warn};
 
-/// Parse early command line arguments to get debug flag and config file path
+/// Parse early command line arguments to get debug flags and config file path
 /// This is needed before logging initialization
-fn parse_early_args() -> (bool, Option<String>) {
+fn parse_early_args() -> (bool, bool, bool, bool, Option<String>) {
     use clap::Parser;
     
     #[derive(Parser, Debug)]
@@ -36,25 +47,42 @@ fn parse_early_args() -> (bool, Option<String>) {
         #[arg(long)]
         debug: bool,
 
+        /// Log every SSDP packet sent and received
+        #[arg(long)]
+        trace_ssdp: bool,
+
         /// Path to configuration file
         #[arg(short, long)]
         config: Option<String>,
+
+        /// Rescan all media directories, print a summary and exit
+        #[arg(long)]
+        rescan: bool,
+
+        /// Scan the library and print what would be served without starting network services
+        #[arg(long)]
+        scan_only: bool,
     }
     
     // Parse args, but ignore errors since we'll parse them again later
     match EarlyArgs::try_parse() {
-        Ok(args) => (args.debug, args.config),
-        Err(_) => (false, None), // Default to no debug and no config file
+        Ok(args) => (args.debug, args.trace_ssdp, args.rescan, args.scan_only, args.config),
+        Err(_) => (false, false, false, false, None), // Default to no debug and no config file
     }
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     // Parse command line arguments first to get debug flag
-    let (debug_enabled, config_file_path) = parse_early_args();
+    let (debug_enabled, trace_ssdp, rescan_only, scan_only, config_file_path) = parse_early_args();
     
     // Initialize logging with debug flag
-    if debug_enabled {
+    if trace_ssdp {
+        // Packet summaries are logged at debug and raw packets at trace level
+        let log_level = if debug_enabled { "debug,vuio::ssdp=trace" } else { "info,vuio::ssdp=trace" };
+        logging::init_logging_with_options(Some(log_level), None, debug_enabled)
+            .context("Failed to initialize SSDP trace logging")?;
+    } else if debug_enabled {
         logging::init_logging_with_debug(true).context("Failed to initialize debug logging")?;
     } else {
         logging::init_logging().context("Failed to initialize logging")?;
@@ -78,23 +106,38 @@ async fn main() -> anyhow::Result<()> {
     }
 
     // Load or create configuration with platform-specific defaults
-    let config = match initialize_configuration(&platform_info).await {
-        Ok(config) => Arc::new(config),
+    // Validating a configuration must not silently fall back to another one
+    let require_cli_config = scan_only && config_file_path.is_some();
+    let config = match initialize_configuration(&platform_info, require_cli_config).await {
+        Ok(mut config) => {
+            if trace_ssdp {
+                config.network.trace_ssdp = true;
+            }
+            Arc::new(config)
+        }
         Err(e) => {
             error!("Failed to initialize configuration: {}", e);
             return Err(e);
         }
     };
 
+
     // Initialize database manager
     let database = match initialize_database(&config).await {
-        Ok(db) => Arc::new(db) as Arc<dyn DatabaseManager>,
+        Ok(db) => db,
         Err(e) => {
             error!("Failed to initialize database: {}", e);
             return Err(e);
         }
     };
 
+    if rescan_only {
+        return run_one_shot_rescan(&config, &database).await;
+    }
+    if scan_only {
+        return run_scan_only(&config, &database).await;
+    }
+
     // Initialize file system watcher
     let file_watcher = match initialize_file_watcher(&config, database.clone()).await {
         Ok(watcher) => Arc::new(watcher),
@@ -105,8 +148,18 @@ async fn main() -> anyhow::Result<()> {
     };
 
     // Perform initial media scan or load from database
-    let media_files = match perform_initial_media_scan(&config, &database).await {
-        Ok(files) => Arc::new(RwLock::new(files)),
+    let metrics = ServerMetrics::default();
+    let scan_started = std::time::Instant::now();
+    let initial_scan = perform_initial_media_scan(&config, &database).await;
+    if config.media.scan_on_startup {
+        metrics.record_scan(scan_started.elapsed());
+    }
+    let (media_files, scan_errors) = match initial_scan {
+        Ok((files, errors)) if config.server.in_memory_cache => (Arc::new(RwLock::new(files)), errors),
+        Ok((files, errors)) => {
+            info!("In-memory media cache disabled; {} files will be served from the database", files.len());
+            (Arc::new(RwLock::new(Vec::new())), errors)
+        }
         Err(e) => {
             error!("Failed to perform initial media scan: {}", e);
             return Err(e);
@@ -114,13 +167,65 @@ async fn main() -> anyhow::Result<()> {
     };
 
     // Create shared application state
-    let app_state = AppState {
+    let system_update_id = match restore_system_update_id(database.as_ref()).await {
+        Ok(update_id) => update_id,
+        Err(e) => {
+            warn!("Failed to restore SystemUpdateID, starting from 1: {}", e);
+            1
+        }
+    };
+    let boot_id = match database.next_boot_id().await {
+        Ok(boot_id) => boot_id,
+        Err(e) => {
+            warn!("Failed to advance BOOTID, announcing 1: {}", e);
+            1
+        }
+    };
+    let content_update_id = Arc::new(std::sync::atomic::AtomicU32::new(system_update_id));
+    let content_updates = ContentUpdateNotifier::new(
+        content_update_id.clone(),
+        std::time::Duration::from_millis(config.media.content_update_debounce_ms),
+    );
+    content_updates.persist_to(database.clone());
+    let mut app_state = AppState {
         config: config.clone(),
+        applied_config: SharedConfig::new(config.clone()),
         media_files: media_files.clone(),
         database: database.clone(),
-        platform_info: platform_info.clone(),
-        content_update_id: Arc::new(std::sync::atomic::AtomicU32::new(1)),
+        platform_info: SharedPlatformInfo::new((*platform_info).clone()),
+        content_update_id,
+        content_updates,
+        scan_lock: Arc::new(tokio::sync::Mutex::new(())),
+        media_lookups: Default::default(),
+        library_snapshot: Default::default(),
+        small_files: SmallFileCache::new(config.server.small_file_cache_bytes),
+        didl_cache: DidlCache::new(config.server.didl_cache_bytes),
+        metrics,
+        api_tokens: ApiTokenStore::from_config(&config.server),
+        client_filter: Arc::new(ClientFilter::from_config(&config.network)),
+        thumbnails: ThumbnailCache::new(AppConfig::get_platform_cache_dir().join("thumbnails")),
+        album_art: AlbumArtCache::default(),
+        event_subscriptions: EventSubscriptions::default(),
+        connection_manager_subscriptions: EventSubscriptions::default(),
+        transcoder: Transcoder::detect(&config.media.transcoding),
+        subtitles: Default::default(),
+        playlists: Default::default(),
+        icon: DeviceIcon::from_config(&config.server),
+        started_at: std::time::Instant::now(),
+        boot_id,
+        config_id: 0,
+        scan_errors: Arc::new(std::sync::RwLock::new(scan_errors)),
     };
+    app_state.update_config_id();
+    // Pictures cached by the initial scan and earlier runs
+    app_state.album_art.load().await;
+    if let Err(e) = app_state.refresh_subtitles().await {
+        warn!("Failed to load subtitle sidecars: {}", e);
+    }
+    if let Err(e) = app_state.refresh_playlists().await {
+        warn!("Failed to load playlists: {}", e);
+    }
+    app_state.event_subscriptions.start(&app_state.content_updates);
 
     // Start file system monitoring
     if let Err(e) = start_file_monitoring(file_watcher.clone(), app_state.clone()).await {
@@ -128,74 +233,201 @@ async fn main() -> anyhow::Result<()> {
         warn!("Continuing without real-time file monitoring");
     }
 
+    if config.media.tv_show_grouping {
+        start_tv_show_grouping(app_state.clone());
+    }
+
     // Start runtime platform adaptation services
-    let adaptation_handle = start_platform_adaptation(
-        platform_info.clone(),
-        config.clone(),
-        database.clone(),
-        media_files.clone(),
-    ).await?;
+    // Configuration read from stdin or a URL has no file to watch
+    let config_reload_enabled = config_file_path
+        .as_deref()
+        .is_none_or(|arg| ConfigSource::parse(arg).supports_hot_reload());
+
+    // Bind the HTTP listener first so the LOCATION advertised by SSDP is reachable
+    let listener = match bind_http_listener(&config).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Failed to bind HTTP server: {}", e);
+            return Err(e);
+        }
+    };
 
     // Start SSDP discovery service with platform abstraction
-    if let Err(e) = start_ssdp_service(app_state.clone()).await {
-        error!("Failed to start SSDP service: {}", e);
-        return Err(e);
+    let ssdp = match start_ssdp_service(app_state.clone()).await {
+        Ok(ssdp) => ssdp,
+        Err(e) => {
+            error!("Failed to start SSDP service: {}", e);
+            return Err(e);
+        }
+    };
+
+    // One signal stops every service; nothing else listens for Ctrl+C
+    let shutdown = Shutdown::new();
+    shutdown.trigger_on_ctrl_c();
+
+    // Adaptation rebinds the SSDP service when the network changes; shutdown
+    // needs it afterwards to announce the server's departure
+    let ssdp = Arc::new(tokio::sync::Mutex::new(ssdp));
+    let mut adaptation_handle =
+        start_platform_adaptation(app_state.clone(), ssdp.clone(), file_watcher.clone(), config_reload_enabled, shutdown.clone()).await?;
+    start_backup_schedule(app_state.clone(), shutdown.clone());
+    if config.media.rescan_interval_minutes > 0 {
+        info!("Rescanning media directories every {} minutes", config.media.rescan_interval_minutes);
+        app_state.spawn_periodic_rescan(
+            std::time::Duration::from_secs(config.media.rescan_interval_minutes * 60),
+            shutdown.clone(),
+        );
     }
 
     // Start the HTTP server
-    if let Err(e) = start_http_server(app_state).await {
-        error!("Failed to start HTTP server: {}", e);
-        return Err(e);
-    }
+    let mut http_handle = tokio::spawn(start_http_server(app_state, listener, shutdown.clone()));
 
-    // Wait for shutdown signal and cleanup
+    // Wait for shutdown signal, or for a service to stop on its own
+    let mut server_error = None;
     tokio::select! {
-        _ = tokio::signal::ctrl_c() => {
-            info!("Received shutdown signal (Ctrl+C)");
+        _ = shutdown.wait() => {}
+        result = &mut http_handle => {
+            let error = match result {
+                Ok(Ok(())) => anyhow::anyhow!("HTTP server stopped unexpectedly"),
+                Ok(Err(e)) => e,
+                Err(e) => anyhow::anyhow!("HTTP server task failed: {}", e),
+            };
+            error!("{:#}", error);
+            server_error = Some(error);
         }
-        _ = adaptation_handle => {
+        _ = &mut adaptation_handle => {
             warn!("Platform adaptation service stopped unexpectedly");
         }
     }
+    shutdown.trigger();
 
-    // Perform graceful shutdown
-    perform_graceful_shutdown(database, file_watcher).await?;
+    // Stop the services in order: no more requests, no more adaptation, then
+    // the SSDP byebye and database cleanup
+    if !http_handle.is_finished() {
+        info!("Waiting for HTTP connections to finish...");
+        if tokio::time::timeout(HTTP_DRAIN_TIMEOUT, &mut http_handle).await.is_err() {
+            warn!("HTTP connections still open after {:?}; closing them", HTTP_DRAIN_TIMEOUT);
+            http_handle.abort();
+        }
+    }
+    if !adaptation_handle.is_finished() {
+        let _ = adaptation_handle.await;
+    }
+    perform_graceful_shutdown(ssdp, database, file_watcher, &config).await?;
+    if let Some(error) = server_error {
+        return Err(error);
+    }
     
     info!("Shutdown completed successfully");
     Ok(())
 }
 
+/// Longest wait for in-flight HTTP responses, such as media streams, at shutdown
+const HTTP_DRAIN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Back up the database every `database.backup.interval_hours` while the server runs
+fn start_backup_schedule(app_state: AppState, shutdown: Shutdown) {
+    let backup = app_state.config.database.backup.clone();
+    // A Postgres server is backed up with its own tools
+    if !backup.enabled || backup.interval_hours == 0 || app_state.config.database.backend != DatabaseBackend::Sqlite {
+        return;
+    }
+    let backup_dir = app_state.config.get_backup_directory();
+    info!("Scheduled database backups every {} hours to {}", backup.interval_hours, backup_dir.display());
+
+    tokio::spawn(async move {
+        let period = std::time::Duration::from_secs(backup.interval_hours.saturating_mul(3600));
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.wait() => break,
+            }
+            if let Err(e) = database::backup::create_backup(
+                app_state.database.as_ref(),
+                &backup_dir,
+                "scheduled",
+                backup.max_backups,
+            )
+            .await
+            {
+                warn!("Scheduled database backup failed: {:#}", e);
+            }
+        }
+    });
+}
+
+/// Build the TV show grouping now and rebuild it whenever clients are told the
+/// library changed, so scans and watcher events both reach it
+fn start_tv_show_grouping(app_state: AppState) {
+    let mut updates = app_state.content_updates.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match app_state.refresh_tv_library().await {
+                Ok(count) => info!("TV show grouping refreshed: {} episodes", count),
+                Err(e) => warn!("Failed to refresh TV show grouping: {}", e),
+            }
+            match updates.recv().await {
+                Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
 /// Start platform adaptation services for runtime detection and adaptation
 async fn start_platform_adaptation(
-    platform_info: Arc<PlatformInfo>,
-    config: Arc<AppConfig>,
-    database: Arc<dyn DatabaseManager>,
-    media_files: Arc<RwLock<Vec<database::MediaFile>>>,
+    app_state: AppState,
+    ssdp: Arc<tokio::sync::Mutex<SsdpController>>,
+    file_watcher: Arc<CrossPlatformWatcher>,
+    config_reload_enabled: bool,
+    shutdown: Shutdown,
 ) -> anyhow::Result<tokio::task::JoinHandle<()>> {
     info!("Starting platform adaptation services...");
+    if !config_reload_enabled {
+        info!("Configuration hot-reload disabled for stdin/URL configuration sources");
+    }
     
-    let platform_info_clone = platform_info.clone();
-    let config_clone = config.clone();
-    let database_clone = database.clone();
-    let media_files_clone = media_files.clone();
+    // start_file_monitoring only starts the watcher when watching was enabled at startup
+    let watcher_started = app_state.config.media.watch_for_changes;
+
+    // Validation already rejected malformed expressions, so a parse failure here is unexpected
+    let scan_schedule = match app_state.config.media.scan_schedule.as_deref().map(media::parse_scan_schedule) {
+        Some(Ok(schedule)) => Some(schedule),
+        Some(Err(e)) => {
+            warn!("Scheduled scans disabled: {}", e);
+            None
+        }
+        None => None,
+    };
     
     let handle = tokio::spawn(async move {
         let mut network_check_interval = tokio::time::interval(std::time::Duration::from_secs(30));
         let mut config_check_interval = tokio::time::interval(std::time::Duration::from_secs(60));
+        let mut next_scan = scan_schedule.as_ref().and_then(next_scan_deadline);
+        let mut config_file = ConfigFileTracker::new(AppConfig::get_platform_config_file_path());
         
         loop {
             tokio::select! {
                 _ = network_check_interval.tick() => {
-                    if let Err(e) = check_and_adapt_network_changes(&platform_info_clone).await {
+                    if let Err(e) = check_and_adapt_network_changes(&mut *ssdp.lock().await, &app_state.didl_cache).await {
                         warn!("Network adaptation check failed: {}", e);
                     }
                 }
-                _ = config_check_interval.tick() => {
-                    if let Err(e) = check_and_reload_configuration(&config_clone, &database_clone, &media_files_clone).await {
+                _ = config_check_interval.tick(), if config_reload_enabled => {
+                    if let Err(e) = check_and_reload_configuration(&mut config_file, &app_state, watcher_started.then_some(file_watcher.as_ref() as &dyn FileSystemWatcher)).await {
                         warn!("Configuration reload check failed: {}", e);
                     }
                 }
-                _ = tokio::signal::ctrl_c() => {
+                _ = async { tokio::time::sleep_until(next_scan.unwrap()).await }, if next_scan.is_some() => {
+                    info!("Starting scheduled media scan");
+                    match app_state.rescan_all_directories().await {
+                        Ok(result) => info!("Scheduled media scan completed: {}", result.summary()),
+                        Err(e) => warn!("Scheduled media scan failed: {}", e),
+                    }
+                    next_scan = scan_schedule.as_ref().and_then(next_scan_deadline);
+                }
+                _ = shutdown.wait() => {
                     info!("Platform adaptation service received shutdown signal");
                     break;
                 }
@@ -209,8 +441,19 @@ async fn start_platform_adaptation(
     Ok(handle)
 }
 
+/// Convert the next fire time of a scan schedule into a tokio deadline
+fn next_scan_deadline(schedule: &cron::Schedule) -> Option<tokio::time::Instant> {
+    let now = chrono::Local::now();
+    let next = media::next_scheduled_scan(schedule, &now)?;
+    info!("Next scheduled media scan at {}", next);
+    let delay = (next - now).to_std().unwrap_or_default();
+    Some(tokio::time::Instant::now() + delay)
+}
+
 /// Check for network changes and adapt accordingly
-async fn check_and_adapt_network_changes(platform_info: &Arc<PlatformInfo>) -> anyhow::Result<()> {
+async fn check_and_adapt_network_changes(ssdp: &mut SsdpController, didl_cache: &DidlCache) -> anyhow::Result<()> {
+    let platform_info = ssdp.platform_info();
+
     // Re-detect network interfaces to check for changes
     let current_platform_info = PlatformInfo::detect().await
         .context("Failed to re-detect platform information")?;
@@ -262,7 +505,6 @@ async fn check_and_adapt_network_changes(platform_info: &Arc<PlatformInfo>) -> a
             (Some(old), Some(new)) if old.name != new.name || old.ip_address != new.ip_address => {
                 info!("Primary network interface changed: {} ({}) -> {} ({})",
                     old.name, old.ip_address, new.name, new.ip_address);
-                // TODO: Restart SSDP service with new interface
             }
             (Some(old), None) => {
                 warn!("Primary network interface lost: {} ({})", old.name, old.ip_address);
@@ -270,7 +512,6 @@ async fn check_and_adapt_network_changes(platform_info: &Arc<PlatformInfo>) -> a
             }
             (None, Some(new)) => {
                 info!("Primary network interface available: {} ({})", new.name, new.ip_address);
-                // TODO: Start SSDP service if it wasn't running
             }
             _ => {} // No change in primary interface
         }
@@ -281,6 +522,16 @@ async fn check_and_adapt_network_changes(platform_info: &Arc<PlatformInfo>) -> a
         } else if new_interfaces.iter().all(|iface| !iface.supports_multicast) {
             warn!("No multicast-capable interfaces available - DLNA discovery may not work");
         }
+
+        // Cached Browse responses embed the server's address
+        didl_cache.clear();
+
+        match ssdp.update_platform_info(current_platform_info)? {
+            SsdpAdaptation::Restarted => info!("SSDP service rebound to the new primary interface"),
+            SsdpAdaptation::Paused => warn!("SSDP service paused until a network interface is available"),
+            SsdpAdaptation::Resumed => info!("SSDP service resumed"),
+            SsdpAdaptation::Unchanged => {}
+        }
     }
     
     Ok(())
@@ -288,32 +539,39 @@ async fn check_and_adapt_network_changes(platform_info: &Arc<PlatformInfo>) -> a
 
 /// Check for configuration changes and reload if necessary
 async fn check_and_reload_configuration(
-    config: &Arc<AppConfig>,
-    database: &Arc<dyn DatabaseManager>,
-    media_files: &Arc<RwLock<Vec<database::MediaFile>>>,
+    config_file: &mut ConfigFileTracker,
+    state: &AppState,
+    file_watcher: Option<&dyn FileSystemWatcher>,
 ) -> anyhow::Result<()> {
-    let config_path = AppConfig::get_platform_config_file_path();
-    
-    // Check if configuration file has been modified
-    if let Ok(metadata) = tokio::fs::metadata(&config_path).await {
-        let modified = metadata.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH);
+    // Only reload once the file's modification time moves past the last loaded one
+    if let Some(modified) = config_file.modified_since_load().await {
+        info!("Configuration file was modified, checking for changes...");
         
-        // For simplicity, we'll check if the file was modified in the last minute
-        // In a real implementation, we'd track the last known modification time
-        let one_minute_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(60);
-        
-        if modified > one_minute_ago {
-            info!("Configuration file may have been modified, checking for changes...");
-            
-            match AppConfig::load_from_file(&config_path) {
-                Ok(new_config) => {
-                    if let Err(e) = handle_configuration_changes(config, &new_config, database, media_files).await {
-                        warn!("Failed to handle configuration changes: {}", e);
-                    }
+        let reloaded = AppConfig::load_from_file(config_file.path()).and_then(|mut new_config| {
+            new_config.apply_env_overrides()?;
+            Ok(new_config)
+        });
+        match reloaded {
+            Ok(new_config) => {
+                config_file.mark_loaded(modified);
+                // Reloads are compared against the last applied configuration, not the startup one
+                let config = state.applied_config.get();
+                // Token changes apply immediately so revoked tokens stop working
+                if new_config.server.api_tokens != config.server.api_tokens
+                    || new_config.server.api_token != config.server.api_token
+                {
+                    info!("API tokens reloaded: {:?}", new_config.server.api_tokens.iter().map(|t| &t.label).collect::<Vec<_>>());
                 }
-                Err(e) => {
-                    warn!("Failed to load updated configuration: {}", e);
+                state.api_tokens.reload(&new_config.server);
+                if let Err(e) = handle_configuration_changes(&config, &new_config, &state.database, &state.media_files, &state.album_art, file_watcher).await {
+                    warn!("Failed to handle configuration changes: {}", e);
                 }
+                // Cached Browse responses may reflect display names or thumbnail settings
+                state.didl_cache.clear();
+                state.applied_config.set(Arc::new(new_config));
+            }
+            Err(e) => {
+                warn!("Failed to load updated configuration: {}", e);
             }
         }
     }
@@ -327,15 +585,27 @@ async fn handle_configuration_changes(
     new_config: &AppConfig,
     database: &Arc<dyn DatabaseManager>,
     media_files: &Arc<RwLock<Vec<database::MediaFile>>>,
+    album_art: &AlbumArtCache,
+    file_watcher: Option<&dyn FileSystemWatcher>,
 ) -> anyhow::Result<()> {
     let mut changes_detected = false;
+
+    // Live monitoring follows added and removed directories, and watching being switched off,
+    // once the watcher was started
+    if let Some(file_watcher) = file_watcher {
+        if let Err(e) = apply_directory_changes(file_watcher, &old_config.media, &new_config.media).await {
+            warn!("Failed to update watched directories: {}", e);
+        }
+    }
     
     // Check for media directory changes
-    let old_dirs: std::collections::HashSet<_> = old_config.media.directories
+    let old_directories = old_config.media.effective_directories();
+    let new_directories = new_config.media.effective_directories();
+    let old_dirs: std::collections::HashSet<_> = old_directories
         .iter()
         .map(|d| &d.path)
         .collect();
-    let new_dirs: std::collections::HashSet<_> = new_config.media.directories
+    let new_dirs: std::collections::HashSet<_> = new_directories
         .iter()
         .map(|d| &d.path)
         .collect();
@@ -344,7 +614,14 @@ async fn handle_configuration_changes(
         info!("Media directory configuration changed");
         changes_detected = true;
         
-        let scanner = media::MediaScanner::with_database(database.clone());
+        let scanner = media::MediaScanner::with_database(database.clone())
+            .with_hidden_files(new_config.media.include_hidden)
+            .with_follow_symlinks(new_config.media.follow_symlinks)
+            .with_min_file_size(new_config.media.min_file_size_bytes)
+            .with_scan_concurrency(new_config.media.scan_concurrency)
+            .with_media_types(new_config.media.media_types())
+            .with_metadata_extraction(new_config.media.extract_metadata)
+            .with_album_art(album_art.clone());
         let mut cache_needs_reload = false;
 
         // Find added directories
@@ -352,7 +629,7 @@ async fn handle_configuration_changes(
             if !old_dirs.contains(new_dir_path_str) {
                 info!("New media directory added: {}", new_dir_path_str);
                 
-                if let Some(dir_config) = new_config.media.directories.iter().find(|d| &d.path == *new_dir_path_str) {
+                if let Some(dir_config) = new_directories.iter().find(|d| &d.path == *new_dir_path_str) {
                     let dir_path = std::path::PathBuf::from(&dir_config.path);
                     if dir_path.exists() && dir_path.is_dir() {
                         let scan_result = if dir_config.recursive {
@@ -386,24 +663,19 @@ async fn handle_configuration_changes(
                 
                 // Remove files from this directory from database and cache
                 let dir_path = std::path::PathBuf::from(old_dir);
-                let files_to_remove = database.get_files_in_directory(&dir_path).await
-                    .unwrap_or_default();
-                
-                if !files_to_remove.is_empty() {
-                    cache_needs_reload = true;
-                }
-
-                for file in &files_to_remove {
-                    if let Err(e) = database.remove_media_file(&file.path).await {
-                        warn!("Failed to remove media file from database: {} - {}", file.path.display(), e);
+                match database.remove_files_in_directory(&dir_path).await {
+                    Ok(removed) => {
+                        if removed > 0 {
+                            cache_needs_reload = true;
+                        }
+                        info!("Removed {} files from removed directory", removed);
                     }
+                    Err(e) => warn!("Failed to remove media files of {} from database: {}", dir_path.display(), e),
                 }
-                
-                info!("Removed {} files from removed directory", files_to_remove.len());
             }
         }
 
-        if cache_needs_reload {
+        if cache_needs_reload && new_config.server.in_memory_cache {
             info!("Reloading in-memory media cache due to directory changes...");
             let all_files = database.get_all_media_files().await?;
             *media_files.write().await = all_files;
@@ -419,10 +691,11 @@ async fn handle_configuration_changes(
         
         if new_config.media.watch_for_changes {
             info!("File watching enabled - new file changes will be detected");
-            // TODO: Start file watcher if not already running
+            if file_watcher.is_none() {
+                warn!("File watching was disabled at startup; enabling it requires a restart to take effect");
+            }
         } else {
             info!("File watching disabled - file changes will not be detected automatically");
-            // TODO: Stop file watcher if running
         }
     }
     
@@ -470,12 +743,14 @@ async fn handle_configuration_changes(
 }
 
 /// Implement graceful degradation when platform features are unavailable
+#[allow(dead_code)]
 async fn handle_platform_feature_unavailable(feature: &str, error: &anyhow::Error) -> anyhow::Result<()> {
     match feature {
         "multicast" => {
             warn!("Multicast networking unavailable: {}", error);
             warn!("DLNA discovery will be limited - clients may need manual configuration");
-            info!("Consider using unicast discovery or manual IP configuration");
+            info!("SSDP answers unicast M-SEARCH and broadcasts announcements instead");
+            info!("Clients can add the server manually using its description.xml URL");
         }
         "privileged_ports" => {
             warn!("Privileged port access unavailable: {}", error);
@@ -580,7 +855,7 @@ async fn detect_platform_with_diagnostics() -> anyhow::Result<PlatformInfo> {
 }
 
 /// Initialize configuration with platform-specific defaults and validation
-async fn initialize_configuration(_platform_info: &PlatformInfo) -> anyhow::Result<AppConfig> {
+async fn initialize_configuration(_platform_info: &PlatformInfo, require_cli_config: bool) -> anyhow::Result<AppConfig> {
     info!("Initializing configuration...");
     
     let config_path = AppConfig::get_platform_config_file_path();
@@ -590,7 +865,7 @@ async fn initialize_configuration(_platform_info: &PlatformInfo) -> anyhow::Resu
     match AppConfig::from_args().await {
         Ok((config, debug, config_path)) => {
             if let Some(path) = config_path {
-                info!("Using configuration from file: {}", path);
+                info!("Using configuration from: {}", path);
             } else {
                 info!("Using configuration from command line arguments");
             }
@@ -619,6 +894,9 @@ async fn initialize_configuration(_platform_info: &PlatformInfo) -> anyhow::Resu
             
             return Ok(config);
         }
+        Err(e) if require_cli_config => {
+            return Err(e.context("Invalid configuration given on the command line"));
+        }
         Err(e) => {
             debug!("No valid command line arguments provided: {}", e);
             info!("Falling back to configuration file or platform defaults");
@@ -639,11 +917,19 @@ async fn initialize_configuration(_platform_info: &PlatformInfo) -> anyhow::Resu
     config.apply_platform_defaults()
         .context("Failed to apply platform-specific defaults")?;
     
+    // Environment variables override the file but are never saved to it
+    let file_config = config.clone();
+    let applied = config.apply_env_overrides()
+        .context("Failed to apply environment variable overrides")?;
+    if !applied.is_empty() {
+        info!("Configuration overridden by environment: {}", applied.join(", "));
+    }
+    
     config.validate_for_platform()
         .context("Configuration validation failed")?;
     
     // Save the configuration (creates file if it doesn't exist, updates if needed)
-    config.save_to_file(&config_path)
+    file_config.save_to_file(&config_path)
         .context("Failed to save configuration file")?;
     
     info!("Configuration initialized successfully");
@@ -659,14 +945,11 @@ async fn initialize_configuration(_platform_info: &PlatformInfo) -> anyhow::Resu
 }
 
 /// Initialize database manager with health checks and recovery
-async fn initialize_database(config: &AppConfig) -> anyhow::Result<SqliteDatabase> {
+async fn initialize_database(config: &AppConfig) -> anyhow::Result<Arc<dyn DatabaseManager>> {
     info!("Initializing database...");
     
-    let db_path = config.get_database_path();
-    info!("Database path: {}", db_path.display());
-    
     // Create database manager
-    let database = SqliteDatabase::new(db_path.clone()).await
+    let database = open_database(config).await
         .context("Failed to create database manager")?;
     
     // Initialize database schema
@@ -697,15 +980,26 @@ async fn initialize_database(config: &AppConfig) -> anyhow::Result<SqliteDatabas
     } else {
         info!("Database health check passed");
     }
+
+    if health.rows_repaired() > 0 {
+        let repairs: Vec<&str> = health.issues.iter()
+            .filter(|issue| issue.rows_affected.is_some())
+            .map(|issue| issue.description.as_str())
+            .collect();
+        info!("Database repair: {}", repairs.join(", "));
+    }
     
     // Get database statistics
-    let stats = database.get_stats().await
+    let stats = database.get_stats_detailed().await
         .context("Failed to get database statistics")?;
     
     info!("Database statistics:");
-    info!("  - Total media files: {}", stats.total_files);
-    info!("  - Total media size: {} bytes", stats.total_size);
-    info!("  - Database file size: {} bytes", stats.database_size);
+    info!("  - Total media files: {}", stats.totals.total_files);
+    info!("  - Total media size: {} bytes", stats.totals.total_size);
+    info!("  - Video: {} files, {} bytes", stats.video.file_count, stats.video.total_size);
+    info!("  - Audio: {} files, {} bytes", stats.audio.file_count, stats.audio.total_size);
+    info!("  - Images: {} files, {} bytes", stats.image.file_count, stats.image.total_size);
+    info!("  - Database file size: {} bytes", stats.totals.database_size);
     
     // Vacuum database if configured
     if config.database.vacuum_on_startup {
@@ -719,6 +1013,38 @@ async fn initialize_database(config: &AppConfig) -> anyhow::Result<SqliteDatabas
     Ok(database)
 }
 
+/// Connect to the database backend selected by `database.backend`
+async fn open_database(config: &AppConfig) -> anyhow::Result<Arc<dyn DatabaseManager>> {
+    match config.database.backend {
+        DatabaseBackend::Sqlite => {
+            let db_path = config.get_database_path();
+            info!("Database path: {}", db_path.display());
+            let retry_policy = RetryPolicy {
+                max_retries: config.database.write_retry_attempts,
+                ..RetryPolicy::default()
+            };
+            let database = SqliteDatabase::with_options(
+                db_path,
+                std::time::Duration::from_millis(config.database.busy_timeout_ms),
+                retry_policy,
+            )
+            .await?;
+            Ok(Arc::new(database))
+        }
+        #[cfg(feature = "postgres")]
+        DatabaseBackend::Postgres => {
+            let url = config.database.url.as_deref()
+                .ok_or_else(|| anyhow::anyhow!("Database backend 'postgres' requires database.url"))?;
+            info!("Database: PostgreSQL");
+            Ok(Arc::new(database::postgres::PostgresDatabase::new(url).await?))
+        }
+        #[cfg(not(feature = "postgres"))]
+        DatabaseBackend::Postgres => {
+            anyhow::bail!("Database backend 'postgres' requires a build with the 'postgres' feature")
+        }
+    }
+}
+
 /// Initialize file system watcher for real-time media monitoring
 async fn initialize_file_watcher(config: &AppConfig, _database: Arc<dyn DatabaseManager>) -> anyhow::Result<CrossPlatformWatcher> {
     info!("Initializing file system watcher...");
@@ -728,11 +1054,15 @@ async fn initialize_file_watcher(config: &AppConfig, _database: Arc<dyn Database
         return Ok(CrossPlatformWatcher::new());
     }
     
-    let watcher = CrossPlatformWatcher::new();
+    let watcher = CrossPlatformWatcher::new()
+        .with_hidden_files(config.media.include_hidden)
+        .with_follow_symlinks(config.media.follow_symlinks)
+        .with_media_types(config.media.media_types())
+        .with_exclude_patterns(exclude_patterns(&config.media));
     
     // Validate that all monitored directories exist
     let mut valid_directories = Vec::new();
-    for dir_config in &config.media.directories {
+    for dir_config in &config.media.effective_directories() {
         let dir_path = std::path::PathBuf::from(&dir_config.path);
         if dir_path.exists() && dir_path.is_dir() {
             valid_directories.push(dir_path);
@@ -781,18 +1111,70 @@ async fn validate_and_cleanup_deleted_files(
     Ok(valid_files)
 }
 
-/// Perform initial media scan, using database cache when possible
-async fn perform_initial_media_scan(config: &AppConfig, database: &Arc<dyn DatabaseManager>) -> anyhow::Result<Vec<database::MediaFile>> {
+/// Rescan all configured directories once and print a summary, for `--rescan`
+async fn run_one_shot_rescan(config: &AppConfig, database: &Arc<dyn DatabaseManager>) -> anyhow::Result<()> {
+    info!("Rescanning all media directories...");
+    let scanner = media::MediaScanner::with_database(database.clone())
+        .with_hidden_files(config.media.include_hidden)
+        .with_follow_symlinks(config.media.follow_symlinks)
+        .with_min_file_size(config.media.min_file_size_bytes)
+        .with_scan_concurrency(config.media.scan_concurrency)
+        .with_media_types(config.media.media_types())
+        .with_metadata_extraction(config.media.extract_metadata)
+        .with_album_art(AlbumArtCache::default());
+    let result = scanner.scan_all_directories(&config.media.effective_directories()).await;
+
+    for err in &result.errors {
+        eprintln!("Scan error in {}: {}", err.path.display(), err.error);
+    }
+    println!("{}", result.summary());
+    Ok(())
+}
+
+/// Scan the library once and print what would be served, without starting SSDP
+/// or HTTP, for `--scan-only`. A missing media directory already fails config validation.
+async fn run_scan_only(config: &AppConfig, database: &Arc<dyn DatabaseManager>) -> anyhow::Result<()> {
+    // Always scan, even when the configuration only loads the cache on startup
+    let mut scan_config = config.clone();
+    scan_config.media.scan_on_startup = true;
+    let (_, errors) = perform_initial_media_scan(&scan_config, database).await?;
+    let stats = database.get_stats_detailed().await?;
+
+    println!("Library: {} files, {} bytes", stats.totals.total_files, stats.totals.total_size);
+    for (media_type, type_stats) in [("video", &stats.video), ("audio", &stats.audio), ("image", &stats.image), ("other", &stats.other)] {
+        println!("  {}: {} files, {} bytes", media_type, type_stats.file_count, type_stats.total_size);
+    }
+    println!("Scan errors: {}", errors.len());
+    for err in &errors {
+        println!("  {}: {}", err.path.display(), err.error);
+    }
+    Ok(())
+}
+
+/// Perform initial media scan, using database cache when possible. Returns the
+/// library together with the errors met while scanning.
+async fn perform_initial_media_scan(
+    config: &AppConfig,
+    database: &Arc<dyn DatabaseManager>,
+) -> anyhow::Result<(Vec<database::MediaFile>, Vec<media::ScanError>)> {
     info!("Performing initial media scan...");
 
     if config.media.scan_on_startup {
         info!("Full media scan enabled - scanning all directories");
 
-        let scanner = media::MediaScanner::with_database(database.clone());
+        let scanner = media::MediaScanner::with_database(database.clone())
+            .with_hidden_files(config.media.include_hidden)
+            .with_follow_symlinks(config.media.follow_symlinks)
+            .with_min_file_size(config.media.min_file_size_bytes)
+            .with_scan_concurrency(config.media.scan_concurrency)
+            .with_media_types(config.media.media_types())
+            .with_metadata_extraction(config.media.extract_metadata)
+            .with_album_art(AlbumArtCache::default());
         let mut total_changes = 0;
         let mut total_files_scanned = 0;
+        let mut errors = Vec::new();
 
-        for dir_config in &config.media.directories {
+        for dir_config in &config.media.effective_directories() {
             let dir_path = std::path::PathBuf::from(&dir_config.path);
 
             if !dir_path.exists() {
@@ -819,6 +1201,7 @@ async fn perform_initial_media_scan(config: &AppConfig, database: &Arc<dyn Datab
             }
             total_changes += scan_result.total_changes();
             total_files_scanned += scan_result.total_scanned;
+            errors.extend(scan_result.errors);
         }
 
         info!("Initial media scan completed - total files scanned: {}, total changes: {}", total_files_scanned, total_changes);
@@ -837,7 +1220,7 @@ async fn perform_initial_media_scan(config: &AppConfig, database: &Arc<dyn Datab
             all_media_files
         };
         
-        Ok(validated_files)
+        Ok((validated_files, errors))
     } else {
         info!("Loading media files from database cache (scan on startup disabled)");
 
@@ -854,7 +1237,7 @@ async fn perform_initial_media_scan(config: &AppConfig, database: &Arc<dyn Datab
             cached_files
         };
 
-        Ok(validated_files)
+        Ok((validated_files, Vec::new()))
     }
 }
 
@@ -871,19 +1254,19 @@ async fn start_file_monitoring(
     info!("Starting file system monitoring...");
     
     // Get directories to monitor
-    let directories: Vec<std::path::PathBuf> = app_state.config.media.directories
-        .iter()
-        .map(|dir| std::path::PathBuf::from(&dir.path))
-        .filter(|path| path.exists() && path.is_dir())
+    // Non-recursive directories are watched without their subdirectories, as they are scanned
+    let directories: Vec<(std::path::PathBuf, RecursiveMode)> = watched_directories(&app_state.config.media)
+        .into_iter()
+        .filter(|(path, _)| path.exists() && path.is_dir())
         .collect();
     
+    // The watcher still starts, so directories added to the configuration later are watched
     if directories.is_empty() {
         warn!("No valid directories to monitor");
-        return Ok(());
     }
     
     info!("Starting to monitor {} directories:", directories.len());
-    for (i, dir) in directories.iter().enumerate() {
+    for (i, (dir, _)) in directories.iter().enumerate() {
         info!("  {}: {}", i + 1, dir.display());
     }
     
@@ -903,9 +1286,17 @@ async fn start_file_monitoring(
         info!("File system event handler started");
         
         while let Some(event) = event_receiver.recv().await {
+            // Entries of the playlists listing the event's files may now match
+            // files that were just added or removed
+            let paths: Vec<std::path::PathBuf> = event.paths().into_iter().cloned().collect();
             if let Err(e) = handle_file_system_event(event, &app_state_clone).await {
                 error!("Failed to handle file system event: {}", e);
             }
+            match app_state_clone.refresh_playlists_listing(&paths).await {
+                Ok(true) => increment_content_update_id(&app_state_clone, Vec::new()),
+                Ok(false) => {}
+                Err(e) => warn!("Failed to refresh playlists: {}", e),
+            }
         }
         
         warn!("File system event handler stopped");
@@ -915,16 +1306,15 @@ async fn start_file_monitoring(
     Ok(())
 }
 
-/// Increment the content update ID to notify DLNA clients of changes
-fn increment_content_update_id(app_state: &AppState) {
-    let old_id = app_state.content_update_id.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-    let new_id = old_id + 1;
-    info!("Content update ID incremented from {} to {}", old_id, new_id);
-    
-    // Send UPnP event notifications to subscribed clients
-    // In a full implementation, we would maintain a list of subscribed clients
-    // For now, we'll just log that an event should be sent
-    info!("UPnP event notification should be sent with UpdateID: {}", new_id);
+/// Record a content change affecting `changes`; DLNA clients are notified once
+/// the burst settles
+fn increment_content_update_id(app_state: &AppState, changes: Vec<ContentChange>) {
+    app_state.content_updates.record_object_changes(changes);
+    debug!(
+        "Content change recorded ({} pending, current UpdateID: {})",
+        app_state.content_updates.pending_changes(),
+        app_state.content_updates.current_update_id()
+    );
 }
 
 /// Handle individual file system events
@@ -932,8 +1322,37 @@ async fn handle_file_system_event(
     event: FileSystemEvent,
     app_state: &AppState,
 ) -> anyhow::Result<()> {
+    app_state.small_files.handle_event(&event);
     let database = &app_state.database;
+
+    // Only the playlist named by the event is read and resolved again
+    let playlist_paths: Vec<&std::path::PathBuf> =
+        event.paths().into_iter().filter(|path| media::playlist::is_playlist_file(path)).collect();
+    if !playlist_paths.is_empty() {
+        let mut changed = false;
+        for path in playlist_paths {
+            changed |= app_state.refresh_playlist(path).await?;
+            info!("Playlist updated: {}", path.display());
+        }
+        if changed {
+            increment_content_update_id(app_state, Vec::new());
+        }
+        return Ok(());
+    }
+
     let media_files = &app_state.media_files;
+    let use_cache = app_state.uses_media_cache();
+    // Directory settings follow configuration reloads
+    let config = app_state.applied_config.get();
+    let min_file_size = config.media.min_file_size_bytes;
+    let media_types = config.media.media_types();
+    // A file skipped while it was too small is indexed once it has grown
+    let event = match event {
+        FileSystemEvent::Modified(path) if path.is_file() && database.get_file_by_path(&path).await?.is_none() => {
+            FileSystemEvent::Created(path)
+        }
+        event => event,
+    };
     match event {
         FileSystemEvent::Created(path) => {
             // Check if this is a directory or a file
@@ -941,13 +1360,20 @@ async fn handle_file_system_event(
                 info!("Directory created: {}", path.display());
                 
                 // Scan the new directory for media files
-                let scanner = media::MediaScanner::with_database(database.clone());
+                let scanner = media::MediaScanner::with_database(database.clone())
+                    .with_hidden_files(config.media.include_hidden)
+                    .with_follow_symlinks(config.media.follow_symlinks)
+                    .with_min_file_size(config.media.min_file_size_bytes)
+                    .with_scan_concurrency(config.media.scan_concurrency)
+                    .with_media_types(media_types.clone())
+                    .with_metadata_extraction(config.media.extract_metadata)
+                    .with_album_art(app_state.album_art.clone());
                 match scanner.scan_directory_recursive(&path).await {
                     Ok(scan_result) => {
                         info!("Scanned new directory {}: {}", path.display(), scan_result.summary());
                         
                         // Update in-memory cache with newly found files
-                        if !scan_result.new_files.is_empty() {
+                        if use_cache && !scan_result.new_files.is_empty() {
                             let mut files = media_files.write().await;
                             for new_file in &scan_result.new_files {
                                 // Only add if not already in cache
@@ -961,7 +1387,7 @@ async fn handle_file_system_event(
                         
                         // Increment update ID to notify DLNA clients
                         if !scan_result.new_files.is_empty() {
-                            increment_content_update_id(app_state);
+                            increment_content_update_id(app_state, scan_changes(&scan_result));
                         }
                     }
                     Err(e) => {
@@ -972,65 +1398,80 @@ async fn handle_file_system_event(
                 // Handle individual media file creation
                 info!("Media file created: {}", path.display());
                 
-                // Check if it's actually a media file
-                let is_media_file = if let Some(extension) = path.extension() {
-                    if let Some(ext_str) = extension.to_str() {
-                        crate::platform::filesystem::is_supported_media_extension(ext_str)
-                    } else {
-                        false
-                    }
-                } else {
-                    false
-                };
-                
-                if !is_media_file {
+                // Check if it's actually a media file, by its content where sniffing applies
+                let Some(mime_type) = media::detect_mime_type(&path, &media_types).await else {
                     debug!("Not a supported media file, ignoring: {}", path.display());
                     return Ok(());
-                }
+                };
                 
                 // Create MediaFile record
                 let metadata = tokio::fs::metadata(&path).await?;
-                let mime_type = media::get_mime_type(&path);
+                if metadata.len() < min_file_size {
+                    debug!("Ignoring {} until it reaches {} bytes", path.display(), min_file_size);
+                    return Ok(());
+                }
                 let mut media_file = database::MediaFile::new(path.clone(), metadata.len(), mime_type);
                 media_file.modified = metadata.modified().unwrap_or(std::time::SystemTime::now());
+                if config.media.extract_metadata {
+                    let cover_art = metadata::probe_media_file(&mut media_file).await;
+                    app_state.album_art.cache_extracted(&media_file, cover_art).await;
+                }
                 
                 // Store in database
                 let file_id = database.store_media_file(&media_file).await?;
                 media_file.id = Some(file_id);
                 
                 // Add to in-memory cache
-                let mut files = media_files.write().await;
-                files.push(media_file);
+                if use_cache {
+                    media_files.write().await.push(media_file);
+                }
                 
                 info!("Added new media file to database: {}", path.display());
                 
                 // Increment update ID to notify DLNA clients
-                increment_content_update_id(app_state);
+                increment_content_update_id(app_state, vec![ContentChange::added(file_id)]);
             }
         }
         
         FileSystemEvent::Modified(path) => {
             info!("Media file modified: {}", path.display());
             
-            // Update database record
-            if let Some(mut existing_file) = database.get_file_by_path(&path).await? {
-                let metadata = tokio::fs::metadata(&path).await?;
-                existing_file.size = metadata.len();
-                existing_file.modified = metadata.modified().unwrap_or(std::time::SystemTime::now());
-                
-                database.update_media_file(&existing_file).await?;
-                
-                // Update in-memory cache
+            // Unindexed files were turned into Created events above
+            let Some(mut media_file) = database.get_file_by_path(&path).await? else {
+                return Ok(());
+            };
+
+            let metadata = tokio::fs::metadata(&path).await?;
+            if metadata.len() < min_file_size {
+                // Being rewritten; serving refuses it until it has grown again
+                debug!("Not updating {} while it is below {} bytes", path.display(), min_file_size);
+                return Ok(());
+            }
+            media_file.size = metadata.len();
+            media_file.modified = metadata.modified().unwrap_or(std::time::SystemTime::now());
+            if config.media.extract_metadata {
+                let cover_art = metadata::probe_media_file(&mut media_file).await;
+                app_state.album_art.invalidate(&media_file).await;
+                app_state.album_art.cache_extracted(&media_file, cover_art).await;
+            }
+
+            // Inserts the record again if it was removed meanwhile
+            database.update_media_file(&media_file).await?;
+            let changes: Vec<_> = media_file.id.map(ContentChange::modified).into_iter().collect();
+
+            // Update in-memory cache
+            if use_cache {
                 let mut files = media_files.write().await;
-                if let Some(cached_file) = files.iter_mut().find(|f| f.path == path) {
-                    *cached_file = existing_file;
+                match files.iter_mut().find(|f| f.path == path) {
+                    Some(cached_file) => *cached_file = media_file,
+                    None => files.push(media_file),
                 }
-                
-                info!("Updated media file in database: {}", path.display());
-                
-                // Increment update ID to notify DLNA clients
-                increment_content_update_id(app_state);
             }
+
+            info!("Updated media file in database: {}", path.display());
+
+            // Increment update ID to notify DLNA clients
+            increment_content_update_id(app_state, changes);
         }
         
         FileSystemEvent::Deleted(path) => {
@@ -1041,10 +1482,13 @@ async fn handle_file_system_event(
             info!("Path deleted: {}", path.display());
             
             // First, try to remove as a single file
+            let mut changes = Vec::new();
+            let single_file_id = database.get_file_by_path(&path).await.ok().flatten().and_then(|file| file.id);
             let single_file_removed = match database.remove_media_file(&path).await {
                 Ok(removed) => {
                     if removed {
                         info!("Removed single file from database: {}", path.display());
+                        changes.extend(single_file_id.map(ContentChange::removed));
                     } else {
                         info!("Single file not found in database: {}", path.display());
                     }
@@ -1056,63 +1500,38 @@ async fn handle_file_system_event(
                 }
             };
             
-            // Also check for files that were in this directory path
-            let all_files = match database.get_all_media_files().await {
-                Ok(files) => files,
+            // Then remove everything that was below it as a directory in one statement,
+            // reading the ids first so renderers learn which objects went away
+            let filesystem = create_platform_filesystem_manager();
+            let removed_ids: Vec<i64> = match database.get_files_in_directory(&path).await {
+                Ok(files) => files.into_iter().filter_map(|file| file.id).collect(),
                 Err(e) => {
-                    warn!("Error getting all media files: {}", e);
-                    return Ok(());
+                    warn!("Error listing media files in deleted path {}: {}", path.display(), e);
+                    Vec::new()
                 }
             };
-            
-            // Normalize paths for case-insensitive comparison on Windows
-            let normalized_deleted_path = path.to_string_lossy().to_lowercase();
-            let files_in_deleted_path: Vec<_> = all_files
-                .iter()
-                .filter(|file| {
-                    let normalized_file_path = file.path.to_string_lossy().to_lowercase();
-                    let matches = normalized_file_path.starts_with(&normalized_deleted_path);
-                    if matches {
-                        info!("Found file in deleted path: {} starts with {}", file.path.display(), path.display());
-                    }
-                    matches
-                })
-                .collect();
-            
             let mut total_removed = if single_file_removed { 1 } else { 0 };
-            
-            if !files_in_deleted_path.is_empty() {
-                info!("Found {} media files in deleted directory: {}", files_in_deleted_path.len(), path.display());
-                
-                for file in &files_in_deleted_path {
-                    match database.remove_media_file(&file.path).await {
-                        Ok(true) => {
-                            total_removed += 1;
-                            info!("Removed file from database: {}", file.path.display());
-                        }
-                        Ok(false) => {
-                            info!("File not found in database: {}", file.path.display());
-                        }
-                        Err(e) => {
-                            warn!("Error removing file from database {}: {}", file.path.display(), e);
-                        }
-                    }
+            match database.remove_files_in_directory(&path).await {
+                Ok(0) => info!("No files found in deleted path: {}", path.display()),
+                Ok(removed) => {
+                    info!("Removed {} media files in deleted directory: {}", removed, path.display());
+                    total_removed += removed;
+                    changes.extend(removed_ids.into_iter().map(ContentChange::removed));
+                }
+                Err(e) => {
+                    warn!("Error removing files in deleted directory {}: {}", path.display(), e);
                 }
-            } else {
-                info!("No files found in deleted path: {}", path.display());
-                // Debug: show some database paths for comparison
-                let sample_paths: Vec<_> = all_files.iter().take(5).map(|f| f.path.display().to_string()).collect();
-                info!("Sample database paths: {:?}", sample_paths);
             }
             
-            // Remove from in-memory cache (case-insensitive on Windows)
-            let mut files = media_files.write().await;
-            let initial_count = files.len();
-            files.retain(|f| {
-                let normalized_file_path = f.path.to_string_lossy().to_lowercase();
-                !normalized_file_path.starts_with(&normalized_deleted_path)
-            });
-            let removed_from_cache = initial_count - files.len();
+            // Remove from in-memory cache
+            let removed_from_cache = if use_cache {
+                let mut files = media_files.write().await;
+                let initial_count = files.len();
+                files.retain(|f| !filesystem.path_starts_with(&f.path, &path));
+                initial_count - files.len()
+            } else {
+                0
+            };
             
             info!("Cache cleanup: removed {} files from in-memory cache", removed_from_cache);
             
@@ -1121,7 +1540,7 @@ async fn handle_file_system_event(
                       total_removed, removed_from_cache, path.display());
                 
                 // Increment update ID to notify DLNA clients
-                increment_content_update_id(app_state);
+                increment_content_update_id(app_state, changes);
                 info!("Notified DLNA clients of content change");
             } else {
                 info!("No files were removed for deleted path: {}", path.display());
@@ -1137,31 +1556,47 @@ async fn handle_file_system_event(
                 info!("Directory renamed: {} -> {}", from.display(), to.display());
                 
                 // Get all files that were in the old directory path
+                let filesystem = create_platform_filesystem_manager();
                 let all_files = database.get_all_media_files().await?;
                 let files_in_old_path: Vec<_> = all_files
                     .iter()
-                    .filter(|file| file.path.starts_with(&from))
+                    .filter(|file| filesystem.path_starts_with(&file.path, &from))
                     .collect();
                 
                 if !files_in_old_path.is_empty() {
                     info!("Updating {} media files for renamed directory", files_in_old_path.len());
                     
-                    // Remove old files from database and cache
-                    let mut files = media_files.write().await;
+                    // Move records to the new location so their ids (and media URLs) stay stable
+                    let mut changes: Vec<_> = files_in_old_path.iter().filter_map(|file| file.id).map(ContentChange::modified).collect();
                     for old_file in &files_in_old_path {
-                        database.remove_media_file(&old_file.path).await?;
-                        files.retain(|f| f.path != old_file.path);
+                        if let Some(relative) = filesystem.strip_path_prefix(&old_file.path, &from) {
+                            database.rename_media_file(&old_file.path, &to.join(relative)).await?;
+                        }
+                    }
+                    if use_cache {
+                        let mut files = media_files.write().await;
+                        for cached_file in files.iter_mut() {
+                            if let Some(relative) = filesystem.strip_path_prefix(&cached_file.path, &from) {
+                                cached_file.path = to.join(relative);
+                            }
+                        }
                     }
-                    drop(files); // Release the lock before scanning
                     
-                    // Scan the new directory location
-                    let scanner = media::MediaScanner::with_database(database.clone());
+                    // Scan the new directory location for anything not yet indexed
+                    let scanner = media::MediaScanner::with_database(database.clone())
+                        .with_hidden_files(config.media.include_hidden)
+                        .with_follow_symlinks(config.media.follow_symlinks)
+                        .with_min_file_size(config.media.min_file_size_bytes)
+                        .with_scan_concurrency(config.media.scan_concurrency)
+                        .with_media_types(media_types.clone())
+                        .with_metadata_extraction(config.media.extract_metadata)
+                        .with_album_art(app_state.album_art.clone());
                     match scanner.scan_directory_recursive(&to).await {
                         Ok(scan_result) => {
                             info!("Rescanned renamed directory {}: {}", to.display(), scan_result.summary());
                             
                             // Update in-memory cache with newly found files
-                            if !scan_result.new_files.is_empty() {
+                            if use_cache && !scan_result.new_files.is_empty() {
                                 let mut files = media_files.write().await;
                                 for new_file in &scan_result.new_files {
                                     files.push(new_file.clone());
@@ -1169,10 +1604,12 @@ async fn handle_file_system_event(
                             }
                             
                             // Increment update ID to notify DLNA clients
-                            increment_content_update_id(app_state);
+                            changes.extend(scan_changes(&scan_result));
+                            increment_content_update_id(app_state, changes);
                         }
                         Err(e) => {
                             error!("Failed to rescan renamed directory {}: {}", to.display(), e);
+                            increment_content_update_id(app_state, changes);
                         }
                     }
                 }
@@ -1180,44 +1617,112 @@ async fn handle_file_system_event(
                 // Handle individual file rename
                 info!("File renamed: {} -> {}", from.display(), to.display());
                 
-                // Check if it's a media file
-                let is_media_file = if let Some(extension) = to.extension() {
-                    if let Some(ext_str) = extension.to_str() {
-                        crate::platform::filesystem::is_supported_media_extension(ext_str)
-                    } else {
-                        false
-                    }
-                } else {
-                    false
-                };
-                
-                if !is_media_file {
+                // Check if it's a media file, or one identified by its content
+                if !media_types.is_media_file(&to) && !media_types.sniffs(&to) {
                     debug!("Renamed file is not a media file, ignoring: {}", to.display());
                     return Ok(());
                 }
                 
-                // Remove old file from database and cache
-                database.remove_media_file(&from).await?;
-                let mut files = media_files.write().await;
-                files.retain(|f| f.path != from);
-                
-                // Create MediaFile record for new location
                 let metadata = tokio::fs::metadata(&to).await?;
-                let mime_type = media::get_mime_type(&to);
-                let mut media_file = database::MediaFile::new(to.clone(), metadata.len(), mime_type);
-                media_file.modified = metadata.modified().unwrap_or(std::time::SystemTime::now());
+                let modified = metadata.modified().unwrap_or(std::time::SystemTime::now());
+
+                // Move the existing record so its id (and media URL) stays stable
+                let renamed = database.rename_media_file(&from, &to).await?;
+                let media_file = if renamed {
+                    let mut media_file = database.get_file_by_path(&to).await?
+                        .ok_or_else(|| anyhow::anyhow!("Renamed file missing from database: {}", to.display()))?;
+                    media_file.size = metadata.len();
+                    media_file.modified = modified;
+                    database.update_media_file(&media_file).await?;
+                    media_file
+                } else if metadata.len() < min_file_size {
+                    debug!("Ignoring renamed file {} below {} bytes", to.display(), min_file_size);
+                    return Ok(());
+                } else {
+                    // Not indexed before the rename; treat as a new file
+                    let Some(mime_type) = media::detect_mime_type(&to, &media_types).await else {
+                        debug!("Renamed file is not a media file, ignoring: {}", to.display());
+                        return Ok(());
+                    };
+                    let mut media_file = database::MediaFile::new(to.clone(), metadata.len(), mime_type);
+                    media_file.modified = modified;
+                    if config.media.extract_metadata {
+                        let cover_art = metadata::probe_media_file(&mut media_file).await;
+                        app_state.album_art.cache_extracted(&media_file, cover_art).await;
+                    }
+                    media_file.id = Some(database.store_media_file(&media_file).await?);
+                    media_file
+                };
                 
-                // Store in database
-                let file_id = database.store_media_file(&media_file).await?;
-                media_file.id = Some(file_id);
+                let change = media_file
+                    .id
+                    .map(if renamed { ContentChange::modified } else { ContentChange::added });
                 
-                // Add to in-memory cache
-                files.push(media_file);
+                // Update in-memory cache
+                if use_cache {
+                    let mut files = media_files.write().await;
+                    files.retain(|f| f.path != from && f.path != to);
+                    files.push(media_file);
+                }
                 
                 info!("Renamed media file: {} -> {}", from.display(), to.display());
                 
                 // Increment update ID to notify DLNA clients
-                increment_content_update_id(app_state);
+                increment_content_update_id(app_state, change.into_iter().collect());
+            }
+        }
+
+        FileSystemEvent::ResyncNeeded(directories) => {
+            info!("Resyncing {} director(ies) after dropped file system events", directories.len());
+            let _scan_guard = app_state.scan_lock.lock().await;
+
+            let scanner = media::MediaScanner::with_database(database.clone())
+                .with_hidden_files(config.media.include_hidden)
+                .with_follow_symlinks(config.media.follow_symlinks)
+                .with_min_file_size(config.media.min_file_size_bytes)
+                .with_scan_concurrency(config.media.scan_concurrency)
+                .with_media_types(media_types.clone())
+                .with_metadata_extraction(config.media.extract_metadata)
+                .with_album_art(app_state.album_art.clone());
+            let monitored = config.media.effective_directories();
+            let mut scan_result = media::ScanResult::new();
+            for dir in directories.iter().filter(|dir| dir.is_dir()) {
+                // Subfolders are only indexed below directories scanned recursively
+                let recursive = monitored.iter().any(|root| root.recursive && dir.starts_with(&root.path));
+                let scan = if recursive {
+                    scanner.scan_directory_recursive(dir).await
+                } else {
+                    scanner.scan_directory(dir).await
+                };
+                match scan {
+                    Ok(result) => scan_result.merge(result),
+                    Err(e) => warn!("Failed to resync directory {}: {}", dir.display(), e),
+                }
+            }
+
+            // Files under directories removed during the burst are not visited by the scan
+            let mut stale_removed = 0;
+            let mut changes = scan_changes(&scan_result);
+            for dir in &directories {
+                for file in database.get_files_in_directory(dir).await? {
+                    if !tokio::fs::try_exists(&file.path).await.unwrap_or(false)
+                        && database.remove_media_file(&file.path).await?
+                    {
+                        stale_removed += 1;
+                        changes.extend(file.id.map(ContentChange::removed));
+                    }
+                }
+            }
+
+            // The scan recorded the playlists of the directories again
+            let playlists_changed = app_state.refresh_playlists().await?;
+
+            info!("Resync complete: {}, {} stale file(s) removed", scan_result.summary(), stale_removed);
+            if scan_result.has_changes() || stale_removed > 0 || playlists_changed {
+                if use_cache {
+                    *media_files.write().await = database.get_all_media_files().await?;
+                }
+                increment_content_update_id(app_state, changes);
             }
         }
     }
@@ -1226,26 +1731,18 @@ async fn handle_file_system_event(
 }
 
 /// Start SSDP service with platform abstraction
-async fn start_ssdp_service(app_state: AppState) -> anyhow::Result<()> {
+async fn start_ssdp_service(app_state: AppState) -> anyhow::Result<SsdpController> {
     info!("Starting SSDP discovery service...");
     
-    // Start SSDP service using existing implementation
-    ssdp::run_ssdp_service(app_state)
+    let ssdp = SsdpController::start(app_state, Arc::new(PlatformNetworkManager::new()))
         .context("Failed to start SSDP service")?;
     
     info!("SSDP discovery service started successfully");
-    Ok(())
+    Ok(ssdp)
 }
 
-/// Start HTTP server with proper error handling
-async fn start_http_server(app_state: AppState) -> anyhow::Result<()> {
-    info!("Starting HTTP server...");
-    
-    let config = app_state.config.clone();
-    
-    // Create the Axum web server
-    let app = web::create_router(app_state);
-    
+/// Bind the HTTP listener on the configured interface and port
+async fn bind_http_listener(config: &AppConfig) -> anyhow::Result<tokio::net::TcpListener> {
     // Parse server interface address
     let interface_addr = if config.server.interface == "0.0.0.0" || config.server.interface.is_empty() {
         "0.0.0.0".parse().unwrap()
@@ -1256,25 +1753,38 @@ async fn start_http_server(app_state: AppState) -> anyhow::Result<()> {
     
     let addr = SocketAddr::new(interface_addr, config.server.port);
     
-    info!("Server UUID: {}", config.server.uuid);
-    info!("Server name: {}", config.server.name);
-    info!("Listening on http://{}", addr);
-    
     // Attempt to bind to the address
     let listener = tokio::net::TcpListener::bind(addr).await
         .with_context(|| format!("Failed to bind to address: {}", addr))?;
     
+    info!("Listening on http://{}", addr);
+    Ok(listener)
+}
+
+/// Run the HTTP server until `shutdown` is triggered and open connections finish
+async fn start_http_server(app_state: AppState, listener: tokio::net::TcpListener, shutdown: Shutdown) -> anyhow::Result<()> {
+    info!("Starting HTTP server...");
+    
+    let config = app_state.config.clone();
+    
+    // Create the Axum web server
+    let app = web::create_router(app_state);
+    
+    info!("Server UUID: {}", config.server.uuid);
+    info!("Server name: {}", config.server.name);
+    
     info!("HTTP server started successfully");
     
     // Start the server
-    axum::serve(listener, app.into_make_service())
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(async move { shutdown.wait().await })
         .await
         .context("HTTP server failed")?;
     
+    info!("HTTP server stopped");
     Ok(())
 }
 
-///
 // Perform platform-specific security checks and permission requests
 async fn perform_security_checks(platform_info: &PlatformInfo) -> anyhow::Result<()> {
     info!("Performing platform-specific security checks...");
@@ -1645,7 +2155,7 @@ async fn check_linux_root() -> anyhow::Result<bool> {
         use std::process::Command;
         
         let output = Command::new("id")
-            .args(&["-u"])
+            .args(["-u"])
             .output()
             .context("Failed to check Linux user ID")?;
         
@@ -1670,7 +2180,7 @@ async fn check_linux_capabilities() -> anyhow::Result<bool> {
         use std::process::Command;
         
         let output = Command::new("capsh")
-            .args(&["--print"])
+            .args(["--print"])
             .output()
             .context("Failed to check Linux capabilities")?;
         
@@ -1740,7 +2250,7 @@ async fn check_linux_firewall() -> anyhow::Result<String> {
         use std::process::Command;
         
         // Check for ufw
-        if let Ok(output) = Command::new("ufw").args(&["status"]).output() {
+        if let Ok(output) = Command::new("ufw").args(["status"]).output() {
             if output.status.success() {
                 let output_str = String::from_utf8_lossy(&output.stdout);
                 if output_str.contains("Status: active") {
@@ -1750,7 +2260,7 @@ async fn check_linux_firewall() -> anyhow::Result<String> {
         }
         
         // Check for firewalld
-        if let Ok(output) = Command::new("firewall-cmd").args(&["--state"]).output() {
+        if let Ok(output) = Command::new("firewall-cmd").args(["--state"]).output() {
             if output.status.success() {
                 let output_str = String::from_utf8_lossy(&output.stdout);
                 if output_str.trim() == "running" {
@@ -1760,7 +2270,7 @@ async fn check_linux_firewall() -> anyhow::Result<String> {
         }
         
         // Check for iptables
-        if let Ok(output) = Command::new("iptables").args(&["-L", "-n"]).output() {
+        if let Ok(output) = Command::new("iptables").args(["-L", "-n"]).output() {
             if output.status.success() {
                 let output_str = String::from_utf8_lossy(&output.stdout);
                 if !output_str.is_empty() && !output_str.contains("Chain INPUT (policy ACCEPT)") {
@@ -1780,12 +2290,20 @@ async fn check_linux_firewall() -> anyhow::Result<String> {
 
 /// Perform graceful shutdown with proper cleanup of all resources
 async fn perform_graceful_shutdown(
+    ssdp: Arc<tokio::sync::Mutex<SsdpController>>,
     database: Arc<dyn DatabaseManager>,
     file_watcher: Arc<CrossPlatformWatcher>,
+    config: &AppConfig,
 ) -> anyhow::Result<()> {
     info!("Starting graceful shutdown sequence...");
     
-    // Step 1: Stop file system monitoring
+    // Step 1: Tell control points the server is going away
+    info!("Sending SSDP byebye notifications...");
+    if let Err(e) = ssdp.lock().await.shutdown().await {
+        warn!("Failed to send SSDP byebye notifications: {}", e);
+    }
+    
+    // Step 2: Stop file system monitoring
     info!("Stopping file system monitoring...");
     if let Err(e) = file_watcher.stop_watching().await {
         warn!("Failed to stop file system watcher cleanly: {}", e);
@@ -1793,7 +2311,7 @@ async fn perform_graceful_shutdown(
         info!("File system monitoring stopped");
     }
     
-    // Step 2: Flush any pending database operations
+    // Step 3: Flush any pending database operations
     info!("Flushing database operations...");
     if let Err(e) = flush_database_operations(&database).await {
         warn!("Failed to flush database operations: {}", e);
@@ -1801,15 +2319,15 @@ async fn perform_graceful_shutdown(
         info!("Database operations flushed");
     }
     
-    // Step 3: Create final database backup if enabled
+    // Step 4: Create final database backup if enabled
     info!("Creating shutdown backup...");
-    if let Err(e) = create_shutdown_backup(&database).await {
+    if let Err(e) = create_shutdown_backup(&database, config).await {
         warn!("Failed to create shutdown backup: {}", e);
     } else {
         info!("Shutdown backup created");
     }
     
-    // Step 4: Vacuum database for optimization
+    // Step 5: Vacuum database for optimization
     info!("Optimizing database...");
     if let Err(e) = database.vacuum().await {
         warn!("Failed to vacuum database: {}", e);
@@ -1817,7 +2335,7 @@ async fn perform_graceful_shutdown(
         info!("Database optimized");
     }
     
-    // Step 5: Log final statistics
+    // Step 6: Log final statistics
     if let Ok(stats) = database.get_stats().await {
         info!("Final database statistics:");
         info!("  - Total media files: {}", stats.total_files);
@@ -1850,63 +2368,17 @@ async fn flush_database_operations(database: &Arc<dyn DatabaseManager>) -> anyho
     Ok(())
 }
 
-/// Create a backup during shutdown if backup is enabled
-async fn create_shutdown_backup(database: &Arc<dyn DatabaseManager>) -> anyhow::Result<()> {
-    // Create backup with timestamp
-    let timestamp = chrono::Utc::now().format("%Y%m%d_%H%M%S");
-    let backup_name = format!("vuio_shutdown_backup_{}.db", timestamp);
-    
-    // Use platform-appropriate backup directory
-    let platform_config = crate::platform::config::PlatformConfig::for_current_platform();
-    let backup_dir = platform_config.database_dir.join("backups");
-    
-    // Ensure backup directory exists
-    tokio::fs::create_dir_all(&backup_dir).await
-        .context("Failed to create backup directory")?;
-    
-    let backup_path = backup_dir.join(backup_name);
-    
-    database.create_backup(&backup_path).await
-        .context("Failed to create shutdown backup")?;
-    
-    info!("Shutdown backup created at: {}", backup_path.display());
-    
-    // Clean up old backups (keep only last 5)
-    if let Err(e) = cleanup_old_backups(&backup_dir).await {
-        warn!("Failed to clean up old backups: {}", e);
-    }
-    
-    Ok(())
-}
-
-/// Clean up old backup files, keeping only the most recent ones
-async fn cleanup_old_backups(backup_dir: &std::path::Path) -> anyhow::Result<()> {
-    let mut entries = tokio::fs::read_dir(backup_dir).await?;
-    let mut backup_files = Vec::new();
-    
-    while let Some(entry) = entries.next_entry().await? {
-        let path = entry.path();
-        if path.is_file() && path.extension().map_or(false, |ext| ext == "db") {
-            if let Ok(metadata) = entry.metadata().await {
-                backup_files.push((path, metadata.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH)));
-            }
-        }
+/// Create a backup during shutdown if backups are enabled
+async fn create_shutdown_backup(database: &Arc<dyn DatabaseManager>, config: &AppConfig) -> anyhow::Result<()> {
+    let backup = &config.database.backup;
+    if !backup.enabled {
+        info!("Database backups disabled; skipping shutdown backup");
+        return Ok(());
     }
-    
-    // Sort by modification time, newest first
-    backup_files.sort_by(|a, b| b.1.cmp(&a.1));
-    
-    // Keep only the 5 most recent backups
-    const MAX_BACKUPS: usize = 5;
-    if backup_files.len() > MAX_BACKUPS {
-        for (old_backup, _) in backup_files.iter().skip(MAX_BACKUPS) {
-            if let Err(e) = tokio::fs::remove_file(old_backup).await {
-                warn!("Failed to remove old backup {}: {}", old_backup.display(), e);
-            } else {
-                info!("Removed old backup: {}", old_backup.display());
-            }
-        }
+    if config.database.backend != DatabaseBackend::Sqlite {
+        info!("Postgres database is backed up on the server; skipping shutdown backup");
+        return Ok(());
     }
-    
+    database::backup::create_backup(database.as_ref(), &config.get_backup_directory(), "shutdown", backup.max_backups).await?;
     Ok(())
-}
\ No newline at end of file
+}
@@ -1,19 +1,49 @@
+pub mod album_art;
+pub mod dlna_profile;
+pub mod mime;
+pub mod playlist;
+pub mod subtitles;
+pub mod thumbnail;
+pub mod transcode;
+
 use anyhow::Result;
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::SystemTime;
-use tracing::warn;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tracing::{debug, warn};
 
-use crate::database::{DatabaseManager, MediaFile};
-use crate::platform::filesystem::{create_platform_filesystem_manager, FileSystemManager};
+use crate::config::{default_scan_concurrency, MonitoredDirectoryConfig};
+use crate::database::{DatabaseManager, MediaFile, Subtitle};
+use crate::media::album_art::AlbumArtCache;
+use crate::platform::filesystem::{
+    create_platform_filesystem_manager, is_hidden_path, ErrorSeverity, FileSystemError, FileSystemManager, MediaTypes,
+};
 
 /// Media scanner that uses the file system manager and database for efficient scanning
 pub struct MediaScanner {
     filesystem_manager: Box<dyn FileSystemManager>,
     database_manager: Arc<dyn DatabaseManager>,
+    include_hidden: bool,
+    extract_metadata: bool,
+    /// Where embedded cover art found during metadata extraction is cached
+    album_art: Option<AlbumArtCache>,
+    incremental: bool,
+    follow_symlinks: bool,
+    /// Files smaller than this are left out of the library
+    min_file_size: u64,
+    /// Configured extension to MIME type mappings recognized besides the built-in ones
+    media_types: MediaTypes,
+    /// Bounds how many files are probed and stored at once, across every
+    /// directory this scanner walks
+    scan_permits: Arc<Semaphore>,
 }
 
+/// A processed file and whether it is new, or why it was left out
+type FileOutcome = std::result::Result<(MediaFile, bool), ScanError>;
+
 impl MediaScanner {
     /// Create a new media scanner with platform-specific file system manager
     pub async fn new() -> anyhow::Result<Self> {
@@ -24,6 +54,14 @@ impl MediaScanner {
         Ok(Self {
             filesystem_manager: create_platform_filesystem_manager(),
             database_manager,
+            include_hidden: false,
+            extract_metadata: false,
+            album_art: None,
+            incremental: true,
+            follow_symlinks: false,
+            min_file_size: 0,
+            media_types: MediaTypes::default(),
+            scan_permits: Arc::new(Semaphore::new(default_scan_concurrency())),
         })
     }
     
@@ -32,8 +70,122 @@ impl MediaScanner {
         Self {
             filesystem_manager: create_platform_filesystem_manager(),
             database_manager,
+            include_hidden: false,
+            extract_metadata: false,
+            album_art: None,
+            incremental: true,
+            follow_symlinks: false,
+            min_file_size: 0,
+            media_types: MediaTypes::default(),
+            scan_permits: Arc::new(Semaphore::new(default_scan_concurrency())),
         }
     }
+
+    /// Index hidden files and directories instead of skipping them
+    pub fn with_hidden_files(mut self, include_hidden: bool) -> Self {
+        self.include_hidden = include_hidden;
+        self
+    }
+
+    /// Probe new and changed files for duration and tags before storing them
+    pub fn with_metadata_extraction(mut self, extract_metadata: bool) -> Self {
+        self.extract_metadata = extract_metadata;
+        self
+    }
+
+    /// Cache cover art embedded in audio files while extracting metadata
+    pub fn with_album_art(mut self, album_art: AlbumArtCache) -> Self {
+        self.album_art = Some(album_art);
+        self
+    }
+
+    /// Skip files whose size and modification time match the database (the
+    /// default). Disabling this reprocesses every file, re-extracting metadata.
+    pub fn with_incremental_scan(mut self, incremental: bool) -> Self {
+        self.incremental = incremental;
+        self
+    }
+
+    /// Descend into symlinked directories during recursive scans instead of skipping them
+    pub fn with_follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Leave files smaller than `bytes` out of the library, e.g. empty placeholders
+    /// of downloads in progress
+    pub fn with_min_file_size(mut self, bytes: u64) -> Self {
+        self.min_file_size = bytes;
+        self
+    }
+
+    /// Recognize the configured `custom_mime_types` as media besides the built-in extensions
+    pub fn with_media_types(mut self, media_types: MediaTypes) -> Self {
+        self.media_types = media_types;
+        self
+    }
+
+    /// Probe and store at most `limit` new or changed files at once (at least one)
+    pub fn with_scan_concurrency(mut self, limit: usize) -> Self {
+        self.scan_permits = Arc::new(Semaphore::new(limit.max(1)));
+        self
+    }
+
+    /// Subdirectories of `directory` to descend into. Symlinked directories are
+    /// followed only with `follow_symlinks`; `visited` holds the canonical paths
+    /// already scanned, so a symlink cycle ends instead of recursing forever.
+    async fn subdirectories_to_scan(&self, directory: &Path, visited: &mut HashSet<PathBuf>) -> Vec<PathBuf> {
+        let mut subdirectories = Vec::new();
+        let Ok(mut entries) = tokio::fs::read_dir(directory).await else {
+            return subdirectories;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            let Ok(file_type) = entry.file_type().await else {
+                continue;
+            };
+            let is_directory = if file_type.is_symlink() {
+                self.follow_symlinks && tokio::fs::metadata(&path).await.is_ok_and(|metadata| metadata.is_dir())
+            } else {
+                file_type.is_dir()
+            };
+            if !is_directory || self.skips_hidden(&path) {
+                continue;
+            }
+
+            let canonical = self
+                .filesystem_manager
+                .canonicalize_path(&path)
+                .await
+                .unwrap_or_else(|_| path.clone());
+            if visited.insert(canonical) {
+                subdirectories.push(path);
+            } else {
+                debug!("Skipping {}: directory already scanned through another path", path.display());
+            }
+        }
+        subdirectories
+    }
+
+    /// Canonical form of a scan root, the first entry of the visited set
+    async fn visited_from(&self, root: &Path) -> HashSet<PathBuf> {
+        let canonical = self
+            .filesystem_manager
+            .canonicalize_path(root)
+            .await
+            .unwrap_or_else(|_| root.to_path_buf());
+        HashSet::from([canonical])
+    }
+
+    /// Whether a path should be skipped because it is hidden
+    fn skips_hidden(&self, path: &Path) -> bool {
+        !self.include_hidden && is_hidden_path(path)
+    }
+
+    /// Whether a scanned file is left out of the library: hidden, or below the minimum size
+    fn skips_file(&self, file: &MediaFile) -> bool {
+        self.skips_hidden(&file.path) || file.size < self.min_file_size
+    }
     
     /// Simple directory scan that returns files without database operations
     pub async fn scan_directory_simple(&self, directory: &Path) -> Result<Vec<MediaFile>> {
@@ -50,10 +202,11 @@ impl MediaScanner {
         }
         
         // Scan the file system for current files
-        let fs_files = self.filesystem_manager
-            .scan_media_directory(&normalized_dir)
+        let mut fs_files = self.filesystem_manager
+            .scan_media_directory_with_types(&normalized_dir, &self.media_types)
             .await
             .map_err(|e| anyhow::anyhow!("File system scan failed: {}", e))?;
+        fs_files.retain(|file| !self.skips_file(file));
         
         Ok(fs_files)
     }
@@ -62,25 +215,19 @@ impl MediaScanner {
     pub async fn scan_directory_recursively_simple(&self, directory: &Path) -> Result<Vec<MediaFile>> {
         let mut all_files = Vec::new();
         let mut dirs_to_scan = vec![directory.to_path_buf()];
+        let mut visited = self.visited_from(directory).await;
 
         while let Some(current_dir) = dirs_to_scan.pop() {
             // Scan current directory for files
-            match self.filesystem_manager.scan_media_directory(&current_dir).await {
+            match self.filesystem_manager.scan_media_directory_with_types(&current_dir, &self.media_types).await {
                 Ok(fs_files) => {
-                    all_files.extend(fs_files);
+                    all_files.extend(fs_files.into_iter().filter(|file| !self.skips_file(file)));
                 }
                 Err(e) => warn!("Failed to scan directory {}: {}", current_dir.display(), e),
             }
 
             // Find subdirectories and add to the queue
-            if let Ok(mut entries) = tokio::fs::read_dir(&current_dir).await {
-                while let Ok(Some(entry)) = entries.next_entry().await {
-                    let path = entry.path();
-                    if path.is_dir() {
-                        dirs_to_scan.push(path);
-                    }
-                }
-            }
+            dirs_to_scan.extend(self.subdirectories_to_scan(&current_dir, &mut visited).await);
         }
         Ok(all_files)
     }
@@ -93,6 +240,14 @@ impl MediaScanner {
         Self {
             filesystem_manager,
             database_manager,
+            include_hidden: false,
+            extract_metadata: false,
+            album_art: None,
+            incremental: true,
+            follow_symlinks: false,
+            min_file_size: 0,
+            media_types: MediaTypes::default(),
+            scan_permits: Arc::new(Semaphore::new(default_scan_concurrency())),
         }
     }
     
@@ -132,11 +287,15 @@ impl MediaScanner {
                 .await?
         };
         
-        // Scan the file system for current files
-        let current_files = self.filesystem_manager
-            .scan_media_directory(&normalized_dir)
+        // Scan the file system for current files, keeping the cause for structured scan errors
+        let mut current_files = self.filesystem_manager
+            .scan_media_directory_with_types(&normalized_dir, &self.media_types)
             .await
-            .map_err(|e| anyhow::anyhow!("File system scan failed: {}", e))?;
+            .map_err(|e| {
+                let message = format!("File system scan failed: {}", e);
+                anyhow::Error::new(e).context(message)
+            })?;
+        current_files.retain(|file| !self.skips_file(file));
         
         // Perform incremental update
         self.perform_incremental_update(&normalized_dir, existing_files, current_files).await
@@ -145,7 +304,7 @@ impl MediaScanner {
     /// Perform an incremental update by comparing database state with file system state
     async fn perform_incremental_update(
         &self,
-        _directory: &Path,
+        directory: &Path,
         existing_files: Vec<MediaFile>,
         current_files: Vec<MediaFile>,
     ) -> Result<ScanResult> {
@@ -181,7 +340,10 @@ impl MediaScanner {
         
 
         
-        // Process current files - add new ones or update changed ones
+        // Process current files - add new ones or update changed ones. Probing
+        // and storing run as tasks, each spawned once it holds a scan permit;
+        // results are collected here so the scan result needs no locking.
+        let mut pending = JoinSet::new();
         for (normalized_current_path, current_file) in &current_normalized {
             // Try to find existing file by normalized path first, then by original path
             let existing_file = existing_by_normalized.get(normalized_current_path)
@@ -200,9 +362,7 @@ impl MediaScanner {
                         updated_file.id = existing_file.id; // Preserve database ID
                         updated_file.created_at = existing_file.created_at; // Preserve creation time
                         updated_file.updated_at = SystemTime::now();
-                        
-                        self.database_manager.update_media_file(&updated_file).await?;
-                        result.updated_files.push(updated_file);
+                        self.spawn_file_task(&mut pending, updated_file, false).await?;
                     } else {
                         // Check if the existing file path needs normalization
                         let existing_normalized = self.filesystem_manager.normalize_path(&existing_file.path);
@@ -224,13 +384,33 @@ impl MediaScanner {
                     // New file, add to database with normalized path
                     let mut normalized_file = current_file.clone();
                     normalized_file.path = normalized_current_path.clone();
-                    let id = self.database_manager.store_media_file(&normalized_file).await?;
-                    normalized_file.id = Some(id);
-                    result.new_files.push(normalized_file);
+                    self.spawn_file_task(&mut pending, normalized_file, true).await?;
                 }
             }
         }
         
+        while let Some(joined) = pending.join_next().await {
+            match joined.map_err(|e| anyhow::anyhow!("Scan task failed: {}", e))?? {
+                Ok((file, true)) => result.new_files.push(file),
+                Ok((file, false)) => result.updated_files.push(file),
+                Err(scan_error) => {
+                    warn!("Skipping {}: {}", scan_error.path.display(), scan_error.error);
+                    result.errors.push(scan_error);
+                }
+            }
+        }
+
+        // New files are committed together; one transaction per file is dominated by fsyncs
+        if !result.new_files.is_empty() {
+            let ids = self.database_manager.batch_store_media_files(&result.new_files).await?;
+            for (file, id) in result.new_files.iter_mut().zip(ids) {
+                file.id = Some(id);
+            }
+        }
+        
+        self.sync_subtitles(directory, &mut result).await?;
+        self.sync_playlists(directory).await?;
+        
         // Find files that were removed from the file system
         // Check both normalized and original paths to handle legacy entries
         for (normalized_existing_path, existing_file) in existing_by_normalized {
@@ -247,8 +427,113 @@ impl MediaScanner {
         Ok(result)
     }
     
+    /// Wait for a free scan permit, then probe `file` (when metadata extraction
+    /// is on) in a task holding it, so at most the configured number of files
+    /// are in flight. Changed files are written over their existing row; new
+    /// files are left for the caller to store in one batch. Files that cannot
+    /// be opened are reported as scan errors instead, since clients could not
+    /// play them either.
+    async fn spawn_file_task(&self, pending: &mut JoinSet<Result<FileOutcome>>, mut file: MediaFile, is_new: bool) -> Result<()> {
+        let permit = self.scan_permits.clone().acquire_owned().await?;
+        let database = self.database_manager.clone();
+        let extract_metadata = self.extract_metadata;
+        let album_art = self.album_art.clone();
+        pending.spawn(async move {
+            let _permit = permit;
+            if let Err(e) = tokio::fs::File::open(&file.path).await {
+                let error = FileSystemError::from_io_error(&file.path, &e);
+                return Ok(Err(ScanError::from_filesystem_error(file.path, &error)));
+            }
+            if extract_metadata {
+                let cover_art = crate::metadata::probe_media_file(&mut file).await;
+                if let Some(cache) = album_art {
+                    if !is_new {
+                        cache.invalidate(&file).await;
+                    }
+                    cache.cache_extracted(&file, cover_art).await;
+                }
+            }
+            if !is_new {
+                database.update_media_file(&file).await?;
+            }
+            Ok(Ok((file, is_new)))
+        });
+        Ok(())
+    }
+    
+    /// Record the subtitle sidecars of every video in `result`. Videos whose
+    /// sidecars changed are reported as updated so clients pick up the change.
+    async fn sync_subtitles(&self, directory: &Path, result: &mut ScanResult) -> Result<()> {
+        let videos: Vec<(i64, PathBuf)> = result
+            .new_files
+            .iter()
+            .chain(&result.updated_files)
+            .chain(&result.unchanged_files)
+            .filter(|file| file.mime_type.starts_with("video/"))
+            .filter_map(|file| Some((file.id?, file.path.clone())))
+            .collect();
+        if videos.is_empty() {
+            return Ok(());
+        }
+
+        let candidates = subtitles::list_subtitle_files(directory).await;
+        let key = |s: &Subtitle| (s.path.clone(), s.language.clone(), s.mime_type.clone());
+        let mut changed = HashSet::new();
+        for (id, path) in videos {
+            let sidecars = subtitles::find_subtitle_sidecars(&path, id, &candidates);
+            let mut recorded: Vec<_> = self.database_manager.get_subtitles(id).await?.iter().map(key).collect();
+            recorded.sort();
+            if sidecars.iter().map(key).collect::<Vec<_>>() != recorded {
+                self.database_manager.replace_subtitles(id, &sidecars).await?;
+                changed.insert(id);
+            }
+        }
+
+        let (moved, unchanged): (Vec<_>, Vec<_>) = std::mem::take(&mut result.unchanged_files)
+            .into_iter()
+            .partition(|file| file.id.is_some_and(|id| changed.contains(&id)));
+        result.unchanged_files = unchanged;
+        result.updated_files.extend(moved);
+        Ok(())
+    }
+    
+    /// Record the playlists in `directory` and forget those that were removed.
+    /// Entries are kept as paths; they are matched to media files when served.
+    async fn sync_playlists(&self, directory: &Path) -> Result<()> {
+        let mut found = HashSet::new();
+        for path in playlist::list_playlist_files(directory).await {
+            if self.skips_hidden(&path) {
+                continue;
+            }
+            let parsed = match playlist::read_playlist(&path, self.filesystem_manager.as_ref()).await {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    warn!("Failed to read playlist {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            self.database_manager.store_playlist(&parsed).await?;
+            found.insert(parsed.path);
+        }
+
+        for recorded in self.database_manager.get_playlists().await? {
+            let in_directory = recorded
+                .path
+                .parent()
+                .is_some_and(|parent| self.filesystem_manager.normalize_path(parent) == directory);
+            if in_directory && !found.contains(&recorded.path) {
+                self.database_manager.remove_playlist(&recorded.path).await?;
+            }
+        }
+        Ok(())
+    }
+
     /// Check if a file needs to be updated in the database
     fn file_needs_update(&self, existing: &MediaFile, current: &MediaFile) -> bool {
+        if !self.incremental {
+            return true;
+        }
+
         // Compare file sizes first (most reliable)
         if existing.size != current.size {
             return true;
@@ -259,19 +544,7 @@ impl MediaScanner {
             return true;
         }
         
-        // Compare modification times with tolerance for Windows timestamp precision issues
-        // Windows can have different precision depending on filesystem and access method
-        let time_diff = if existing.modified > current.modified {
-            existing.modified.duration_since(current.modified)
-        } else {
-            current.modified.duration_since(existing.modified)
-        };
-        
-        // Allow up to 10 seconds difference to account for timestamp precision issues
-        match time_diff {
-            Ok(diff) => diff.as_secs() > 10,
-            Err(_) => true, // If we can't calculate the difference, assume it needs updating
-        }
+        modified_seconds(existing.modified) != modified_seconds(current.modified)
     }
     
     /// Scan multiple directories and return combined results
@@ -285,10 +558,7 @@ impl MediaScanner {
                 }
                 Err(e) => {
                     tracing::warn!("Failed to scan directory {}: {}", directory.display(), e);
-                    combined_result.errors.push(ScanError {
-                        path: directory.clone(),
-                        error: e.to_string(),
-                    });
+                    combined_result.errors.push(ScanError::new(directory.clone(), &e));
                 }
             }
         }
@@ -305,6 +575,7 @@ impl MediaScanner {
         
         let mut combined_result = ScanResult::new();
         let mut directories_to_scan = vec![normalized_root.clone()];
+        let mut visited = self.visited_from(&normalized_root).await;
         
         while let Some(current_dir) = directories_to_scan.pop() {
             // Scan current directory with the pre-loaded existing files
@@ -314,29 +585,19 @@ impl MediaScanner {
                 }
                 Err(e) => {
                     tracing::warn!("Failed to scan directory {}: {}", current_dir.display(), e);
-                    combined_result.errors.push(ScanError {
-                        path: current_dir.clone(),
-                        error: e.to_string(),
-                    });
+                    combined_result.errors.push(ScanError::new(current_dir.clone(), &e));
                     continue; // Skip subdirectory scanning if parent failed
                 }
             }
             
-            // Find subdirectories to scan
-            if let Ok(mut entries) = tokio::fs::read_dir(&current_dir).await {
-                while let Ok(Some(entry)) = entries.next_entry().await {
-                    let entry_path = entry.path();
-                    if entry_path.is_dir() {
-                        // Skip hidden directories and common system directories
-                        if let Some(dir_name) = entry_path.file_name().and_then(|n| n.to_str()) {
-                            if !dir_name.starts_with('.') && 
-                               !matches!(dir_name.to_lowercase().as_str(), 
-                                   "system volume information" | "$recycle.bin" | "recycler" | 
-                                   "windows" | "program files" | "program files (x86)"
-                               ) {
-                                directories_to_scan.push(entry_path);
-                            }
-                        }
+            // Find subdirectories to scan, skipping common system directories
+            for entry_path in self.subdirectories_to_scan(&current_dir, &mut visited).await {
+                if let Some(dir_name) = entry_path.file_name().and_then(|n| n.to_str()) {
+                    if !matches!(dir_name.to_lowercase().as_str(),
+                        "system volume information" | "$recycle.bin" | "recycler" |
+                        "windows" | "program files" | "program files (x86)"
+                    ) {
+                        directories_to_scan.push(entry_path);
                     }
                 }
             }
@@ -351,6 +612,70 @@ impl MediaScanner {
     }
 }
 
+impl MediaScanner {
+    /// Scan every configured directory, honoring each directory's `recursive` flag.
+    /// Directories that are missing or fail to scan are reported as scan errors.
+    pub async fn scan_all_directories(&self, directories: &[MonitoredDirectoryConfig]) -> ScanResult {
+        let mut result = ScanResult::new();
+
+        for dir_config in directories {
+            let dir_path = PathBuf::from(&dir_config.path);
+            if !dir_path.exists() {
+                warn!("Media directory does not exist: {}", dir_config.path);
+                let missing = FileSystemError::PathNotFound { path: dir_config.path.clone() };
+                result.errors.push(ScanError::from_filesystem_error(dir_path, &missing));
+                continue;
+            }
+
+            let scan = if dir_config.recursive {
+                self.scan_directory_recursive(&dir_path).await
+            } else {
+                self.scan_directory(&dir_path).await
+            };
+
+            match scan {
+                Ok(scan_result) => result.merge(scan_result),
+                Err(e) => result.errors.push(ScanError::new(dir_path, &e)),
+            }
+        }
+
+        result
+    }
+}
+
+/// Modification time in whole seconds since the epoch. File systems report
+/// timestamps at different precisions and the database keeps whole seconds,
+/// so scans compare files at this granularity.
+fn modified_seconds(modified: SystemTime) -> u64 {
+    modified.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// Parse a `media.scan_schedule` cron expression.
+///
+/// Both the standard five-field form (`min hour day month weekday`) and the
+/// six/seven-field form with seconds (and years) are accepted.
+pub fn parse_scan_schedule(expression: &str) -> Result<cron::Schedule> {
+    use std::str::FromStr;
+
+    let expression = expression.trim();
+    let normalized = if expression.split_whitespace().count() == 5 {
+        format!("0 {}", expression)
+    } else {
+        expression.to_string()
+    };
+
+    cron::Schedule::from_str(&normalized)
+        .map_err(|e| anyhow::anyhow!("Invalid scan schedule '{}': {}", expression, e))
+}
+
+/// Next time a scheduled scan should fire strictly after `after`
+pub fn next_scheduled_scan<Tz: chrono::TimeZone>(
+    schedule: &cron::Schedule,
+    after: &chrono::DateTime<Tz>,
+) -> Option<chrono::DateTime<Tz>> {
+    schedule.after(after).next()
+}
+
 /// Result of a media scanning operation
 #[derive(Debug, Clone)]
 pub struct ScanResult {
@@ -409,7 +734,7 @@ impl ScanResult {
     /// Get a summary string of the scan results
     pub fn summary(&self) -> String {
         format!(
-            "Scanned {} files: {} new, {} updated, {} removed, {} unchanged, {} errors",
+            "Scanned {} files: {} new, {} updated, {} removed, {} skipped as unchanged, {} errors",
             self.total_scanned,
             self.new_files.len(),
             self.updated_files.len(),
@@ -434,13 +759,81 @@ pub struct ScanError {
     
     /// Error description
     pub error: String,
+
+    /// What kind of problem this is, for grouping without parsing `error`
+    pub kind: ScanErrorKind,
+
+    pub severity: ErrorSeverity,
+
+    /// Steps that may fix the problem
+    pub suggestions: Vec<String>,
+}
+
+/// Kind of problem behind a [`ScanError`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanErrorKind {
+    Permission,
+    Encoding,
+    TooLong,
+    InvalidPath,
+    Io,
+}
+
+impl ScanErrorKind {
+    fn of(error: &FileSystemError) -> Self {
+        match error {
+            FileSystemError::AccessDenied { .. } | FileSystemError::Permission { .. } => ScanErrorKind::Permission,
+            FileSystemError::Encoding { .. } => ScanErrorKind::Encoding,
+            FileSystemError::PathTooLong { .. } => ScanErrorKind::TooLong,
+            FileSystemError::InvalidPath { .. }
+            | FileSystemError::InvalidWindowsCharacter { .. }
+            | FileSystemError::InvalidColonUsage { .. }
+            | FileSystemError::ReservedName { .. } => ScanErrorKind::InvalidPath,
+            FileSystemError::PathNotFound { .. } | FileSystemError::Io(_) | FileSystemError::Platform(_) => {
+                ScanErrorKind::Io
+            }
+        }
+    }
+}
+
+impl ScanError {
+    /// Report `error` at `path`, classified by the file system or I/O error
+    /// behind it; errors with neither count as I/O errors
+    pub fn new(path: PathBuf, error: &anyhow::Error) -> Self {
+        let filesystem_error = error.chain().find_map(|cause| cause.downcast_ref::<FileSystemError>());
+        let io_error = error.chain().find_map(|cause| cause.downcast_ref::<std::io::Error>());
+        let cause = match (filesystem_error, io_error) {
+            (Some(FileSystemError::Io(io_error)), _) | (None, Some(io_error)) => {
+                FileSystemError::from_io_error(&path, io_error)
+            }
+            (Some(filesystem_error), _) => return Self::classified(path, error.to_string(), filesystem_error),
+            (None, None) => FileSystemError::Io(std::io::Error::other(error.to_string())),
+        };
+        Self::classified(path, error.to_string(), &cause)
+    }
+
+    /// Report a file system error at `path`
+    pub fn from_filesystem_error(path: PathBuf, error: &FileSystemError) -> Self {
+        Self::classified(path, error.to_string(), error)
+    }
+
+    fn classified(path: PathBuf, error: String, cause: &FileSystemError) -> Self {
+        Self {
+            path,
+            error,
+            kind: ScanErrorKind::of(cause),
+            severity: cause.severity(),
+            suggestions: cause.recovery_suggestions(),
+        }
+    }
 }
 
 /// Legacy function for backward compatibility - performs a simple directory scan
 /// 
 /// This function is deprecated in favor of using MediaScanner directly
 #[deprecated(note = "Use MediaScanner::scan_directory instead")]
-pub async fn scan_media_files(dir: &PathBuf) -> Result<Vec<MediaFile>> {
+pub async fn scan_media_files(dir: &Path) -> Result<Vec<MediaFile>> {
     let filesystem_manager = create_platform_filesystem_manager();
     
     let fs_files = filesystem_manager
@@ -451,48 +844,27 @@ pub async fn scan_media_files(dir: &PathBuf) -> Result<Vec<MediaFile>> {
     Ok(fs_files)
 }
 
-/// Get MIME type for a file based on its extension
-pub fn get_mime_type(path: &std::path::Path) -> String {
-    let extension = path.extension()
-        .and_then(|ext| ext.to_str())
-        .unwrap_or("")
-        .to_lowercase();
-    
-    match extension.as_str() {
-        // Video formats
-        "mp4" => "video/mp4",
-        "mkv" => "video/x-matroska",
-        "avi" => "video/x-msvideo",
-        "mov" => "video/quicktime",
-        "wmv" => "video/x-ms-wmv",
-        "flv" => "video/x-flv",
-        "webm" => "video/webm",
-        "m4v" => "video/x-m4v",
-        "3gp" => "video/3gpp",
-        "mpg" | "mpeg" => "video/mpeg",
-        
-        // Audio formats
-        "mp3" => "audio/mpeg",
-        "flac" => "audio/flac",
-        "wav" => "audio/wav",
-        "aac" => "audio/aac",
-        "ogg" => "audio/ogg",
-        "wma" => "audio/x-ms-wma",
-        "m4a" => "audio/mp4",
-        "opus" => "audio/opus",
-        "aiff" => "audio/aiff",
-        
-        // Image formats
-        "jpg" | "jpeg" => "image/jpeg",
-        "png" => "image/png",
-        "gif" => "image/gif",
-        "bmp" => "image/bmp",
-        "tiff" => "image/tiff",
-        "webp" => "image/webp",
-        "svg" => "image/svg+xml",
-        
-        _ => "application/octet-stream",
-    }.to_string()
+/// MIME type of a media file, by its content when `media.sniff_content`
+/// applies to its extension, else by its extension. `None` when the file is
+/// not media.
+pub async fn detect_mime_type(path: &Path, media_types: &MediaTypes) -> Option<String> {
+    if media_types.sniffs(path) {
+        let path = path.to_path_buf();
+        return tokio::task::spawn_blocking(move || mime::sniff_file_blocking(&path))
+            .await
+            .ok()
+            .flatten()
+            .map(str::to_string);
+    }
+    media_types.is_media_file(path).then(|| media_types.mime_type(path))
+}
+
+/// Get MIME type for a file based on its extension, or on its content when
+/// `media.sniff_content` applies to the extension
+pub async fn get_mime_type(path: &Path, media_types: &MediaTypes) -> String {
+    detect_mime_type(path, media_types)
+        .await
+        .unwrap_or_else(|| "application/octet-stream".to_string())
 }
 
 /// Get MIME type for a file based on its extension (legacy function)
@@ -500,7 +872,7 @@ pub fn get_mime_type(path: &std::path::Path) -> String {
 /// This function is deprecated in favor of using the filesystem module directly
 #[deprecated(note = "Use crate::platform::filesystem::get_mime_type_for_extension instead")]
 pub fn get_mime_type_legacy(path: &std::path::Path) -> String {
-    get_mime_type(path)
+    MediaTypes::default().mime_type(path)
 }
 
 #[cfg(test)]
@@ -545,6 +917,8 @@ mod tests {
             title: None,
             artist: None,
             album: None,
+            width: None,
+            height: None,
             created_at: SystemTime::now(),
             updated_at: SystemTime::now(),
         });
@@ -562,6 +936,8 @@ mod tests {
             title: None,
             artist: None,
             album: None,
+            width: None,
+            height: None,
             created_at: SystemTime::now(),
             updated_at: SystemTime::now(),
         });
@@ -578,4 +954,212 @@ mod tests {
         assert!(summary.contains("1 new"));
         assert!(summary.contains("1 updated"));
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_incremental_scan_skips_unchanged_files() {
+        use std::time::Duration;
+
+        let temp_dir = tempdir().unwrap();
+        let media_dir = temp_dir.path().join("media");
+        std::fs::create_dir(&media_dir).unwrap();
+        let base = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        for name in ["stable.mp4", "touched.mp4"] {
+            let file = std::fs::File::create(media_dir.join(name)).unwrap();
+            file.set_len(1024).unwrap();
+            file.set_modified(base).unwrap();
+        }
+
+        let db = Arc::new(SqliteDatabase::new(temp_dir.path().join("test.db")).await.unwrap());
+        db.initialize().await.unwrap();
+        let scanner = MediaScanner::with_filesystem_manager(Box::new(BaseFileSystemManager::new(true)), db.clone());
+        assert_eq!(scanner.scan_directory(&media_dir).await.unwrap().new_files.len(), 2);
+
+        // Sub-second differences are below the compared granularity; a later mtime is not
+        let options = std::fs::OpenOptions::new().write(true).clone();
+        options.open(media_dir.join("stable.mp4")).unwrap().set_modified(base + Duration::from_millis(400)).unwrap();
+        options.open(media_dir.join("touched.mp4")).unwrap().set_modified(base + Duration::from_secs(2)).unwrap();
+
+        let result = scanner.scan_directory(&media_dir).await.unwrap();
+        let names = |files: &[MediaFile]| files.iter().map(|f| f.filename.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&result.unchanged_files), vec!["stable.mp4"]);
+        assert_eq!(names(&result.updated_files), vec!["touched.mp4"]);
+        assert!(result.summary().contains("1 updated"));
+        assert!(result.summary().contains("1 skipped as unchanged"));
+
+        // A full scan reprocesses everything
+        let full = MediaScanner::with_filesystem_manager(Box::new(BaseFileSystemManager::new(true)), db)
+            .with_incremental_scan(false);
+        let result = full.scan_directory(&media_dir).await.unwrap();
+        assert_eq!(result.updated_files.len(), 2);
+        assert!(result.unchanged_files.is_empty());
+    }
+
+    #[test]
+    fn test_scan_schedule_next_fire_time() {
+        use chrono::{TimeZone, Timelike, Utc};
+
+        let now = Utc.with_ymd_and_hms(2024, 3, 10, 12, 0, 0).unwrap();
+
+        // Five-field expression: every day at 02:30
+        let nightly = parse_scan_schedule("30 2 * * *").unwrap();
+        let next = next_scheduled_scan(&nightly, &now).unwrap();
+        assert_eq!(next, Utc.with_ymd_and_hms(2024, 3, 11, 2, 30, 0).unwrap());
+        let after_next = next_scheduled_scan(&nightly, &next).unwrap();
+        assert_eq!(after_next - next, chrono::Duration::days(1));
+
+        // Six-field expression with seconds: every 15 minutes
+        let quarterly = parse_scan_schedule("0 */15 * * * *").unwrap();
+        let next = next_scheduled_scan(&quarterly, &now).unwrap();
+        assert!(next > now);
+        assert_eq!(next.minute(), 15);
+        assert_eq!(next.second(), 0);
+
+        assert!(parse_scan_schedule("not a schedule").is_err());
+        assert!(parse_scan_schedule("61 * * * *").is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_hidden_files_skipped_unless_included() {
+        let temp_dir = tempdir().unwrap();
+        let media_dir = temp_dir.path().join("media");
+        std::fs::create_dir_all(media_dir.join(".hidden_dir")).unwrap();
+        std::fs::write(media_dir.join("visible.mp4"), b"video").unwrap();
+        std::fs::write(media_dir.join(".secret.mp4"), b"video").unwrap();
+        std::fs::write(media_dir.join(".hidden_dir").join("inner.mp4"), b"video").unwrap();
+
+        let db = Arc::new(SqliteDatabase::new(temp_dir.path().join("hidden.db")).await.unwrap());
+        db.initialize().await.unwrap();
+
+        let scanner = MediaScanner::with_database(db.clone());
+        let result = scanner.scan_directory_recursive(&media_dir).await.unwrap();
+        let names: Vec<_> = result.new_files.iter().map(|f| f.filename.as_str()).collect();
+        assert_eq!(names, vec!["visible.mp4"]);
+
+        let scanner = MediaScanner::with_database(db).with_hidden_files(true);
+        let result = scanner.scan_directory_recursive(&media_dir).await.unwrap();
+        let mut names: Vec<_> = result.new_files.iter().map(|f| f.filename.clone()).collect();
+        names.sort();
+        assert_eq!(names, vec![".secret.mp4".to_string(), "inner.mp4".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_files_below_minimum_size_skipped() {
+        let temp_dir = tempdir().unwrap();
+        let media_dir = temp_dir.path().join("media");
+        std::fs::create_dir_all(&media_dir).unwrap();
+        std::fs::write(media_dir.join("movie.mp4"), b"video").unwrap();
+        std::fs::write(media_dir.join("placeholder.mp4"), b"").unwrap();
+        std::fs::write(media_dir.join("partial.mp4"), b"vi").unwrap();
+
+        let db = Arc::new(SqliteDatabase::new(temp_dir.path().join("min_size.db")).await.unwrap());
+        db.initialize().await.unwrap();
+
+        let scanner = MediaScanner::with_database(db.clone()).with_min_file_size(1);
+        let result = scanner.scan_directory(&media_dir).await.unwrap();
+        let mut names: Vec<_> = result.new_files.iter().map(|f| f.filename.as_str()).collect();
+        names.sort();
+        assert_eq!(names, vec!["movie.mp4", "partial.mp4"]);
+
+        // Raising the threshold drops the small file that was indexed before
+        let scanner = MediaScanner::with_database(db.clone()).with_min_file_size(4);
+        let result = scanner.scan_directory(&media_dir).await.unwrap();
+        assert_eq!(result.removed_files.len(), 1);
+        assert_eq!(result.removed_files[0].filename, "partial.mp4");
+        let indexed: Vec<_> = db.get_all_media_files().await.unwrap().into_iter().map(|f| f.filename).collect();
+        assert_eq!(indexed, vec!["movie.mp4".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_missing_directory_reported_as_scan_error() {
+        let temp_dir = tempdir().unwrap();
+        let media_dir = temp_dir.path().join("media");
+        let missing_dir = temp_dir.path().join("unplugged");
+        std::fs::create_dir_all(&media_dir).unwrap();
+        std::fs::write(media_dir.join("movie.mp4"), b"video").unwrap();
+
+        let db = Arc::new(SqliteDatabase::new(temp_dir.path().join("missing.db")).await.unwrap());
+        db.initialize().await.unwrap();
+        let directory = |path: &Path| MonitoredDirectoryConfig {
+            path: path.to_string_lossy().to_string(),
+            recursive: true,
+            extensions: None,
+            exclude_patterns: None,
+            display_name: None,
+            custom_mime_types: None,
+        };
+
+        let scanner = MediaScanner::with_database(db);
+        let result = scanner.scan_all_directories(&[directory(&media_dir), directory(&missing_dir)]).await;
+        assert_eq!(result.new_files.len(), 1);
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].path, missing_dir);
+        assert_eq!((result.errors[0].kind, result.errors[0].severity), (ScanErrorKind::Io, ErrorSeverity::Warning));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_symlinked_directories_followed_only_when_enabled() {
+        let temp_dir = tempdir().unwrap();
+        let media_dir = temp_dir.path().join("media");
+        let drive = temp_dir.path().join("drive");
+        std::fs::create_dir_all(&media_dir).unwrap();
+        std::fs::create_dir_all(drive.join("nested")).unwrap();
+        std::fs::write(media_dir.join("local.mp4"), b"video").unwrap();
+        std::fs::write(drive.join("linked.mp4"), b"video").unwrap();
+        std::os::unix::fs::symlink(&drive, media_dir.join("drive")).unwrap();
+        // A cycle back to the media directory and one inside the linked tree
+        std::os::unix::fs::symlink(&media_dir, media_dir.join("loop")).unwrap();
+        std::os::unix::fs::symlink(&drive, drive.join("nested").join("back")).unwrap();
+
+        let db = Arc::new(SqliteDatabase::new(temp_dir.path().join("links.db")).await.unwrap());
+        db.initialize().await.unwrap();
+
+        let scanner = MediaScanner::with_database(db.clone());
+        let result = scanner.scan_directory_recursive(&media_dir).await.unwrap();
+        let names: Vec<_> = result.new_files.iter().map(|f| f.filename.as_str()).collect();
+        assert_eq!(names, vec!["local.mp4"]);
+
+        let scanner = MediaScanner::with_database(db).with_follow_symlinks(true);
+        let result = tokio::time::timeout(std::time::Duration::from_secs(10), scanner.scan_directory_recursive(&media_dir))
+            .await
+            .expect("symlink cycle was not broken")
+            .unwrap();
+        let paths: Vec<_> = result.new_files.iter().map(|f| f.path.clone()).collect();
+        assert_eq!(paths, vec![media_dir.join("drive").join("linked.mp4")]);
+        assert_eq!(result.total_changes(), 1);
+    }
+
+    #[test]
+    fn test_scan_errors_are_classified_by_cause() {
+        let path = PathBuf::from("/media/locked");
+        let denied = anyhow::Error::new(FileSystemError::Io(std::io::ErrorKind::PermissionDenied.into()))
+            .context("File system scan failed");
+        let error = ScanError::new(path.clone(), &denied);
+        assert_eq!((error.kind, error.severity), (ScanErrorKind::Permission, ErrorSeverity::Error));
+        assert_eq!(error.suggestions, FileSystemError::Permission { path: String::new(), details: String::new() }.recovery_suggestions());
+
+        let too_long = FileSystemError::PathTooLong { path: "/media/long".to_string(), details: "too long".to_string() };
+        let error = ScanError::new(path.clone(), &anyhow::Error::new(too_long));
+        assert_eq!((error.kind, error.severity), (ScanErrorKind::TooLong, ErrorSeverity::Warning));
+
+        let error = ScanError::new(path, &anyhow::anyhow!("database is locked"));
+        assert_eq!((error.kind, error.error.as_str()), (ScanErrorKind::Io, "database is locked"));
+    }
+
+    #[test]
+    fn test_unopenable_files_are_classified_by_io_error_kind() {
+        // As spawn_file_task reports files it cannot open
+        let path = PathBuf::from("/media/movie.mp4");
+        let open_error = |error: std::io::Error| {
+            ScanError::from_filesystem_error(path.clone(), &FileSystemError::from_io_error(&path, &error))
+        };
+
+        let error = open_error(std::io::ErrorKind::PermissionDenied.into());
+        assert_eq!((error.kind, error.severity), (ScanErrorKind::Permission, ErrorSeverity::Error));
+        assert_eq!(open_error(std::io::ErrorKind::InvalidFilename.into()).kind, ScanErrorKind::InvalidPath);
+        assert_eq!(open_error(std::io::ErrorKind::InvalidData.into()).kind, ScanErrorKind::Encoding);
+        #[cfg(unix)]
+        assert_eq!(open_error(std::io::Error::from_raw_os_error(libc::ENAMETOOLONG)).kind, ScanErrorKind::TooLong);
+    }
+}
@@ -0,0 +1,306 @@
+//! Cover art embedded in audio files, cached on disk during scans.
+//!
+//! Pictures are keyed by album (its title and the directory holding the
+//! tracks) so every track of an album shares one cached image; tracks without
+//! an album tag are keyed by their own path. An in-memory index of the cached
+//! pictures answers lookups without touching the disk.
+
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::{debug, warn};
+
+use crate::config::AppConfig;
+use crate::database::MediaFile;
+use crate::media::dlna_profile;
+use crate::metadata::{image_resolution, CoverArt};
+
+/// Extensions of cached pictures, matching the formats `CoverArt` keeps
+const CACHED_FORMATS: [(&str, &str); 2] = [("jpg", "image/jpeg"), ("png", "image/png")];
+
+/// Pictures kept in the cache; the oldest are evicted beyond it
+const MAX_CACHED_PICTURES: usize = 10_000;
+
+/// A cached picture with its MIME type and the DLNA profile its size fits
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CachedArt {
+    pub path: PathBuf,
+    pub mime_type: &'static str,
+    pub profile: Option<&'static str>,
+}
+
+impl CachedArt {
+    fn new(path: PathBuf, mime_type: &'static str, data: &[u8]) -> Self {
+        let mut picture = MediaFile::new(path.clone(), data.len() as u64, mime_type.to_string());
+        if let Some((width, height)) = image_resolution(data) {
+            picture.width = Some(width);
+            picture.height = Some(height);
+        }
+        let profile = dlna_profile::detect(&picture);
+        Self { path, mime_type, profile }
+    }
+}
+
+/// Cached pictures by album key, oldest first in `order`
+#[derive(Debug, Default)]
+struct ArtIndex {
+    entries: HashMap<String, CachedArt>,
+    order: VecDeque<String>,
+    loaded: bool,
+}
+
+impl ArtIndex {
+    /// Add a picture, returning the ones evicted to stay within the limit
+    fn insert(&mut self, key: String, art: CachedArt) -> Vec<CachedArt> {
+        if self.entries.insert(key.clone(), art).is_some() {
+            self.order.retain(|existing| existing != &key);
+        }
+        self.order.push_back(key);
+        let mut evicted = Vec::new();
+        while self.order.len() > MAX_CACHED_PICTURES {
+            if let Some(art) = self.order.pop_front().and_then(|oldest| self.entries.remove(&oldest)) {
+                evicted.push(art);
+            }
+        }
+        evicted
+    }
+
+    fn remove(&mut self, key: &str) -> Option<CachedArt> {
+        let art = self.entries.remove(key)?;
+        self.order.retain(|existing| existing != key);
+        Some(art)
+    }
+}
+
+/// Whether a file of this MIME type can carry embedded cover art
+pub fn supports(mime_type: &str) -> bool {
+    mime_type.starts_with("audio/")
+}
+
+/// Disk cache of extracted cover art
+#[derive(Clone, Debug)]
+pub struct AlbumArtCache {
+    directory: PathBuf,
+    index: Arc<Mutex<ArtIndex>>,
+}
+
+impl Default for AlbumArtCache {
+    fn default() -> Self {
+        Self::new(AppConfig::get_platform_cache_dir().join("album_art"))
+    }
+}
+
+impl AlbumArtCache {
+    /// Cache pictures in `directory`, created on first use
+    pub fn new(directory: PathBuf) -> Self {
+        Self { directory, index: Arc::default() }
+    }
+
+    /// Index the pictures cached by earlier runs, oldest first, evicting those
+    /// beyond the limit. Only the first call reads the directory.
+    pub async fn load(&self) {
+        if self.index.lock().unwrap().loaded {
+            return;
+        }
+        let directory = self.directory.clone();
+        let cached = tokio::task::spawn_blocking(move || read_cached_pictures(&directory))
+            .await
+            .unwrap_or_default();
+
+        let evicted = {
+            let mut index = self.index.lock().unwrap();
+            if index.loaded {
+                return;
+            }
+            index.loaded = true;
+            let mut evicted = Vec::new();
+            for (key, art) in cached {
+                // Pictures stored meanwhile are newer than those on disk
+                if !index.entries.contains_key(&key) {
+                    evicted.extend(index.insert(key, art));
+                }
+            }
+            evicted
+        };
+        remove_pictures(evicted).await;
+    }
+
+    /// Digest shared by every track of the album `file` belongs to
+    fn art_key(file: &MediaFile) -> String {
+        let key = match &file.album {
+            Some(album) => {
+                let folder = file.path.parent().unwrap_or(Path::new(""));
+                format!("album:{}\0{}", folder.to_string_lossy(), album.to_lowercase())
+            }
+            None => format!("file:{}", file.path.to_string_lossy()),
+        };
+        hex::encode(Sha256::digest(key.as_bytes()))
+    }
+
+    /// Cached picture of `file`, if one was extracted
+    pub fn lookup(&self, file: &MediaFile) -> Option<CachedArt> {
+        if !supports(&file.mime_type) {
+            return None;
+        }
+        self.index.lock().unwrap().entries.get(&Self::art_key(file)).cloned()
+    }
+
+    /// Cache `art` for `file`, keeping the picture already cached for its album
+    pub async fn store(&self, file: &MediaFile, art: &CoverArt) -> io::Result<CachedArt> {
+        self.load().await;
+        if let Some(cached) = self.lookup(file) {
+            return Ok(cached);
+        }
+        let (extension, mime_type) = CACHED_FORMATS
+            .iter()
+            .find(|(_, mime_type)| *mime_type == art.mime_type)
+            .copied()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "unsupported cover art format"))?;
+
+        tokio::fs::create_dir_all(&self.directory).await?;
+        // Write to a private name so concurrent scans never expose a partial file
+        let key = Self::art_key(file);
+        let path = self.directory.join(format!("{}.{}", key, extension));
+        let partial = self.directory.join(format!("{}.partial", uuid::Uuid::new_v4()));
+        if let Err(e) = tokio::fs::write(&partial, &art.data).await {
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(e);
+        }
+        tokio::fs::rename(&partial, &path).await?;
+        debug!("Cached cover art of {}", file.path.display());
+
+        let cached = CachedArt::new(path, mime_type, &art.data);
+        let evicted = self.index.lock().unwrap().insert(key, cached.clone());
+        remove_pictures(evicted).await;
+        Ok(cached)
+    }
+
+    /// Drop the picture cached for the album of `file`, whose tags changed
+    pub async fn invalidate(&self, file: &MediaFile) {
+        if !supports(&file.mime_type) {
+            return;
+        }
+        self.load().await;
+        let removed = self.index.lock().unwrap().remove(&Self::art_key(file));
+        remove_pictures(removed.into_iter().collect()).await;
+    }
+
+    /// Cache the art returned by metadata extraction, if any; failures only lose the artwork
+    pub async fn cache_extracted(&self, file: &MediaFile, art: Option<CoverArt>) {
+        let Some(art) = art else { return };
+        if let Err(e) = self.store(file, &art).await {
+            warn!("Could not cache cover art of {}: {}", file.path.display(), e);
+        }
+    }
+}
+
+/// Pictures in the cache directory by album key, oldest first
+fn read_cached_pictures(directory: &Path) -> Vec<(String, CachedArt)> {
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return Vec::new();
+    };
+    let mut pictures: Vec<_> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let key = path.file_stem()?.to_str()?.to_string();
+            let extension = path.extension()?.to_str()?;
+            let (_, mime_type) = CACHED_FORMATS.iter().find(|(known, _)| *known == extension)?;
+            let modified = entry.metadata().and_then(|metadata| metadata.modified()).ok()?;
+            let data = std::fs::read(&path).ok()?;
+            Some((modified, key, CachedArt::new(path, mime_type, &data)))
+        })
+        .collect();
+    pictures.sort_by_key(|(modified, _, _)| *modified);
+    pictures.into_iter().map(|(_, key, art)| (key, art)).collect()
+}
+
+async fn remove_pictures(pictures: Vec<CachedArt>) {
+    for art in pictures {
+        if let Err(e) = tokio::fs::remove_file(&art.path).await {
+            if e.kind() != io::ErrorKind::NotFound {
+                warn!("Could not remove cached cover art {}: {}", art.path.display(), e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = include_bytes!("../web/icon.png");
+
+    /// A minimal MP3: an ID3v2.3 tag with album and front cover, then one frame header
+    fn mp3_with_cover(album: &str, picture: &[u8]) -> Vec<u8> {
+        let frame = |id: &[u8; 4], body: &[u8]| [&id[..], &(body.len() as u32).to_be_bytes(), &[0, 0], body].concat();
+        let album_body = [&[0u8][..], album.as_bytes()].concat();
+        let apic_body = [&[0u8][..], b"image/png\0", &[3], b"Cover\0", picture].concat();
+        let frames = [frame(b"TALB", &album_body), frame(b"APIC", &apic_body)].concat();
+        let size = frames.len() as u32;
+        let syncsafe_size = [(size >> 21) as u8 & 0x7F, (size >> 14) as u8 & 0x7F, (size >> 7) as u8 & 0x7F, size as u8 & 0x7F];
+        let mut mp3 = [&b"ID3\x03\x00\x00"[..], &syncsafe_size, &frames].concat();
+        mp3.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+        mp3.resize(mp3.len() + 4096, 0);
+        mp3
+    }
+
+    #[tokio::test]
+    async fn test_embedded_cover_art_is_extracted_and_shared_by_album() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cache = AlbumArtCache::new(temp_dir.path().join("art"));
+
+        let mut tracks = Vec::new();
+        for name in ["01.mp3", "02.mp3"] {
+            let path = temp_dir.path().join(name);
+            std::fs::write(&path, mp3_with_cover("Record", PNG)).unwrap();
+            let mut track = MediaFile::new(path, 0, "audio/mpeg".to_string());
+            let art = crate::metadata::probe_media_file(&mut track).await.expect("cover art was not extracted");
+            assert_eq!(art.mime_type, "image/png");
+            assert_eq!(art.data, PNG);
+            assert_eq!(track.album.as_deref(), Some("Record"));
+            cache.store(&track, &art).await.unwrap();
+            tracks.push(track);
+        }
+
+        // Both tracks resolve to the same cached picture
+        let first = cache.lookup(&tracks[0]).unwrap();
+        assert_eq!((first.mime_type, first.profile), ("image/png", Some("PNG_TN")));
+        assert_eq!(cache.lookup(&tracks[1]).unwrap(), first);
+        assert_eq!(std::fs::read(&first.path).unwrap(), PNG);
+        assert_eq!(std::fs::read_dir(temp_dir.path().join("art")).unwrap().count(), 1);
+
+        // A new instance finds the picture on disk once loaded
+        let reloaded = AlbumArtCache::new(temp_dir.path().join("art"));
+        assert!(reloaded.lookup(&tracks[0]).is_none());
+        reloaded.load().await;
+        assert_eq!(reloaded.lookup(&tracks[1]), Some(first.clone()));
+
+        // Changed tags drop the album's picture
+        reloaded.invalidate(&tracks[0]).await;
+        assert!(reloaded.lookup(&tracks[1]).is_none());
+        assert!(!first.path.exists());
+
+        // A track from another album, or without art, has none cached
+        let mut other = tracks[0].clone();
+        other.album = Some("Other".to_string());
+        assert!(cache.lookup(&other).is_none());
+        let plain = temp_dir.path().join("plain.mp3");
+        std::fs::write(&plain, mp3_with_cover("Plain", b"not an image")).unwrap();
+        assert!(crate::metadata::probe_media_file(&mut MediaFile::new(plain, 0, "audio/mpeg".to_string())).await.is_none());
+    }
+
+    #[test]
+    fn test_profile_follows_picture_size() {
+        let png = |width: u32, height: u32| {
+            [&b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR"[..], &width.to_be_bytes(), &height.to_be_bytes(), &[8, 6, 0, 0, 0]].concat()
+        };
+        let profile = |data: &[u8]| CachedArt::new(PathBuf::from("art.png"), "image/png", data).profile;
+        assert_eq!(profile(&png(160, 120)), Some("PNG_TN"));
+        assert_eq!(profile(&png(800, 600)), Some("PNG_LRG"));
+        assert_eq!(profile(&png(5000, 5000)), None);
+        assert_eq!(profile(b"\x89PNG truncated"), None);
+    }
+}
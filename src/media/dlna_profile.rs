@@ -0,0 +1,165 @@
+//! DLNA media format profiles, the `DLNA.ORG_PN` field of protocolInfo and
+//! `contentFeatures.dlna.org`.
+//!
+//! Strict renderers refuse resources whose profile they do not recognize, so
+//! a profile is only announced when the file fits it: the MIME type picks the
+//! profile family and the picture size and average bitrate pick the level.
+//! Codecs are not probed, so MP4 files are assumed to hold H.264 video with
+//! AAC audio, as nearly all do. Files fitting no profile, or whose resolution
+//! is unknown, are announced without one.
+
+use std::collections::HashMap;
+
+use crate::database::MediaFile;
+
+/// Profiles `detect` gives video MP4 files, smallest first
+const AVC_MP4_PROFILES: &[&str] = &[
+    "AVC_MP4_BL_CIF15_AAC_520",
+    "AVC_MP4_BL_CIF30_AAC_940",
+    "AVC_MP4_MP_SD_AAC_MTL",
+    "AVC_MP4_MP_HD_720p_AAC",
+    "AVC_MP4_HP_HD_AAC",
+];
+
+/// Every profile `detect` can give files of `mime_type`, for the source protocolInfo
+pub fn profiles_for_mime(mime_type: &str) -> &'static [&'static str] {
+    match mime_type {
+        "video/mp4" | "video/x-m4v" => AVC_MP4_PROFILES,
+        "video/mpeg" => &["MPEG1", "MPEG_PS_NTSC", "MPEG_PS_PAL"],
+        "video/x-ms-wmv" => &["WMVMED_BASE", "WMVHIGH_FULL"],
+        "audio/mpeg" => &["MP3"],
+        "audio/mp4" => &["AAC_ISO_320", "AAC_ISO"],
+        "audio/aac" => &["AAC_ADTS_320", "AAC_ADTS"],
+        "audio/x-ms-wma" => &["WMABASE", "WMAFULL"],
+        "audio/wav" => &["LPCM"],
+        "image/jpeg" => &["JPEG_TN", "JPEG_SM", "JPEG_MED", "JPEG_LRG"],
+        "image/png" => &["PNG_TN", "PNG_LRG"],
+        "image/gif" => &["GIF_LRG"],
+        _ => &[],
+    }
+}
+
+/// Average bitrate of the whole file in bits per second, when its duration is known
+fn average_bitrate(file: &MediaFile) -> Option<u64> {
+    let millis = file.duration?.as_millis() as u64;
+    (millis > 0).then(|| file.size.saturating_mul(8000) / millis)
+}
+
+/// Whether the picture fits within `width` x `height`
+fn fits(size: (u32, u32), width: u32, height: u32) -> bool {
+    size.0 <= width && size.1 <= height
+}
+
+/// Profile of `file` detected from its MIME type, resolution and average bitrate
+pub fn detect(file: &MediaFile) -> Option<&'static str> {
+    let size = file.width.zip(file.height);
+    let bitrate = average_bitrate(file);
+    match file.mime_type.as_str() {
+        "video/mp4" | "video/x-m4v" => match size? {
+            s if fits(s, 352, 288) && bitrate.is_some_and(|b| b <= 520_000) => Some("AVC_MP4_BL_CIF15_AAC_520"),
+            s if fits(s, 352, 288) && bitrate.is_some_and(|b| b <= 940_000) => Some("AVC_MP4_BL_CIF30_AAC_940"),
+            s if fits(s, 720, 576) => Some("AVC_MP4_MP_SD_AAC_MTL"),
+            s if fits(s, 1280, 720) => Some("AVC_MP4_MP_HD_720p_AAC"),
+            s if fits(s, 1920, 1080) => Some("AVC_MP4_HP_HD_AAC"),
+            _ => None,
+        },
+        "video/mpeg" => match size? {
+            s if fits(s, 352, 288) => Some("MPEG1"),
+            s if fits(s, 720, 480) => Some("MPEG_PS_NTSC"),
+            s if fits(s, 720, 576) => Some("MPEG_PS_PAL"),
+            _ => None,
+        },
+        "video/x-ms-wmv" => match size? {
+            s if fits(s, 720, 576) => Some("WMVMED_BASE"),
+            s if fits(s, 1920, 1080) => Some("WMVHIGH_FULL"),
+            _ => None,
+        },
+        // Tags and cover art inflate the estimate, so only clearly high bitrates move up a level
+        "audio/mpeg" => Some("MP3"),
+        "audio/mp4" if bitrate.is_some_and(|b| b > 320_000) => Some("AAC_ISO"),
+        "audio/mp4" => Some("AAC_ISO_320"),
+        "audio/aac" if bitrate.is_some_and(|b| b > 320_000) => Some("AAC_ADTS"),
+        "audio/aac" => Some("AAC_ADTS_320"),
+        "audio/x-ms-wma" if bitrate.is_some_and(|b| b > 193_000) => Some("WMAFULL"),
+        "audio/x-ms-wma" => Some("WMABASE"),
+        "audio/wav" => Some("LPCM"),
+        "image/jpeg" => match size? {
+            s if fits(s, 160, 160) => Some("JPEG_TN"),
+            s if fits(s, 640, 480) => Some("JPEG_SM"),
+            s if fits(s, 1024, 768) => Some("JPEG_MED"),
+            s if fits(s, 4096, 4096) => Some("JPEG_LRG"),
+            _ => None,
+        },
+        "image/png" => match size? {
+            s if fits(s, 160, 160) => Some("PNG_TN"),
+            s if fits(s, 4096, 4096) => Some("PNG_LRG"),
+            _ => None,
+        },
+        "image/gif" => size.filter(|s| fits(*s, 1600, 1200)).map(|_| "GIF_LRG"),
+        _ => None,
+    }
+}
+
+/// Profile announced for `file`: the one configured for its MIME type in
+/// `media.dlna_profiles`, where an empty value announces none, or the detected one
+pub fn profile_for<'a>(file: &MediaFile, overrides: Option<&'a HashMap<String, String>>) -> Option<&'a str> {
+    match overrides.and_then(|profiles| profiles.get(&file.mime_type)) {
+        Some(profile) => Some(profile.as_str()).filter(|p| !p.is_empty()),
+        None => detect(file),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    fn file(name: &str, mime: &str, size: u64, resolution: Option<(u32, u32)>) -> MediaFile {
+        let mut file = MediaFile::new(PathBuf::from(name), size, mime.to_string());
+        file.width = resolution.map(|r| r.0);
+        file.height = resolution.map(|r| r.1);
+        file
+    }
+
+    #[test]
+    fn test_detects_profiles_from_resolution_and_bitrate() {
+        let mut movie = file("/v/movie.mp4", "video/mp4", 2_000_000_000, Some((1920, 1080)));
+        movie.duration = Some(Duration::from_secs(2 * 3600));
+        assert_eq!(detect(&movie), Some("AVC_MP4_HP_HD_AAC"));
+        assert_eq!(detect(&file("/p/photo.jpg", "image/jpeg", 5_000_000, Some((4032, 3024)))), Some("JPEG_LRG"));
+
+        assert_eq!(detect(&file("/v/hd.mp4", "video/mp4", 1, Some((1280, 720)))), Some("AVC_MP4_MP_HD_720p_AAC"));
+        let mut clip = file("/v/clip.mp4", "video/mp4", 3_000_000, Some((352, 288)));
+        clip.duration = Some(Duration::from_secs(60));
+        assert_eq!(detect(&clip), Some("AVC_MP4_BL_CIF15_AAC_520"));
+        assert_eq!(detect(&file("/v/dvd.mpg", "video/mpeg", 1, Some((720, 576)))), Some("MPEG_PS_PAL"));
+        assert_eq!(detect(&file("/p/thumb.png", "image/png", 1, Some((160, 90)))), Some("PNG_TN"));
+        assert_eq!(detect(&file("/a/song.mp3", "audio/mpeg", 1, None)), Some("MP3"));
+
+        // Too large for any profile, resolution unknown, or no profile for the format
+        assert_eq!(detect(&file("/v/uhd.mp4", "video/mp4", 1, Some((3840, 2160)))), None);
+        assert_eq!(detect(&file("/p/pano.jpg", "image/jpeg", 1, Some((8000, 2000)))), None);
+        assert_eq!(detect(&file("/v/movie.mp4", "video/mp4", 1, None)), None);
+        assert_eq!(detect(&file("/v/movie.mkv", "video/x-matroska", 1, Some((1920, 1080)))), None);
+
+        for profile in ["AVC_MP4_HP_HD_AAC", "AVC_MP4_BL_CIF15_AAC_520"] {
+            assert!(profiles_for_mime("video/mp4").contains(&profile));
+        }
+    }
+
+    #[test]
+    fn test_configured_profiles_replace_detection() {
+        let overrides: HashMap<String, String> = [
+            ("video/x-matroska".to_string(), "AVC_MKV_HP_HD_AAC_MULT5".to_string()),
+            ("audio/mpeg".to_string(), String::new()),
+        ]
+        .into();
+        let mkv = file("/v/movie.mkv", "video/x-matroska", 1, None);
+        assert_eq!(profile_for(&mkv, Some(&overrides)), Some("AVC_MKV_HP_HD_AAC_MULT5"));
+        assert_eq!(profile_for(&file("/a/song.mp3", "audio/mpeg", 1, None), Some(&overrides)), None);
+        let photo = file("/p/photo.jpg", "image/jpeg", 1, Some((640, 480)));
+        assert_eq!(profile_for(&photo, Some(&overrides)), Some("JPEG_SM"));
+        assert_eq!(profile_for(&photo, None), Some("JPEG_SM"));
+    }
+}
@@ -0,0 +1,203 @@
+//! Content sniffing for files whose extension does not settle their MIME type.
+//!
+//! With `media.sniff_content` enabled, files with an unknown or missing
+//! extension, and those whose extension is listed in `media.sniff_extensions`
+//! (`.ts` is MPEG-TS video as often as TypeScript source), have their first
+//! few KB checked against the magic bytes of media formats. A match decides the
+//! MIME type; without one the file is not treated as media. Sniffing is off by
+//! default because it reads from every such file during scans.
+
+use std::io::Read;
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+
+/// Bytes read from the start of a file for sniffing
+pub const SNIFF_LEN: usize = 4096;
+
+/// Media MIME type identified from the first bytes of a file
+pub fn sniff(bytes: &[u8]) -> Option<&'static str> {
+    let at = |offset: usize, magic: &[u8]| bytes.get(offset..offset + magic.len()) == Some(magic);
+    let contains = |needle: &[u8]| bytes.windows(needle.len()).any(|window| window == needle);
+
+    if at(4, b"ftyp") {
+        return match bytes.get(8..12)? {
+            b"qt  " => Some("video/quicktime"),
+            b"M4A " | b"M4B " => Some("audio/mp4"),
+            b"M4V " | b"M4VH" | b"M4VP" => Some("video/x-m4v"),
+            brand if brand.starts_with(b"3g") => Some("video/3gpp"),
+            // Still images in the same container
+            b"avif" | b"heic" | b"heix" | b"mif1" => None,
+            _ => Some("video/mp4"),
+        };
+    }
+    if at(0, &[0x1A, 0x45, 0xDF, 0xA3]) {
+        return Some(if contains(b"webm") { "video/webm" } else { "video/x-matroska" });
+    }
+    if at(0, b"RIFF") {
+        return match bytes.get(8..12)? {
+            b"AVI " => Some("video/x-msvideo"),
+            b"WAVE" => Some("audio/wav"),
+            b"WEBP" => Some("image/webp"),
+            _ => None,
+        };
+    }
+    // Transport streams repeat a sync byte every 188 bytes, or every 192 in M2TS
+    if [0, 188, 376].iter().all(|&offset| at(offset, &[0x47])) || [4, 196, 388].iter().all(|&offset| at(offset, &[0x47])) {
+        return Some("video/mp2t");
+    }
+    if at(0, &[0x00, 0x00, 0x01, 0xBA]) || at(0, &[0x00, 0x00, 0x01, 0xB3]) {
+        return Some("video/mpeg");
+    }
+    if at(0, b"FLV\x01") {
+        return Some("video/x-flv");
+    }
+    if at(0, &[0x30, 0x26, 0xB2, 0x75, 0x8E, 0x66, 0xCF, 0x11]) {
+        return Some("video/x-ms-asf");
+    }
+    if at(0, b"OggS") {
+        return Some(if contains(b"OpusHead") {
+            "audio/opus"
+        } else if contains(b"\x80theora") {
+            "video/ogg"
+        } else {
+            "audio/ogg"
+        });
+    }
+    if at(0, b"fLaC") {
+        return Some("audio/flac");
+    }
+    if at(0, b"ID3") {
+        return Some("audio/mpeg");
+    }
+    if at(0, b"FORM") && (at(8, b"AIFF") || at(8, b"AIFC")) {
+        return Some("audio/aiff");
+    }
+    if at(0, &[0xFF, 0xD8, 0xFF]) {
+        return Some("image/jpeg");
+    }
+    if at(0, b"\x89PNG\r\n\x1a\n") {
+        return Some("image/png");
+    }
+    if at(0, b"GIF87a") || at(0, b"GIF89a") {
+        return Some("image/gif");
+    }
+    if at(0, b"II*\0") || at(0, b"MM\0*") {
+        return Some("image/tiff");
+    }
+    // Frame sync: ADTS AAC has layer bits 00, MPEG audio layers 1-3 do not
+    match bytes {
+        [0xFF, second, ..] if second & 0xF6 == 0xF0 => Some("audio/aac"),
+        [0xFF, second, ..] if second & 0xE0 == 0xE0 && second & 0x06 != 0 => Some("audio/mpeg"),
+        _ => None,
+    }
+}
+
+/// Sniff the media type of the file at `path`
+pub async fn sniff_file(path: &Path) -> Option<&'static str> {
+    let file = tokio::fs::File::open(path).await.ok()?;
+    let mut bytes = Vec::with_capacity(SNIFF_LEN);
+    file.take(SNIFF_LEN as u64).read_to_end(&mut bytes).await.ok()?;
+    sniff(&bytes)
+}
+
+/// Blocking form of [`sniff_file`], for callers outside the async runtime or on a blocking thread
+pub fn sniff_file_blocking(path: &Path) -> Option<&'static str> {
+    let file = std::fs::File::open(path).ok()?;
+    let mut bytes = Vec::with_capacity(SNIFF_LEN);
+    file.take(SNIFF_LEN as u64).read_to_end(&mut bytes).ok()?;
+    sniff(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::filesystem::{BaseFileSystemManager, MediaTypes};
+    use tempfile::tempdir;
+
+    fn ftyp(brand: &[u8; 4]) -> Vec<u8> {
+        let mut bytes = vec![0x00, 0x00, 0x00, 0x20];
+        bytes.extend_from_slice(b"ftyp");
+        bytes.extend_from_slice(brand);
+        bytes.extend_from_slice(&[0x00, 0x00, 0x02, 0x00]);
+        bytes
+    }
+
+    fn transport_stream() -> Vec<u8> {
+        let mut bytes = vec![0u8; 188 * 3];
+        for packet in bytes.chunks_mut(188) {
+            packet[0] = 0x47;
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_sniffs_media_signatures() {
+        assert_eq!(sniff(&ftyp(b"isom")), Some("video/mp4"));
+        assert_eq!(sniff(&ftyp(b"qt  ")), Some("video/quicktime"));
+        assert_eq!(sniff(&ftyp(b"M4A ")), Some("audio/mp4"));
+        assert_eq!(sniff(&ftyp(b"heic")), None);
+
+        let mut matroska = vec![0x1A, 0x45, 0xDF, 0xA3, 0x9F, 0x42, 0x82, 0x88];
+        assert_eq!(sniff(&[&matroska[..], b"matroska"].concat()), Some("video/x-matroska"));
+        matroska.truncate(7);
+        assert_eq!(sniff(&[&matroska[..], b"\x84webm"].concat()), Some("video/webm"));
+
+        assert_eq!(sniff(&[0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, b'J', b'F', b'I', b'F']), Some("image/jpeg"));
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), Some("image/png"));
+        assert_eq!(sniff(&transport_stream()), Some("video/mp2t"));
+        assert_eq!(sniff(b"RIFF\x24\0\0\0WAVEfmt "), Some("audio/wav"));
+        assert_eq!(sniff(b"ID3\x04\0\0\0\0\0\0"), Some("audio/mpeg"));
+        assert_eq!(sniff(&[0xFF, 0xFB, 0x90, 0x64]), Some("audio/mpeg"));
+        assert_eq!(sniff(&[0xFF, 0xF1, 0x50, 0x80]), Some("audio/aac"));
+
+        // Text, including TypeScript source in a .ts file, is not media
+        assert_eq!(sniff(b"import { Component } from '@angular/core';\n"), None);
+        assert_eq!(sniff(b""), None);
+        assert_eq!(sniff(&[0x47]), None);
+    }
+
+    #[tokio::test]
+    async fn test_scan_sniffs_unknown_and_ambiguous_files() {
+        let temp_dir = tempdir().unwrap();
+        std::fs::write(temp_dir.path().join("holiday"), ftyp(b"isom")).unwrap();
+        std::fs::write(temp_dir.path().join("recording.ts"), transport_stream()).unwrap();
+        std::fs::write(temp_dir.path().join("app.ts"), b"export const answer = 42;\n").unwrap();
+        std::fs::write(temp_dir.path().join("notes.txt"), b"shopping list").unwrap();
+        // Known extensions keep the fast path and are not read
+        std::fs::write(temp_dir.path().join("movie.mkv"), b"not really matroska").unwrap();
+
+        let filesystem = &BaseFileSystemManager::new(true);
+        let dir = temp_dir.path();
+        let mime_types = |media_types: MediaTypes| async move {
+            let mut files: Vec<(String, String)> = filesystem
+                .scan_directory_common(dir, &media_types)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|file| (file.filename, file.mime_type))
+                .collect();
+            files.sort();
+            files
+        };
+        let pair = |name: &str, mime: &str| (name.to_string(), mime.to_string());
+
+        assert_eq!(mime_types(MediaTypes::default()).await, vec![pair("movie.mkv", "video/x-matroska")]);
+
+        let sniffing = MediaTypes::default().with_content_sniffing(true, &[".TS".to_string()]);
+        let sniffed = mime_types(sniffing.clone()).await;
+        let path = |name: &str| dir.join(name);
+        assert!(!sniffing.sniffs(&path("movie.mkv")) && sniffing.sniffs(&path("app.ts")) && sniffing.sniffs(&path("holiday")));
+        assert_eq!(crate::media::get_mime_type(&path("holiday"), &sniffing).await, "video/mp4");
+        assert_eq!(crate::media::detect_mime_type(&path("notes.txt"), &sniffing).await, None);
+        assert_eq!(crate::media::get_mime_type(&path("holiday"), &MediaTypes::default()).await, "application/octet-stream");
+
+        assert_eq!(
+            sniffed,
+            vec![
+                pair("holiday", "video/mp4"),
+                pair("movie.mkv", "video/x-matroska"),
+                pair("recording.ts", "video/mp2t"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,364 @@
+//! M3U and PLS playlists served as ContentDirectory containers.
+//!
+//! Playlist files found while scanning are parsed into the ordered paths they
+//! list, with relative entries resolved against the playlist's directory, and
+//! recorded in the database. [`PlaylistIndex`] holds those paths resolved to
+//! indexed media files, so each playlist browses as a container of its items in order.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tracing::{debug, warn};
+
+use crate::database::Playlist;
+use crate::platform::filesystem::FileSystemManager;
+
+/// Extensions of the playlist formats understood here
+pub const PLAYLIST_EXTENSIONS: &[&str] = &["m3u", "m3u8", "pls"];
+
+/// Whether `path` names an M3U or PLS playlist
+pub fn is_playlist_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| PLAYLIST_EXTENSIONS.iter().any(|known| ext.eq_ignore_ascii_case(known)))
+}
+
+/// Entries and `#PLAYLIST:` title of an M3U or extended M3U playlist
+fn parse_m3u(content: &str) -> (Vec<String>, Option<String>) {
+    let mut entries = Vec::new();
+    let mut title = None;
+    for line in content.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix("#PLAYLIST:") {
+            title = Some(name.trim().to_string()).filter(|name| !name.is_empty());
+        } else if !line.is_empty() && !line.starts_with('#') {
+            entries.push(line.to_string());
+        }
+    }
+    (entries, title)
+}
+
+/// Entries of a PLS playlist, ordered by their `FileN` number
+fn parse_pls(content: &str) -> Vec<String> {
+    let mut entries: Vec<(u32, String)> = content
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.trim().split_once('=')?;
+            let key = key.trim();
+            key.get(..4).filter(|prefix| prefix.eq_ignore_ascii_case("file"))?;
+            Some((key[4..].parse().ok()?, value.trim().to_string()))
+        })
+        .filter(|(_, value)| !value.is_empty())
+        .collect();
+    entries.sort_by_key(|(number, _)| *number);
+    entries.into_iter().map(|(_, value)| value).collect()
+}
+
+/// Decode the `%XX` escapes of a URI path; malformed escapes are kept as is
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Absolute path of a playlist entry, or `None` for streams such as `http://`
+/// URLs. `file://` URIs are percent-decoded and relative entries are taken
+/// from `base_dir`; Windows separators are accepted everywhere since
+/// playlists are often written on Windows.
+pub fn resolve_entry(base_dir: &Path, entry: &str) -> Option<PathBuf> {
+    let decoded;
+    let entry = match entry.strip_prefix("file://") {
+        Some(local) => {
+            decoded = percent_decode(local.strip_prefix("localhost").unwrap_or(local));
+            decoded.strip_prefix('/').filter(|rest| rest.get(1..2) == Some(":")).unwrap_or(&decoded)
+        }
+        None if entry.contains("://") => return None,
+        None => entry,
+    };
+    let entry = if cfg!(windows) { entry.to_string() } else { entry.replace('\\', "/") };
+    let path = Path::new(&entry);
+    let joined = if path.is_absolute() { path.to_path_buf() } else { base_dir.join(path) };
+
+    // Collapse `.` and `..` lexically; the files need not exist yet
+    let mut resolved = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            other => resolved.push(other),
+        }
+    }
+    Some(resolved)
+}
+
+/// Parse the playlist at `path` from its `content`. Entries are resolved
+/// against the playlist's directory; the title is the M3U `#PLAYLIST:` name or
+/// else the file stem.
+pub fn parse_playlist(path: &Path, content: &str) -> Playlist {
+    let content = content.trim_start_matches('\u{feff}');
+    let is_pls = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pls"));
+    let (entries, title) = if is_pls { (parse_pls(content), None) } else { parse_m3u(content) };
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+    Playlist {
+        id: None,
+        path: path.to_path_buf(),
+        title: title.unwrap_or_else(|| {
+            path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default()
+        }),
+        entries: entries.iter().filter_map(|entry| resolve_entry(base_dir, entry)).collect(),
+    }
+}
+
+/// Read and parse the playlist file at `path`, with its path and entries
+/// normalized like scanned media paths. Playlists that are not valid UTF-8
+/// (older `.m3u` files are often Latin-1) are read lossily.
+pub async fn read_playlist(path: &Path, filesystem: &dyn FileSystemManager) -> std::io::Result<Playlist> {
+    let bytes = tokio::fs::read(path).await?;
+    let mut playlist = parse_playlist(path, &String::from_utf8_lossy(&bytes));
+    playlist.path = filesystem.normalize_path(&playlist.path);
+    for entry in &mut playlist.entries {
+        *entry = filesystem.normalize_path(entry);
+    }
+    Ok(playlist)
+}
+
+/// Playlist files directly inside `directory`; an unreadable directory has none
+pub async fn list_playlist_files(directory: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut entries = match tokio::fs::read_dir(directory).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            debug!("Cannot list playlists in {}: {}", directory.display(), e);
+            return files;
+        }
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        if is_playlist_file(&path) && entry.file_type().await.is_ok_and(|t| t.is_file()) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    files
+}
+
+/// A playlist whose entries were matched to indexed media files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedPlaylist {
+    pub id: i64,
+    pub title: String,
+    /// Media file ids in playlist order
+    pub file_ids: Vec<i64>,
+}
+
+/// Match the entries of `playlist` to the media file `ids` by path, returning
+/// the entries without an indexed file alongside
+pub fn resolve_playlist(playlist: &Playlist, ids: &HashMap<PathBuf, i64>) -> (Option<ResolvedPlaylist>, Vec<PathBuf>) {
+    let mut unresolved = Vec::new();
+    let file_ids = playlist
+        .entries
+        .iter()
+        .filter_map(|entry| {
+            let id = ids.get(entry).copied();
+            if id.is_none() {
+                unresolved.push(entry.clone());
+            }
+            id
+        })
+        .collect();
+    let resolved = playlist
+        .id
+        .map(|id| ResolvedPlaylist { id, title: playlist.title.clone(), file_ids });
+    (resolved, unresolved)
+}
+
+/// A recorded playlist and how it resolved
+struct IndexedPlaylist {
+    playlist: Playlist,
+    resolved: Option<ResolvedPlaylist>,
+    unresolved: HashSet<PathBuf>,
+}
+
+/// Resolved playlists served to clients, by playlist path
+#[derive(Clone, Default)]
+pub struct PlaylistIndex {
+    playlists: Arc<RwLock<HashMap<PathBuf, IndexedPlaylist>>>,
+}
+
+impl PlaylistIndex {
+    /// Resolve `playlists` against the media file `ids` and replace the index
+    /// with the result, returning whether any playlist served to clients changed
+    pub fn replace(&self, playlists: &[Playlist], ids: &HashMap<PathBuf, i64>) -> bool {
+        let mut previous = std::mem::take(&mut *self.playlists.write().unwrap());
+        let mut changed = false;
+        for playlist in playlists {
+            changed |= self.update_from(previous.remove(&playlist.path), playlist, ids);
+        }
+        changed || previous.values().any(|gone| gone.resolved.is_some())
+    }
+
+    /// Resolve one recorded playlist again and replace its entry in the index,
+    /// returning whether what clients see of it changed
+    pub fn update(&self, playlist: &Playlist, ids: &HashMap<PathBuf, i64>) -> bool {
+        let previous = self.playlists.write().unwrap().remove(&playlist.path);
+        self.update_from(previous, playlist, ids)
+    }
+
+    fn update_from(&self, previous: Option<IndexedPlaylist>, playlist: &Playlist, ids: &HashMap<PathBuf, i64>) -> bool {
+        let (resolved, unresolved) = resolve_playlist(playlist, ids);
+        let unresolved: HashSet<PathBuf> = unresolved.into_iter().collect();
+        let reported = previous.as_ref().map(|indexed| &indexed.unresolved);
+        // Each unresolved entry is warned about once
+        for entry in unresolved.iter().filter(|entry| !reported.is_some_and(|reported| reported.contains(*entry))) {
+            warn!(
+                "Skipping playlist entry {} in {}: not an indexed media file",
+                entry.display(),
+                playlist.path.display()
+            );
+        }
+        let changed = previous.and_then(|indexed| indexed.resolved) != resolved;
+        self.playlists.write().unwrap().insert(
+            playlist.path.clone(),
+            IndexedPlaylist { playlist: playlist.clone(), resolved, unresolved },
+        );
+        changed
+    }
+
+    /// Forget the playlist at `path`, returning whether clients saw it
+    pub fn remove(&self, path: &Path) -> bool {
+        self.playlists.write().unwrap().remove(path).is_some_and(|indexed| indexed.resolved.is_some())
+    }
+
+    /// Recorded playlists listing any of `paths`
+    pub fn listing(&self, paths: &[PathBuf]) -> Vec<Playlist> {
+        self.playlists
+            .read()
+            .unwrap()
+            .values()
+            .filter(|indexed| indexed.playlist.entries.iter().any(|entry| paths.contains(entry)))
+            .map(|indexed| indexed.playlist.clone())
+            .collect()
+    }
+
+    /// All playlists, sorted by title
+    pub fn all(&self) -> Vec<ResolvedPlaylist> {
+        let mut all: Vec<ResolvedPlaylist> = self
+            .playlists
+            .read()
+            .unwrap()
+            .values()
+            .filter_map(|indexed| indexed.resolved.clone())
+            .collect();
+        all.sort_by_key(|playlist| playlist.title.to_lowercase());
+        all
+    }
+
+    /// The playlist with database id `id`
+    pub fn get(&self, id: i64) -> Option<ResolvedPlaylist> {
+        self.playlists
+            .read()
+            .unwrap()
+            .values()
+            .find_map(|indexed| indexed.resolved.clone().filter(|playlist| playlist.id == id))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.playlists.read().unwrap().values().all(|indexed| indexed.resolved.is_none())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn indexed(path: &str, id: i64) -> (PathBuf, i64) {
+        (PathBuf::from(path), id)
+    }
+
+    #[test]
+    fn test_m3u_relative_entries_resolve_in_order() {
+        let content = "\u{feff}#EXTM3U\n#PLAYLIST:Road Trip\n#EXTINF:215,Artist - Song\nAlbum/02 - Second.mp3\n\n\
+                       ../Other/first.flac\r\n./Album/01 - First.mp3\nhttp://radio.example/stream\n/music/Missing.mp3\n";
+        let mut playlist = parse_playlist(Path::new("/music/Lists/trip.m3u"), content);
+        assert_eq!(playlist.title, "Road Trip");
+        assert_eq!(
+            playlist.entries,
+            vec![
+                PathBuf::from("/music/Lists/Album/02 - Second.mp3"),
+                PathBuf::from("/music/Other/first.flac"),
+                PathBuf::from("/music/Lists/Album/01 - First.mp3"),
+                PathBuf::from("/music/Missing.mp3"),
+            ]
+        );
+
+        playlist.id = Some(7);
+        let ids = HashMap::from([
+            indexed("/music/Lists/Album/01 - First.mp3", 1),
+            indexed("/music/Lists/Album/02 - Second.mp3", 2),
+            indexed("/music/Other/first.flac", 3),
+        ]);
+        let (resolved, unresolved) = resolve_playlist(&playlist, &ids);
+        assert_eq!(resolved, Some(ResolvedPlaylist { id: 7, title: "Road Trip".to_string(), file_ids: vec![2, 3, 1] }));
+        assert_eq!(unresolved, vec![PathBuf::from("/music/Missing.mp3")]);
+
+        // Only playlists listing a changed path need resolving again
+        let index = PlaylistIndex::default();
+        assert!(index.replace(std::slice::from_ref(&playlist), &ids));
+        assert!(index.listing(&[PathBuf::from("/music/Unlisted.mp3")]).is_empty());
+        assert_eq!(index.listing(&[PathBuf::from("/music/Missing.mp3")]), vec![playlist.clone()]);
+        let mut with_missing = ids.clone();
+        with_missing.insert(PathBuf::from("/music/Missing.mp3"), 4);
+        assert!(index.update(&playlist, &with_missing));
+        assert_eq!(index.get(7).unwrap().file_ids, vec![2, 3, 1, 4]);
+        assert!(!index.update(&playlist, &with_missing));
+        assert!(index.remove(&playlist.path));
+        assert!(index.is_empty());
+    }
+
+    #[test]
+    fn test_file_uri_entries_are_percent_decoded() {
+        let base = Path::new("/music");
+        assert_eq!(
+            resolve_entry(base, "file:///music/My%20Album/01%20%C3%89t%C3%A9.mp3"),
+            Some(PathBuf::from("/music/My Album/01 Été.mp3"))
+        );
+        assert_eq!(resolve_entry(base, "file://localhost/music/a%2"), Some(PathBuf::from("/music/a%2")));
+        // Plain paths are taken literally
+        assert_eq!(resolve_entry(base, "100%25.mp3"), Some(PathBuf::from("/music/100%25.mp3")));
+    }
+
+    #[test]
+    fn test_pls_entries_follow_file_numbers() {
+        let content = "[playlist]\nNumberOfEntries=3\nFile2=b.mp3\nTitle2=B\nFile10=c.mp3\nFile1=sub\\a.mp3\nVersion=2\n";
+        let playlist = parse_playlist(Path::new("/music/Mix.PLS"), content);
+        assert_eq!(playlist.title, "Mix");
+        assert_eq!(
+            playlist.entries,
+            vec![
+                Path::new("/music").join(if cfg!(windows) { "sub\\a.mp3" } else { "sub/a.mp3" }),
+                PathBuf::from("/music/b.mp3"),
+                PathBuf::from("/music/c.mp3"),
+            ]
+        );
+        assert!(is_playlist_file(Path::new("list.M3U8")));
+        assert!(!is_playlist_file(Path::new("song.mp3")));
+    }
+}
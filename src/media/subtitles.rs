@@ -0,0 +1,153 @@
+//! External subtitle files stored next to videos.
+//!
+//! A subtitle file is a sidecar of a video when it shares the video's file
+//! stem, optionally followed by a language suffix: `movie.srt` and
+//! `movie.en.srt` both belong to `movie.mkv`. Sidecars are recorded while
+//! scanning and advertised in the video's DIDL-Lite so renderers can load them.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tracing::debug;
+
+use crate::database::Subtitle;
+
+/// MIME type served for a subtitle file extension, or `None` for other files
+pub fn subtitle_mime_type(extension: &str) -> Option<&'static str> {
+    match extension.to_ascii_lowercase().as_str() {
+        "srt" => Some("text/srt"),
+        "ass" | "ssa" => Some("text/x-ssa"),
+        "vtt" => Some("text/vtt"),
+        _ => None,
+    }
+}
+
+/// Language suffix of `subtitle_stem` when it names a sidecar of a video with
+/// stem `video_stem`: `Some(None)` for an exact match, `Some(Some("en"))` for
+/// `<stem>.en`, and `None` when the file belongs to another video
+fn sidecar_language(video_stem: &str, subtitle_stem: &str) -> Option<Option<String>> {
+    if subtitle_stem.eq_ignore_ascii_case(video_stem) {
+        return Some(None);
+    }
+    let prefix = subtitle_stem.get(..video_stem.len())?;
+    let suffix = subtitle_stem[video_stem.len()..].strip_prefix('.')?;
+    (prefix.eq_ignore_ascii_case(video_stem) && !suffix.is_empty()).then(|| Some(suffix.to_string()))
+}
+
+/// Sidecars of `video` among `candidates`, the subtitle files of its directory
+pub fn find_subtitle_sidecars(video: &Path, media_file_id: i64, candidates: &[PathBuf]) -> Vec<Subtitle> {
+    let Some(video_stem) = video.file_stem().and_then(|stem| stem.to_str()) else {
+        return Vec::new();
+    };
+    let mut sidecars: Vec<Subtitle> = candidates
+        .iter()
+        .filter(|candidate| candidate.parent() == video.parent())
+        .filter_map(|candidate| {
+            let mime_type = subtitle_mime_type(candidate.extension()?.to_str()?)?;
+            let language = sidecar_language(video_stem, candidate.file_stem()?.to_str()?)?;
+            Some(Subtitle {
+                id: None,
+                media_file_id,
+                path: candidate.clone(),
+                language,
+                mime_type: mime_type.to_string(),
+            })
+        })
+        .collect();
+    sidecars.sort_by(|a, b| a.path.cmp(&b.path));
+    sidecars
+}
+
+/// Subtitle files directly inside `directory`; an unreadable directory has none
+pub async fn list_subtitle_files(directory: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut entries = match tokio::fs::read_dir(directory).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            debug!("Cannot list subtitles in {}: {}", directory.display(), e);
+            return files;
+        }
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        let is_subtitle = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| subtitle_mime_type(ext).is_some());
+        if is_subtitle && entry.file_type().await.is_ok_and(|t| t.is_file()) {
+            files.push(path);
+        }
+    }
+    files
+}
+
+/// Recorded sidecars keyed by video id, so DIDL-Lite can be generated
+/// without a database round-trip per item
+#[derive(Clone, Default)]
+pub struct SubtitleIndex {
+    by_file: Arc<RwLock<HashMap<i64, Vec<Subtitle>>>>,
+}
+
+impl SubtitleIndex {
+    /// Replace the index with `subtitles`
+    pub fn replace(&self, subtitles: Vec<Subtitle>) {
+        let mut by_file: HashMap<i64, Vec<Subtitle>> = HashMap::new();
+        for subtitle in subtitles {
+            by_file.entry(subtitle.media_file_id).or_default().push(subtitle);
+        }
+        *self.by_file.write().unwrap() = by_file;
+    }
+
+    /// Sidecars of the video `media_file_id`
+    pub fn for_file(&self, media_file_id: i64) -> Vec<Subtitle> {
+        self.by_file.read().unwrap().get(&media_file_id).cloned().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sidecars(video: &str, candidates: &[&str]) -> Vec<(String, Option<String>)> {
+        let candidates: Vec<PathBuf> = candidates.iter().map(PathBuf::from).collect();
+        find_subtitle_sidecars(Path::new(video), 1, &candidates)
+            .into_iter()
+            .map(|s| (s.path.to_string_lossy().to_string(), s.language))
+            .collect()
+    }
+
+    #[test]
+    fn test_sidecars_match_stem_and_language_suffix() {
+        let found = sidecars(
+            "/videos/Movie.mkv",
+            &[
+                "/videos/Movie.srt",
+                "/videos/Movie.en.srt",
+                "/videos/movie.FR.ass",
+                "/videos/Movie2.srt",
+                "/videos/Movie.nfo",
+                "/videos/Movie..srt",
+                "/other/Movie.de.srt",
+            ],
+        );
+        assert_eq!(
+            found,
+            vec![
+                ("/videos/Movie.en.srt".to_string(), Some("en".to_string())),
+                ("/videos/Movie.srt".to_string(), None),
+                ("/videos/movie.FR.ass".to_string(), Some("FR".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dotted_video_names_keep_their_stem() {
+        let found = sidecars(
+            "/videos/Show.S01E02.mp4",
+            &["/videos/Show.S01E02.en.vtt", "/videos/Show.S01E03.en.vtt", "/videos/Show.srt"],
+        );
+        assert_eq!(found, vec![("/videos/Show.S01E02.en.vtt".to_string(), Some("en".to_string()))]);
+        assert_eq!(subtitle_mime_type("VTT"), Some("text/vtt"));
+        assert_eq!(subtitle_mime_type("idx"), None);
+    }
+}
@@ -0,0 +1,236 @@
+//! On-demand JPEG thumbnails for image and video files, cached on disk.
+//!
+//! Thumbnails are rendered by `ffmpeg` (a scaled copy for images, a
+//! representative frame for videos) and stored under the platform cache
+//! directory, named after the source path and its modification time so a
+//! changed source gets a fresh thumbnail. Renders that run past
+//! [`THUMBNAIL_TIMEOUT`] are abandoned, and images larger than
+//! [`MAX_IMAGE_SOURCE_BYTES`] are not decoded at all.
+
+use futures_util::future::{BoxFuture, FutureExt};
+use sha2::{Digest, Sha256};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::process::Command;
+use tracing::debug;
+
+use crate::config::AppConfig;
+use crate::database::MediaFile;
+
+/// Width of generated thumbnails; the height keeps the aspect ratio
+pub const THUMBNAIL_WIDTH: u32 = 320;
+
+/// Longest a thumbnail may take to render; the renderer is killed after that
+pub const THUMBNAIL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Largest image decoded for a thumbnail, since decoding needs memory in
+/// proportion to the pixel count. Videos are sampled, so their size does not matter.
+pub const MAX_IMAGE_SOURCE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Renders a thumbnail of `source` into `destination`; the flag is set for video sources
+pub type ThumbnailGenerator = Arc<dyn Fn(PathBuf, PathBuf, bool) -> BoxFuture<'static, io::Result<()>> + Send + Sync>;
+
+/// Whether thumbnails can be produced for a file of this MIME type
+pub fn supports(mime_type: &str) -> bool {
+    mime_type.starts_with("image/") || mime_type.starts_with("video/")
+}
+
+/// Render a thumbnail with `ffmpeg`, which must be on the PATH. The process is
+/// killed if the future is dropped, e.g. on timeout.
+async fn ffmpeg_thumbnail(source: &Path, destination: &Path, is_video: bool) -> io::Result<()> {
+    let filter = if is_video {
+        // Pick a representative frame rather than a black first frame
+        format!("thumbnail,scale={}:-2", THUMBNAIL_WIDTH)
+    } else {
+        format!("scale='min({},iw)':-2", THUMBNAIL_WIDTH)
+    };
+    let output = Command::new("ffmpeg")
+        .args(["-v", "error", "-y", "-i"])
+        .arg(source)
+        .args(["-vf", &filter, "-frames:v", "1", "-f", "image2", "-c:v", "mjpeg"])
+        .arg(destination)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "ffmpeg failed for {}: {}",
+            source.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+/// Disk cache of generated thumbnails
+#[derive(Clone)]
+pub struct ThumbnailCache {
+    directory: PathBuf,
+    generator: ThumbnailGenerator,
+}
+
+impl Default for ThumbnailCache {
+    fn default() -> Self {
+        Self::new(AppConfig::get_platform_cache_dir().join("thumbnails"))
+    }
+}
+
+impl ThumbnailCache {
+    /// Cache thumbnails in `directory`, created on first use
+    pub fn new(directory: PathBuf) -> Self {
+        Self {
+            directory,
+            generator: Arc::new(|source, destination, is_video| {
+                async move { ffmpeg_thumbnail(&source, &destination, is_video).await }.boxed()
+            }),
+        }
+    }
+
+    /// Replace how thumbnails are rendered
+    pub fn with_generator(mut self, generator: ThumbnailGenerator) -> Self {
+        self.generator = generator;
+        self
+    }
+
+    /// Digest of the source path, shared by every cached version of its thumbnail
+    fn source_key(file: &MediaFile) -> String {
+        hex::encode(Sha256::digest(file.path.to_string_lossy().as_bytes()))
+    }
+
+    /// Cache location of the thumbnail for `file` at its current modification time
+    pub fn cache_path(&self, file: &MediaFile) -> PathBuf {
+        let modified = file
+            .modified
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        self.directory.join(format!("{}-{}.jpg", Self::source_key(file), modified))
+    }
+
+    /// Path of the thumbnail for `file`, generating it when missing or stale
+    pub async fn get_or_generate(&self, file: &MediaFile) -> io::Result<PathBuf> {
+        if !supports(&file.mime_type) {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "no thumbnail for this media type"));
+        }
+        let is_video = file.mime_type.starts_with("video/");
+        if !is_video && file.size > MAX_IMAGE_SOURCE_BYTES {
+            return Err(io::Error::new(io::ErrorKind::FileTooLarge, "image too large to thumbnail"));
+        }
+        let path = self.cache_path(file);
+        if tokio::fs::try_exists(&path).await? {
+            return Ok(path);
+        }
+
+        tokio::fs::create_dir_all(&self.directory).await?;
+        // Render to a private name so concurrent requests never read a partial file
+        let partial = self.directory.join(format!("{}.partial", uuid::Uuid::new_v4()));
+        let render = (self.generator)(file.path.clone(), partial.clone(), is_video);
+        let rendered = tokio::time::timeout(THUMBNAIL_TIMEOUT, render)
+            .await
+            .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::TimedOut, "thumbnail render timed out")));
+        if let Err(e) = rendered {
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(e);
+        }
+        tokio::fs::rename(&partial, &path).await?;
+        debug!("Generated thumbnail for {}", file.path.display());
+
+        self.remove_stale(file, &path).await;
+        Ok(path)
+    }
+
+    /// Delete thumbnails rendered from earlier versions of `file`
+    async fn remove_stale(&self, file: &MediaFile, current: &Path) {
+        let prefix = format!("{}-", Self::source_key(file));
+        let Ok(mut entries) = tokio::fs::read_dir(&self.directory).await else {
+            return;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if path != current && entry.file_name().to_string_lossy().starts_with(&prefix) {
+                let _ = tokio::fs::remove_file(&path).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_thumbnails_are_cached_until_source_changes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let renders = Arc::new(AtomicUsize::new(0));
+        let counter = renders.clone();
+        let cache = ThumbnailCache::new(temp_dir.path().join("thumbs")).with_generator(Arc::new(move |source, destination, is_video| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async move { tokio::fs::write(destination, format!("{}:{}", source.display(), is_video)).await }.boxed()
+        }));
+
+        let mut clip = MediaFile::new(PathBuf::from("/videos/clip.mp4"), 1024, "video/mp4".to_string());
+        let first = cache.get_or_generate(&clip).await.unwrap();
+        assert_eq!(cache.get_or_generate(&clip).await.unwrap(), first);
+        assert_eq!(renders.load(Ordering::SeqCst), 1);
+        assert_eq!(std::fs::read_to_string(&first).unwrap(), "/videos/clip.mp4:true");
+
+        // A newer source replaces the old thumbnail
+        clip.modified += Duration::from_secs(60);
+        let second = cache.get_or_generate(&clip).await.unwrap();
+        assert_ne!(second, first);
+        assert_eq!(renders.load(Ordering::SeqCst), 2);
+        assert!(!first.exists());
+        assert!(second.exists());
+
+        let song = MediaFile::new(PathBuf::from("/music/song.mp3"), 1024, "audio/mpeg".to_string());
+        assert!(cache.get_or_generate(&song).await.is_err());
+        assert_eq!(renders.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failed_render_leaves_no_cache_entry() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let directory = temp_dir.path().join("thumbs");
+        let cache = ThumbnailCache::new(directory.clone()).with_generator(Arc::new(|_, destination, _| {
+            async move {
+                tokio::fs::write(destination, b"partial").await?;
+                Err(io::Error::other("decoder failed"))
+            }
+            .boxed()
+        }));
+
+        let photo = MediaFile::new(PathBuf::from("/photos/a.jpg"), 1024, "image/jpeg".to_string());
+        assert!(cache.get_or_generate(&photo).await.is_err());
+        assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_slow_renders_time_out_and_large_images_are_skipped() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let directory = temp_dir.path().join("thumbs");
+        let renders = Arc::new(AtomicUsize::new(0));
+        let counter = renders.clone();
+        let cache = ThumbnailCache::new(directory.clone()).with_generator(Arc::new(move |_, destination, _| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                tokio::fs::write(destination, b"partial").await?;
+                std::future::pending().await
+            }
+            .boxed()
+        }));
+
+        let clip = MediaFile::new(PathBuf::from("/videos/clip.mkv"), 1024, "video/x-matroska".to_string());
+        let timed_out = cache.get_or_generate(&clip).await.unwrap_err();
+        assert_eq!(timed_out.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 0);
+
+        let poster = MediaFile::new(PathBuf::from("/photos/poster.png"), MAX_IMAGE_SOURCE_BYTES + 1, "image/png".to_string());
+        assert_eq!(cache.get_or_generate(&poster).await.unwrap_err().kind(), io::ErrorKind::FileTooLarge);
+        assert_eq!(renders.load(Ordering::SeqCst), 1);
+    }
+}
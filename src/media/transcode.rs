@@ -0,0 +1,288 @@
+//! On-the-fly transcoding of videos that renderers cannot play as stored.
+//!
+//! Incompatible videos are piped through `ffmpeg`, which converts them to
+//! H.264/AAC in the configured container and writes the result to stdout. The
+//! output is streamed to the client as it is produced, and the `ffmpeg`
+//! process is killed as soon as the stream is dropped, e.g. when the client
+//! disconnects mid-stream. At most `transcoding.max_concurrent` processes run
+//! at once.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use axum::body::Bytes;
+use futures_util::Stream;
+use tokio::process::{Child, ChildStdout, Command};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio_util::io::ReaderStream;
+use tracing::{debug, warn};
+
+use crate::config::{TranscodeTarget, TranscodingConfig};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Streams incompatible videos through `ffmpeg`
+#[derive(Debug, Clone)]
+pub struct Transcoder {
+    ffmpeg: PathBuf,
+    target: TranscodeTarget,
+    compatible_formats: Vec<String>,
+    /// One permit per `ffmpeg` process allowed to run
+    permits: Arc<Semaphore>,
+}
+
+impl Transcoder {
+    pub fn new(ffmpeg: PathBuf, config: &TranscodingConfig) -> Self {
+        Self {
+            ffmpeg,
+            target: config.target,
+            compatible_formats: config.compatible_formats.clone(),
+            permits: Arc::new(Semaphore::new(config.max_concurrent)),
+        }
+    }
+
+    /// Create a transcoder when transcoding is enabled and an `ffmpeg` binary is found
+    pub fn detect(config: &TranscodingConfig) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+        match find_ffmpeg(config.ffmpeg_path.as_deref()) {
+            Some(ffmpeg) => {
+                debug!("Transcoding with {}", ffmpeg.display());
+                Some(Self::new(ffmpeg, config))
+            }
+            None => {
+                warn!("Transcoding is enabled but no ffmpeg binary was found; serving files as stored");
+                None
+            }
+        }
+    }
+
+    /// Whether files of this MIME type are transcoded rather than served as stored
+    pub fn needs_transcoding(&self, mime_type: &str) -> bool {
+        mime_type.starts_with("video/")
+            && !self
+                .compatible_formats
+                .iter()
+                .any(|format| format.eq_ignore_ascii_case(mime_type))
+    }
+
+    /// MIME type of the transcoded stream
+    pub fn mime_type(&self) -> &'static str {
+        match self.target {
+            TranscodeTarget::MpegTs => "video/mpeg",
+            TranscodeTarget::Mp4 => "video/mp4",
+        }
+    }
+
+    /// DLNA media profile of the transcoded stream
+    pub fn dlna_profile(&self) -> &'static str {
+        match self.target {
+            TranscodeTarget::MpegTs => "AVC_TS_MP_HD_AAC_MULT5_ISO",
+            TranscodeTarget::Mp4 => "AVC_MP4_HP_HD_AAC",
+        }
+    }
+
+    fn arguments(&self, source: &Path) -> Vec<std::ffi::OsString> {
+        let mut args: Vec<std::ffi::OsString> = ["-v", "error", "-nostdin", "-i"].map(Into::into).to_vec();
+        args.push(source.into());
+        args.extend(
+            [
+                "-map", "0:v:0", "-map", "0:a:0?", "-c:v", "libx264", "-preset", "veryfast", "-pix_fmt", "yuv420p",
+                "-c:a", "aac", "-ac", "2",
+            ]
+            .map(Into::into),
+        );
+        let container: &[&str] = match self.target {
+            TranscodeTarget::MpegTs => &["-f", "mpegts"],
+            // Plain MP4 needs a seekable output; fragments can be written to a pipe
+            TranscodeTarget::Mp4 => &["-movflags", "frag_keyframe+empty_moov", "-f", "mp4"],
+        };
+        args.extend(container.iter().map(Into::into));
+        args.push("pipe:1".into());
+        args
+    }
+
+    /// Start transcoding `source`, returning the output as a stream of chunks.
+    /// Fails with [`io::ErrorKind::ResourceBusy`] while the configured number of
+    /// transcodes is already running.
+    pub fn stream(&self, source: &Path) -> io::Result<TranscodeStream> {
+        let permit = self
+            .permits
+            .clone()
+            .try_acquire_owned()
+            .map_err(|_| io::Error::new(io::ErrorKind::ResourceBusy, "every transcoder is busy"))?;
+        let mut child = Command::new(&self.ffmpeg)
+            .args(self.arguments(source))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdout = child.stdout.take().ok_or_else(|| io::Error::other("ffmpeg stdout was not captured"))?;
+        debug!("Transcoding {} (pid {:?})", source.display(), child.id());
+        Ok(TranscodeStream {
+            output: ReaderStream::with_capacity(stdout, CHUNK_SIZE),
+            process: Some((child, permit)),
+        })
+    }
+}
+
+/// Locate `ffmpeg` at the configured path, or on the PATH
+pub fn find_ffmpeg(configured: Option<&str>) -> Option<PathBuf> {
+    if let Some(path) = configured {
+        let path = PathBuf::from(path);
+        return path.is_file().then_some(path);
+    }
+    let name = if cfg!(windows) { "ffmpeg.exe" } else { "ffmpeg" };
+    std::env::var_os("PATH")
+        .iter()
+        .flat_map(std::env::split_paths)
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Output of a running transcode
+pub struct TranscodeStream {
+    output: ReaderStream<ChildStdout>,
+    /// The process and the permit it holds until it has been reaped
+    process: Option<(Child, OwnedSemaphorePermit)>,
+}
+
+impl TranscodeStream {
+    /// Process id of the transcoder, while it has not been reaped
+    pub fn pid(&self) -> Option<u32> {
+        self.process.as_ref().and_then(|(child, _)| child.id())
+    }
+}
+
+impl Stream for TranscodeStream {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.output).poll_next(cx)
+    }
+}
+
+impl Drop for TranscodeStream {
+    /// Kill the process if it is still running and reap it in the background,
+    /// releasing its permit once it has exited
+    fn drop(&mut self) {
+        let Some((mut child, permit)) = self.process.take() else {
+            return;
+        };
+        // Without a runtime, kill_on_drop still kills the process as `child` is dropped
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(async move {
+                if let Ok(None) = child.try_wait() {
+                    debug!("Stopping transcoder (pid {:?})", child.id());
+                    let _ = child.start_kill();
+                }
+                let _ = child.wait().await;
+                drop(permit);
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+
+    fn transcoder(compatible: &[&str]) -> Transcoder {
+        let config = TranscodingConfig {
+            enabled: true,
+            compatible_formats: compatible.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        Transcoder::new(PathBuf::from("ffmpeg"), &config)
+    }
+
+    #[test]
+    fn test_only_incompatible_videos_are_transcoded() {
+        let transcoder = transcoder(&["video/mp4"]);
+        assert!(transcoder.needs_transcoding("video/x-matroska"));
+        assert!(!transcoder.needs_transcoding("video/MP4"));
+        assert!(!transcoder.needs_transcoding("audio/flac"));
+        assert_eq!(transcoder.mime_type(), "video/mpeg");
+    }
+
+    #[test]
+    fn test_detect_requires_enabled_flag_and_binary() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let ffmpeg = temp_dir.path().join("ffmpeg");
+        std::fs::write(&ffmpeg, b"").unwrap();
+
+        let mut config = TranscodingConfig {
+            ffmpeg_path: Some(ffmpeg.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        assert!(Transcoder::detect(&config).is_none());
+        config.enabled = true;
+        assert_eq!(Transcoder::detect(&config).unwrap().ffmpeg, ffmpeg);
+        config.ffmpeg_path = Some(temp_dir.path().join("missing").to_string_lossy().to_string());
+        assert!(Transcoder::detect(&config).is_none());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_dropping_stream_kills_transcoder() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let script = temp_dir.path().join("ffmpeg");
+        // Stands in for an endless transcode
+        std::fs::write(&script, "#!/bin/sh\nexec yes transcoded\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let transcoder = Transcoder::new(script, &TranscodingConfig::default());
+
+        let mut stream = transcoder.stream(Path::new("/videos/clip.mkv")).unwrap();
+        let chunk = stream.next().await.unwrap().unwrap();
+        assert!(chunk.starts_with(b"transcoded\n"));
+
+        let proc_entry = PathBuf::from(format!("/proc/{}", stream.pid().unwrap()));
+        drop(stream);
+        // The process is reaped in the background
+        for _ in 0..200 {
+            if !proc_entry.exists() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(!proc_entry.exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_concurrent_transcodes_are_capped() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let script = temp_dir.path().join("ffmpeg");
+        std::fs::write(&script, "#!/bin/sh\nexec sleep 10\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let config = TranscodingConfig { max_concurrent: 1, ..Default::default() };
+        let transcoder = Transcoder::new(script, &config);
+
+        let running = transcoder.stream(Path::new("/videos/a.mkv")).unwrap();
+        let busy = transcoder.stream(Path::new("/videos/b.mkv")).err().unwrap();
+        assert_eq!(busy.kind(), io::ErrorKind::ResourceBusy);
+
+        // The permit returns once the killed process has been reaped
+        drop(running);
+        let mut next = transcoder.stream(Path::new("/videos/c.mkv"));
+        for _ in 0..200 {
+            if next.is_ok() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            next = transcoder.stream(Path::new("/videos/c.mkv"));
+        }
+        assert!(next.is_ok());
+    }
+}
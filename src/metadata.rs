@@ -0,0 +1,994 @@
+// src/metadata.rs
+//! Lightweight probing of media containers for duration, resolution and
+//! descriptive tags.
+//!
+//! Only the structures needed for DIDL-Lite are read: MP4 `mvhd`/`tkhd`/`ilst`,
+//! Matroska `Info` and video `Tracks`, MP3 ID3v2 tags with Xing/Info or
+//! constant bitrate frames, FLAC `STREAMINFO`/`VORBIS_COMMENT` blocks, and the
+//! image headers of JPEG, PNG and GIF files. Cover art embedded in ID3 `APIC`
+//! frames, FLAC `PICTURE` blocks and MP4 `covr` items is picked up as well.
+//! Anything else, or any parse failure, yields no metadata rather than an error.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::database::MediaFile;
+
+/// Largest tag or metadata block read into memory
+const MAX_BLOCK_BYTES: u64 = 1024 * 1024;
+
+/// Duration, resolution and tags read from a media file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MediaMetadata {
+    pub duration: Option<Duration>,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub cover_art: Option<CoverArt>,
+}
+
+/// A picture embedded in an audio file's tags
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoverArt {
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+impl CoverArt {
+    /// Keep JPEG and PNG pictures, the formats renderers display; the MIME type
+    /// comes from the image signature since tags often mislabel it
+    fn from_data(data: Vec<u8>) -> Option<Self> {
+        let mime_type = if data.starts_with(b"\x89PNG") {
+            "image/png"
+        } else if data.starts_with(&[0xFF, 0xD8]) {
+            "image/jpeg"
+        } else {
+            return None;
+        };
+        Some(Self { mime_type: mime_type.to_string(), data })
+    }
+}
+
+impl MediaMetadata {
+    fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Copy the probed fields onto `file`, keeping its values for fields that were not found.
+    /// Cover art is not part of the media record and is returned instead.
+    pub fn apply_to(self, file: &mut MediaFile) -> Option<CoverArt> {
+        file.duration = self.duration.or(file.duration);
+        file.title = self.title.or(file.title.take());
+        file.artist = self.artist.or(file.artist.take());
+        file.album = self.album.or(file.album.take());
+        file.width = self.width.or(file.width);
+        file.height = self.height.or(file.height);
+        self.cover_art
+    }
+
+    /// Record a picture size, ignoring zero dimensions (audio tracks, unset fields)
+    fn set_resolution(&mut self, width: u64, height: u64) {
+        if let (Ok(width @ 1..), Ok(height @ 1..)) = (u32::try_from(width), u32::try_from(height)) {
+            self.width = Some(width);
+            self.height = Some(height);
+        }
+    }
+}
+
+/// Probe a media file by extension. Returns `None` for unsupported or unreadable files.
+pub fn probe_file(path: &Path) -> Option<MediaMetadata> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    let file = File::open(path).ok()?;
+    let len = file.metadata().ok()?.len();
+    let mut reader = BufReader::new(file);
+
+    let metadata = match extension.as_str() {
+        "mp4" | "m4v" | "m4a" | "mov" => probe_mp4(&mut reader, len),
+        "mkv" | "mka" | "webm" => probe_matroska(&mut reader, len),
+        "mp3" => probe_mp3(&mut reader, len),
+        "flac" => probe_flac(&mut reader),
+        "jpg" | "jpeg" => probe_jpeg(&mut reader),
+        "png" => probe_png(&mut reader),
+        "gif" => probe_gif(&mut reader),
+        _ => return None,
+    };
+    match metadata {
+        Ok(metadata) if !metadata.is_empty() => Some(metadata),
+        Ok(_) => None,
+        Err(e) => {
+            tracing::debug!("Could not read metadata from {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Probe `file` off the async runtime and fill in whatever metadata was found,
+/// returning its embedded cover art
+pub async fn probe_media_file(file: &mut MediaFile) -> Option<CoverArt> {
+    let path = file.path.clone();
+    match tokio::task::spawn_blocking(move || probe_file(&path)).await {
+        Ok(Some(metadata)) => metadata.apply_to(file),
+        _ => None,
+    }
+}
+
+/// Width and height of an in-memory PNG or JPEG image, recognized by its signature
+pub fn image_resolution(data: &[u8]) -> Option<(u32, u32)> {
+    let mut reader = io::Cursor::new(data);
+    let metadata = if data.starts_with(b"\x89PNG") {
+        probe_png(&mut reader)
+    } else if data.starts_with(&[0xFF, 0xD8]) {
+        probe_jpeg(&mut reader)
+    } else {
+        return None;
+    };
+    let metadata = metadata.ok()?;
+    metadata.width.zip(metadata.height)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn read_exact_vec<R: Read>(reader: &mut R, len: u64) -> io::Result<Vec<u8>> {
+    if len > MAX_BLOCK_BYTES {
+        return Err(invalid("metadata block too large"));
+    }
+    let mut buf = vec![0u8; len as usize];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Duration of `secs` seconds read from a file header, or `None` when a corrupt
+/// header yields a negative, NaN or out of range value
+fn duration_from_secs(secs: f64) -> Option<Duration> {
+    Duration::try_from_secs_f64(secs).ok()
+}
+
+fn non_empty(text: String) -> Option<String> {
+    let text = text.trim_matches(char::from(0)).trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+// ---------------------------------------------------------------------------
+// MP4 / QuickTime
+
+/// Child boxes in `[start, end)` as (type, body start, body end)
+fn mp4_boxes<R: Read + Seek>(reader: &mut R, start: u64, end: u64) -> io::Result<Vec<([u8; 4], u64, u64)>> {
+    let mut boxes = Vec::new();
+    let mut pos = start;
+    while pos + 8 <= end {
+        reader.seek(SeekFrom::Start(pos))?;
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header)?;
+        let size = u32::from_be_bytes(header[0..4].try_into().unwrap()) as u64;
+        let kind: [u8; 4] = header[4..8].try_into().unwrap();
+        let (body_start, box_end) = match size {
+            0 => (pos + 8, end),
+            1 => {
+                let mut large = [0u8; 8];
+                reader.read_exact(&mut large)?;
+                (pos + 16, pos + u64::from_be_bytes(large))
+            }
+            size => (pos + 8, pos + size),
+        };
+        if box_end < body_start || box_end > end {
+            break;
+        }
+        boxes.push((kind, body_start, box_end));
+        pos = box_end;
+    }
+    Ok(boxes)
+}
+
+fn probe_mp4<R: Read + Seek>(reader: &mut R, len: u64) -> io::Result<MediaMetadata> {
+    let mut metadata = MediaMetadata::default();
+    let Some((_, moov_start, moov_end)) = mp4_boxes(reader, 0, len)?.into_iter().find(|b| &b.0 == b"moov") else {
+        return Ok(metadata);
+    };
+
+    for (kind, start, end) in mp4_boxes(reader, moov_start, moov_end)? {
+        match &kind {
+            b"mvhd" => {
+                reader.seek(SeekFrom::Start(start))?;
+                let body = read_exact_vec(reader, (end - start).min(32))?;
+                let (timescale, duration) = match body.first() {
+                    Some(1) if body.len() >= 32 => (
+                        u32::from_be_bytes(body[20..24].try_into().unwrap()) as u64,
+                        u64::from_be_bytes(body[24..32].try_into().unwrap()),
+                    ),
+                    Some(0) if body.len() >= 20 => (
+                        u32::from_be_bytes(body[12..16].try_into().unwrap()) as u64,
+                        u32::from_be_bytes(body[16..20].try_into().unwrap()) as u64,
+                    ),
+                    _ => continue,
+                };
+                if timescale > 0 && duration > 0 && duration != u32::MAX as u64 && duration != u64::MAX {
+                    metadata.duration = duration_from_secs(duration as f64 / timescale as f64);
+                }
+            }
+            b"trak" if metadata.width.is_none() => probe_mp4_track_size(reader, start, end, &mut metadata)?,
+            b"udta" => probe_mp4_tags(reader, start, end, &mut metadata)?,
+            _ => {}
+        }
+    }
+    Ok(metadata)
+}
+
+/// Read the presentation size of a track from its `tkhd`; audio tracks have none
+fn probe_mp4_track_size<R: Read + Seek>(reader: &mut R, start: u64, end: u64, metadata: &mut MediaMetadata) -> io::Result<()> {
+    let Some((_, tkhd_start, tkhd_end)) = mp4_boxes(reader, start, end)?.into_iter().find(|b| &b.0 == b"tkhd") else {
+        return Ok(());
+    };
+    reader.seek(SeekFrom::Start(tkhd_start))?;
+    let body = read_exact_vec(reader, (tkhd_end - tkhd_start).min(96))?;
+    // Width and height close the box as 16.16 fixed point numbers
+    let size_at = match body.first() {
+        Some(1) => 88,
+        Some(0) => 76,
+        _ => return Ok(()),
+    };
+    if body.len() >= size_at + 8 {
+        let width = u32::from_be_bytes(body[size_at..size_at + 4].try_into().unwrap()) >> 16;
+        let height = u32::from_be_bytes(body[size_at + 4..size_at + 8].try_into().unwrap()) >> 16;
+        metadata.set_resolution(width as u64, height as u64);
+    }
+    Ok(())
+}
+
+/// Read `©nam`, `©ART`, `©alb` and `covr` from `udta/meta/ilst`
+fn probe_mp4_tags<R: Read + Seek>(reader: &mut R, start: u64, end: u64, metadata: &mut MediaMetadata) -> io::Result<()> {
+    let Some((_, meta_start, meta_end)) = mp4_boxes(reader, start, end)?.into_iter().find(|b| &b.0 == b"meta") else {
+        return Ok(());
+    };
+    // `meta` is a full box: version and flags precede its children
+    let Some((_, ilst_start, ilst_end)) = mp4_boxes(reader, meta_start + 4, meta_end)?
+        .into_iter()
+        .find(|b| &b.0 == b"ilst")
+    else {
+        return Ok(());
+    };
+
+    for (kind, item_start, item_end) in mp4_boxes(reader, ilst_start, ilst_end)? {
+        if !matches!(&kind, b"\xA9nam" | b"\xA9ART" | b"\xA9alb" | b"covr") {
+            continue;
+        }
+        let Some((_, data_start, data_end)) = mp4_boxes(reader, item_start, item_end)?
+            .into_iter()
+            .find(|b| &b.0 == b"data")
+        else {
+            continue;
+        };
+        // Skip the type indicator and locale
+        if data_end < data_start + 8 {
+            continue;
+        }
+        reader.seek(SeekFrom::Start(data_start + 8))?;
+        let value = read_exact_vec(reader, data_end - data_start - 8)?;
+        match &kind {
+            b"\xA9nam" => metadata.title = non_empty(String::from_utf8_lossy(&value).to_string()),
+            b"\xA9ART" => metadata.artist = non_empty(String::from_utf8_lossy(&value).to_string()),
+            b"\xA9alb" => metadata.album = non_empty(String::from_utf8_lossy(&value).to_string()),
+            _ => metadata.cover_art = metadata.cover_art.take().or_else(|| CoverArt::from_data(value)),
+        }
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Matroska / WebM
+
+const EBML_SEGMENT: u64 = 0x1853_8067;
+const EBML_INFO: u64 = 0x1549_A966;
+const EBML_CLUSTER: u64 = 0x1F43_B675;
+const EBML_TIMESTAMP_SCALE: u64 = 0x2A_D7B1;
+const EBML_DURATION: u64 = 0x4489;
+const EBML_TITLE: u64 = 0x7BA9;
+const EBML_TRACKS: u64 = 0x1654_AE6B;
+const EBML_TRACK_ENTRY: u64 = 0xAE;
+const EBML_VIDEO: u64 = 0xE0;
+const EBML_PIXEL_WIDTH: u64 = 0xB0;
+const EBML_PIXEL_HEIGHT: u64 = 0xBA;
+
+/// Read an EBML variable-length integer, returning its value (marker kept for
+/// ids, stripped for sizes) and whether a size was "unknown" (all ones)
+fn read_vint<R: Read>(reader: &mut R, keep_marker: bool) -> io::Result<(u64, bool)> {
+    let mut first = [0u8; 1];
+    reader.read_exact(&mut first)?;
+    let len = first[0].leading_zeros() as usize + 1;
+    if len > 8 {
+        return Err(invalid("invalid EBML length"));
+    }
+    let mut value = if keep_marker {
+        first[0] as u64
+    } else {
+        (first[0] & (0xFF >> len)) as u64
+    };
+    let mut all_ones = value == (0xFF >> len) as u64;
+    for _ in 1..len {
+        let mut byte = [0u8; 1];
+        reader.read_exact(&mut byte)?;
+        value = (value << 8) | byte[0] as u64;
+        all_ones &= byte[0] == 0xFF;
+    }
+    Ok((value, !keep_marker && all_ones))
+}
+
+/// Read an element header, returning id, body start and body end (clamped to `limit`)
+fn ebml_element<R: Read + Seek>(reader: &mut R, limit: u64) -> io::Result<(u64, u64, u64)> {
+    let (id, _) = read_vint(reader, true)?;
+    let (size, unknown) = read_vint(reader, false)?;
+    let start = reader.stream_position()?;
+    let end = if unknown { limit } else { start.saturating_add(size).min(limit) };
+    Ok((id, start, end))
+}
+
+/// Children of the element body `[start, end)` as (id, body start, body end)
+fn ebml_children<R: Read + Seek>(reader: &mut R, start: u64, end: u64) -> io::Result<Vec<(u64, u64, u64)>> {
+    let mut children = Vec::new();
+    let mut pos = start;
+    while pos < end {
+        reader.seek(SeekFrom::Start(pos))?;
+        let child = ebml_element(reader, end)?;
+        children.push(child);
+        pos = child.2;
+    }
+    Ok(children)
+}
+
+fn ebml_uint<R: Read + Seek>(reader: &mut R, start: u64, end: u64) -> io::Result<u64> {
+    reader.seek(SeekFrom::Start(start))?;
+    let body = read_exact_vec(reader, (end - start).min(8))?;
+    Ok(body.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64))
+}
+
+/// Read the pixel size of the first video track in `Tracks`
+fn probe_matroska_tracks<R: Read + Seek>(reader: &mut R, start: u64, end: u64, metadata: &mut MediaMetadata) -> io::Result<()> {
+    for (_, entry_start, entry_end) in ebml_children(reader, start, end)?.into_iter().filter(|e| e.0 == EBML_TRACK_ENTRY) {
+        for (_, video_start, video_end) in ebml_children(reader, entry_start, entry_end)?.into_iter().filter(|e| e.0 == EBML_VIDEO) {
+            let (mut width, mut height) = (0, 0);
+            for (id, body_start, body_end) in ebml_children(reader, video_start, video_end)? {
+                match id {
+                    EBML_PIXEL_WIDTH => width = ebml_uint(reader, body_start, body_end)?,
+                    EBML_PIXEL_HEIGHT => height = ebml_uint(reader, body_start, body_end)?,
+                    _ => {}
+                }
+            }
+            metadata.set_resolution(width, height);
+            if metadata.width.is_some() {
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn probe_matroska<R: Read + Seek>(reader: &mut R, len: u64) -> io::Result<MediaMetadata> {
+    let mut metadata = MediaMetadata::default();
+
+    // Skip the EBML header and find the segment
+    let mut pos = 0;
+    let (segment_start, segment_end) = loop {
+        if pos >= len {
+            return Ok(metadata);
+        }
+        reader.seek(SeekFrom::Start(pos))?;
+        let (id, start, end) = ebml_element(reader, len)?;
+        if id == EBML_SEGMENT {
+            break (start, end);
+        }
+        pos = end;
+    };
+
+    let mut pos = segment_start;
+    while pos < segment_end {
+        reader.seek(SeekFrom::Start(pos))?;
+        // Keep what was read when a truncated or damaged element follows
+        let Ok((id, start, end)) = ebml_element(reader, segment_end) else {
+            break;
+        };
+        match id {
+            EBML_INFO => {
+                let mut scale = 1_000_000u64;
+                let mut duration = None;
+                let mut child = start;
+                while child < end {
+                    reader.seek(SeekFrom::Start(child))?;
+                    let (id, body_start, body_end) = ebml_element(reader, end)?;
+                    let body = read_exact_vec(reader, body_end - body_start)?;
+                    match id {
+                        EBML_TIMESTAMP_SCALE => scale = body.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64),
+                        EBML_DURATION => {
+                            duration = match body.len() {
+                                4 => Some(f32::from_be_bytes(body[..].try_into().unwrap()) as f64),
+                                8 => Some(f64::from_be_bytes(body[..].try_into().unwrap())),
+                                _ => None,
+                            }
+                        }
+                        EBML_TITLE => metadata.title = non_empty(String::from_utf8_lossy(&body).to_string()),
+                        _ => {}
+                    }
+                    child = body_end;
+                }
+                if let Some(ticks) = duration.filter(|d| d.is_finite() && *d > 0.0) {
+                    metadata.duration = duration_from_secs(ticks * scale as f64 / 1e9);
+                }
+                pos = end;
+            }
+            EBML_TRACKS => {
+                probe_matroska_tracks(reader, start, end, &mut metadata)?;
+                pos = end;
+            }
+            // Media data follows the headers, so anything not yet seen is missing
+            EBML_CLUSTER => break,
+            _ => pos = end,
+        }
+    }
+    Ok(metadata)
+}
+
+// ---------------------------------------------------------------------------
+// MP3
+
+fn syncsafe(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0u64, |acc, b| (acc << 7) | (*b & 0x7F) as u64)
+}
+
+/// Decode an ID3v2 text frame body
+fn id3_text(body: &[u8]) -> Option<String> {
+    let (encoding, text) = body.split_first()?;
+    let decoded = match encoding {
+        0 => text.iter().map(|b| *b as char).collect(),
+        1 | 2 => {
+            let mut units: Vec<u16> = text.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+            match units.first() {
+                Some(0xFFFE) => {
+                    units.remove(0);
+                    units.iter_mut().for_each(|u| *u = u.swap_bytes());
+                }
+                Some(0xFEFF) => {
+                    units.remove(0);
+                }
+                _ => {}
+            }
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::from_utf8_lossy(text).to_string(),
+    };
+    // Multiple values are NUL separated; keep the first
+    non_empty(decoded.split('\0').next().unwrap_or_default().to_string())
+}
+
+/// Decode the picture of an ID3v2 `APIC` frame, with whether it is the front cover
+fn id3_picture(body: &[u8]) -> Option<(CoverArt, bool)> {
+    let (encoding, rest) = body.split_first()?;
+    let mime_end = rest.iter().position(|b| *b == 0)?;
+    let (picture_type, description) = rest.get(mime_end + 1..)?.split_first()?;
+    // The description is NUL terminated; UTF-16 text ends with two aligned NUL bytes
+    let data_start = if matches!(encoding, 1 | 2) {
+        description.chunks_exact(2).position(|unit| unit == [0, 0])? * 2 + 2
+    } else {
+        description.iter().position(|b| *b == 0)? + 1
+    };
+    let art = CoverArt::from_data(description.get(data_start..)?.to_vec())?;
+    Some((art, *picture_type == 3))
+}
+
+/// Parse an ID3v2.3/2.4 tag at the start of the file, returning the audio start offset
+fn probe_id3v2<R: Read + Seek>(reader: &mut R, metadata: &mut MediaMetadata) -> io::Result<u64> {
+    reader.seek(SeekFrom::Start(0))?;
+    let mut header = [0u8; 10];
+    if reader.read_exact(&mut header).is_err() || &header[0..3] != b"ID3" {
+        return Ok(0);
+    }
+    let version = header[3];
+    let tag_size = syncsafe(&header[6..10]);
+    let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+    let audio_start = 10 + tag_size + footer;
+    if !(3..=4).contains(&version) {
+        return Ok(audio_start);
+    }
+
+    let tag = read_exact_vec(reader, tag_size)?;
+    let mut pos = 0;
+    let mut has_front_cover = false;
+    while pos + 10 <= tag.len() && tag[pos] != 0 {
+        let id = &tag[pos..pos + 4];
+        let size = if version == 4 {
+            syncsafe(&tag[pos + 4..pos + 8]) as usize
+        } else {
+            u32::from_be_bytes(tag[pos + 4..pos + 8].try_into().unwrap()) as usize
+        };
+        let body_start = pos + 10;
+        let Some(body) = tag.get(body_start..body_start + size) else {
+            break;
+        };
+        match id {
+            b"TIT2" => metadata.title = id3_text(body),
+            b"TPE1" => metadata.artist = id3_text(body),
+            b"TALB" => metadata.album = id3_text(body),
+            b"TLEN" => {
+                metadata.duration = id3_text(body)
+                    .and_then(|ms| ms.parse::<u64>().ok())
+                    .filter(|ms| *ms > 0)
+                    .map(Duration::from_millis)
+            }
+            // Prefer the front cover over other pictures
+            b"APIC" => {
+                if let Some((art, front_cover)) = id3_picture(body) {
+                    if metadata.cover_art.is_none() || (front_cover && !has_front_cover) {
+                        metadata.cover_art = Some(art);
+                        has_front_cover = front_cover;
+                    }
+                }
+            }
+            _ => {}
+        }
+        pos = body_start + size;
+    }
+    Ok(audio_start)
+}
+
+fn probe_mp3<R: Read + Seek>(reader: &mut R, len: u64) -> io::Result<MediaMetadata> {
+    let mut metadata = MediaMetadata::default();
+    let audio_start = probe_id3v2(reader, &mut metadata)?;
+    if metadata.duration.is_none() {
+        metadata.duration = mp3_duration(reader, audio_start, len)?;
+    }
+    Ok(metadata)
+}
+
+/// Duration from the Xing/Info frame count, or from the bitrate of the first
+/// frame when the file has constant bitrate
+fn mp3_duration<R: Read + Seek>(reader: &mut R, audio_start: u64, len: u64) -> io::Result<Option<Duration>> {
+    const BITRATES_V1: [u64; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+    const BITRATES_V2: [u64; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+
+    reader.seek(SeekFrom::Start(audio_start))?;
+    let window = read_exact_vec(reader, (len.saturating_sub(audio_start)).min(64 * 1024))?;
+
+    // First valid Layer III frame header
+    let Some(offset) = (0..window.len().saturating_sub(4)).find(|&i| {
+        let h = &window[i..i + 4];
+        h[0] == 0xFF && h[1] & 0xE0 == 0xE0 && (h[1] >> 1) & 0x3 == 0x1 && (h[2] >> 4) != 0xF && (h[2] >> 4) != 0 && (h[2] >> 2) & 0x3 != 0x3
+    }) else {
+        return Ok(None);
+    };
+    let header = &window[offset..offset + 4];
+    let version = (header[1] >> 3) & 0x3; // 3 = MPEG1, 2 = MPEG2, 0 = MPEG2.5
+    if version == 1 {
+        return Ok(None);
+    }
+    let mpeg1 = version == 3;
+    let bitrate_kbps = if mpeg1 { BITRATES_V1 } else { BITRATES_V2 }[(header[2] >> 4) as usize];
+    let base_rate = [44_100u64, 48_000, 32_000][((header[2] >> 2) & 0x3) as usize];
+    let sample_rate = match version {
+        3 => base_rate,
+        2 => base_rate / 2,
+        _ => base_rate / 4,
+    };
+    let samples_per_frame = if mpeg1 { 1152 } else { 576 };
+    let mono = header[3] >> 6 == 0x3;
+    let side_info = match (mpeg1, mono) {
+        (true, true) => 17,
+        (true, false) => 32,
+        (false, true) => 9,
+        (false, false) => 17,
+    };
+
+    let xing = offset + 4 + side_info;
+    if let Some(tag) = window.get(xing..xing + 12) {
+        if &tag[0..4] == b"Xing" || &tag[0..4] == b"Info" {
+            let flags = u32::from_be_bytes(tag[4..8].try_into().unwrap());
+            if flags & 0x1 != 0 {
+                let frames = u32::from_be_bytes(tag[8..12].try_into().unwrap()) as u64;
+                return Ok(duration_from_secs((frames * samples_per_frame) as f64 / sample_rate as f64));
+            }
+        }
+    }
+
+    let audio_bytes = len.saturating_sub(audio_start + offset as u64);
+    Ok((bitrate_kbps > 0).then(|| duration_from_secs(audio_bytes as f64 * 8.0 / (bitrate_kbps * 1000) as f64)).flatten())
+}
+
+// ---------------------------------------------------------------------------
+// FLAC
+
+fn probe_flac<R: Read + Seek>(reader: &mut R) -> io::Result<MediaMetadata> {
+    let mut metadata = MediaMetadata::default();
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != b"fLaC" {
+        return Err(invalid("missing fLaC marker"));
+    }
+
+    loop {
+        let mut header = [0u8; 4];
+        reader.read_exact(&mut header)?;
+        let last = header[0] & 0x80 != 0;
+        let block_type = header[0] & 0x7F;
+        let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as u64;
+        match block_type {
+            0 => {
+                let block = read_exact_vec(reader, len)?;
+                if block.len() >= 18 {
+                    let packed = u64::from_be_bytes(block[10..18].try_into().unwrap());
+                    let sample_rate = (packed >> 44) & 0xF_FFFF;
+                    let total_samples = packed & 0xF_FFFF_FFFF;
+                    if sample_rate > 0 && total_samples > 0 {
+                        metadata.duration = duration_from_secs(total_samples as f64 / sample_rate as f64);
+                    }
+                }
+            }
+            4 => parse_vorbis_comments(&read_exact_vec(reader, len)?, &mut metadata),
+            6 => {
+                let block = read_exact_vec(reader, len)?;
+                if let Some((art, front_cover)) = flac_picture(&block) {
+                    if metadata.cover_art.is_none() || front_cover {
+                        metadata.cover_art = Some(art);
+                    }
+                }
+            }
+            _ => {
+                reader.seek(SeekFrom::Current(len as i64))?;
+            }
+        }
+        if last {
+            break;
+        }
+    }
+    Ok(metadata)
+}
+
+/// Decode a `PICTURE` block, with whether it is the front cover
+fn flac_picture(block: &[u8]) -> Option<(CoverArt, bool)> {
+    let read_u32 = |pos: usize| block.get(pos..pos + 4).map(|b| u32::from_be_bytes(b.try_into().unwrap()) as usize);
+    let picture_type = read_u32(0)?;
+    let description_at = 8 + read_u32(4)?;
+    // Width, height, depth and palette size sit between the description and the data length
+    let length_at = description_at + 4 + read_u32(description_at)? + 16;
+    let data = block.get(length_at + 4..length_at + 4 + read_u32(length_at)?)?;
+    Some((CoverArt::from_data(data.to_vec())?, picture_type == 3))
+}
+
+fn parse_vorbis_comments(block: &[u8], metadata: &mut MediaMetadata) {
+    let read_u32 = |pos: usize| block.get(pos..pos + 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize);
+    let Some(vendor_len) = read_u32(0) else {
+        return;
+    };
+    let mut pos = 4 + vendor_len;
+    let Some(count) = read_u32(pos) else {
+        return;
+    };
+    pos += 4;
+    for _ in 0..count {
+        let Some(len) = read_u32(pos) else {
+            return;
+        };
+        let Some(comment) = block.get(pos + 4..pos + 4 + len) else {
+            return;
+        };
+        pos += 4 + len;
+        let comment = String::from_utf8_lossy(comment);
+        let Some((key, value)) = comment.split_once('=') else {
+            continue;
+        };
+        let field = match key.to_ascii_uppercase().as_str() {
+            "TITLE" => &mut metadata.title,
+            "ARTIST" => &mut metadata.artist,
+            "ALBUM" => &mut metadata.album,
+            _ => continue,
+        };
+        if field.is_none() {
+            *field = non_empty(value.to_string());
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Images
+
+/// Size from the first start-of-frame segment of a JPEG
+fn probe_jpeg<R: Read + Seek>(reader: &mut R) -> io::Result<MediaMetadata> {
+    let mut metadata = MediaMetadata::default();
+    let mut soi = [0u8; 2];
+    reader.read_exact(&mut soi)?;
+    if soi != [0xFF, 0xD8] {
+        return Err(invalid("missing JPEG start of image"));
+    }
+    loop {
+        let mut marker = [0u8; 2];
+        reader.read_exact(&mut marker)?;
+        if marker[0] != 0xFF {
+            return Err(invalid("invalid JPEG marker"));
+        }
+        match marker[1] {
+            // Fill bytes before a marker
+            0xFF => {
+                reader.seek(SeekFrom::Current(-1))?;
+                continue;
+            }
+            // Markers without a segment
+            0x01 | 0xD0..=0xD7 => continue,
+            // Start of scan or end of image without a frame header
+            0xD9 | 0xDA => return Ok(metadata),
+            _ => {}
+        }
+        let mut length = [0u8; 2];
+        reader.read_exact(&mut length)?;
+        let length = u16::from_be_bytes(length) as i64;
+        // SOF0-SOF15, except DHT (C4), JPG (C8) and DAC (CC)
+        if matches!(marker[1], 0xC0..=0xCF) && !matches!(marker[1], 0xC4 | 0xC8 | 0xCC) {
+            let mut frame = [0u8; 5];
+            reader.read_exact(&mut frame)?;
+            let height = u16::from_be_bytes([frame[1], frame[2]]);
+            let width = u16::from_be_bytes([frame[3], frame[4]]);
+            metadata.set_resolution(width as u64, height as u64);
+            return Ok(metadata);
+        }
+        reader.seek(SeekFrom::Current(length - 2))?;
+    }
+}
+
+/// Size from the `IHDR` chunk that opens every PNG
+fn probe_png<R: Read>(reader: &mut R) -> io::Result<MediaMetadata> {
+    let mut metadata = MediaMetadata::default();
+    let mut header = [0u8; 24];
+    reader.read_exact(&mut header)?;
+    if &header[..8] != b"\x89PNG\r\n\x1a\n" || &header[12..16] != b"IHDR" {
+        return Err(invalid("missing PNG header"));
+    }
+    let width = u32::from_be_bytes(header[16..20].try_into().unwrap());
+    let height = u32::from_be_bytes(header[20..24].try_into().unwrap());
+    metadata.set_resolution(width as u64, height as u64);
+    Ok(metadata)
+}
+
+/// Size of the GIF logical screen
+fn probe_gif<R: Read>(reader: &mut R) -> io::Result<MediaMetadata> {
+    let mut metadata = MediaMetadata::default();
+    let mut header = [0u8; 10];
+    reader.read_exact(&mut header)?;
+    if &header[..6] != b"GIF87a" && &header[..6] != b"GIF89a" {
+        return Err(invalid("missing GIF header"));
+    }
+    let width = u16::from_le_bytes([header[6], header[7]]);
+    let height = u16::from_le_bytes([header[8], header[9]]);
+    metadata.set_resolution(width as u64, height as u64);
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn mp4_box(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut out = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(body);
+        out
+    }
+
+    fn probe<F>(bytes: Vec<u8>, parse: F) -> MediaMetadata
+    where
+        F: FnOnce(&mut Cursor<Vec<u8>>, u64) -> io::Result<MediaMetadata>,
+    {
+        let len = bytes.len() as u64;
+        parse(&mut Cursor::new(bytes), len).unwrap()
+    }
+
+    #[test]
+    fn test_mp4_duration_and_tags() {
+        let mut mvhd = vec![0u8; 100];
+        mvhd[12..16].copy_from_slice(&1000u32.to_be_bytes());
+        mvhd[16..20].copy_from_slice(&90_500u32.to_be_bytes());
+        let data = |text: &str| mp4_box(b"data", &[&[0, 0, 0, 1, 0, 0, 0, 0], text.as_bytes()].concat());
+        let ilst = mp4_box(
+            b"ilst",
+            &[mp4_box(b"\xA9nam", &data("Opening")), mp4_box(b"\xA9ART", &data("Band")), mp4_box(b"\xA9alb", &data("Live"))]
+                .concat(),
+        );
+        let meta = mp4_box(b"meta", &[&[0u8; 4][..], &mp4_box(b"hdlr", &[0; 25]), &ilst].concat());
+        let moov = mp4_box(b"moov", &[mp4_box(b"mvhd", &mvhd), mp4_box(b"udta", &meta)].concat());
+        let file = [mp4_box(b"ftyp", b"isom\0\0\0\0"), mp4_box(b"mdat", &[0; 64]), moov].concat();
+
+        let metadata = probe(file, probe_mp4);
+        assert_eq!(metadata.duration, Some(Duration::from_millis(90_500)));
+        assert_eq!(metadata.title.as_deref(), Some("Opening"));
+        assert_eq!(metadata.artist.as_deref(), Some("Band"));
+        assert_eq!(metadata.album.as_deref(), Some("Live"));
+    }
+
+    #[test]
+    fn test_matroska_duration_and_title() {
+        let element = |id: &[u8], body: &[u8]| [id, &[0x80 | body.len() as u8], body].concat();
+        let info = [
+            element(&[0x2A, 0xD7, 0xB1], &[0x0F, 0x42, 0x40]),
+            element(&[0x44, 0x89], &125_250.0f64.to_be_bytes()),
+            element(&[0x7B, 0xA9], b"Feature"),
+        ]
+        .concat();
+        let segment = [element(&[0x11, 0x4D, 0x9B, 0x74], &[0; 4]), element(&[0x15, 0x49, 0xA9, 0x66], &info)].concat();
+        // An unknown segment size, as written by live muxers
+        let file = [
+            element(&[0x1A, 0x45, 0xDF, 0xA3], &[0x42, 0x82, 0x84, b'w', b'e', b'b', b'm']),
+            vec![0x18, 0x53, 0x80, 0x67, 0xFF],
+            segment,
+        ]
+        .concat();
+
+        let metadata = probe(file, probe_matroska);
+        assert_eq!(metadata.duration, Some(Duration::from_millis(125_250)));
+        assert_eq!(metadata.title.as_deref(), Some("Feature"));
+    }
+
+    #[test]
+    fn test_unrepresentable_durations_are_ignored() {
+        let element = |id: &[u8], body: &[u8]| [id, &[0x80 | body.len() as u8], body].concat();
+        let info = [element(&[0x2A, 0xD7, 0xB1], &[0x0F, 0x42, 0x40]), element(&[0x44, 0x89], &1e300f64.to_be_bytes())].concat();
+        let segment = element(&[0x15, 0x49, 0xA9, 0x66], &info);
+        let file = [element(&[0x1A, 0x45, 0xDF, 0xA3], &[0x42, 0x82, 0x84, b'w', b'e', b'b', b'm']), element(&[0x18, 0x53, 0x80, 0x67], &segment)].concat();
+        assert_eq!(probe(file, probe_matroska).duration, None);
+
+        assert_eq!(duration_from_secs(f64::NAN), None);
+        assert_eq!(duration_from_secs(-1.0), None);
+        assert_eq!(duration_from_secs(1.5), Some(Duration::from_millis(1500)));
+    }
+
+    #[test]
+    fn test_mp4_and_matroska_video_size() {
+        let tkhd = |width: u32, height: u32| {
+            let mut body = vec![0u8; 84];
+            body[76..80].copy_from_slice(&(width << 16).to_be_bytes());
+            body[80..84].copy_from_slice(&(height << 16).to_be_bytes());
+            mp4_box(b"trak", &mp4_box(b"tkhd", &body))
+        };
+        // The audio track comes first and has no size
+        let moov = mp4_box(b"moov", &[tkhd(0, 0), tkhd(1280, 720)].concat());
+        let metadata = probe([mp4_box(b"ftyp", b"isom\0\0\0\0"), moov].concat(), probe_mp4);
+        assert_eq!((metadata.width, metadata.height), (Some(1280), Some(720)));
+
+        let element = |id: &[u8], body: &[u8]| [id, &[0x80 | body.len() as u8], body].concat();
+        let video = element(&[0xE0], &[element(&[0xB0], &[0x07, 0x80]), element(&[0xBA], &[0x04, 0x38])].concat());
+        let tracks = element(
+            &[0x16, 0x54, 0xAE, 0x6B],
+            &[element(&[0xAE], &element(&[0xD7], &[1])), element(&[0xAE], &video)].concat(),
+        );
+        let info = element(&[0x15, 0x49, 0xA9, 0x66], &element(&[0x44, 0x89], &5000.0f64.to_be_bytes()));
+        let file = [
+            element(&[0x1A, 0x45, 0xDF, 0xA3], &[0x42, 0x82, 0x84, b'w', b'e', b'b', b'm']),
+            element(&[0x18, 0x53, 0x80, 0x67], &[info, tracks].concat()),
+        ]
+        .concat();
+        let metadata = probe(file, probe_matroska);
+        assert_eq!((metadata.width, metadata.height), (Some(1920), Some(1080)));
+        assert_eq!(metadata.duration, Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn test_image_sizes() {
+        let png = [&b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR"[..], &800u32.to_be_bytes(), &600u32.to_be_bytes(), &[8, 6, 0, 0, 0]].concat();
+        let metadata = probe(png, |reader, _| probe_png(reader));
+        assert_eq!((metadata.width, metadata.height), (Some(800), Some(600)));
+
+        let gif = [&b"GIF89a"[..], &320u16.to_le_bytes(), &200u16.to_le_bytes(), &[0; 3]].concat();
+        let metadata = probe(gif, |reader, _| probe_gif(reader));
+        assert_eq!((metadata.width, metadata.height), (Some(320), Some(200)));
+
+        // An APP0 segment and fill bytes precede the baseline frame header
+        let jpeg = [
+            &[0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x06][..],
+            b"JFIF",
+            &[0xFF, 0xFF, 0xC0, 0x00, 0x11, 0x08],
+            &3024u16.to_be_bytes(),
+            &4032u16.to_be_bytes(),
+            &[0x03; 12],
+        ]
+        .concat();
+        let metadata = probe(jpeg, |reader, _| probe_jpeg(reader));
+        assert_eq!((metadata.width, metadata.height), (Some(4032), Some(3024)));
+    }
+
+    #[test]
+    fn test_mp3_id3_tags_and_duration() {
+        let text_frame = |id: &[u8; 4], encoding: u8, text: &[u8]| {
+            let body = [&[encoding][..], text].concat();
+            [&id[..], &(body.len() as u32).to_be_bytes(), &[0, 0], &body].concat()
+        };
+        // UTF-16 with a little-endian BOM
+        let artist: Vec<u8> = [0xFF, 0xFE].into_iter().chain("Künstler".encode_utf16().flat_map(|u| u.to_le_bytes())).collect();
+        let frames = [text_frame(b"TIT2", 3, b"Song"), text_frame(b"TPE1", 1, &artist), text_frame(b"TALB", 0, b"Album")].concat();
+        let size = frames.len() as u32;
+        let syncsafe_size = [(size >> 21) as u8 & 0x7F, (size >> 14) as u8 & 0x7F, (size >> 7) as u8 & 0x7F, size as u8 & 0x7F];
+        let tag = [&b"ID3\x03\x00\x00"[..], &syncsafe_size, &frames].concat();
+
+        // 128 kbps MPEG1 Layer III, 44.1 kHz stereo: 16000 bytes of audio per second
+        let mut cbr = tag.clone();
+        cbr.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+        cbr.resize(tag.len() + 160_000, 0);
+        let metadata = probe(cbr, probe_mp3);
+        assert_eq!(metadata.title.as_deref(), Some("Song"));
+        assert_eq!(metadata.artist.as_deref(), Some("Künstler"));
+        assert_eq!(metadata.album.as_deref(), Some("Album"));
+        assert_eq!(metadata.duration, Some(Duration::from_secs(10)));
+
+        // A Xing header counting 2297 frames of 1152 samples is about 60 seconds
+        let mut vbr = vec![0xFF, 0xFB, 0x90, 0x00];
+        vbr.extend_from_slice(&[0; 32]);
+        vbr.extend_from_slice(b"Xing\x00\x00\x00\x01");
+        vbr.extend_from_slice(&2297u32.to_be_bytes());
+        vbr.resize(4096, 0);
+        let duration = probe(vbr, probe_mp3).duration.unwrap();
+        assert_eq!(duration.as_secs(), 60);
+    }
+
+    #[test]
+    fn test_flac_streaminfo_and_comments() {
+        let mut streaminfo = vec![0u8; 34];
+        // 48 kHz, stereo, 16 bits, 240000 samples = 5 seconds
+        let packed: u64 = (48_000u64 << 44) | (1 << 41) | (15 << 36) | 240_000;
+        streaminfo[10..18].copy_from_slice(&packed.to_be_bytes());
+        let comment = |text: &str| [&(text.len() as u32).to_le_bytes()[..], text.as_bytes()].concat();
+        let comments = [
+            &comment("vendor")[..],
+            &3u32.to_le_bytes(),
+            &comment("title=Track"),
+            &comment("ARTIST=Singer"),
+            &comment("Album=Record"),
+        ]
+        .concat();
+        let block = |kind: u8, body: &[u8]| [&[kind][..], &(body.len() as u32).to_be_bytes()[1..], body].concat();
+        let file = [&b"fLaC"[..], &block(0, &streaminfo), &block(4, &comments), &block(0x81, &[0; 8])].concat();
+
+        let metadata = probe(file, |reader, _| probe_flac(reader));
+        assert_eq!(metadata.duration, Some(Duration::from_secs(5)));
+        assert_eq!(metadata.title.as_deref(), Some("Track"));
+        assert_eq!(metadata.artist.as_deref(), Some("Singer"));
+        assert_eq!(metadata.album.as_deref(), Some("Record"));
+    }
+
+    #[test]
+    fn test_mp4_and_flac_cover_art() {
+        let jpeg = [&[0xFF, 0xD8, 0xFF, 0xE0][..], &[7; 32]].concat();
+        let covr = mp4_box(b"covr", &mp4_box(b"data", &[&[0, 0, 0, 13, 0, 0, 0, 0][..], &jpeg].concat()));
+        let meta = mp4_box(b"meta", &[&[0u8; 4][..], &mp4_box(b"ilst", &covr)].concat());
+        let moov = mp4_box(b"moov", &mp4_box(b"udta", &meta));
+        let metadata = probe([mp4_box(b"ftyp", b"M4A \0\0\0\0"), moov].concat(), probe_mp4);
+        assert_eq!(metadata.cover_art, Some(CoverArt { mime_type: "image/jpeg".to_string(), data: jpeg.clone() }));
+
+        // A back cover followed by the front cover, which wins
+        let picture = |kind: u32, data: &[u8]| {
+            [
+                &kind.to_be_bytes()[..],
+                &10u32.to_be_bytes(),
+                b"image/jpeg",
+                &0u32.to_be_bytes(),
+                &[0; 16],
+                &(data.len() as u32).to_be_bytes(),
+                data,
+            ]
+            .concat()
+        };
+        let back = [&[0xFF, 0xD8][..], b"back"].concat();
+        let block = |kind: u8, body: &[u8]| [&[kind][..], &(body.len() as u32).to_be_bytes()[1..], body].concat();
+        let file = [&b"fLaC"[..], &block(6, &picture(4, &back)), &block(0x86, &picture(3, &jpeg))].concat();
+        let metadata = probe(file, |reader, _| probe_flac(reader));
+        assert_eq!(metadata.cover_art.map(|art| art.data), Some(jpeg));
+    }
+
+    #[test]
+    fn test_unparseable_files_yield_no_metadata() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        for name in ["broken.mp4", "broken.mkv", "broken.mp3", "broken.flac", "broken.jpg", "broken.png", "notes.txt"] {
+            let path = temp_dir.path().join(name);
+            std::fs::write(&path, b"not really media").unwrap();
+            assert_eq!(probe_file(&path), None, "{}", name);
+        }
+        assert_eq!(probe_file(&temp_dir.path().join("missing.mp4")), None);
+    }
+}
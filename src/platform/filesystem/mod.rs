@@ -1,10 +1,12 @@
-use std::collections::HashMap;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 use thiserror::Error;
 use tokio::fs;
 
 use crate::database::MediaFile;
+use crate::media::mime;
 
 #[cfg(target_os = "windows")]
 pub mod windows;
@@ -13,7 +15,16 @@ pub mod windows;
 #[async_trait::async_trait]
 pub trait FileSystemManager: Send + Sync {
     /// Scan a media directory and return all media files
-    async fn scan_media_directory(&self, path: &Path) -> Result<Vec<MediaFile>, FileSystemError>;
+    async fn scan_media_directory(&self, path: &Path) -> Result<Vec<MediaFile>, FileSystemError> {
+        self.scan_media_directory_with_types(path, &MediaTypes::default()).await
+    }
+
+    /// Scan a media directory, recognizing media files by `media_types`
+    async fn scan_media_directory_with_types(
+        &self,
+        path: &Path,
+        media_types: &MediaTypes,
+    ) -> Result<Vec<MediaFile>, FileSystemError>;
     
     /// Normalize a path for the current platform
     fn normalize_path(&self, path: &Path) -> PathBuf;
@@ -26,6 +37,18 @@ pub trait FileSystemManager: Send + Sync {
     
     /// Check if two paths refer to the same file (handles case sensitivity)
     fn paths_equal(&self, path1: &Path, path2: &Path) -> bool;
+
+    /// The part of `path` below `ancestor`, or `None` when `path` is not
+    /// `ancestor` or inside it. Components are compared with [`Self::paths_equal`].
+    fn strip_path_prefix(&self, path: &Path, ancestor: &Path) -> Option<PathBuf> {
+        let matched = path.ancestors().find(|candidate| self.paths_equal(candidate, ancestor))?;
+        path.strip_prefix(matched).ok().map(Path::to_path_buf)
+    }
+
+    /// Whether `path` is `ancestor` or lies inside it, honoring case sensitivity
+    fn path_starts_with(&self, path: &Path, ancestor: &Path) -> bool {
+        self.strip_path_prefix(path, ancestor).is_some()
+    }
     
     /// Validate that a path is safe to access (security check)
     fn validate_path(&self, path: &Path) -> Result<(), FileSystemError>;
@@ -87,7 +110,33 @@ pub enum FileSystemError {
     Platform(String),
 }
 
+/// Whether `error` is the OS rejecting a path or file name as too long, which
+/// std reports under the same kind as otherwise invalid names
+fn is_name_too_long(error: &std::io::Error) -> bool {
+    #[cfg(unix)]
+    let too_long = libc::ENAMETOOLONG;
+    // ERROR_FILENAME_EXCED_RANGE
+    #[cfg(windows)]
+    let too_long = 206;
+    error.raw_os_error() == Some(too_long)
+}
+
 impl FileSystemError {
+    /// Classify an I/O error met while accessing `path`, so permission, length
+    /// and encoding problems get their own severity and suggestions
+    pub fn from_io_error(path: &Path, error: &std::io::Error) -> Self {
+        let path = path.display().to_string();
+        let details = error.to_string();
+        match error.kind() {
+            std::io::ErrorKind::PermissionDenied => FileSystemError::Permission { path, details },
+            std::io::ErrorKind::InvalidFilename if is_name_too_long(error) => FileSystemError::PathTooLong { path, details },
+            std::io::ErrorKind::InvalidFilename => FileSystemError::InvalidPath { path, reason: details },
+            std::io::ErrorKind::InvalidData => FileSystemError::Encoding { path, details },
+            std::io::ErrorKind::NotFound => FileSystemError::PathNotFound { path },
+            kind => FileSystemError::Io(std::io::Error::new(kind, details)),
+        }
+    }
+
     /// Get a user-friendly error message with detailed explanation
     pub fn user_message(&self) -> String {
         match self {
@@ -268,7 +317,8 @@ impl FileSystemError {
 }
 
 /// Error severity levels
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ErrorSeverity {
     Warning,
     Error,
@@ -348,6 +398,116 @@ pub const SUPPORTED_MEDIA_TYPES: &[(&str, &str)] = &[
     ("svg", "image/svg+xml"),
 ];
 
+/// Extension to MIME type mappings from `custom_mime_types`, consulted before the
+/// built-in table: the media-wide ones and those of each monitored directory
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MediaTypes {
+    custom: BTreeMap<String, String>,
+    /// Mappings of monitored directories, deepest directory first
+    directories: Vec<(PathBuf, BTreeMap<String, String>)>,
+    /// Lowercased extensions sniffed even though a mapping knows them, or
+    /// `None` while `media.sniff_content` is off
+    sniffed: Option<BTreeSet<String>>,
+}
+
+/// Configured mappings keyed by lowercased extension without the leading dot
+fn custom_media_types(types: Option<&HashMap<String, String>>) -> BTreeMap<String, String> {
+    types
+        .into_iter()
+        .flatten()
+        .map(|(ext, mime)| (ext.trim_start_matches('.').to_lowercase(), mime.clone()))
+        .collect()
+}
+
+impl MediaTypes {
+    /// Mappings applying to files in every directory
+    pub fn new(types: Option<&HashMap<String, String>>) -> Self {
+        Self { custom: custom_media_types(types), directories: Vec::new(), sniffed: None }
+    }
+
+    /// Identify files by their content when their extension is unknown, missing
+    /// or one of `ambiguous_extensions`, as `media.sniff_content` does
+    pub fn with_content_sniffing(mut self, enabled: bool, ambiguous_extensions: &[String]) -> Self {
+        self.sniffed = enabled.then(|| {
+            ambiguous_extensions
+                .iter()
+                .map(|ext| ext.trim_start_matches('.').to_lowercase())
+                .collect()
+        });
+        self
+    }
+
+    /// Whether a file has its content sniffed instead of being typed by its extension
+    pub fn sniffs(&self, path: &Path) -> bool {
+        let Some(ambiguous) = &self.sniffed else {
+            return false;
+        };
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(extension) if !extension.is_empty() => {
+                !self.is_media_file(path) || ambiguous.contains(&extension.to_lowercase())
+            }
+            _ => true,
+        }
+    }
+
+    /// Add mappings for files below `directory`, taking precedence over the media-wide ones
+    pub fn with_directory(mut self, directory: impl Into<PathBuf>, types: Option<&HashMap<String, String>>) -> Self {
+        let types = custom_media_types(types);
+        if !types.is_empty() {
+            self.directories.push((directory.into(), types));
+            self.directories.sort_by_key(|(directory, _)| std::cmp::Reverse(directory.components().count()));
+        }
+        self
+    }
+
+    /// MIME type configured for a file's extension in the directory it is in, if any
+    pub fn custom_mime_type(&self, path: &Path) -> Option<&str> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        self.directories
+            .iter()
+            .filter(|(directory, _)| path.starts_with(directory))
+            .map(|(_, types)| types)
+            .chain([&self.custom])
+            .find_map(|types| types.get(&extension))
+            .map(String::as_str)
+    }
+
+    /// MIME type of a file: the configured one, else the built-in one for its extension
+    pub fn mime_type(&self, path: &Path) -> String {
+        match self.custom_mime_type(path) {
+            Some(mime) => mime.to_string(),
+            None => path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map(get_mime_type_for_extension)
+                .unwrap_or_else(|| "application/octet-stream".to_string()),
+        }
+    }
+
+    /// Whether a file is served as media, by a configured or built-in mapping of its extension
+    pub fn is_media_file(&self, path: &Path) -> bool {
+        self.custom_mime_type(path).is_some()
+            || path.extension().and_then(|ext| ext.to_str()).is_some_and(is_supported_media_extension)
+    }
+
+    /// MIME types of all servable files, built-in ones first, without duplicates
+    pub fn supported_mime_types(&self) -> Vec<String> {
+        let custom = self
+            .directories
+            .iter()
+            .map(|(_, types)| types)
+            .chain([&self.custom])
+            .flat_map(|types| types.values().map(String::as_str));
+        let mut mime_types: Vec<String> = Vec::new();
+        for mime in SUPPORTED_MEDIA_TYPES.iter().map(|(_, mime)| *mime).chain(custom) {
+            if !mime_types.iter().any(|m| m == mime) {
+                mime_types.push(mime.to_string());
+            }
+        }
+        mime_types
+    }
+}
+
 /// Get MIME type for a file based on its extension
 pub fn get_mime_type_for_extension(extension: &str) -> String {
     let ext_lower = extension.to_lowercase();
@@ -425,11 +585,7 @@ impl BaseFileSystemManager {
             .map(get_mime_type_for_extension)
             .unwrap_or_else(|| "application/octet-stream".to_string());
         
-        let is_hidden = path
-            .file_name()
-            .and_then(|name| name.to_str())
-            .map(|name| name.starts_with('.'))
-            .unwrap_or(false);
+        let is_hidden = is_hidden_path(path);
         
         Ok(FileInfo {
             size: metadata.len(),
@@ -442,7 +598,11 @@ impl BaseFileSystemManager {
     }
     
     /// Common media file scanning logic
-    pub async fn scan_directory_common(&self, path: &Path) -> Result<Vec<MediaFile>, FileSystemError> {
+    pub async fn scan_directory_common(
+        &self,
+        path: &Path,
+        media_types: &MediaTypes,
+    ) -> Result<Vec<MediaFile>, FileSystemError> {
         let mut media_files = Vec::new();
         let mut entries = fs::read_dir(path).await?;
         
@@ -454,12 +614,15 @@ impl BaseFileSystemManager {
                 continue;
             }
             
-            // Check if it's a supported media file
-            if let Some(extension) = entry_path.extension().and_then(|ext| ext.to_str()) {
-                if !is_supported_media_extension(extension) {
-                    continue;
-                }
-                
+            // Check if it's a supported media file, by content when the extension is unknown or ambiguous
+            let mime_type = if media_types.sniffs(&entry_path) {
+                mime::sniff_file(&entry_path).await.map(str::to_string)
+            } else if media_types.is_media_file(&entry_path) {
+                Some(media_types.mime_type(&entry_path))
+            } else {
+                None
+            };
+            if let Some(mime_type) = mime_type {
                 // Get file metadata
                 let metadata = entry.metadata().await?;
                 let filename = entry_path
@@ -468,7 +631,6 @@ impl BaseFileSystemManager {
                     .to_string_lossy()
                     .to_string();
                 
-                let mime_type = get_mime_type_for_extension(extension);
                 let now = SystemTime::now();
                 
                 media_files.push(MediaFile {
@@ -478,10 +640,13 @@ impl BaseFileSystemManager {
                     size: metadata.len(),
                     modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
                     mime_type,
-                    duration: None, // TODO: Extract from metadata
-                    title: None,    // TODO: Extract from metadata
-                    artist: None,   // TODO: Extract from metadata
-                    album: None,    // TODO: Extract from metadata
+                    // Filled in by MediaScanner when `media.extract_metadata` is enabled
+                    duration: None,
+                    title: None,
+                    artist: None,
+                    album: None,
+                    width: None,
+                    height: None,
                     created_at: now,
                     updated_at: now,
                 });
@@ -494,7 +659,11 @@ impl BaseFileSystemManager {
 
 #[async_trait::async_trait]
 impl FileSystemManager for BaseFileSystemManager {
-    async fn scan_media_directory(&self, path: &Path) -> Result<Vec<MediaFile>, FileSystemError> {
+    async fn scan_media_directory_with_types(
+        &self,
+        path: &Path,
+        media_types: &MediaTypes,
+    ) -> Result<Vec<MediaFile>, FileSystemError> {
         self.validate_path_common(path)?;
         
         if !self.is_accessible(path).await {
@@ -504,7 +673,7 @@ impl FileSystemManager for BaseFileSystemManager {
             });
         }
         
-        self.scan_directory_common(path).await
+        self.scan_directory_common(path, media_types).await
     }
     
     fn normalize_path(&self, path: &Path) -> PathBuf {
@@ -558,6 +727,52 @@ impl FileSystemManager for BaseFileSystemManager {
     }
 }
 
+/// Whether a file or directory is hidden on the current platform.
+///
+/// On Windows this checks the hidden file attribute as well as the dotfile
+/// convention; elsewhere names starting with `.` are hidden.
+pub fn is_hidden_path(path: &Path) -> bool {
+    let is_dotfile = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.starts_with('.'))
+        .unwrap_or(false);
+
+    #[cfg(target_os = "windows")]
+    {
+        is_dotfile || windows::has_hidden_attribute(path)
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        is_dotfile
+    }
+}
+
+/// Strip the Windows extended-length prefix so every file maps to a single path form.
+///
+/// `\\?\C:\media` becomes `C:\media` and `\\?\UNC\server\share` becomes
+/// `\\server\share`. The standard library re-adds the prefix internally for paths
+/// that exceed `MAX_PATH`, so stripping it is safe for file access. Other platforms
+/// return the path unchanged.
+pub fn strip_extended_length_prefix(path: &Path) -> PathBuf {
+    #[cfg(target_os = "windows")]
+    {
+        let path_str = path.to_string_lossy();
+        if let Some(rest) = path_str.strip_prefix(r"\\?\UNC\") {
+            return PathBuf::from(format!(r"\\{}", rest));
+        }
+        if let Some(rest) = path_str.strip_prefix(r"\\?\") {
+            // Only drive-letter paths have a plain equivalent; keep volume GUID paths verbatim
+            let bytes = rest.as_bytes();
+            if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+                return PathBuf::from(rest);
+            }
+        }
+    }
+
+    path.to_path_buf()
+}
+
 /// Create a platform-specific file system manager
 pub fn create_platform_filesystem_manager() -> Box<dyn FileSystemManager> {
     #[cfg(target_os = "windows")]
@@ -579,7 +794,6 @@ pub fn create_platform_filesystem_manager() -> Box<dyn FileSystemManager> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::PathBuf;
     
     #[test]
     fn test_mime_type_detection() {
@@ -598,6 +812,41 @@ mod tests {
         assert!(!is_supported_media_extension("unknown"));
     }
     
+    #[test]
+    fn test_custom_media_types_take_precedence() {
+        let custom = HashMap::from([
+            (".DIVX".to_string(), "video/divx".to_string()),
+            ("m2ts".to_string(), "video/mp2t".to_string()),
+        ]);
+        let media_types = MediaTypes::new(Some(&custom));
+        assert!(media_types.is_media_file(Path::new("/media/film.divx")));
+        assert_eq!(media_types.mime_type(Path::new("/media/film.DivX")), "video/divx");
+        assert_eq!(media_types.mime_type(Path::new("/media/film.m2ts")), "video/mp2t");
+        assert_eq!(media_types.mime_type(Path::new("/media/film.mp4")), "video/mp4");
+        assert!(media_types.supported_mime_types().contains(&"video/divx".to_string()));
+
+        let builtin = MediaTypes::default();
+        assert!(!builtin.is_media_file(Path::new("/media/film.divx")));
+        assert_eq!(builtin.mime_type(Path::new("/media/film.m2ts")), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_directory_media_types_apply_below_their_directory() {
+        let media_wide = HashMap::from([("ts".to_string(), "video/mp2t".to_string())]);
+        let recordings = HashMap::from([("ts".to_string(), "video/vnd.dlna.mpeg-tts".to_string())]);
+        let media_types = MediaTypes::new(Some(&media_wide))
+            .with_directory("/media/tv", Some(&recordings))
+            .with_directory("/media/tv/raw", Some(&HashMap::from([("bin".to_string(), "video/mpeg".to_string())])));
+
+        assert_eq!(media_types.mime_type(Path::new("/media/movies/film.ts")), "video/mp2t");
+        assert_eq!(media_types.mime_type(Path::new("/media/tv/show.ts")), "video/vnd.dlna.mpeg-tts");
+        // The deepest directory wins, falling back to its parents for other extensions
+        assert_eq!(media_types.mime_type(Path::new("/media/tv/raw/show.ts")), "video/vnd.dlna.mpeg-tts");
+        assert!(media_types.is_media_file(Path::new("/media/tv/raw/capture.bin")));
+        assert!(!media_types.is_media_file(Path::new("/media/tv/capture.bin")));
+        assert!(media_types.supported_mime_types().contains(&"video/vnd.dlna.mpeg-tts".to_string()));
+    }
+    
     #[test]
     fn test_path_validation() {
         let manager = BaseFileSystemManager::new(true);
@@ -622,6 +871,24 @@ mod tests {
         assert!(!case_sensitive.paths_equal(path1, path2));
         assert!(case_insensitive.paths_equal(path1, path2));
     }
+
+    #[test]
+    fn test_path_prefix_follows_case_sensitivity() {
+        let case_sensitive = BaseFileSystemManager::new(true);
+        let case_insensitive = BaseFileSystemManager::new(false);
+
+        let file = Path::new("/Media/Movies/Film.mkv");
+        let dir = Path::new("/media/movies");
+
+        assert!(!case_sensitive.path_starts_with(file, dir));
+        assert!(case_sensitive.path_starts_with(file, Path::new("/Media/Movies")));
+        assert!(case_insensitive.path_starts_with(file, dir));
+        assert_eq!(case_insensitive.strip_path_prefix(file, dir), Some(PathBuf::from("Film.mkv")));
+
+        // Whole components only, never a sibling sharing a name prefix
+        assert!(!case_insensitive.path_starts_with(Path::new("/media/MoviesExtra/a.mkv"), dir));
+        assert!(!case_sensitive.path_starts_with(Path::new("/Media/MoviesExtra/a.mkv"), Path::new("/Media/Movies")));
+    }
     
     #[test]
     fn test_extension_matching() {
@@ -1,9 +1,42 @@
-use super::{BaseFileSystemManager, FileInfo, FilePermissions, FileSystemError, FileSystemManager, MediaFile};
+use super::{
+    strip_extended_length_prefix, BaseFileSystemManager, FileInfo, FilePermissions, FileSystemError,
+    FileSystemManager, MediaFile,
+};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 use tokio::fs;
 
+/// Whether the hidden attribute is set on a file or directory
+pub(super) fn has_hidden_attribute(path: &Path) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+
+    std::fs::symlink_metadata(path)
+        .map(|metadata| metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+        .unwrap_or(false)
+}
+
+/// Length at which a path no longer fits the legacy Win32 `MAX_PATH` buffer,
+/// which also holds the terminating NUL
+const MAX_PATH: usize = 260;
+
+/// Resolve `.` and `..` segments of a backslash-separated path below its root;
+/// Win32 does this for plain paths but not for extended-length ones
+fn collapse_dot_segments(rest: &str) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in rest.split('\\') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    segments.join("\\")
+}
+
 /// Windows-specific file system manager
 pub struct WindowsFileSystemManager {
     base: BaseFileSystemManager,
@@ -31,8 +64,33 @@ impl WindowsFileSystemManager {
         path_str.chars().nth(2) == Some('\\')
     }
     
-    /// Normalize Windows path separators, case, and handle drive letters
+    /// Give an absolute path too long for `MAX_PATH` the extended-length form:
+    /// `\\?\C:\...` for drive paths and `\\?\UNC\server\share\...` for shares.
+    /// Shorter and relative paths are returned unchanged.
+    fn with_extended_length_prefix(&self, path: PathBuf) -> PathBuf {
+        let path_str = path.to_string_lossy().to_string();
+        if path_str.len() < MAX_PATH {
+            path
+        } else if self.is_unc_path(&path) {
+            PathBuf::from(format!(r"\\?\UNC\{}", collapse_dot_segments(&path_str[2..])))
+        } else if self.has_drive_letter(&path) {
+            PathBuf::from(format!(r"\\?\{}\{}", &path_str[..2], collapse_dot_segments(&path_str[3..])))
+        } else {
+            path
+        }
+    }
+
+    /// Normalize Windows path separators and case, handle drive letters, and
+    /// use the extended-length form for paths beyond `MAX_PATH`
     fn normalize_windows_path(&self, path: &Path) -> PathBuf {
+        let normalized = self.normalize_short_windows_path(path);
+        self.with_extended_length_prefix(normalized)
+    }
+
+    /// Normalize Windows path separators, case, and handle drive letters
+    fn normalize_short_windows_path(&self, path: &Path) -> PathBuf {
+        let path = strip_extended_length_prefix(path);
+        let path = path.as_path();
         let path_str = path.to_string_lossy();
         
         // Convert forward slashes to backslashes and path to lowercase
@@ -56,6 +114,12 @@ impl WindowsFileSystemManager {
     
     /// Validate Windows-specific path constraints
     fn validate_windows_path(&self, path: &Path) -> Result<(), FileSystemError> {
+        // Long paths are accepted in the extended-length form normalization
+        // gives them; its `?` and prefix are not part of the path itself
+        let extended = path.to_string_lossy().starts_with(r"\\?\");
+        let plain = strip_extended_length_prefix(path);
+        let path = plain.as_path();
+
         // First run common validation
         self.base.validate_path_common(path)?;
         
@@ -103,11 +167,12 @@ impl WindowsFileSystemManager {
             }
         }
         
-        // Check path length limits
-        if path_str.len() > 260 && !path_str.starts_with(r"\\?\") {
+        // Only absolute paths can take the extended-length form
+        let can_extend = extended || self.has_drive_letter(path) || self.is_unc_path(path);
+        if path_str.len() >= MAX_PATH && !can_extend {
             return Err(FileSystemError::PathTooLong {
                 path: path.display().to_string(),
-                details: format!("Path length is {} characters, which exceeds the Windows MAX_PATH limit of 260 characters. Consider using shorter names or enabling long path support.", path_str.len()),
+                details: format!("Path length is {} characters, which exceeds the Windows MAX_PATH limit of 260 characters. Use an absolute path so the extended-length form can be used, or shorten the names.", path_str.len()),
             });
         }
         
@@ -145,8 +210,11 @@ impl WindowsFileSystemManager {
     
     /// Check if a file is hidden on Windows
     fn is_hidden_windows(&self, path: &Path) -> bool {
-        // On Windows, files starting with '.' are not necessarily hidden
-        // The hidden attribute is set via file attributes, but we'll use a simple heuristic
+        if has_hidden_attribute(path) {
+            return true;
+        }
+
+        // Well-known system files and dotfiles are treated as hidden even without the attribute
         if let Some(filename) = path.file_name().and_then(|name| name.to_str()) {
             // Common Windows hidden files
             matches!(filename.to_lowercase().as_str(), 
@@ -285,7 +353,11 @@ impl WindowsFileSystemManager {
 
 #[async_trait::async_trait]
 impl FileSystemManager for WindowsFileSystemManager {
-    async fn scan_media_directory(&self, path: &Path) -> Result<Vec<MediaFile>, FileSystemError> {
+    async fn scan_media_directory_with_types(
+        &self,
+        path: &Path,
+        media_types: &super::MediaTypes,
+    ) -> Result<Vec<MediaFile>, FileSystemError> {
         self.validate_windows_path(path)?;
         
         if !self.is_accessible(path).await {
@@ -297,7 +369,7 @@ impl FileSystemManager for WindowsFileSystemManager {
         
         // Use the base implementation for scanning, but with Windows-specific path handling
         let normalized_path = self.normalize_windows_path(path);
-        self.base.scan_directory_common(&normalized_path).await
+        self.base.scan_directory_common(&normalized_path, media_types).await
     }
     
     fn normalize_path(&self, path: &Path) -> PathBuf {
@@ -362,7 +434,8 @@ impl FileSystemManager for WindowsFileSystemManager {
         let normalized_path = self.normalize_windows_path(path);
         
         match fs::canonicalize(&normalized_path).await {
-            Ok(canonical) => Ok(canonical),
+            // canonicalize always returns \\?\ paths; store them in their plain form
+            Ok(canonical) => Ok(strip_extended_length_prefix(&canonical)),
             Err(err) => {
                 // If canonicalization fails, try to provide a helpful error
                 if err.kind() == std::io::ErrorKind::NotFound {
@@ -441,6 +514,65 @@ mod tests {
         assert_eq!(normalized, PathBuf::from(r"\\server\share\subfolder"));
     }
     
+    #[test]
+    fn test_extended_length_path_normalization() {
+        let manager = WindowsFileSystemManager::new();
+
+        assert_eq!(
+            strip_extended_length_prefix(Path::new(r"\\?\C:\Media\movie.mp4")),
+            PathBuf::from(r"C:\Media\movie.mp4")
+        );
+        assert_eq!(
+            strip_extended_length_prefix(Path::new(r"\\?\UNC\nas\share\Movies\movie.mp4")),
+            PathBuf::from(r"\\nas\share\Movies\movie.mp4")
+        );
+        assert_eq!(
+            strip_extended_length_prefix(Path::new(r"\\nas\share\Movies")),
+            PathBuf::from(r"\\nas\share\Movies")
+        );
+
+        // Extended-length and plain UNC forms of the same share normalize identically
+        assert_eq!(
+            manager.normalize_windows_path(Path::new(r"\\?\UNC\NAS\Share\Movies")),
+            manager.normalize_windows_path(Path::new(r"\\nas\share\movies"))
+        );
+        assert!(manager.paths_equal(
+            Path::new(r"\\?\C:\Media\Movie.mp4"),
+            Path::new(r"c:\media\movie.mp4")
+        ));
+    }
+    
+    #[test]
+    fn test_long_paths_get_extended_length_prefix() {
+        let manager = WindowsFileSystemManager::new();
+        let deep = vec!["Folder"; 45].join(r"\");
+
+        let long = manager.normalize_path(&PathBuf::from(format!(r"C:\Media\{}\.\Movie.mp4", deep)));
+        assert_eq!(long, PathBuf::from(format!(r"\\?\c:\media\{}\movie.mp4", deep.to_lowercase())));
+        // Normalizing again keeps the same form
+        assert_eq!(manager.normalize_path(&long), long);
+        assert!(manager.validate_path(&long).is_ok());
+        assert!(manager.paths_equal(&long, &PathBuf::from(format!(r"c:\media\{}\movie.mp4", deep))));
+
+        let unc = manager.normalize_path(&PathBuf::from(format!(r"\\NAS\Share\{}\extra\..\Movie.mp4", deep)));
+        assert_eq!(unc, PathBuf::from(format!(r"\\?\UNC\nas\share\{}\movie.mp4", deep.to_lowercase())));
+
+        // Relative paths cannot take the prefix and stay too long
+        let relative = PathBuf::from(format!(r"Media\{}\Movie.mp4", deep));
+        assert_eq!(manager.normalize_path(&relative), PathBuf::from(relative.to_string_lossy().to_lowercase()));
+        assert!(matches!(manager.validate_path(&relative), Err(FileSystemError::PathTooLong { .. })));
+    }
+
+    #[test]
+    fn test_short_paths_keep_plain_form() {
+        let manager = WindowsFileSystemManager::new();
+
+        assert_eq!(manager.normalize_path(Path::new(r"C:\Media\Movie.mp4")), PathBuf::from(r"c:\media\movie.mp4"));
+        assert_eq!(manager.normalize_path(Path::new(r"\\NAS\Share\Movie.mp4")), PathBuf::from(r"\\nas\share\movie.mp4"));
+        // A short extended-length path goes back to its plain form
+        assert_eq!(manager.normalize_path(Path::new(r"\\?\C:\Media")), PathBuf::from(r"c:\media"));
+    }
+
     #[test]
     fn test_reserved_name_validation() {
         let manager = WindowsFileSystemManager::new();
@@ -503,6 +635,31 @@ mod tests {
         assert!(!manager.is_hidden_windows(Path::new(r"C:\path\normal.txt")));
     }
     
+    #[test]
+    fn test_hidden_attribute_detection() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let hidden = temp_dir.path().join("movie.mp4");
+        let visible = temp_dir.path().join("visible.mp4");
+        std::fs::write(&hidden, b"video").unwrap();
+        std::fs::write(&visible, b"video").unwrap();
+        
+        let status = std::process::Command::new("attrib")
+            .arg("+h")
+            .arg(&hidden)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        
+        // The attribute is honored even though the name is not a dotfile
+        assert!(has_hidden_attribute(&hidden));
+        assert!(super::super::is_hidden_path(&hidden));
+        assert!(!super::super::is_hidden_path(&visible));
+        
+        let manager = WindowsFileSystemManager::new();
+        assert!(manager.is_hidden_windows(&hidden));
+        assert!(!manager.is_hidden_windows(&visible));
+    }
+    
     #[test]
     fn test_valid_drive_letter_paths() {
         let manager = WindowsFileSystemManager::new();
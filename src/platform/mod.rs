@@ -1,6 +1,5 @@
 use std::collections::HashMap;
 use std::net::IpAddr;
-use thiserror::Error;
 
 pub mod config;
 pub mod diagnostics;
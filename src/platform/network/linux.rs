@@ -1,6 +1,6 @@
 use crate::platform::{
     NetworkInterface, InterfaceType, PlatformError, PlatformResult,
-    network::{NetworkManager, SsdpSocket, SsdpConfig, NetworkDiagnostics, InterfaceStatus, FirewallStatus}
+    network::{bind_shared_udp, bind_with_fallback, NetworkManager, SsdpSocket, SsdpConfig, NetworkDiagnostics, InterfaceStatus, FirewallStatus, select_ssdp_interfaces, multicast_round_trip, MULTICAST_TEST_TIMEOUT}
 };
 use async_trait::async_trait;
 use std::net::{IpAddr, SocketAddr};
@@ -44,7 +44,7 @@ impl LinuxNetworkManager {
     async fn try_bind_port_linux(&self, port: u16) -> PlatformResult<UdpSocket> {
         let socket_addr = SocketAddr::from(([0, 0, 0, 0], port));
         
-        match UdpSocket::bind(socket_addr).await {
+        match bind_shared_udp(socket_addr) {
             Ok(socket) => {
                 debug!("Successfully bound to port {} on Linux", port);
                 Ok(socket)
@@ -127,7 +127,7 @@ impl LinuxNetworkManager {
             }
         } else if has_iptables {
             // Check iptables rules
-            match Command::new("iptables").args(&["-L", "-n"]).output() {
+            match Command::new("iptables").args(["-L", "-n"]).output() {
                 Ok(output) if output.status.success() => {
                     let output_str = String::from_utf8_lossy(&output.stdout);
                     detected = !output_str.is_empty() && output_str.lines().count() > 3; // More than just headers
@@ -199,7 +199,7 @@ impl LinuxNetworkManager {
     /// Parse output from 'ip addr show' command
     async fn parse_ip_command_output(&self) -> PlatformResult<Vec<NetworkInterface>> {
         let output = Command::new("ip")
-            .args(&["addr", "show"])
+            .args(["addr", "show"])
             .output()
             .map_err(|e| PlatformError::NetworkConfig(format!("Failed to run 'ip addr show': {}", e)))?;
         
@@ -224,7 +224,10 @@ impl LinuxNetworkManager {
             let line = line.trim();
             
             // Interface line: "2: eth0: <BROADCAST,MULTICAST,UP,LOWER_UP> mtu 1500 qdisc pfifo_fast state UP group default qlen 1000"
-            if let Some(colon_pos) = line.find(':') {
+            // Only lines starting with the numeric interface index are headers; MAC
+            // addresses on "link/ether" lines also contain colons.
+            let is_header = line.split(':').next().is_some_and(|idx| idx.parse::<u32>().is_ok());
+            if let Some(colon_pos) = line.find(':').filter(|_| is_header) {
                 if let Some(second_colon) = line[colon_pos + 1..].find(':') {
                     let second_colon_pos = colon_pos + 1 + second_colon;
                     
@@ -336,7 +339,7 @@ impl LinuxNetworkManager {
     /// Get IP address for a specific interface
     fn get_interface_ip(&self, interface_name: &str) -> Option<IpAddr> {
         match Command::new("ip")
-            .args(&["addr", "show", interface_name])
+            .args(["addr", "show", interface_name])
             .output()
         {
             Ok(output) if output.status.success() => {
@@ -394,11 +397,12 @@ impl LinuxNetworkManager {
     /// Enable multicast on Linux socket with proper interface binding
     async fn enable_multicast_linux(&self, socket: &mut SsdpSocket, group: IpAddr, interface: Option<&NetworkInterface>) -> PlatformResult<()> {
         let selected_interface = if let Some(iface) = interface {
-            iface
+            iface.clone()
         } else {
             // Use the first suitable interface
             socket.interfaces.iter()
                 .find(|iface| !iface.is_loopback && iface.is_up && iface.supports_multicast)
+                .cloned()
                 .ok_or_else(|| PlatformError::NetworkConfig("No suitable interface for multicast on Linux".to_string()))?
         };
         
@@ -438,61 +442,21 @@ impl NetworkManager for LinuxNetworkManager {
     }
     
     async fn create_ssdp_socket_with_config(&self, config: &SsdpConfig) -> PlatformResult<SsdpSocket> {
-        let mut last_error = None;
+        // Try the primary port first, then the fallback ports
+        let (socket, port) = bind_with_fallback(config, |port| self.try_bind_port_linux(port)).await?;
+        let interfaces = self.get_local_interfaces().await?;
+        let suitable_interfaces = select_ssdp_interfaces(interfaces);
         
-        // Try primary port first
-        match self.try_bind_port_linux(config.primary_port).await {
-            Ok(socket) => {
-                let interfaces = self.get_local_interfaces().await?;
-                let suitable_interfaces: Vec<_> = interfaces.into_iter()
-                    .filter(|iface| !iface.is_loopback && iface.is_up && iface.supports_multicast)
-                    .collect();
-                
-                if suitable_interfaces.is_empty() {
-                    return Err(PlatformError::NetworkConfig("No suitable network interfaces found on Linux".to_string()));
-                }
-                
-                return Ok(SsdpSocket {
-                    socket,
-                    port: config.primary_port,
-                    interfaces: suitable_interfaces,
-                    multicast_enabled: false,
-                });
-            }
-            Err(e) => {
-                warn!("Primary port {} failed on Linux: {}", config.primary_port, e);
-                last_error = Some(e);
-            }
+        if suitable_interfaces.is_empty() {
+            return Err(PlatformError::NetworkConfig("No suitable network interfaces found on Linux".to_string()));
         }
         
-        // Try fallback ports
-        for &port in &config.fallback_ports {
-            match self.try_bind_port_linux(port).await {
-                Ok(socket) => {
-                    info!("Using fallback port {} on Linux", port);
-                    let interfaces = self.get_local_interfaces().await?;
-                    let suitable_interfaces: Vec<_> = interfaces.into_iter()
-                        .filter(|iface| !iface.is_loopback && iface.is_up && iface.supports_multicast)
-                        .collect();
-                    
-                    return Ok(SsdpSocket {
-                        socket,
-                        port,
-                        interfaces: suitable_interfaces,
-                        multicast_enabled: false,
-                    });
-                }
-                Err(e) => {
-                    debug!("Fallback port {} failed on Linux: {}", port, e);
-                    last_error = Some(e);
-                }
-            }
-        }
-        
-        // If we get here, all ports failed
-        Err(last_error.unwrap_or_else(|| 
-            PlatformError::NetworkConfig("All ports failed on Linux".to_string())
-        ))
+        Ok(SsdpSocket {
+            socket,
+            port,
+            interfaces: suitable_interfaces,
+            multicast_enabled: false,
+        })
     }
     
     async fn get_local_interfaces(&self) -> PlatformResult<Vec<NetworkInterface>> {
@@ -584,10 +548,7 @@ impl NetworkManager for LinuxNetworkManager {
     }
     
     async fn is_port_available(&self, port: u16) -> bool {
-        match self.try_bind_port_linux(port).await {
-            Ok(_) => true,
-            Err(_) => false,
-        }
+        self.try_bind_port_linux(port).await.is_ok()
     }
     
     async fn get_network_diagnostics(&self) -> PlatformResult<NetworkDiagnostics> {
@@ -659,36 +620,12 @@ impl NetworkManager for LinuxNetworkManager {
     }
     
     async fn test_multicast(&self, interface: &NetworkInterface) -> PlatformResult<bool> {
-        // Basic test for Linux - check if interface supports multicast
         if !interface.supports_multicast || !interface.is_up || interface.is_loopback {
             return Ok(false);
         }
-        
-        // Try to create a test socket and join multicast group
-        match UdpSocket::bind("0.0.0.0:0").await {
-            Ok(test_socket) => {
-                match interface.ip_address {
-                    IpAddr::V4(local_v4) => {
-                        let multicast_addr = "239.255.255.250".parse::<std::net::Ipv4Addr>().unwrap();
-                        match test_socket.join_multicast_v4(multicast_addr, local_v4) {
-                            Ok(()) => {
-                                debug!("Multicast test successful on Linux interface {}", interface.name);
-                                Ok(true)
-                            }
-                            Err(e) => {
-                                debug!("Multicast test failed on Linux interface {}: {}", interface.name, e);
-                                Ok(false)
-                            }
-                        }
-                    }
-                    IpAddr::V6(_) => {
-                        // IPv6 multicast test would go here
-                        Ok(true) // Assume it works for now
-                    }
-                }
-            }
-            Err(_) => Ok(false),
-        }
+
+        // Send a datagram to the SSDP group and wait for it to loop back
+        Ok(multicast_round_trip(interface, MULTICAST_TEST_TIMEOUT).await)
     }
 }
 
@@ -797,6 +734,42 @@ mod tests {
         assert_eq!(wlan0.interface_type, InterfaceType::WiFi);
     }
     
+    #[test]
+    fn test_ip_addr_parsing_skips_link_layer_lines() {
+        let manager = LinuxNetworkManager::new();
+
+        // `ip addr show` on a desktop with Docker; the MAC addresses on the
+        // link/ether lines and the inet6 addresses contain colons too
+        let sample_output = r#"1: lo: <LOOPBACK,UP,LOWER_UP> mtu 65536 qdisc noqueue state UNKNOWN group default qlen 1000
+    link/loopback 00:00:00:00:00:00 brd 00:00:00:00:00:00
+    inet 127.0.0.1/8 scope host lo
+       valid_lft forever preferred_lft forever
+    inet6 ::1/128 scope host noprefixroute
+       valid_lft forever preferred_lft forever
+2: enp3s0: <BROADCAST,MULTICAST,UP,LOWER_UP> mtu 1500 qdisc fq_codel state UP group default qlen 1000
+    link/ether 3c:7c:3f:1e:4a:9b brd ff:ff:ff:ff:ff:ff
+    inet 10.0.0.12/24 brd 10.0.0.255 scope global dynamic noprefixroute enp3s0
+       valid_lft 85833sec preferred_lft 85833sec
+    inet6 fe80::3e7c:3fff:fe1e:4a9b/64 scope link noprefixroute
+       valid_lft forever preferred_lft forever
+3: docker0: <NO-CARRIER,BROADCAST,MULTICAST,UP> mtu 1500 qdisc noqueue state DOWN group default
+    link/ether 02:42:9a:1c:55:0e brd ff:ff:ff:ff:ff:ff
+    inet 172.17.0.1/16 brd 172.17.255.255 scope global docker0
+       valid_lft forever preferred_lft forever
+"#;
+
+        let interfaces = manager.parse_ip_addr_output(sample_output).unwrap();
+        let parsed: Vec<_> = interfaces.iter().map(|iface| (iface.name.as_str(), iface.ip_address)).collect();
+        assert_eq!(
+            parsed,
+            vec![
+                ("enp3s0", "10.0.0.12".parse::<IpAddr>().unwrap()),
+                ("docker0", "172.17.0.1".parse::<IpAddr>().unwrap()),
+            ]
+        );
+        assert!(interfaces.iter().all(|iface| iface.supports_multicast));
+    }
+
     #[test]
     fn test_network_namespaces() {
         let manager = LinuxNetworkManager::new();
@@ -1,5 +1,5 @@
 use crate::platform::{
-    network::{NetworkDiagnostics, NetworkManager, SsdpConfig, SsdpSocket, InterfaceStatus, FirewallStatus},
+    network::{bind_shared_udp, bind_with_fallback, NetworkDiagnostics, NetworkManager, SsdpConfig, SsdpSocket, InterfaceStatus, FirewallStatus, select_ssdp_interfaces, multicast_round_trip, MULTICAST_TEST_TIMEOUT},
     InterfaceType, NetworkInterface, PlatformError, PlatformResult,
 };
 use async_trait::async_trait;
@@ -44,7 +44,7 @@ impl MacOSNetworkManager {
     async fn try_bind_port_macos(&self, port: u16) -> PlatformResult<UdpSocket> {
         let socket_addr = SocketAddr::from(([0, 0, 0, 0], port));
         
-        match UdpSocket::bind(socket_addr).await {
+        match bind_shared_udp(socket_addr) {
             Ok(socket) => {
                 debug!("Successfully bound to port {} on macOS", port);
                 Ok(socket)
@@ -374,63 +374,23 @@ impl NetworkManager for MacOSNetworkManager {
     }
     
     async fn create_ssdp_socket_with_config(&self, config: &SsdpConfig) -> PlatformResult<SsdpSocket> {
-        // Try primary port first
-        match self.try_bind_port_macos(config.primary_port).await {
-            Ok(socket) => {
-                let interfaces = self.get_local_interfaces().await?;
-                let suitable_interfaces: Vec<_> = interfaces
-                    .into_iter()
-                    .filter(|iface| !iface.is_loopback && iface.is_up && iface.supports_multicast)
-                    .collect();
-
-                if suitable_interfaces.is_empty() {
-                    return Err(PlatformError::NetworkConfig(
-                        "No suitable network interfaces found on macOS".to_string(),
-                    ));
-                }
-
-                return Ok(SsdpSocket {
-                    socket,
-                    port: config.primary_port,
-                    interfaces: suitable_interfaces,
-                    multicast_enabled: false,
-                });
-            }
-            Err(primary_error) => {
-                warn!(
-                    "Primary port {} failed on macOS: {}. Trying fallback ports.",
-                    config.primary_port, primary_error
-                );
-
-                // Try fallback ports
-                for &port in &config.fallback_ports {
-                    if let Ok(socket) = self.try_bind_port_macos(port).await {
-                        info!("Using fallback port {} on macOS", port);
-                        let interfaces = self.get_local_interfaces().await?;
-                        let suitable_interfaces: Vec<_> = interfaces
-                            .into_iter()
-                            .filter(|iface| !iface.is_loopback && iface.is_up && iface.supports_multicast)
-                            .collect();
-                        
-                        if suitable_interfaces.is_empty() {
-                             return Err(PlatformError::NetworkConfig(
-                                "No suitable network interfaces found on macOS".to_string(),
-                            ));
-                        }
+        // Try the primary port first, then the fallback ports
+        let (socket, port) = bind_with_fallback(config, |port| self.try_bind_port_macos(port)).await?;
+        let interfaces = self.get_local_interfaces().await?;
+        let suitable_interfaces = select_ssdp_interfaces(interfaces);
 
-                        return Ok(SsdpSocket {
-                            socket,
-                            port,
-                            interfaces: suitable_interfaces,
-                            multicast_enabled: false,
-                        });
-                    }
-                }
-                
-                // If all fallbacks also fail, return the original error from the primary port
-                Err(primary_error)
-            }
+        if suitable_interfaces.is_empty() {
+            return Err(PlatformError::NetworkConfig(
+                "No suitable network interfaces found on macOS".to_string(),
+            ));
         }
+
+        Ok(SsdpSocket {
+            socket,
+            port,
+            interfaces: suitable_interfaces,
+            multicast_enabled: false,
+        })
     }
     
     async fn get_local_interfaces(&self) -> PlatformResult<Vec<NetworkInterface>> {
@@ -584,36 +544,12 @@ impl NetworkManager for MacOSNetworkManager {
     }
     
     async fn test_multicast(&self, interface: &NetworkInterface) -> PlatformResult<bool> {
-        // Basic test for macOS - check if interface supports multicast
         if !interface.supports_multicast || !interface.is_up || interface.is_loopback {
             return Ok(false);
         }
-        
-        // Try to create a test socket and join multicast group
-        match UdpSocket::bind("0.0.0.0:0").await {
-            Ok(test_socket) => {
-                match interface.ip_address {
-                    IpAddr::V4(local_v4) => {
-                        let multicast_addr = "239.255.255.250".parse::<std::net::Ipv4Addr>().unwrap();
-                        match test_socket.join_multicast_v4(multicast_addr, local_v4) {
-                            Ok(()) => {
-                                debug!("Multicast test successful on macOS interface {}", interface.name);
-                                Ok(true)
-                            }
-                            Err(e) => {
-                                debug!("Multicast test failed on macOS interface {}: {}", interface.name, e);
-                                Ok(false)
-                            }
-                        }
-                    }
-                    IpAddr::V6(_) => {
-                        // IPv6 multicast test would go here
-                        Ok(true) // Assume it works for now
-                    }
-                }
-            }
-            Err(_) => Ok(false),
-        }
+
+        // Send a datagram to the SSDP group and wait for it to loop back
+        Ok(multicast_round_trip(interface, MULTICAST_TEST_TIMEOUT).await)
     }
 }
 
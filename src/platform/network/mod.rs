@@ -1,6 +1,8 @@
+use crate::config::NetworkConfig;
 use crate::platform::{NetworkInterface, PlatformError, PlatformResult};
 use async_trait::async_trait;
-use std::net::{IpAddr, SocketAddr};
+use ipnet::IpNet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::time::Duration;
 use tokio::net::UdpSocket;
 use tracing::{debug, info, warn};
@@ -33,6 +35,42 @@ pub type PlatformNetworkManager = MacOSNetworkManager;
 #[cfg(target_os = "linux")]
 pub type PlatformNetworkManager = LinuxNetworkManager;
 
+/// Index of the network interface called `name`, as used for IPv6 scope ids
+/// and multicast membership
+pub fn interface_index(name: &str) -> Option<u32> {
+    let name = std::ffi::CString::new(name).ok()?;
+    #[cfg(unix)]
+    let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    #[cfg(windows)]
+    let index = unsafe {
+        windows::Win32::NetworkManagement::IpHelper::if_nametoindex(windows::core::PCSTR(name.as_ptr() as *const u8))
+    };
+    (index != 0).then_some(index)
+}
+
+/// Let other sockets bind the same port, so several DLNA servers and our own
+/// restarted SSDP service can listen on 1900 together. Unix also needs
+/// `SO_REUSEPORT` for two sockets to share a port; on Windows `SO_REUSEADDR`
+/// alone allows it.
+fn allow_port_sharing(socket: &socket2::Socket) -> std::io::Result<()> {
+    socket.set_reuse_address(true)?;
+    #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+    socket.set_reuse_port(true)?;
+    Ok(())
+}
+
+/// Bind a non-blocking UDP socket to `addr` that other SSDP listeners can share
+pub fn bind_shared_udp(addr: SocketAddr) -> std::io::Result<UdpSocket> {
+    let socket = socket2::Socket::new(socket2::Domain::for_address(addr), socket2::Type::DGRAM, Some(socket2::Protocol::UDP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    allow_port_sharing(&socket)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    UdpSocket::from_std(socket.into())
+}
+
 /// SSDP socket wrapper with platform-specific configuration
 #[derive(Debug)]
 pub struct SsdpSocket {
@@ -50,8 +88,7 @@ impl SsdpSocket {
     /// Create a new SSDP socket bound to the specified port
     pub async fn new(port: u16, interfaces: Vec<NetworkInterface>) -> PlatformResult<Self> {
         let socket_addr = SocketAddr::from(([0, 0, 0, 0], port));
-        let socket = UdpSocket::bind(socket_addr)
-            .await
+        let socket = bind_shared_udp(socket_addr)
             .map_err(|e| PlatformError::NetworkConfig(format!("Failed to bind to port {}: {}", port, e)))?;
         
         debug!("Created SSDP socket bound to port {}", port);
@@ -64,7 +101,26 @@ impl SsdpSocket {
         })
     }
     
-    /// Enable multicast on this socket for the specified group
+    /// Create an IPv6-only SSDP socket bound to the specified port, alongside
+    /// the IPv4 socket on the same port
+    pub async fn new_v6(port: u16, interfaces: Vec<NetworkInterface>) -> PlatformResult<Self> {
+        let bind_error = |e: std::io::Error| PlatformError::NetworkConfig(format!("Failed to bind to IPv6 port {}: {}", port, e));
+        let socket = bind_shared_udp(SocketAddr::from((Ipv6Addr::UNSPECIFIED, port))).map_err(bind_error)?;
+        let port = socket.local_addr().map_err(bind_error)?.port();
+
+        debug!("Created IPv6 SSDP socket bound to port {}", port);
+
+        Ok(SsdpSocket {
+            socket,
+            port,
+            interfaces,
+            multicast_enabled: false,
+        })
+    }
+
+    /// Enable multicast on this socket for the specified group. For IPv6
+    /// groups the membership is scoped to the interface owning `local_addr`,
+    /// or to the first multicast-capable interface of the socket.
     pub async fn enable_multicast(&mut self, multicast_addr: IpAddr, local_addr: IpAddr) -> PlatformResult<()> {
         match (multicast_addr, local_addr) {
             (IpAddr::V4(multi_v4), IpAddr::V4(local_v4)) => {
@@ -75,18 +131,48 @@ impl SsdpSocket {
                 Ok(())
             }
             (IpAddr::V6(multi_v6), _) => {
-                // For IPv6, we need to specify the interface index
-                // This is a simplified implementation - platform-specific implementations should handle this properly
-                self.socket.join_multicast_v6(&multi_v6, 0)
-                    .map_err(|e| PlatformError::NetworkConfig(format!("Failed to join IPv6 multicast group: {}", e)))?;
-                self.multicast_enabled = true;
-                info!("Enabled IPv6 multicast on port {} for group {}", self.port, multi_v6);
+                let interface = self
+                    .interfaces
+                    .iter()
+                    .find(|iface| iface.ip_address == local_addr)
+                    .or_else(|| self.interfaces.iter().find(|iface| iface.supports_multicast && !iface.is_loopback))
+                    .ok_or_else(|| PlatformError::NetworkConfig(format!("No interface for IPv6 multicast via {}", local_addr)))?;
+                let index = interface_index(&interface.name).ok_or_else(|| {
+                    PlatformError::NetworkConfig(format!("Unknown index for interface {}", interface.name))
+                })?;
+                let name = interface.name.clone();
+                self.enable_multicast_v6(multi_v6, index)?;
+                info!("Enabled IPv6 multicast on {} (index {}) port {} for group {}", name, index, self.port, multi_v6);
                 Ok(())
             }
             _ => Err(PlatformError::NetworkConfig("IP version mismatch for multicast".to_string()))
         }
     }
     
+    /// Join an IPv6 multicast group on the interface with index `interface_index`
+    /// and send multicast traffic through that interface
+    pub fn enable_multicast_v6(&mut self, group: Ipv6Addr, interface_index: u32) -> PlatformResult<()> {
+        self.socket
+            .join_multicast_v6(&group, interface_index)
+            .map_err(|e| PlatformError::NetworkConfig(format!("Failed to join IPv6 multicast group: {}", e)))?;
+        socket2::SockRef::from(&self.socket)
+            .set_multicast_if_v6(interface_index)
+            .map_err(|e| PlatformError::NetworkConfig(format!("Failed to select IPv6 multicast interface: {}", e)))?;
+        self.multicast_enabled = true;
+        Ok(())
+    }
+
+    /// Set how many hops outgoing multicast packets may travel; routed
+    /// multicast needs more than the link-local default of 1
+    pub fn set_multicast_ttl(&self, ttl: u32) -> PlatformResult<()> {
+        let result = if self.socket.local_addr().is_ok_and(|addr| addr.is_ipv6()) {
+            socket2::SockRef::from(&self.socket).set_multicast_hops_v6(ttl)
+        } else {
+            self.socket.set_multicast_ttl_v4(ttl)
+        };
+        result.map_err(|e| PlatformError::NetworkConfig(format!("Failed to set multicast TTL to {}: {}", ttl, e)))
+    }
+
     /// Send data to a specific address
     pub async fn send_to(&self, data: &[u8], addr: SocketAddr) -> PlatformResult<usize> {
         self.socket.send_to(data, addr)
@@ -123,6 +209,8 @@ pub struct SsdpConfig {
     pub announce_interval: Duration,
     /// Maximum number of retries for network operations
     pub max_retries: u32,
+    /// Time-to-live (IPv4) or hop limit (IPv6) of outgoing multicast packets
+    pub multicast_ttl: u32,
     /// Specific interfaces to use (empty means use all suitable interfaces)
     pub interfaces: Vec<NetworkInterface>,
 }
@@ -135,11 +223,247 @@ impl Default for SsdpConfig {
             multicast_address: "239.255.255.250".parse().unwrap(),
             announce_interval: Duration::from_secs(300), // 5 minutes
             max_retries: 3,
+            multicast_ttl: 4,
             interfaces: Vec::new(),
         }
     }
 }
 
+/// Bind the primary SSDP port of `config` with `bind`, falling back to each of
+/// its fallback ports in turn when the port is taken (for example by the
+/// Windows SSDP Discovery service or another DLNA server). Returns the bound
+/// value and the port it landed on.
+pub async fn bind_with_fallback<T, F, Fut>(config: &SsdpConfig, mut bind: F) -> PlatformResult<(T, u16)>
+where
+    F: FnMut(u16) -> Fut,
+    Fut: std::future::Future<Output = PlatformResult<T>>,
+{
+    let primary_error = match bind(config.primary_port).await {
+        Ok(bound) => return Ok((bound, config.primary_port)),
+        Err(e) => e,
+    };
+    warn!("SSDP port {} is unavailable: {}", config.primary_port, primary_error);
+
+    for &port in &config.fallback_ports {
+        match bind(port).await {
+            Ok(bound) => {
+                info!("SSDP bound to fallback port {} because port {} is in use", port, config.primary_port);
+                return Ok((bound, port));
+            }
+            Err(e) => debug!("SSDP fallback port {} is unavailable: {}", port, e),
+        }
+    }
+
+    Err(PlatformError::NetworkConfig(format!(
+        "Could not bind SSDP to port {} or any fallback port {:?}: {}",
+        config.primary_port, config.fallback_ports, primary_error
+    )))
+}
+
+/// How SSDP reaches clients on the available interfaces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiscoveryMode {
+    /// Announce and listen on the SSDP multicast group
+    Multicast,
+    /// No multicast-capable interface: answer M-SEARCH sent directly to this
+    /// host and announce via subnet broadcast
+    Unicast,
+}
+
+impl DiscoveryMode {
+    /// Select the mode supported by an SSDP socket's interfaces
+    pub fn for_interfaces(interfaces: &[NetworkInterface]) -> Self {
+        if interfaces.iter().any(|iface| iface.supports_multicast) {
+            DiscoveryMode::Multicast
+        } else {
+            DiscoveryMode::Unicast
+        }
+    }
+
+    /// Like [`DiscoveryMode::for_interfaces`], but multicast is only chosen
+    /// when at least one interface passes [`NetworkManager::test_multicast`]
+    pub async fn probe(network_manager: &dyn NetworkManager, interfaces: &[NetworkInterface]) -> Self {
+        if Self::for_interfaces(interfaces) == DiscoveryMode::Unicast {
+            return DiscoveryMode::Unicast;
+        }
+        for interface in interfaces.iter().filter(|iface| iface.supports_multicast) {
+            if network_manager.test_multicast(interface).await.unwrap_or(false) {
+                return DiscoveryMode::Multicast;
+            }
+        }
+        debug!("Multicast did not round-trip on any SSDP interface; using unicast discovery");
+        DiscoveryMode::Unicast
+    }
+}
+
+/// SSDP multicast group used by the multicast self-test
+const SSDP_GROUP_V4: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+const SSDP_GROUP_V6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xc);
+
+/// How long the multicast self-test waits for its datagram to come back
+pub const MULTICAST_TEST_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Check that `interface` really delivers multicast: join the SSDP group on
+/// it, send a datagram to the group with loopback enabled and wait up to
+/// `timeout` for it to arrive back. The probe uses an ephemeral port, so SSDP
+/// listeners on port 1900 never see it.
+pub async fn multicast_round_trip(interface: &NetworkInterface, timeout: Duration) -> bool {
+    match try_multicast_round_trip(interface, timeout).await {
+        Ok(true) => {
+            debug!("Multicast round-trip succeeded on interface {}", interface.name);
+            true
+        }
+        Ok(false) => {
+            debug!("Multicast test datagram did not return on interface {} within {:?}", interface.name, timeout);
+            false
+        }
+        Err(e) => {
+            debug!("Multicast test failed on interface {}: {}", interface.name, e);
+            false
+        }
+    }
+}
+
+async fn try_multicast_round_trip(interface: &NetworkInterface, timeout: Duration) -> PlatformResult<bool> {
+    let setup_error = |e: std::io::Error| PlatformError::NetworkConfig(format!("Multicast test setup failed: {}", e));
+    let (mut socket, group) = match interface.ip_address {
+        IpAddr::V4(_) => (SsdpSocket::new(0, vec![interface.clone()]).await?, IpAddr::V4(SSDP_GROUP_V4)),
+        IpAddr::V6(_) => (SsdpSocket::new_v6(0, vec![interface.clone()]).await?, IpAddr::V6(SSDP_GROUP_V6)),
+    };
+    let port = socket.socket.local_addr().map_err(setup_error)?.port();
+    socket.enable_multicast(group, interface.ip_address).await?;
+
+    let target = match interface.ip_address {
+        IpAddr::V4(local_v4) => {
+            let sock = socket2::SockRef::from(&socket.socket);
+            sock.set_multicast_if_v4(&local_v4).map_err(setup_error)?;
+            sock.set_multicast_loop_v4(true).map_err(setup_error)?;
+            sock.set_multicast_ttl_v4(1).map_err(setup_error)?;
+            SocketAddr::new(group, port)
+        }
+        IpAddr::V6(_) => {
+            socket2::SockRef::from(&socket.socket).set_multicast_loop_v6(true).map_err(setup_error)?;
+            let scope_id = interface_index(&interface.name).unwrap_or(0);
+            SocketAddr::V6(std::net::SocketAddrV6::new(SSDP_GROUP_V6, port, 0, scope_id))
+        }
+    };
+
+    // A per-probe nonce, so a stray datagram on the group cannot pass for ours
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or_default();
+    let nonce = format!("vuio-multicast-test {} {} {}", std::process::id(), interface.name, nanos);
+    socket.send_to(nonce.as_bytes(), target).await?;
+
+    let mut buf = [0u8; 256];
+    let echoed = tokio::time::timeout(timeout, async {
+        loop {
+            match socket.recv_from(&mut buf).await {
+                Ok((len, _)) if &buf[..len] == nonce.as_bytes() => return true,
+                Ok(_) => continue,
+                Err(_) => return false,
+            }
+        }
+    })
+    .await;
+    Ok(echoed.unwrap_or(false))
+}
+
+/// Parse a client subnet in CIDR notation; a bare address stands for that single host
+pub fn parse_client_subnet(subnet: &str) -> Result<IpNet, ipnet::AddrParseError> {
+    let subnet = subnet.trim();
+    subnet
+        .parse::<IpNet>()
+        .or_else(|e| subnet.parse::<IpAddr>().map(IpNet::from).map_err(|_| e))
+}
+
+/// Which clients the server answers, from the `network` client subnet lists
+#[derive(Debug, Clone, Default)]
+pub struct ClientFilter {
+    allowed: Vec<IpNet>,
+    blocked: Vec<IpNet>,
+}
+
+impl ClientFilter {
+    /// Build the filter from the configured subnets; entries that fail to
+    /// parse were already rejected by validation and are skipped
+    pub fn from_config(network: &NetworkConfig) -> Self {
+        let parse = |subnets: &[String]| -> Vec<IpNet> {
+            subnets.iter().filter_map(|subnet| parse_client_subnet(subnet).ok()).collect()
+        };
+        Self {
+            allowed: parse(&network.allowed_client_subnets),
+            blocked: parse(&network.blocked_client_subnets),
+        }
+    }
+
+    /// Whether requests from `ip` should be answered. Blocked subnets win over
+    /// allowed ones, and an empty allowlist admits every other client.
+    pub fn permits(&self, ip: IpAddr) -> bool {
+        // IPv4 clients reaching a dual-stack socket show up as ::ffff:a.b.c.d
+        let ip = ip.to_canonical();
+        if self.blocked.iter().any(|subnet| subnet.contains(&ip)) {
+            return false;
+        }
+        self.allowed.is_empty() || self.allowed.iter().any(|subnet| subnet.contains(&ip))
+    }
+}
+
+/// Interfaces an SSDP socket should serve: the multicast-capable ones when any
+/// exist, otherwise every active non-loopback interface so discovery degrades
+/// to unicast rather than failing
+pub fn select_ssdp_interfaces(interfaces: Vec<NetworkInterface>) -> Vec<NetworkInterface> {
+    let active: Vec<_> = interfaces
+        .into_iter()
+        .filter(|iface| !iface.is_loopback && iface.is_up)
+        .collect();
+    if active.iter().any(|iface| iface.supports_multicast) {
+        active.into_iter().filter(|iface| iface.supports_multicast).collect()
+    } else {
+        if !active.is_empty() {
+            warn!("No multicast-capable network interface found; SSDP discovery will use unicast only");
+        }
+        active
+    }
+}
+
+/// Host part of an HTTP URL reaching `ip`. IPv6 literals are bracketed, and a
+/// link-local one carries `zone` as `%25<zone>`, the RFC 6874 encoding that
+/// keeps the URL valid in HTTP headers and XML.
+pub fn url_host(ip: IpAddr, zone: Option<&str>) -> String {
+    match ip.to_canonical() {
+        IpAddr::V4(v4) => v4.to_string(),
+        IpAddr::V6(v6) => match zone.filter(|_| v6.is_unicast_link_local()) {
+            Some(zone) => format!("[{}%25{}]", v6, zone),
+            None => format!("[{}]", v6),
+        },
+    }
+}
+
+/// Zone of the interface with index `scope_id`: its name when it is one of
+/// `interfaces`, otherwise the index itself
+pub fn scope_zone(scope_id: u32, interfaces: &[NetworkInterface]) -> String {
+    interfaces
+        .iter()
+        .find(|iface| interface_index(&iface.name) == Some(scope_id))
+        .map(|iface| iface.name.clone())
+        .unwrap_or_else(|| scope_id.to_string())
+}
+
+/// Local address the host sends traffic to `peer` from, in the peer's address
+/// family. Connecting a UDP socket only consults the routing table, so nothing
+/// is sent.
+pub fn local_address_for(peer: SocketAddr) -> Option<SocketAddr> {
+    let unspecified = match peer {
+        SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+        SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+    };
+    let socket = std::net::UdpSocket::bind(unspecified).ok()?;
+    socket.connect(peer).ok()?;
+    socket.local_addr().ok()
+}
+
 /// Cross-platform network manager trait
 #[async_trait]
 pub trait NetworkManager: Send + Sync {
@@ -234,8 +558,7 @@ impl BaseNetworkManager {
     /// Try to bind to a specific port
     async fn try_bind_port(&self, port: u16) -> PlatformResult<UdpSocket> {
         let socket_addr = SocketAddr::from(([0, 0, 0, 0], port));
-        UdpSocket::bind(socket_addr)
-            .await
+        bind_shared_udp(socket_addr)
             .map_err(|e| PlatformError::NetworkConfig(format!("Failed to bind to port {}: {}", port, e)))
     }
     
@@ -264,10 +587,7 @@ impl BaseNetworkManager {
     
     /// Internal method to check if a port is available
     async fn is_port_available_internal(&self, port: u16) -> bool {
-        match self.try_bind_port(port).await {
-            Ok(_) => true,
-            Err(_) => false,
-        }
+        self.try_bind_port(port).await.is_ok()
     }
     
     /// Filter interfaces to find suitable ones for DLNA
@@ -315,7 +635,7 @@ impl NetworkManager for BaseNetworkManager {
             config.interfaces.clone()
         };
         
-        let suitable_interfaces = self.filter_suitable_interfaces(interfaces);
+        let suitable_interfaces = select_ssdp_interfaces(interfaces);
         if suitable_interfaces.is_empty() {
             return Err(PlatformError::NetworkConfig("No suitable network interfaces found".to_string()));
         }
@@ -454,9 +774,10 @@ impl NetworkManager for BaseNetworkManager {
     }
     
     async fn test_multicast(&self, interface: &NetworkInterface) -> PlatformResult<bool> {
-        // Basic test - just check if the interface claims to support multicast
-        // Platform-specific implementations should do actual multicast testing
-        Ok(interface.supports_multicast && interface.is_up && !interface.is_loopback)
+        if !interface.supports_multicast || !interface.is_up || interface.is_loopback {
+            return Ok(false);
+        }
+        Ok(multicast_round_trip(interface, MULTICAST_TEST_TIMEOUT).await)
     }
 }
 
@@ -469,7 +790,6 @@ impl Default for BaseNetworkManager {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::net::Ipv4Addr;
     use crate::platform::InterfaceType;
     
     fn create_test_interface(name: &str, ip: &str, interface_type: InterfaceType) -> NetworkInterface {
@@ -483,6 +803,23 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_url_host_formats_each_address_family() {
+        let host = |ip: &str, zone| url_host(ip.parse().unwrap(), zone);
+        assert_eq!(host("192.168.1.10", Some("eth0")), "192.168.1.10");
+        assert_eq!(host("2001:db8::10", Some("eth0")), "[2001:db8::10]");
+        assert_eq!(host("2001:db8::10", None), "[2001:db8::10]");
+        assert_eq!(host("fe80::1", Some("eth0")), "[fe80::1%25eth0]");
+        assert_eq!(host("fe80::1", None), "[fe80::1]");
+        // IPv4 clients of a dual-stack socket appear as mapped IPv6 addresses
+        assert_eq!(host("::ffff:192.168.1.10", None), "192.168.1.10");
+
+        let link_local = create_test_interface("eth0", "fe80::1", InterfaceType::Ethernet);
+        assert_eq!(scope_zone(u32::MAX, &[link_local]), u32::MAX.to_string());
+        let local = local_address_for("127.0.0.1:1900".parse().unwrap()).unwrap();
+        assert_eq!(local.ip(), "127.0.0.1".parse::<IpAddr>().unwrap());
+    }
+
     #[test]
     fn test_ssdp_config_default() {
         let config = SsdpConfig::default();
@@ -557,4 +894,143 @@ mod tests {
         assert_eq!(prioritized[1].name, "wlan0"); // WiFi should be second
         assert_eq!(prioritized[2].name, "vpn0"); // VPN should be last
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_unicast_discovery_without_multicast_interfaces() {
+        let mut interfaces = vec![
+            create_test_interface("eth0", "192.168.1.100", InterfaceType::Ethernet),
+            create_test_interface("wlan0", "192.168.2.100", InterfaceType::WiFi),
+        ];
+        for iface in &mut interfaces {
+            iface.supports_multicast = false;
+        }
+
+        let selected = select_ssdp_interfaces(interfaces.clone());
+        assert_eq!(selected.len(), 2);
+        assert_eq!(DiscoveryMode::for_interfaces(&selected), DiscoveryMode::Unicast);
+
+        // Socket creation no longer fails outright without multicast
+        let manager = BaseNetworkManager::with_config(SsdpConfig {
+            primary_port: 0,
+            interfaces,
+            ..SsdpConfig::default()
+        });
+        let socket = manager.create_ssdp_socket().await.unwrap();
+        assert!(!socket.multicast_enabled);
+        assert_eq!(DiscoveryMode::for_interfaces(&socket.interfaces), DiscoveryMode::Unicast);
+
+        // Multicast-capable interfaces are preferred when any exist
+        let mixed = vec![
+            NetworkInterface { supports_multicast: false, ..create_test_interface("eth1", "10.0.0.2", InterfaceType::Ethernet) },
+            create_test_interface("eth0", "192.168.1.100", InterfaceType::Ethernet),
+        ];
+        let selected = select_ssdp_interfaces(mixed);
+        assert_eq!(selected.len(), 1);
+        assert_eq!(DiscoveryMode::for_interfaces(&selected), DiscoveryMode::Multicast);
+    }
+
+    #[tokio::test]
+    async fn test_multicast_requires_round_trip() {
+        let manager = BaseNetworkManager::new();
+
+        // Claiming multicast support is not enough when the address is not ours
+        let foreign = create_test_interface("eth0", "192.0.2.1", InterfaceType::Ethernet);
+        assert!(!manager.test_multicast(&foreign).await.unwrap());
+        let down = NetworkInterface { is_up: false, ..foreign.clone() };
+        assert!(!manager.test_multicast(&down).await.unwrap());
+
+        // An interface that fails the round-trip drives discovery to unicast
+        let mode = DiscoveryMode::probe(&manager, std::slice::from_ref(&foreign)).await;
+        assert_eq!(DiscoveryMode::for_interfaces(std::slice::from_ref(&foreign)), DiscoveryMode::Multicast);
+        assert_eq!(mode, DiscoveryMode::Unicast);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs multicast routing on the loopback interface"]
+    async fn test_multicast_round_trip_on_loopback() {
+        let loopback = NetworkInterface {
+            is_loopback: true,
+            ..create_test_interface("lo", "127.0.0.1", InterfaceType::Loopback)
+        };
+        assert!(multicast_round_trip(&loopback, Duration::from_secs(2)).await);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_ipv6_ssdp_socket_joins_group_on_interface_index() {
+        let Some((name, index)) = ["lo", "lo0"].iter().find_map(|name| interface_index(name).map(|i| (*name, i))) else {
+            println!("No loopback interface index available, skipping");
+            return;
+        };
+        let group: Ipv6Addr = "ff02::c".parse().unwrap();
+        let loopback = create_test_interface(name, "::1", InterfaceType::Loopback);
+
+        let mut socket = match SsdpSocket::new_v6(0, vec![loopback]).await {
+            Ok(socket) => socket,
+            Err(e) => {
+                println!("IPv6 is not available on this host, skipping: {}", e);
+                return;
+            }
+        };
+        socket.enable_multicast(IpAddr::V6(group), "::1".parse().unwrap()).await.unwrap();
+        assert!(socket.multicast_enabled);
+
+        // Multicast traffic leaves through the interface the membership is scoped to
+        assert_eq!(socket2::SockRef::from(&socket.socket).multicast_if_v6().unwrap(), index);
+
+        // Interfaces without a system index cannot scope the membership
+        let mut unknown = SsdpSocket::new_v6(0, vec![create_test_interface("vuio-missing0", "::1", InterfaceType::Ethernet)])
+            .await
+            .unwrap();
+        assert!(unknown.enable_multicast(IpAddr::V6(group), "::1".parse().unwrap()).await.is_err());
+    }
+
+    #[test]
+    fn test_client_filter_blocks_and_allows_subnets() {
+        let mut network = crate::config::AppConfig::default_for_platform().network;
+        assert!(ClientFilter::from_config(&network).permits("203.0.113.9".parse().unwrap()));
+
+        network.allowed_client_subnets = vec!["192.168.1.0/24".to_string(), "fd00::/8".to_string()];
+        network.blocked_client_subnets = vec!["192.168.1.77".to_string()];
+        let filter = ClientFilter::from_config(&network);
+        assert!(filter.permits("192.168.1.20".parse().unwrap()));
+        assert!(filter.permits("fd12::1".parse().unwrap()));
+        assert!(filter.permits("::ffff:192.168.1.20".parse().unwrap()));
+        assert!(!filter.permits("192.168.1.77".parse().unwrap()));
+        assert!(!filter.permits("::ffff:192.168.1.77".parse().unwrap()));
+        assert!(!filter.permits("10.0.0.5".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_bind_with_fallback_skips_ports_in_use() {
+        let bind = |port: u16| async move {
+            UdpSocket::bind(("127.0.0.1", port))
+                .await
+                .map_err(|e| PlatformError::NetworkConfig(format!("Failed to bind to port {}: {}", port, e)))
+        };
+        let taken = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let taken_port = taken.local_addr().unwrap().port();
+        let free_port = UdpSocket::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+
+        let config = SsdpConfig { primary_port: taken_port, fallback_ports: vec![taken_port, free_port], ..SsdpConfig::default() };
+        let (socket, port) = bind_with_fallback(&config, bind).await.unwrap();
+        assert_eq!(port, free_port);
+        assert_eq!(socket.local_addr().unwrap().port(), free_port);
+
+        // With every port taken the error names the ports that were tried
+        let config = SsdpConfig { primary_port: taken_port, fallback_ports: vec![free_port], ..SsdpConfig::default() };
+        let error = bind_with_fallback(&config, bind).await.unwrap_err().to_string();
+        assert!(error.contains(&format!("port {} or any fallback port [{}]", taken_port, free_port)), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_shared_sockets_bind_the_same_port() {
+        let first = bind_shared_udp("0.0.0.0:0".parse().unwrap()).unwrap();
+        let port = first.local_addr().unwrap().port();
+        let second = bind_shared_udp(SocketAddr::from(([0, 0, 0, 0], port))).unwrap();
+        assert_eq!(second.local_addr().unwrap().port(), port);
+
+        // A socket that does not opt into sharing is still refused
+        assert!(UdpSocket::bind(SocketAddr::from(([0, 0, 0, 0], port))).await.is_err());
+    }
+}
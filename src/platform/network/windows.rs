@@ -1,5 +1,5 @@
 use crate::platform::{
-    network::{NetworkDiagnostics, NetworkManager, SsdpConfig, SsdpSocket, InterfaceStatus, FirewallStatus},
+    network::{bind_shared_udp, bind_with_fallback, NetworkDiagnostics, NetworkManager, SsdpConfig, SsdpSocket, InterfaceStatus, FirewallStatus, select_ssdp_interfaces, multicast_round_trip, MULTICAST_TEST_TIMEOUT},
     InterfaceType, NetworkInterface, PlatformError, PlatformResult,
 };
 use async_trait::async_trait;
@@ -45,11 +45,8 @@ impl WindowsNetworkManager {
     async fn try_bind_port_windows(&self, port: u16) -> PlatformResult<UdpSocket> {
         let socket_addr = SocketAddr::from(([0, 0, 0, 0], port));
 
-        match UdpSocket::bind(socket_addr).await {
+        match bind_shared_udp(socket_addr) {
             Ok(socket) => {
-                // Set SO_REUSEADDR for Windows compatibility
-                // Note: tokio's UdpSocket doesn't expose setsockopt directly
-                // In a real implementation, you would use raw sockets or winapi
                 debug!("Successfully bound to port {} on Windows", port);
                 Ok(socket)
             }
@@ -181,63 +178,24 @@ impl NetworkManager for WindowsNetworkManager {
         &self,
         config: &SsdpConfig,
     ) -> PlatformResult<SsdpSocket> {
-        // Try primary port first
-        let primary_result = self.try_bind_port_windows(config.primary_port).await;
-
-        if let Ok(socket) = primary_result {
-            let interfaces = self.get_local_interfaces().await?;
-            let suitable_interfaces: Vec<_> = interfaces
-                .into_iter()
-                .filter(|iface| !iface.is_loopback && iface.is_up && iface.supports_multicast)
-                .collect();
-
-            if suitable_interfaces.is_empty() {
-                return Err(PlatformError::NetworkConfig(
-                    "No suitable network interfaces found on Windows".to_string(),
-                ));
-            }
-
-            return Ok(SsdpSocket {
-                socket,
-                port: config.primary_port,
-                interfaces: suitable_interfaces,
-                multicast_enabled: false,
-            });
-        }
+        // Try the primary port first, then the fallback ports; port 1900 is
+        // often held by the Windows SSDP Discovery service
+        let (socket, port) = bind_with_fallback(config, |port| self.try_bind_port_windows(port)).await?;
+        let interfaces = self.get_local_interfaces().await?;
+        let suitable_interfaces = select_ssdp_interfaces(interfaces);
 
-        let primary_error = primary_result.unwrap_err();
-        warn!(
-            "Primary port {} failed on Windows: {}",
-            config.primary_port, primary_error
-        );
-        let mut last_error = primary_error;
-
-        // Try fallback ports
-        for &port in &config.fallback_ports {
-            match self.try_bind_port_windows(port).await {
-                Ok(socket) => {
-                    info!("Using fallback port {} on Windows", port);
-                    let interfaces = self.get_local_interfaces().await?;
-                    let suitable_interfaces: Vec<_> = interfaces
-                        .into_iter()
-                        .filter(|iface| !iface.is_loopback && iface.is_up && iface.supports_multicast)
-                        .collect();
-
-                    return Ok(SsdpSocket {
-                        socket,
-                        port,
-                        interfaces: suitable_interfaces,
-                        multicast_enabled: false,
-                    });
-                }
-                Err(e) => {
-                    debug!("Fallback port {} failed on Windows: {}", port, e);
-                    last_error = e;
-                }
-            }
+        if suitable_interfaces.is_empty() {
+            return Err(PlatformError::NetworkConfig(
+                "No suitable network interfaces found on Windows".to_string(),
+            ));
         }
 
-        Err(last_error)
+        Ok(SsdpSocket {
+            socket,
+            port,
+            interfaces: suitable_interfaces,
+            multicast_enabled: false,
+        })
     }
 
     async fn get_local_interfaces(&self) -> PlatformResult<Vec<NetworkInterface>> {
@@ -437,41 +395,12 @@ impl NetworkManager for WindowsNetworkManager {
     }
 
     async fn test_multicast(&self, interface: &NetworkInterface) -> PlatformResult<bool> {
-        // Basic test for Windows - check if interface supports multicast
         if !interface.supports_multicast || !interface.is_up || interface.is_loopback {
             return Ok(false);
         }
 
-        // Try to create a test socket and join multicast group
-        // This is a simplified test - real implementation would be more thorough
-        match UdpSocket::bind("0.0.0.0:0").await {
-            Ok(test_socket) => match interface.ip_address {
-                IpAddr::V4(local_v4) => {
-                    let multicast_addr = "239.255.255.250".parse::<std::net::Ipv4Addr>().unwrap();
-                    match test_socket.join_multicast_v4(multicast_addr, local_v4) {
-                        Ok(()) => {
-                            debug!(
-                                "Multicast test successful on Windows interface {}",
-                                interface.name
-                            );
-                            Ok(true)
-                        }
-                        Err(e) => {
-                            debug!(
-                                "Multicast test failed on Windows interface {}: {}",
-                                interface.name, e
-                            );
-                            Ok(false)
-                        }
-                    }
-                }
-                IpAddr::V6(_) => {
-                    // IPv6 multicast test would go here
-                    Ok(true) // Assume it works for now
-                }
-            },
-            Err(_) => Ok(false),
-        }
+        // Send a datagram to the SSDP group and wait for it to loop back
+        Ok(multicast_round_trip(interface, MULTICAST_TEST_TIMEOUT).await)
     }
 }
 
@@ -0,0 +1,99 @@
+//! A single shutdown signal shared by every long-running service.
+//!
+//! `main` creates one [`Shutdown`] and hands a clone to each service; the
+//! first Ctrl+C (or a programmatic [`Shutdown::trigger`]) wakes all of them at
+//! once, so no service has to listen for the OS signal itself.
+
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Cloneable handle to the shared shutdown signal
+#[derive(Clone)]
+pub struct Shutdown {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self {
+            sender: Arc::new(watch::channel(false).0),
+        }
+    }
+
+    /// Ask every service to stop; triggering again has no further effect
+    pub fn trigger(&self) {
+        self.sender.send_replace(true);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    /// Resolve once shutdown has been triggered, immediately if it already was
+    pub async fn wait(&self) {
+        let mut receiver = self.sender.subscribe();
+        // The sender lives as long as `self`, so the channel cannot close here
+        let _ = receiver.wait_for(|triggered| *triggered).await;
+    }
+
+    /// Trigger shutdown on the first Ctrl+C
+    pub fn trigger_on_ctrl_c(&self) -> tokio::task::JoinHandle<()> {
+        let shutdown = self.clone();
+        tokio::spawn(async move {
+            match tokio::signal::ctrl_c().await {
+                Ok(()) => {
+                    tracing::info!("Received shutdown signal (Ctrl+C)");
+                    shutdown.trigger();
+                }
+                Err(e) => tracing::error!("Failed to listen for Ctrl+C: {}", e),
+            }
+        })
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_trigger_stops_spawned_services() {
+        let shutdown = Shutdown::new();
+        let services: Vec<_> = (0..3)
+            .map(|_| {
+                let shutdown = shutdown.clone();
+                tokio::spawn(async move {
+                    let mut ticks = tokio::time::interval(Duration::from_millis(5));
+                    let mut ticked = 0;
+                    loop {
+                        tokio::select! {
+                            _ = ticks.tick() => ticked += 1,
+                            _ = shutdown.wait() => return ticked,
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(!shutdown.is_triggered());
+        shutdown.trigger();
+        for service in services {
+            let ticked = tokio::time::timeout(Duration::from_secs(1), service)
+                .await
+                .expect("service should stop once shutdown is triggered")
+                .unwrap();
+            assert!(ticked > 0);
+        }
+
+        // Late waiters and repeated triggers return at once
+        shutdown.trigger();
+        tokio::time::timeout(Duration::from_millis(100), shutdown.wait()).await.unwrap();
+    }
+}
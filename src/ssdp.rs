@@ -1,38 +1,406 @@
 use crate::state::AppState;
-use crate::platform::network::{NetworkManager, SsdpConfig, PlatformNetworkManager};
+use crate::platform::network::{
+    interface_index, local_address_for, scope_zone, select_ssdp_interfaces, url_host, DiscoveryMode,
+    NetworkManager, PlatformNetworkManager, SsdpConfig, SsdpSocket,
+};
+use crate::config::NetworkConfig;
+use crate::platform::{PlatformInfo, PlatformResult};
 use anyhow::Result;
-use std::{net::SocketAddr, sync::Arc, time::Duration};
-use tokio::time::interval;
-use tracing::{debug, error, info, warn};
+use rand::Rng;
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    net::{IpAddr, Ipv6Addr, SocketAddr, SocketAddrV6},
+    sync::atomic::{AtomicU16, Ordering},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, trace, warn};
 
-const SSDP_MULTICAST_ADDR: &str = "239.255.255.250";
+const SSDP_MULTICAST_V4: std::net::Ipv4Addr = std::net::Ipv4Addr::new(239, 255, 255, 250);
 const SSDP_PORT: u16 = 1900;
-const ANNOUNCE_INTERVAL_SECS: u64 = 300; // Announce every 5 minutes
+/// Device and service types advertised besides the root device and its UUID
+const DEVICE_AND_SERVICE_TYPES: [&str; 3] = [
+    "urn:schemas-upnp-org:device:MediaServer:1",
+    "urn:schemas-upnp-org:service:ContentDirectory:1",
+    "urn:schemas-upnp-org:service:ConnectionManager:1",
+];
+/// Spacing between the repeated announcements sent at startup
+const INITIAL_ANNOUNCE_SPACING_MS: u64 = 300;
 
-pub fn run_ssdp_service(state: AppState) -> Result<()> {
-    let network_manager = Arc::new(PlatformNetworkManager::new());
+/// Start line and headers of a received or sent SSDP message
+#[derive(Debug, Clone, PartialEq)]
+pub struct SsdpMessage {
+    pub start_line: String,
+    /// Header values keyed by upper-cased header name
+    headers: HashMap<String, String>,
+}
+
+impl SsdpMessage {
+    /// Parse an SSDP packet, describing why it is malformed on failure
+    pub fn parse(packet: &[u8]) -> std::result::Result<Self, String> {
+        let text = std::str::from_utf8(packet).map_err(|_| "packet is not valid UTF-8".to_string())?;
+        let mut lines = text.split("\r\n").flat_map(|line| line.split('\n'));
+
+        let start_line = lines.next().unwrap_or_default().trim().to_string();
+        if !start_line.contains("HTTP/1.") {
+            return Err(format!("invalid start line {:?}", start_line));
+        }
+
+        let mut headers = HashMap::new();
+        for line in lines.map(str::trim).filter(|line| !line.is_empty()) {
+            let (name, value) = line
+                .split_once(':')
+                .ok_or_else(|| format!("invalid header line {:?}", line))?;
+            headers.insert(name.trim().to_ascii_uppercase(), value.trim().to_string());
+        }
+
+        let message = Self { start_line, headers };
+        if message.is_search() && message.header("ST").is_none() {
+            return Err("M-SEARCH without ST header".to_string());
+        }
+        Ok(message)
+    }
+
+    /// Look up a header value by case-insensitive name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_ascii_uppercase()).map(String::as_str)
+    }
+
+    pub fn is_search(&self) -> bool {
+        self.start_line.starts_with("M-SEARCH ")
+    }
+}
+
+/// Notification type and unique service name of one SSDP advertisement. The
+/// NT doubles as the ST of the matching M-SEARCH response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SsdpTarget {
+    pub nt: String,
+    pub usn: String,
+}
+
+/// Every advertisement of the MediaServer root device with `uuid`: the root
+/// device, the device UUID, the device type and each embedded service
+pub fn advertisement_targets(uuid: &str) -> Vec<SsdpTarget> {
+    let device = format!("uuid:{}", uuid);
+    let mut targets = vec![
+        SsdpTarget { nt: "upnp:rootdevice".to_string(), usn: format!("{}::upnp:rootdevice", device) },
+        SsdpTarget { nt: device.clone(), usn: device.clone() },
+    ];
+    targets.extend(DEVICE_AND_SERVICE_TYPES.iter().map(|nt| SsdpTarget {
+        nt: nt.to_string(),
+        usn: format!("{}::{}", device, nt),
+    }));
+    targets
+}
+
+/// Advertisements answering an M-SEARCH for `st`: all of them for `ssdp:all`,
+/// otherwise the one whose NT is `st`, if any
+pub fn search_targets(uuid: &str, st: &str) -> Vec<SsdpTarget> {
+    let st = st.trim();
+    advertisement_targets(uuid)
+        .into_iter()
+        .filter(|target| st == "ssdp:all" || target.nt.eq_ignore_ascii_case(st))
+        .collect()
+}
+
+/// Logs SSDP traffic when `network.trace_ssdp` is enabled.
+///
+/// Packet summaries are logged at debug level and raw packets as hex at trace
+/// level, so normal operation stays quiet.
+#[derive(Debug, Clone, Copy)]
+pub struct SsdpTracer {
+    enabled: bool,
+}
+
+impl SsdpTracer {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled }
+    }
+
+    /// Log a well-formed packet received from `from`
+    pub fn inbound(&self, from: SocketAddr, message: &SsdpMessage, packet: &[u8]) {
+        if !self.enabled {
+            return;
+        }
+        if message.is_search() {
+            debug!(
+                "SSDP <- M-SEARCH from {} ST={} MX={}",
+                from,
+                message.header("ST").unwrap_or("-"),
+                message.header("MX").unwrap_or("-")
+            );
+        } else {
+            debug!("SSDP <- {} from {} NT={}", message.start_line, from, message.header("NT").unwrap_or("-"));
+        }
+        trace!("SSDP <- {} raw: {}", from, hex_dump(packet));
+    }
+
+    /// Log a packet that could not be parsed; these are always logged at debug level
+    pub fn malformed(&self, from: SocketAddr, reason: &str, packet: &[u8]) {
+        debug!("Dropping malformed SSDP packet from {} ({} bytes): {}", from, packet.len(), reason);
+        if self.enabled {
+            trace!("SSDP <- {} raw: {}", from, hex_dump(packet));
+        }
+    }
+
+    /// Log a NOTIFY or search response sent to `to`
+    pub fn outbound(&self, to: impl Display, packet: &[u8]) {
+        if !self.enabled {
+            return;
+        }
+        match SsdpMessage::parse(packet) {
+            Ok(message) => {
+                let kind = if message.start_line.starts_with("NOTIFY") { "NOTIFY" } else { "response" };
+                debug!("SSDP -> {} to {} USN={}", kind, to, message.header("USN").unwrap_or("-"));
+            }
+            Err(reason) => debug!("SSDP -> unparseable packet to {}: {}", to, reason),
+        }
+        trace!("SSDP -> {} raw: {}", to, hex_dump(packet));
+    }
+}
+
+/// Format bytes as space-separated lowercase hex
+pub fn hex_dump(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ")
+}
+
+/// Shortest max-age derived from the announce interval, the UPnP minimum
+const MIN_DERIVED_MAX_AGE_SECONDS: u64 = 1800;
+
+/// Lifetime advertised in `CACHE-CONTROL: max-age`: `network.ssdp_max_age_seconds`,
+/// or three announce intervals so a control point keeps the device through two
+/// lost announcements, but no less than the UPnP minimum of 1800 seconds
+pub fn ssdp_max_age(network: &NetworkConfig) -> Duration {
+    let seconds = network.ssdp_max_age_seconds.unwrap_or_else(|| {
+        network
+            .announce_interval_seconds
+            .saturating_mul(3)
+            .max(MIN_DERIVED_MAX_AGE_SECONDS)
+    });
+    Duration::from_secs(seconds)
+}
 
+/// Compute the delay before the next announcement with random jitter applied.
+///
+/// The result stays within ±`jitter_percent` of `base` and never exceeds half
+/// of the advertised `max_age`, so clients do not expire the device between
+/// announcements.
+pub fn jittered_announce_interval(base: Duration, jitter_percent: u8, max_age: Duration) -> Duration {
+    let max_interval = max_age / 2;
+    let base_ms = base.as_millis() as u64;
+    let jitter_ms = base_ms * jitter_percent.min(100) as u64 / 100;
+    let interval_ms = if jitter_ms == 0 {
+        base_ms
+    } else {
+        let offset = rand::thread_rng().gen_range(0..=jitter_ms * 2);
+        (base_ms + offset).saturating_sub(jitter_ms)
+    };
+    Duration::from_millis(interval_ms).min(max_interval)
+}
+
+/// Compute a random delay before the first announcement so that several
+/// instances started together do not announce in lockstep.
+pub fn initial_announce_delay(base: Duration, jitter_percent: u8) -> Duration {
+    let jitter_ms = base.as_millis() as u64 * jitter_percent.min(100) as u64 / 100;
+    if jitter_ms == 0 {
+        return Duration::ZERO;
+    }
+    Duration::from_millis(rand::thread_rng().gen_range(0..=jitter_ms))
+}
+
+/// Send the startup burst of announcements, `count` times `spacing` apart.
+///
+/// Control points that were already running see the server right away instead
+/// of waiting a full announce interval. Returns the number of successful sends.
+pub async fn send_initial_announcements<F, Fut>(count: u8, spacing: Duration, mut send: F) -> u8
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<()>>,
+{
+    let mut sent = 0;
+    for attempt in 1..=count {
+        match send().await {
+            Ok(()) => sent += 1,
+            Err(e) => warn!("Initial SSDP announcement {} of {} failed: {}", attempt, count, e),
+        }
+        if attempt < count {
+            tokio::time::sleep(spacing).await;
+        }
+    }
+    sent
+}
+
+/// The running SSDP responder and announcer; dropping the handle stops both
+/// and closes their sockets
+pub struct SsdpService {
+    tasks: Vec<JoinHandle<()>>,
+    /// Port of the search responder's socket, 0 until it is bound
+    bound_port: Arc<AtomicU16>,
+}
+
+impl SsdpService {
+    pub fn stop(self) {}
+
+    /// Port the search responder listens on once bound: the configured SSDP
+    /// port, or a fallback port when that one was already in use
+    pub fn bound_port(&self) -> Option<u16> {
+        match self.bound_port.load(Ordering::Relaxed) {
+            0 => None,
+            port => Some(port),
+        }
+    }
+}
+
+impl Drop for SsdpService {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+pub fn run_ssdp_service(state: AppState) -> Result<SsdpService> {
+    run_ssdp_service_with(state, Arc::new(PlatformNetworkManager::new()))
+}
+
+/// Start the SSDP service using `network_manager` for its sockets
+pub fn run_ssdp_service_with(state: AppState, network_manager: Arc<dyn NetworkManager>) -> Result<SsdpService> {
     // Task for responding to M-SEARCH requests
     let search_state = state.clone();
     let search_manager = network_manager.clone();
-    tokio::spawn(async move {
-        if let Err(e) = ssdp_search_responder(search_state, search_manager).await {
+    let bound_port = Arc::new(AtomicU16::new(0));
+    let responder_port = bound_port.clone();
+    let responder = tokio::spawn(async move {
+        if let Err(e) = ssdp_search_responder(search_state, search_manager, responder_port).await {
             error!("SSDP search responder failed: {}", e);
         }
     });
 
+    // Task answering M-SEARCH requests sent to the IPv6 groups
+    let search_state = state.clone();
+    let search_manager = network_manager.clone();
+    let responder_v6 = tokio::spawn(async move {
+        ssdp_search_responder_v6(search_state, search_manager).await;
+    });
+
     // Task for periodically sending NOTIFY announcements
     let announce_state = state;
     let announce_manager = network_manager;
-    tokio::spawn(async move {
+    let announcer = tokio::spawn(async move {
         ssdp_announcer(announce_state, announce_manager).await;
     });
 
     info!("SSDP service started with platform abstraction");
-    Ok(())
+    Ok(SsdpService { tasks: vec![responder, responder_v6, announcer], bound_port })
+}
+
+/// What [`SsdpController::update_platform_info`] did with the SSDP service
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SsdpAdaptation {
+    Unchanged,
+    /// Rebound to a new primary interface or address
+    Restarted,
+    /// Stopped because no usable interface is left
+    Paused,
+    /// Started again once an interface became available
+    Resumed,
+}
+
+/// Keeps the SSDP service bound to the current primary network interface,
+/// restarting it when the interface or its address changes
+pub struct SsdpController {
+    state: AppState,
+    network_manager: Arc<dyn NetworkManager>,
+    service: Option<SsdpService>,
 }
 
-async fn ssdp_search_responder(state: AppState, network_manager: Arc<PlatformNetworkManager>) -> Result<()> {
+impl SsdpController {
+    /// Start the SSDP service for the interfaces in `state`
+    pub fn start(state: AppState, network_manager: Arc<dyn NetworkManager>) -> Result<Self> {
+        let service = run_ssdp_service_with(state.clone(), network_manager.clone())?;
+        Ok(Self {
+            state,
+            network_manager,
+            service: Some(service),
+        })
+    }
+
+    /// Platform information the service is currently bound with
+    pub fn platform_info(&self) -> Arc<PlatformInfo> {
+        self.state.platform_info.get()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.service.is_some()
+    }
+
+    /// Port the running service answers searches on, once bound
+    pub fn bound_port(&self) -> Option<u16> {
+        self.service.as_ref()?.bound_port()
+    }
+
+    /// Adopt freshly detected platform information, rebinding the service if
+    /// the primary interface changed, pausing it while no interface is
+    /// available and resuming it when one reappears. The update is seen by
+    /// everything sharing the state, such as the HTTP handlers.
+    pub fn update_platform_info(&mut self, platform_info: PlatformInfo) -> Result<SsdpAdaptation> {
+        let old_primary = self.state.platform_info.get().get_primary_interface().cloned();
+        let new_primary = platform_info.get_primary_interface().cloned();
+        self.state.platform_info.set(platform_info);
+
+        let Some(new_primary) = new_primary else {
+            return Ok(match self.service.take() {
+                Some(service) => {
+                    service.stop();
+                    SsdpAdaptation::Paused
+                }
+                None => SsdpAdaptation::Unchanged,
+            });
+        };
+
+        let adaptation = if self.service.is_none() {
+            SsdpAdaptation::Resumed
+        } else if old_primary.is_none_or(|old| old.name != new_primary.name || old.ip_address != new_primary.ip_address) {
+            SsdpAdaptation::Restarted
+        } else {
+            return Ok(SsdpAdaptation::Unchanged);
+        };
+
+        // The old sockets must be closed before the new ones bind the SSDP port
+        if let Some(service) = self.service.take() {
+            service.stop();
+        }
+        self.service = Some(run_ssdp_service_with(self.state.clone(), self.network_manager.clone())?);
+        Ok(adaptation)
+    }
+
+    /// Tell control points the server is leaving, returning the number of
+    /// `ssdp:byebye` notifications sent
+    pub async fn send_byebye(&self) -> Result<usize> {
+        send_ssdp_byebye(&self.state, self.network_manager.as_ref()).await
+    }
+
+    /// Announce departure and stop the service, closing its sockets
+    pub async fn shutdown(&mut self) -> Result<()> {
+        let Some(service) = self.service.take() else {
+            return Ok(());
+        };
+        // Stop answering searches first so no late response contradicts the byebye
+        service.stop();
+        let sent = self.send_byebye().await?;
+        info!("Sent {} SSDP byebye notifications", sent);
+        Ok(())
+    }
+}
+
+async fn ssdp_search_responder(
+    state: AppState,
+    network_manager: Arc<dyn NetworkManager>,
+    bound_port: Arc<AtomicU16>,
+) -> Result<()> {
     const MAX_SOCKET_RETRIES: u32 = 3;
     const MAX_MULTICAST_RETRIES: u32 = 5;
     const RETRY_DELAY_MS: u64 = 1000;
@@ -40,8 +408,7 @@ async fn ssdp_search_responder(state: AppState, network_manager: Arc<PlatformNet
     // Create SSDP socket with retry logic
     let mut socket = None;
     for attempt in 1..=MAX_SOCKET_RETRIES {
-        let ssdp_config = SsdpConfig::default();
-        match network_manager.create_ssdp_socket_with_config(&ssdp_config).await {
+        match open_ssdp_socket(network_manager.as_ref(), &ssdp_config(&state.config.network)).await {
             Ok(s) => {
                 info!("Successfully created SSDP socket on port {} (attempt {})", s.port, attempt);
                 socket = Some(s);
@@ -61,34 +428,49 @@ async fn ssdp_search_responder(state: AppState, network_manager: Arc<PlatformNet
 
     let mut socket = socket.unwrap();
     let socket_port = socket.port;
+    bound_port.store(socket_port, Ordering::Relaxed);
+    if socket_port != state.config.network.ssdp_port {
+        warn!(
+            "SSDP is listening on fallback port {} instead of {}; control points searching on port {} will not find this server",
+            socket_port, state.config.network.ssdp_port, state.config.network.ssdp_port
+        );
+    }
 
-    // Join multicast group with retry logic, using the primary interface from AppState
-    let multicast_addr = SSDP_MULTICAST_ADDR.parse().unwrap();
-    let primary_interface = state.platform_info.get_primary_interface().cloned();
+    // Join the IPv4 multicast groups with retry logic, using the primary interface from AppState
+    let multicast_groups = configured_groups(&state, false);
+    let primary_interface = state.platform_info.get().get_primary_interface().cloned();
     
     let mut multicast_enabled = false;
+    let discovery_mode = DiscoveryMode::probe(network_manager.as_ref(), &socket.interfaces).await;
+    if discovery_mode == DiscoveryMode::Unicast {
+        log_degraded_discovery(&state, socket_port).await;
+    }
     
-    for attempt in 1..=MAX_MULTICAST_RETRIES {
-        match network_manager.join_multicast_group(&mut socket, multicast_addr, primary_interface.as_ref()).await {
-            Ok(()) => {
-                info!("Successfully joined SSDP multicast group on port {} (attempt {})", socket_port, attempt);
-                multicast_enabled = true;
-                break;
-            }
-            Err(e) => {
-                warn!("Failed to join multicast group (attempt {}): {}", attempt, e);
-                if attempt < MAX_MULTICAST_RETRIES {
-                    warn!("Retrying multicast join in {}ms...", RETRY_DELAY_MS);
-                    tokio::time::sleep(Duration::from_millis(RETRY_DELAY_MS)).await;
-                } else {
-                    error!("Failed to join multicast group after {} attempts, continuing with unicast only", MAX_MULTICAST_RETRIES);
+    // Without a multicast-capable interface there is no group to join
+    let multicast_attempts = if discovery_mode == DiscoveryMode::Multicast { MAX_MULTICAST_RETRIES } else { 0 };
+    for &multicast_addr in &multicast_groups {
+        for attempt in 1..=multicast_attempts {
+            match network_manager.join_multicast_group(&mut socket, multicast_addr, primary_interface.as_ref()).await {
+                Ok(()) => {
+                    info!("Successfully joined SSDP multicast group {} on port {} (attempt {})", multicast_addr, socket_port, attempt);
+                    multicast_enabled = true;
                     break;
                 }
+                Err(e) => {
+                    warn!("Failed to join multicast group {} (attempt {}): {}", multicast_addr, attempt, e);
+                    if attempt < MAX_MULTICAST_RETRIES {
+                        warn!("Retrying multicast join in {}ms...", RETRY_DELAY_MS);
+                        tokio::time::sleep(Duration::from_millis(RETRY_DELAY_MS)).await;
+                    } else {
+                        error!("Failed to join multicast group {} after {} attempts, continuing with unicast only", multicast_addr, MAX_MULTICAST_RETRIES);
+                        break;
+                    }
+                }
             }
         }
     }
 
-    if !multicast_enabled {
+    if !multicast_enabled && discovery_mode == DiscoveryMode::Multicast {
         warn!("SSDP search responder running without multicast support - discovery may be limited");
         warn!("Troubleshooting tips:");
         warn!("  - Check firewall settings for UDP port {}", socket_port);
@@ -96,6 +478,7 @@ async fn ssdp_search_responder(state: AppState, network_manager: Arc<PlatformNet
         warn!("  - Try running with elevated privileges if using port < 1024");
     }
 
+    let tracer = SsdpTracer::new(state.config.network.trace_ssdp);
     let mut buf = vec![0u8; 2048];
     let mut consecutive_errors = 0;
     const MAX_CONSECUTIVE_ERRORS: u32 = 10;
@@ -104,75 +487,16 @@ async fn ssdp_search_responder(state: AppState, network_manager: Arc<PlatformNet
         match socket.recv_from(&mut buf).await {
             Ok((len, addr)) => {
                 consecutive_errors = 0; // Reset error counter on success
-                let request = String::from_utf8_lossy(&buf[..len]);
-
-                if request.contains("M-SEARCH") {
-                    debug!("Received M-SEARCH from {}", addr);
-                    debug!("M-SEARCH request content: {}", request.trim());
-                    
-                    // Check for various SSDP discovery patterns and determine response types
-                    let mut response_types = Vec::new();
-                    
-                    if request.contains("ssdp:all") {
-                        // Respond with all service types
-                        response_types.push("upnp:rootdevice");
-                        response_types.push("urn:schemas-upnp-org:device:MediaServer:1");
-                        response_types.push("urn:schemas-upnp-org:service:ContentDirectory:1");
-                    } else if request.contains("upnp:rootdevice") {
-                        response_types.push("upnp:rootdevice");
-                    } else if request.contains("urn:schemas-upnp-org:device:MediaServer") {
-                        response_types.push("urn:schemas-upnp-org:device:MediaServer:1");
-                    } else if request.contains("urn:schemas-upnp-org:service:ContentDirectory") {
-                        response_types.push("urn:schemas-upnp-org:service:ContentDirectory:1");
-                    } else if request.contains("ssdp:discover") {
-                        // Generic discovery - respond with main device type
-                        response_types.push("urn:schemas-upnp-org:device:MediaServer:1");
-                    }
-                    
-                    if !response_types.is_empty() {
-                        debug!("Sending {} SSDP response(s) to {} for types: {:?}", response_types.len(), addr, response_types);
-                        
-                        let mut all_responses_sent = true;
-                        let response_count = response_types.len();
-                        for response_type in response_types {
-                            let response = create_ssdp_response(&state, socket_port, response_type).await;
-                            debug!("Sending SSDP response to {} ({}): {}", addr, response_type, response.trim());
-                            
-                            // Retry response sending with exponential backoff
-                            let mut response_sent = false;
-                            for retry in 0..3 {
-                                match socket.send_to(response.as_bytes(), addr).await {
-                                    Ok(_) => {
-                                        debug!("Successfully sent M-SEARCH response to {} for {} (attempt {})", addr, response_type, retry + 1);
-                                        response_sent = true;
-                                        break;
-                                    }
-                                    Err(e) => {
-                                        warn!("Failed to send M-SEARCH response to {} for {} (attempt {}): {}", addr, response_type, retry + 1, e);
-                                        if retry < 2 {
-                                            tokio::time::sleep(Duration::from_millis(100 * (1 << retry))).await;
-                                        }
-                                    }
-                                }
-                            }
-                            
-                            if !response_sent {
-                                error!("Failed to send M-SEARCH response to {} for {} after 3 attempts", addr, response_type);
-                                all_responses_sent = false;
-                            }
-                            
-                            // Small delay between multiple responses to avoid overwhelming the client
-                            if response_count > 1 {
-                                tokio::time::sleep(Duration::from_millis(50)).await;
-                            }
-                        }
-                        
-                        if !all_responses_sent {
-                            warn!("Some M-SEARCH responses to {} failed to send", addr);
-                        }
-                    } else {
-                        info!("M-SEARCH request from {} doesn't match our service types, ignoring", addr);
+                let message = match SsdpMessage::parse(&buf[..len]) {
+                    Ok(message) => message,
+                    Err(reason) => {
+                        tracer.malformed(addr, &reason, &buf[..len]);
+                        continue;
                     }
+                };
+                tracer.inbound(addr, &message, &buf[..len]);
+                if message.is_search() {
+                    answer_search(&state, &socket, &tracer, addr, &message).await;
                 }
             }
             Err(e) => {
@@ -183,16 +507,18 @@ async fn ssdp_search_responder(state: AppState, network_manager: Arc<PlatformNet
                     error!("Too many consecutive errors ({}), attempting to recreate socket", MAX_CONSECUTIVE_ERRORS);
                     
                     // Try to recreate the socket
-                    let ssdp_config = SsdpConfig::default();
-                    match network_manager.create_ssdp_socket_with_config(&ssdp_config).await {
+                    match open_ssdp_socket(network_manager.as_ref(), &ssdp_config(&state.config.network)).await {
                         Ok(new_socket) => {
                             info!("Successfully recreated SSDP socket on port {}", new_socket.port);
+                            bound_port.store(new_socket.port, Ordering::Relaxed);
                             socket = new_socket;
                             consecutive_errors = 0;
                             
-                            // Try to rejoin multicast group
-                            if let Err(e) = network_manager.join_multicast_group(&mut socket, multicast_addr, primary_interface.as_ref()).await {
-                                warn!("Failed to rejoin multicast group after socket recreation: {}", e);
+                            // Try to rejoin the multicast groups
+                            for &multicast_addr in &multicast_groups {
+                                if let Err(e) = network_manager.join_multicast_group(&mut socket, multicast_addr, primary_interface.as_ref()).await {
+                                    warn!("Failed to rejoin multicast group {} after socket recreation: {}", multicast_addr, e);
+                                }
                             }
                         }
                         Err(e) => {
@@ -210,15 +536,204 @@ async fn ssdp_search_responder(state: AppState, network_manager: Arc<PlatformNet
     }
 }
 
-async fn ssdp_announcer(state: AppState, network_manager: Arc<PlatformNetworkManager>) {
-    let mut interval = interval(Duration::from_secs(ANNOUNCE_INTERVAL_SECS));
+/// SSDP socket settings from the `network` configuration
+pub fn ssdp_config(network: &NetworkConfig) -> SsdpConfig {
+    SsdpConfig {
+        primary_port: network.ssdp_port,
+        announce_interval: Duration::from_secs(network.announce_interval_seconds),
+        multicast_ttl: network.multicast_ttl.into(),
+        ..Default::default()
+    }
+}
+
+/// Create an SSDP socket with the configured multicast TTL applied
+pub async fn open_ssdp_socket(network_manager: &dyn NetworkManager, config: &SsdpConfig) -> PlatformResult<SsdpSocket> {
+    let socket = network_manager.create_ssdp_socket_with_config(config).await?;
+    apply_multicast_ttl(&socket, config.multicast_ttl);
+    Ok(socket)
+}
+
+fn apply_multicast_ttl(socket: &SsdpSocket, ttl: u32) {
+    match socket.set_multicast_ttl(ttl) {
+        Ok(()) => debug!("SSDP multicast TTL set to {} on port {}", ttl, socket.port),
+        Err(e) => warn!("{}; using the system default", e),
+    }
+}
+
+/// Configured SSDP multicast groups of one address family
+fn configured_groups(state: &AppState, ipv6: bool) -> Vec<IpAddr> {
+    state
+        .config
+        .network
+        .multicast_groups
+        .iter()
+        .copied()
+        .filter(|group| group.is_ipv6() == ipv6)
+        .collect()
+}
+
+/// Value of the HOST header for messages sent to `group`
+fn ssdp_host(group: IpAddr) -> String {
+    SocketAddr::new(group, SSDP_PORT).to_string()
+}
+
+/// Open an IPv6 SSDP socket on `port` that has joined every IPv6 group,
+/// or `None` when the host has no usable IPv6 multicast interface or the
+/// HTTP server does not listen on IPv6, so IPv6 clients could not reach it
+async fn open_ipv6_socket(state: &AppState, network_manager: &dyn NetworkManager, port: u16) -> Option<SsdpSocket> {
+    let groups = configured_groups(state, true);
+    if groups.is_empty() {
+        return None;
+    }
+    if ipv6_listen_address(state).is_none() {
+        debug!("IPv6 SSDP skipped: the HTTP server listens on {}, not IPv6", state.config.server.interface);
+        return None;
+    }
+    let interfaces = match network_manager.get_local_interfaces().await {
+        Ok(interfaces) => select_ssdp_interfaces(interfaces),
+        Err(e) => {
+            debug!("IPv6 SSDP unavailable: {}", e);
+            return None;
+        }
+    };
+    let mut socket = match SsdpSocket::new_v6(port, interfaces).await {
+        Ok(socket) => socket,
+        Err(e) => {
+            debug!("IPv6 SSDP unavailable: {}", e);
+            return None;
+        }
+    };
+    apply_multicast_ttl(&socket, state.config.network.multicast_ttl.into());
+
+    let primary_interface = state.platform_info.get().get_primary_interface().cloned();
+    for group in groups {
+        if let Err(e) = network_manager.join_multicast_group(&mut socket, group, primary_interface.as_ref()).await {
+            debug!("Not joining IPv6 SSDP group {}: {}", group, e);
+        }
+    }
+    socket.multicast_enabled.then_some(socket)
+}
+
+/// Answer M-SEARCH requests sent to the IPv6 multicast groups. Hosts without
+/// IPv6 are served by the IPv4 responder alone.
+async fn ssdp_search_responder_v6(state: AppState, network_manager: Arc<dyn NetworkManager>) {
+    let Some(socket) = open_ipv6_socket(&state, network_manager.as_ref(), SSDP_PORT).await else {
+        info!("IPv6 SSDP discovery is not available on this host");
+        return;
+    };
+    info!("Listening for IPv6 SSDP searches on port {}", socket.port);
+
+    let tracer = SsdpTracer::new(state.config.network.trace_ssdp);
+    let mut buf = vec![0u8; 2048];
+    loop {
+        match socket.recv_from(&mut buf).await {
+            Ok((len, addr)) => {
+                let message = match SsdpMessage::parse(&buf[..len]) {
+                    Ok(message) => message,
+                    Err(reason) => {
+                        tracer.malformed(addr, &reason, &buf[..len]);
+                        continue;
+                    }
+                };
+                tracer.inbound(addr, &message, &buf[..len]);
+                if message.is_search() {
+                    answer_search(&state, &socket, &tracer, addr, &message).await;
+                }
+            }
+            Err(e) => {
+                warn!("Error receiving IPv6 SSDP data: {}", e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
+
+/// Answer an M-SEARCH request from `addr` with one response per matching search target
+async fn answer_search(state: &AppState, socket: &SsdpSocket, tracer: &SsdpTracer, addr: SocketAddr, request: &SsdpMessage) {
+    // Filtered clients are dropped without a log line so a noisy device cannot flood the logs
+    if !state.client_filter.permits(addr.ip()) {
+        trace!("Ignoring M-SEARCH from filtered client {}", addr);
+        return;
+    }
+    let st = request.header("ST").unwrap_or_default();
+    debug!("Received M-SEARCH from {} for {}", addr, st);
+    let targets = search_targets(&state.config.server.uuid, st);
+
+    if !targets.is_empty() {
+        debug!("Sending {} SSDP response(s) to {} for {}", targets.len(), addr, st);
+        
+        let mut all_responses_sent = true;
+        let response_count = targets.len();
+        for target in &targets {
+            let response_type = &target.nt;
+            let response = create_ssdp_response(state, addr, target).await;
+            debug!("Sending SSDP response to {} ({}): {}", addr, response_type, response.trim());
+            
+            // Retry response sending with exponential backoff
+            let mut response_sent = false;
+            for retry in 0..3 {
+                match socket.send_to(response.as_bytes(), addr).await {
+                    Ok(_) => {
+                        debug!("Successfully sent M-SEARCH response to {} for {} (attempt {})", addr, response_type, retry + 1);
+                        tracer.outbound(addr, response.as_bytes());
+                        state.metrics.record_ssdp_response();
+                        response_sent = true;
+                        break;
+                    }
+                    Err(e) => {
+                        warn!("Failed to send M-SEARCH response to {} for {} (attempt {}): {}", addr, response_type, retry + 1, e);
+                        if retry < 2 {
+                            tokio::time::sleep(Duration::from_millis(100 * (1 << retry))).await;
+                        }
+                    }
+                }
+            }
+            
+            if !response_sent {
+                error!("Failed to send M-SEARCH response to {} for {} after 3 attempts", addr, response_type);
+                all_responses_sent = false;
+            }
+            
+            // Small delay between multiple responses to avoid overwhelming the client
+            if response_count > 1 {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        }
+        
+        if !all_responses_sent {
+            warn!("Some M-SEARCH responses to {} failed to send", addr);
+        }
+    } else {
+        debug!("M-SEARCH from {} for {} doesn't match our advertisements, ignoring", addr, st);
+    }
+}
+
+async fn ssdp_announcer(state: AppState, network_manager: Arc<dyn NetworkManager>) {
+    let base_interval = ssdp_config(&state.config.network).announce_interval;
+    let jitter_percent = state.config.network.announce_jitter_percent;
+    let max_age = ssdp_max_age(&state.config.network);
     let mut consecutive_failures = 0;
     const MAX_CONSECUTIVE_FAILURES: u32 = 5;
+
+    // Desynchronize from other instances before the first announcement
+    tokio::time::sleep(initial_announce_delay(base_interval, jitter_percent)).await;
+
+    let burst = state.config.network.initial_announce_count;
+    let sent = send_initial_announcements(
+        burst,
+        Duration::from_millis(INITIAL_ANNOUNCE_SPACING_MS),
+        || send_ssdp_alive(&state, network_manager.as_ref()),
+    )
+    .await;
+    info!("Sent {} of {} initial SSDP announcements", sent, burst);
     
     loop {
-        interval.tick().await;
-        
-        match send_ssdp_alive(&state, &network_manager).await {
+        // Jitter desynchronizes the periodic announcements from other instances
+        let next_announce = jittered_announce_interval(base_interval, jitter_percent, max_age);
+        debug!("Next SSDP announcement in {:?}", next_announce);
+        tokio::time::sleep(next_announce).await;
+
+        match send_ssdp_alive(&state, network_manager.as_ref()).await {
             Ok(()) => {
                 consecutive_failures = 0; // Reset failure counter on success
             }
@@ -245,7 +760,7 @@ async fn ssdp_announcer(state: AppState, network_manager: Arc<PlatformNetworkMan
     }
 }
 
-async fn send_ssdp_alive(state: &AppState, network_manager: &PlatformNetworkManager) -> Result<()> {
+async fn send_ssdp_alive(state: &AppState, network_manager: &dyn NetworkManager) -> Result<()> {
     const MAX_SOCKET_CREATION_RETRIES: u32 = 3;
     const MAX_SEND_RETRIES: u32 = 3;
     
@@ -254,8 +769,7 @@ async fn send_ssdp_alive(state: &AppState, network_manager: &PlatformNetworkMana
     // Create a temporary socket for announcements with retry logic
     let mut socket = None;
     for attempt in 1..=MAX_SOCKET_CREATION_RETRIES {
-        let ssdp_config = SsdpConfig::default();
-        match network_manager.create_ssdp_socket_with_config(&ssdp_config).await {
+        match open_ssdp_socket(network_manager, &ssdp_config(&state.config.network)).await {
             Ok(s) => {
                 socket = Some(s);
                 break;
@@ -273,76 +787,59 @@ async fn send_ssdp_alive(state: &AppState, network_manager: &PlatformNetworkMana
     }
 
     let mut socket = socket.unwrap();
+    let discovery_mode = DiscoveryMode::probe(network_manager, &socket.interfaces).await;
 
     // Enable multicast on the announcement socket, using the primary interface from AppState
-    let multicast_addr_ip = SSDP_MULTICAST_ADDR.parse().unwrap();
-    let primary_interface = state.platform_info.get_primary_interface().cloned();
-    if let Err(e) = network_manager.join_multicast_group(&mut socket, multicast_addr_ip, primary_interface.as_ref()).await {
-        warn!("Failed to enable multicast on announcement socket: {}", e);
+    let multicast_groups = configured_groups(state, false);
+    let primary_interface = state.platform_info.get().get_primary_interface().cloned();
+    if discovery_mode == DiscoveryMode::Multicast {
+        for &group in &multicast_groups {
+            if let Err(e) = network_manager.join_multicast_group(&mut socket, group, primary_interface.as_ref()).await {
+                warn!("Failed to enable multicast for {} on announcement socket: {}", group, e);
+            }
+        }
     }
+    // Unicast mode goes straight to the broadcast fallback
+    let multicast_attempts = if discovery_mode == DiscoveryMode::Multicast && !multicast_groups.is_empty() {
+        MAX_SEND_RETRIES
+    } else {
+        0
+    };
 
     let server_ip = get_server_ip(state).await;
-    let config = &state.config;
-
-    // Send NOTIFY for multiple service types
-    let service_types = [
-        "upnp:rootdevice",
-        "urn:schemas-upnp-org:device:MediaServer:1",
-        "urn:schemas-upnp-org:service:ContentDirectory:1"
-    ];
-    
-    let multicast_addr = format!("{}:{}", SSDP_MULTICAST_ADDR, SSDP_PORT).parse::<SocketAddr>()?;
+    let tracer = SsdpTracer::new(state.config.network.trace_ssdp);
     
-    for service_type in &service_types {
-        let (nt, usn) = match *service_type {
-            "upnp:rootdevice" => (
-                "upnp:rootdevice".to_string(),
-                format!("uuid:{}::upnp:rootdevice", config.server.uuid)
-            ),
-            "urn:schemas-upnp-org:device:MediaServer:1" => (
-                "urn:schemas-upnp-org:device:MediaServer:1".to_string(),
-                format!("uuid:{}::urn:schemas-upnp-org:device:MediaServer:1", config.server.uuid)
-            ),
-            "urn:schemas-upnp-org:service:ContentDirectory:1" => (
-                "urn:schemas-upnp-org:service:ContentDirectory:1".to_string(),
-                format!("uuid:{}::urn:schemas-upnp-org:service:ContentDirectory:1", config.server.uuid)
-            ),
-            _ => continue,
-        };
-
-        let message = format!(
-            "NOTIFY * HTTP/1.1\r\n\
-            HOST: {}:{}\r\n\
-            CACHE-CONTROL: max-age=1800\r\n\
-            LOCATION: http://{}:{}/description.xml\r\n\
-            NT: {}\r\n\
-            NTS: ssdp:alive\r\n\
-            SERVER: VuIO/1.0 UPnP/1.0\r\n\
-            USN: {}\r\n\r\n",
-            SSDP_MULTICAST_ADDR, SSDP_PORT,
-            server_ip, config.server.port, nt, usn
-        );
-
+    for target in advertisement_targets(&state.config.server.uuid) {
+        let service_type = &target.nt;
         // Try multicast first with retry logic
         let mut multicast_success = false;
-        for attempt in 1..=MAX_SEND_RETRIES {
-            match network_manager.send_multicast(&socket, message.as_bytes(), multicast_addr).await {
-                Ok(()) => {
-                    info!("Successfully sent SSDP NOTIFY for {} via multicast (attempt {})", service_type, attempt);
-                    multicast_success = true;
-                    break;
-                }
-                Err(e) => {
-                    warn!("Multicast NOTIFY for {} failed (attempt {}): {}", service_type, attempt, e);
-                    if attempt < MAX_SEND_RETRIES {
-                        tokio::time::sleep(Duration::from_millis(200 * attempt as u64)).await;
+        for &group in &multicast_groups {
+            let multicast_addr = SocketAddr::new(group, SSDP_PORT);
+            let message = alive_message(state, &server_ip, group, &target);
+            for attempt in 1..=multicast_attempts {
+                match network_manager.send_multicast(&socket, message.as_bytes(), multicast_addr).await {
+                    Ok(()) => {
+                        info!("Successfully sent SSDP NOTIFY for {} to {} (attempt {})", service_type, group, attempt);
+                        tracer.outbound(multicast_addr, message.as_bytes());
+                        multicast_success = true;
+                        break;
+                    }
+                    Err(e) => {
+                        warn!("Multicast NOTIFY for {} to {} failed (attempt {}): {}", service_type, group, attempt, e);
+                        if attempt < MAX_SEND_RETRIES {
+                            tokio::time::sleep(Duration::from_millis(200 * attempt as u64)).await;
+                        }
                     }
                 }
             }
         }
         
         if !multicast_success {
-            warn!("Multicast NOTIFY for {} failed after {} attempts, trying unicast fallback", service_type, MAX_SEND_RETRIES);
+            let group = multicast_groups.first().copied().unwrap_or(IpAddr::V4(SSDP_MULTICAST_V4));
+            let message = alive_message(state, &server_ip, group, &target);
+            if discovery_mode == DiscoveryMode::Multicast {
+                warn!("Multicast NOTIFY for {} failed after {} attempts, trying unicast fallback", service_type, MAX_SEND_RETRIES);
+            }
             
             // Fall back to unicast broadcast on all interfaces with retry logic
             let mut unicast_success = false;
@@ -350,6 +847,7 @@ async fn send_ssdp_alive(state: &AppState, network_manager: &PlatformNetworkMana
                 match network_manager.send_unicast_fallback(&socket, message.as_bytes(), &socket.interfaces).await {
                     Ok(()) => {
                         info!("Successfully sent SSDP NOTIFY for {} via unicast fallback (attempt {})", service_type, attempt);
+                        tracer.outbound("unicast fallback", message.as_bytes());
                         unicast_success = true;
                         break;
                     }
@@ -371,58 +869,229 @@ async fn send_ssdp_alive(state: &AppState, network_manager: &PlatformNetworkMana
         tokio::time::sleep(Duration::from_millis(100)).await;
     }
     
+    send_ssdp_alive_v6(state, network_manager, &tracer).await;
     info!("All SSDP NOTIFY announcements completed");
 
     Ok(())
 }
 
-async fn create_ssdp_response(state: &AppState, _ssdp_port: u16, service_type: &str) -> String {
+/// Announce on the IPv6 multicast groups; skipped quietly on hosts without IPv6
+async fn send_ssdp_alive_v6(state: &AppState, network_manager: &dyn NetworkManager, tracer: &SsdpTracer) {
+    let Some(socket) = open_ipv6_socket(state, network_manager, 0).await else {
+        return;
+    };
+    // Link-local groups are reached through the primary interface, as joined in open_ipv6_socket
+    let scope_id = state
+        .platform_info
+        .get()
+        .get_primary_interface()
+        .and_then(|iface| interface_index(&iface.name))
+        .unwrap_or(0);
+    for group in configured_groups(state, true) {
+        let multicast_addr = SocketAddr::new(group, SSDP_PORT);
+        let group_v6 = match group {
+            IpAddr::V6(group_v6) => group_v6,
+            IpAddr::V4(_) => continue,
+        };
+        let server_host = location_host(state, SocketAddrV6::new(group_v6, SSDP_PORT, 0, scope_id).into()).await;
+        for target in advertisement_targets(&state.config.server.uuid) {
+            let service_type = &target.nt;
+            let message = alive_message(state, &server_host, group, &target);
+            match network_manager.send_multicast(&socket, message.as_bytes(), multicast_addr).await {
+                Ok(()) => tracer.outbound(multicast_addr, message.as_bytes()),
+                Err(e) => debug!("IPv6 NOTIFY for {} to {} failed: {}", service_type, group, e),
+            }
+        }
+    }
+}
+
+/// Send `ssdp:byebye` for the root device and every service to each
+/// configured group, returning the number of notifications sent
+async fn send_ssdp_byebye(state: &AppState, network_manager: &dyn NetworkManager) -> Result<usize> {
+    let mut socket = open_ssdp_socket(network_manager, &ssdp_config(&state.config.network))
+        .await
+        .map_err(|e| anyhow::anyhow!("Byebye socket creation failed: {}", e))?;
+    let discovery_mode = DiscoveryMode::for_interfaces(&socket.interfaces);
+    let multicast_groups = configured_groups(state, false);
+    let primary_interface = state.platform_info.get().get_primary_interface().cloned();
+    if discovery_mode == DiscoveryMode::Multicast {
+        for &group in &multicast_groups {
+            if let Err(e) = network_manager.join_multicast_group(&mut socket, group, primary_interface.as_ref()).await {
+                warn!("Failed to enable multicast for {} on byebye socket: {}", group, e);
+            }
+        }
+    }
+
+    let tracer = SsdpTracer::new(state.config.network.trace_ssdp);
+    let mut sent = 0;
+    for target in advertisement_targets(&state.config.server.uuid) {
+        let service_type = &target.nt;
+        let mut multicast_success = false;
+        if discovery_mode == DiscoveryMode::Multicast {
+            for &group in &multicast_groups {
+                let multicast_addr = SocketAddr::new(group, SSDP_PORT);
+                let message = byebye_message(state, group, &target);
+                match network_manager.send_multicast(&socket, message.as_bytes(), multicast_addr).await {
+                    Ok(()) => {
+                        tracer.outbound(multicast_addr, message.as_bytes());
+                        multicast_success = true;
+                        sent += 1;
+                    }
+                    Err(e) => warn!("Multicast byebye for {} to {} failed: {}", service_type, group, e),
+                }
+            }
+        }
+
+        if !multicast_success {
+            let group = multicast_groups.first().copied().unwrap_or(IpAddr::V4(SSDP_MULTICAST_V4));
+            let message = byebye_message(state, group, &target);
+            match network_manager.send_unicast_fallback(&socket, message.as_bytes(), &socket.interfaces).await {
+                Ok(()) => {
+                    tracer.outbound("unicast fallback", message.as_bytes());
+                    sent += 1;
+                }
+                Err(e) => warn!("Unicast byebye for {} failed: {}", service_type, e),
+            }
+        }
+    }
+
+    if let Some(socket) = open_ipv6_socket(state, network_manager, 0).await {
+        for group in configured_groups(state, true) {
+            let multicast_addr = SocketAddr::new(group, SSDP_PORT);
+            for target in advertisement_targets(&state.config.server.uuid) {
+                let service_type = &target.nt;
+                let message = byebye_message(state, group, &target);
+                match network_manager.send_multicast(&socket, message.as_bytes(), multicast_addr).await {
+                    Ok(()) => {
+                        tracer.outbound(multicast_addr, message.as_bytes());
+                        sent += 1;
+                    }
+                    Err(e) => debug!("IPv6 byebye for {} to {} failed: {}", service_type, group, e),
+                }
+            }
+        }
+    }
+
+    Ok(sent)
+}
+
+/// UPnP 1.1 `BOOTID.UPNP.ORG` and `CONFIGID.UPNP.ORG` header lines. A new
+/// BOOTID tells control points the server restarted; a new CONFIGID that its
+/// descriptions changed and should be fetched again.
+fn upnp_id_headers(state: &AppState) -> String {
+    format!(
+        "BOOTID.UPNP.ORG: {}\r\nCONFIGID.UPNP.ORG: {}\r\n",
+        state.boot_id,
+        state.config_id
+    )
+}
+
+/// `ssdp:alive` NOTIFY for `target` addressed to `group`, with `server_host`
+/// already formatted for a URL
+fn alive_message(state: &AppState, server_host: &str, group: IpAddr, target: &SsdpTarget) -> String {
+    format!(
+        "NOTIFY * HTTP/1.1\r\n\
+        HOST: {}\r\n\
+        CACHE-CONTROL: max-age={}\r\n\
+        LOCATION: http://{}:{}/description.xml\r\n\
+        NT: {}\r\n\
+        NTS: ssdp:alive\r\n\
+        SERVER: VuIO/1.0 UPnP/1.0\r\n\
+        USN: {}\r\n\
+        {}\r\n",
+        ssdp_host(group), ssdp_max_age(&state.config.network).as_secs(),
+        server_host, state.config.server.port, target.nt, target.usn, upnp_id_headers(state)
+    )
+}
+
+/// `ssdp:byebye` NOTIFY for `target` addressed to `group`
+fn byebye_message(state: &AppState, group: IpAddr, target: &SsdpTarget) -> String {
+    format!(
+        "NOTIFY * HTTP/1.1\r\n\
+        HOST: {}\r\n\
+        NT: {}\r\n\
+        NTS: ssdp:byebye\r\n\
+        USN: {}\r\n\
+        {}\r\n",
+        ssdp_host(group), target.nt, target.usn, upnp_id_headers(state)
+    )
+}
+
+/// Explain that discovery is degraded and how clients can still reach the server
+async fn log_degraded_discovery(state: &AppState, ssdp_port: u16) {
     let server_ip = get_server_ip(state).await;
+    warn!("No network interface with working multicast found - SSDP discovery is degraded");
+    warn!("  - Only M-SEARCH requests sent directly to {}:{} are answered", server_ip, ssdp_port);
+    warn!("  - Announcements are broadcast on each interface's subnet instead of multicast");
+    warn!(
+        "  - If the server does not appear, add it manually in the client using http://{}:{}/description.xml",
+        server_ip, state.config.server.port
+    );
+}
+
+/// Search response for `target` sent to the client at `client`
+async fn create_ssdp_response(state: &AppState, client: SocketAddr, target: &SsdpTarget) -> String {
+    let server_host = location_host(state, client).await;
     let config = &state.config;
     
-    let (st, usn) = match service_type {
-        "upnp:rootdevice" => (
-            "upnp:rootdevice".to_string(),
-            format!("uuid:{}::upnp:rootdevice", config.server.uuid)
-        ),
-        "urn:schemas-upnp-org:device:MediaServer:1" => (
-            "urn:schemas-upnp-org:device:MediaServer:1".to_string(),
-            format!("uuid:{}::urn:schemas-upnp-org:device:MediaServer:1", config.server.uuid)
-        ),
-        "urn:schemas-upnp-org:service:ContentDirectory:1" => (
-            "urn:schemas-upnp-org:service:ContentDirectory:1".to_string(),
-            format!("uuid:{}::urn:schemas-upnp-org:service:ContentDirectory:1", config.server.uuid)
-        ),
-        _ => (
-            "urn:schemas-upnp-org:device:MediaServer:1".to_string(),
-            format!("uuid:{}::urn:schemas-upnp-org:device:MediaServer:1", config.server.uuid)
-        )
-    };
-    
     format!(
         "HTTP/1.1 200 OK\r\n\
-        CACHE-CONTROL: max-age=1800\r\n\
+        CACHE-CONTROL: max-age={}\r\n\
         EXT:\r\n\
         LOCATION: http://{}:{}/description.xml\r\n\
         SERVER: VuIO/1.0 UPnP/1.0\r\n\
         ST: {}\r\n\
         USN: {}\r\n\
-        \r\n",
-        server_ip, config.server.port, st, usn
+        {}\r\n",
+        ssdp_max_age(&config.network).as_secs(), server_host, config.server.port, target.nt, target.usn,
+        upnp_id_headers(state)
     )
 }
 
+/// Address the HTTP server listens on when it is an IPv6 one, the unspecified
+/// `::` included
+fn ipv6_listen_address(state: &AppState) -> Option<Ipv6Addr> {
+    state.config.server.interface.parse().ok()
+}
+
+/// Host of the LOCATION URL in messages to `peer`, a searching client or an
+/// announcement group. IPv6 peers get the IPv6 address they reach the server
+/// at, scoped to the peer's interface when link-local, as long as the HTTP
+/// server listens on IPv6; all other peers get the IPv4 server address.
+pub(crate) async fn location_host(state: &AppState, peer: SocketAddr) -> String {
+    if let (SocketAddr::V6(peer_v6), Some(listen)) = (peer, ipv6_listen_address(state)) {
+        let local = if listen.is_unspecified() {
+            match local_address_for(peer) {
+                Some(SocketAddr::V6(local)) => Some(local),
+                _ => None,
+            }
+        } else {
+            Some(SocketAddrV6::new(listen, 0, 0, 0))
+        };
+        if let Some(local) = local {
+            let scope_id = if local.scope_id() != 0 { local.scope_id() } else { peer_v6.scope_id() };
+            let zone = (scope_id != 0).then(|| scope_zone(scope_id, &state.platform_info.get().network_interfaces));
+            return url_host(IpAddr::V6(*local.ip()), zone.as_deref());
+        }
+    }
+    get_server_ip(state).await
+}
+
+/// Host of the IPv4 server address, or of the configured interface, for URLs
 async fn get_server_ip(state: &AppState) -> String {
     // 1. Use the primary interface detected at startup. This is the main path.
-    if let Some(iface) = state.platform_info.get_primary_interface() {
-        return iface.ip_address.to_string();
+    if let Some(iface) = state.platform_info.get().get_primary_interface() {
+        return url_host(iface.ip_address, None);
     }
 
     // 2. If the primary selection logic fails, log a clear warning and try the configured interface.
     warn!("Primary interface selection failed. This might happen if no suitable network connection (Ethernet/WiFi with a private IP) was found.");
-    if state.config.server.interface != "0.0.0.0" && !state.config.server.interface.is_empty() {
+    if !matches!(state.config.server.interface.as_str(), "" | "0.0.0.0" | "::") {
         warn!("Falling back to configured server interface: {}", state.config.server.interface);
-        return state.config.server.interface.clone();
+        return match state.config.server.interface.parse() {
+            Ok(ip) => url_host(ip, None),
+            Err(_) => state.config.server.interface.clone(),
+        };
     }
     
     // 3. As a last resort, log a critical error and use localhost.
@@ -431,4 +1100,320 @@ async fn get_server_ip(state: &AppState) -> String {
     error!("Please check your network connection and ensure you have a valid private IP (e.g., 192.168.x.x).");
     error!("Falling back to 127.0.0.1 - DLNA clients will NOT be able to connect.");
     "127.0.0.1".to_string()
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jittered_announce_interval_within_band() {
+        let base = Duration::from_secs(300);
+        let intervals: Vec<Duration> = (0..50)
+            .map(|_| jittered_announce_interval(base, 20, Duration::from_secs(1800)))
+            .collect();
+
+        for interval in &intervals {
+            assert!(*interval >= Duration::from_secs(240), "interval {:?} below band", interval);
+            assert!(*interval <= Duration::from_secs(360), "interval {:?} above band", interval);
+        }
+
+        // Successive intervals should not all be identical
+        assert!(intervals.windows(2).any(|w| w[0] != w[1]));
+    }
+
+    #[test]
+    fn test_jittered_announce_interval_bounded_by_max_age() {
+        let max_age = Duration::from_secs(1800);
+        for _ in 0..20 {
+            assert!(jittered_announce_interval(max_age, 50, max_age) <= max_age / 2);
+        }
+    }
+
+    #[test]
+    fn test_zero_jitter_is_deterministic() {
+        let base = Duration::from_secs(300);
+        assert_eq!(jittered_announce_interval(base, 0, Duration::from_secs(1800)), base);
+        assert_eq!(initial_announce_delay(base, 0), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_max_age_derived_from_announce_interval() {
+        let mut network = crate::config::AppConfig::default_for_platform().network;
+        network.announce_interval_seconds = 900;
+        assert_eq!(ssdp_max_age(&network), Duration::from_secs(2700));
+        // Short intervals still advertise the UPnP minimum
+        network.announce_interval_seconds = 300;
+        assert_eq!(ssdp_max_age(&network), Duration::from_secs(1800));
+        network.ssdp_max_age_seconds = Some(600);
+        assert_eq!(ssdp_max_age(&network), Duration::from_secs(600));
+    }
+
+    #[tokio::test]
+    async fn test_initial_announce_burst_count() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        let calls = AtomicU32::new(0);
+        let started = tokio::time::Instant::now();
+        let sent = send_initial_announcements(3, Duration::from_millis(20), || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        })
+        .await;
+
+        assert_eq!(sent, 3);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        // Two gaps between three announcements
+        assert!(started.elapsed() >= Duration::from_millis(40));
+
+        // Failed sends are still attempted the configured number of times
+        let attempts = AtomicU32::new(0);
+        let sent = send_initial_announcements(2, Duration::ZERO, || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(anyhow::anyhow!("network unreachable"))
+        })
+        .await;
+        assert_eq!(sent, 0);
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_parse_ssdp_messages() {
+        let search = SsdpMessage::parse(
+            b"M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nmx: 3\r\nST: upnp:rootdevice\r\n\r\n",
+        )
+        .unwrap();
+        assert!(search.is_search());
+        assert_eq!(search.header("st"), Some("upnp:rootdevice"));
+        assert_eq!(search.header("MX"), Some("3"));
+
+        let notify = SsdpMessage::parse(b"NOTIFY * HTTP/1.1\nNT: upnp:rootdevice\nNTS: ssdp:alive\n\n").unwrap();
+        assert!(!notify.is_search());
+
+        assert!(SsdpMessage::parse(&[0xff, 0xfe, 0x00]).is_err());
+        assert!(SsdpMessage::parse(b"hello world").is_err());
+        assert!(SsdpMessage::parse(b"M-SEARCH * HTTP/1.1\r\nMAN: \"ssdp:discover\"\r\n\r\n").is_err());
+        assert!(SsdpMessage::parse(b"NOTIFY * HTTP/1.1\r\nno colon here\r\n\r\n").is_err());
+
+        assert_eq!(hex_dump(b"M-S"), "4d 2d 53");
+    }
+
+    #[test]
+    fn test_advertisement_targets_cover_device_and_services() {
+        let uuid = "4d696e69-444c-164e-9d41-b827eb1f2b6c";
+        let targets: Vec<(String, String)> =
+            advertisement_targets(uuid).into_iter().map(|target| (target.nt, target.usn)).collect();
+        let expected = [
+            ("upnp:rootdevice", "uuid:4d696e69-444c-164e-9d41-b827eb1f2b6c::upnp:rootdevice"),
+            ("uuid:4d696e69-444c-164e-9d41-b827eb1f2b6c", "uuid:4d696e69-444c-164e-9d41-b827eb1f2b6c"),
+            (
+                "urn:schemas-upnp-org:device:MediaServer:1",
+                "uuid:4d696e69-444c-164e-9d41-b827eb1f2b6c::urn:schemas-upnp-org:device:MediaServer:1",
+            ),
+            (
+                "urn:schemas-upnp-org:service:ContentDirectory:1",
+                "uuid:4d696e69-444c-164e-9d41-b827eb1f2b6c::urn:schemas-upnp-org:service:ContentDirectory:1",
+            ),
+            (
+                "urn:schemas-upnp-org:service:ConnectionManager:1",
+                "uuid:4d696e69-444c-164e-9d41-b827eb1f2b6c::urn:schemas-upnp-org:service:ConnectionManager:1",
+            ),
+        ];
+        assert_eq!(targets, expected.map(|(nt, usn)| (nt.to_string(), usn.to_string())));
+
+        assert_eq!(search_targets(uuid, "ssdp:all").len(), 5);
+        let cds = search_targets(uuid, "urn:schemas-upnp-org:service:ContentDirectory:1");
+        assert_eq!(cds.len(), 1);
+        assert_eq!(cds[0].nt, "urn:schemas-upnp-org:service:ContentDirectory:1");
+        assert_eq!(search_targets(uuid, &format!("uuid:{}", uuid))[0].usn, format!("uuid:{}", uuid));
+        assert!(search_targets(uuid, "urn:schemas-upnp-org:device:MediaRenderer:1").is_empty());
+        assert!(search_targets(uuid, "uuid:someone-else").is_empty());
+    }
+
+    #[test]
+    fn test_ssdp_host_brackets_ipv6_groups() {
+        assert_eq!(ssdp_host(IpAddr::V4(SSDP_MULTICAST_V4)), "239.255.255.250:1900");
+        assert_eq!(ssdp_host("ff02::c".parse().unwrap()), "[ff02::c]:1900");
+    }
+
+    #[tokio::test]
+    async fn test_ssdp_socket_uses_configured_ttl_and_interval() {
+        use crate::config::AppConfig;
+        use crate::platform::network::BaseNetworkManager;
+        use crate::platform::{InterfaceType, NetworkInterface};
+
+        let mut network = AppConfig::default_for_platform().network;
+        network.multicast_ttl = 16;
+        network.announce_interval_seconds = 120;
+        let mut config = ssdp_config(&network);
+        assert_eq!(config.announce_interval, Duration::from_secs(120));
+
+        config.primary_port = 0;
+        config.interfaces = vec![NetworkInterface {
+            name: "eth0".to_string(),
+            ip_address: "192.168.1.10".parse().unwrap(),
+            is_loopback: false,
+            is_up: true,
+            supports_multicast: true,
+            interface_type: InterfaceType::Ethernet,
+        }];
+        let socket = open_ssdp_socket(&BaseNetworkManager::new(), &config).await.unwrap();
+        assert_eq!(socket.socket.multicast_ttl_v4().unwrap(), 16);
+    }
+
+    async fn test_state(config: crate::config::AppConfig, db_path: std::path::PathBuf) -> AppState {
+        use crate::database::SqliteDatabase;
+        use crate::platform::{OsType, PlatformCapabilities};
+        use crate::state::{SharedConfig, SharedPlatformInfo};
+        use crate::web::eventing::ContentUpdateNotifier;
+        use std::sync::atomic::AtomicU32;
+
+        let content_update_id = Arc::new(AtomicU32::new(1));
+        let client_filter = crate::platform::network::ClientFilter::from_config(&config.network);
+        let config = Arc::new(config);
+        let mut state = AppState {
+            config: config.clone(),
+            applied_config: SharedConfig::new(config),
+            media_files: Default::default(),
+            database: Arc::new(SqliteDatabase::new(db_path).await.unwrap()),
+            platform_info: SharedPlatformInfo::new(PlatformInfo {
+                os_type: OsType::current(),
+                version: "test".to_string(),
+                capabilities: PlatformCapabilities::for_current_platform(),
+                network_interfaces: Vec::new(),
+                metadata: Default::default(),
+            }),
+            content_updates: ContentUpdateNotifier::new(content_update_id.clone(), Duration::from_millis(10)),
+            scan_lock: Default::default(),
+            media_lookups: Default::default(),
+            library_snapshot: Default::default(),
+            small_files: Default::default(),
+            didl_cache: Default::default(),
+            metrics: Default::default(),
+            api_tokens: Default::default(),
+            client_filter: Arc::new(client_filter),
+            thumbnails: Default::default(),
+            album_art: Default::default(),
+            event_subscriptions: Default::default(),
+            connection_manager_subscriptions: Default::default(),
+            transcoder: None,
+            subtitles: Default::default(),
+            playlists: Default::default(),
+            icon: Default::default(),
+            started_at: std::time::Instant::now(),
+            boot_id: 1,
+            config_id: 0,
+            scan_errors: Default::default(),
+            content_update_id,
+        };
+        state.update_config_id();
+        state
+    }
+
+    #[tokio::test]
+    async fn test_location_matches_client_address_family() {
+        use crate::config::AppConfig;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut config = AppConfig::default_for_platform();
+        config.server.interface = "2001:db8::5".to_string();
+        config.server.port = 8200;
+        let state = test_state(config, temp_dir.path().join("global.db")).await;
+        let client = "[2001:db8::20]:1900".parse().unwrap();
+        let target = &advertisement_targets(&state.config.server.uuid)[0];
+        let response = create_ssdp_response(&state, client, target).await;
+        assert!(response.contains("LOCATION: http://[2001:db8::5]:8200/description.xml\r\n"), "{}", response);
+
+        // Link-local addresses are scoped to the interface the client was seen on
+        let mut config = AppConfig::default_for_platform();
+        config.server.interface = "fe80::5".to_string();
+        let state = test_state(config, temp_dir.path().join("link-local.db")).await;
+        let client = SocketAddrV6::new("fe80::20".parse().unwrap(), 1900, 0, 7).into();
+        assert_eq!(location_host(&state, client).await, "[fe80::5%257]");
+
+        // IPv4 clients of a dual-stack server get the IPv4 address, here the fallback
+        let mut config = AppConfig::default_for_platform();
+        config.server.interface = "::".to_string();
+        let state = test_state(config, temp_dir.path().join("dual-stack.db")).await;
+        assert_eq!(location_host(&state, "192.168.1.20:1900".parse().unwrap()).await, "127.0.0.1");
+    }
+
+    #[tokio::test]
+    async fn test_ipv6_ssdp_skipped_without_ipv6_http() {
+        use crate::config::AppConfig;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut config = AppConfig::default_for_platform();
+        config.server.interface = "0.0.0.0".to_string();
+        let state = test_state(config, temp_dir.path().join("ipv4-only.db")).await;
+        assert!(open_ipv6_socket(&state, &PlatformNetworkManager::new(), 0).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_messages_carry_boot_and_config_ids() {
+        use crate::config::AppConfig;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut config = AppConfig::default_for_platform();
+        config.network.announce_interval_seconds = 60;
+        let mut state = test_state(config, temp_dir.path().join("ids.db")).await;
+        state.boot_id = 42;
+        let config_id = state.config_id;
+        assert_eq!(config_id, crate::web::xml::description_config_id(&state));
+        assert!(config_id <= 0xFF_FFFF);
+
+        let target = &advertisement_targets(&state.config.server.uuid)[0];
+        let group: IpAddr = "239.255.255.250".parse().unwrap();
+        let response = create_ssdp_response(&state, "127.0.0.1:1900".parse().unwrap(), target).await;
+        let alive = alive_message(&state, "127.0.0.1", group, target);
+        let byebye = byebye_message(&state, group, target);
+        for message in [&response, &alive, &byebye] {
+            assert!(message.contains("BOOTID.UPNP.ORG: 42\r\n"), "{}", message);
+            assert!(message.contains(&format!("CONFIGID.UPNP.ORG: {}\r\n", config_id)), "{}", message);
+            assert!(message.ends_with("\r\n\r\n"), "{}", message);
+        }
+        for message in [&response, &alive] {
+            assert!(message.contains("CACHE-CONTROL: max-age=1800\r\n"), "{}", message);
+        }
+
+        // Renaming the server changes the device description and so the CONFIGID
+        let mut config = (*state.config).clone();
+        config.server.name = "Renamed Server".to_string();
+        state.config = Arc::new(config);
+        state.update_config_id();
+        assert_ne!(state.config_id, config_id);
+        assert!(alive_message(&state, "127.0.0.1", group, target).contains(&format!("CONFIGID.UPNP.ORG: {}\r\n", state.config_id)));
+    }
+
+    #[tokio::test]
+    async fn test_filtered_clients_get_no_search_response() {
+        use crate::config::AppConfig;
+
+        async fn state_with(allowed: &[&str], blocked: &[&str], db_path: std::path::PathBuf) -> AppState {
+            let mut config = AppConfig::default_for_platform();
+            config.network.allowed_client_subnets = allowed.iter().map(|s| s.to_string()).collect();
+            config.network.blocked_client_subnets = blocked.iter().map(|s| s.to_string()).collect();
+            test_state(config, db_path).await
+        }
+
+        /// Answer an M-SEARCH from a client bound to `client_ip`, returning whether it got a reply
+        async fn search_from(state: &AppState, client_ip: &str) -> bool {
+            let server = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let socket = SsdpSocket { port: server.local_addr().unwrap().port(), socket: server, interfaces: Vec::new(), multicast_enabled: false };
+            let client = tokio::net::UdpSocket::bind((client_ip, 0)).await.unwrap();
+            let request = "M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: 1\r\nST: upnp:rootdevice\r\n\r\n";
+            let request = SsdpMessage::parse(request.as_bytes()).unwrap();
+            answer_search(state, &socket, &SsdpTracer::new(false), client.local_addr().unwrap(), &request).await;
+            let mut buf = [0u8; 2048];
+            tokio::time::timeout(Duration::from_millis(200), client.recv_from(&mut buf)).await.is_ok()
+        }
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let state = state_with(&[], &["127.0.0.2/32"], temp_dir.path().join("blocked.db")).await;
+        assert!(!search_from(&state, "127.0.0.2").await);
+        assert!(search_from(&state, "127.0.0.1").await);
+
+        let state = state_with(&["127.0.0.1"], &[], temp_dir.path().join("allowed.db")).await;
+        assert!(search_from(&state, "127.0.0.1").await);
+        assert!(!search_from(&state, "127.0.0.3").await);
+    }
+}
@@ -0,0 +1,333 @@
+// src/tvshows.rs
+//! Kodi-style TV show grouping: episodes are organised into show and season
+//! containers using `tvshow.nfo` files, per-episode `.nfo` files and `SxxExx`
+//! filename markers.
+
+use crate::database::MediaFile;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Marks the directory holding a TV show
+pub const TV_SHOW_NFO: &str = "tvshow.nfo";
+
+/// Marks a directory holding a movie, whose files are never grouped as episodes
+pub const MOVIE_NFO: &str = "movie.nfo";
+
+/// Show, season and episode a video file belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EpisodeInfo {
+    pub show_title: String,
+    /// Directory of the show, which tells apart shows sharing a title
+    pub show_path: PathBuf,
+    pub season: u32,
+    pub episode: u32,
+    pub title: Option<String>,
+}
+
+/// Details read from a Kodi `<episodedetails>` file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EpisodeNfo {
+    pub season: Option<u32>,
+    pub episode: Option<u32>,
+    pub title: Option<String>,
+}
+
+/// Byte offset, season and episode of the first `S01E02` or `1x02` marker in a filename
+fn find_episode_marker(filename: &str) -> Option<(usize, u32, u32)> {
+    let bytes = filename.as_bytes();
+    let digits_from = |start: usize, max: usize| {
+        let len = bytes[start..]
+            .iter()
+            .take(max + 1)
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        (len > 0 && len <= max).then_some(len)
+    };
+    let number = |start: usize, len: usize| filename[start..start + len].parse::<u32>().ok();
+
+    for start in 0..bytes.len() {
+        if start > 0 && bytes[start - 1].is_ascii_alphanumeric() {
+            continue;
+        }
+
+        // S01E02
+        if bytes[start].eq_ignore_ascii_case(&b's') {
+            if let Some(season_len) = digits_from(start + 1, 2) {
+                let e = start + 1 + season_len;
+                if bytes.get(e).is_some_and(|b| b.eq_ignore_ascii_case(&b'e')) {
+                    if let Some(episode_len) = digits_from(e + 1, 3) {
+                        return Some((start, number(start + 1, season_len)?, number(e + 1, episode_len)?));
+                    }
+                }
+            }
+        }
+
+        // 1x02, requiring a two or three digit episode so resolutions like 1920x1080 never match
+        if let Some(season_len) = digits_from(start, 2) {
+            let x = start + season_len;
+            if bytes.get(x).is_some_and(|b| b.eq_ignore_ascii_case(&b'x')) {
+                if let Some(episode_len) = digits_from(x + 1, 3).filter(|len| *len >= 2) {
+                    return Some((start, number(start, season_len)?, number(x + 1, episode_len)?));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Parse the season and episode numbers from an `S01E02` or `1x02` marker in a filename
+pub fn parse_episode_numbers(filename: &str) -> Option<(u32, u32)> {
+    find_episode_marker(filename).map(|(_, season, episode)| (season, episode))
+}
+
+/// Parse a season directory name such as `Season 1`, `Season.02`, `S03` or `Specials`
+pub fn parse_season_folder(name: &str) -> Option<u32> {
+    let lower = name.trim().to_lowercase();
+    if lower == "specials" {
+        return Some(0);
+    }
+    let number = lower
+        .strip_prefix("season")
+        .map(|rest| rest.trim_start_matches([' ', '.', '_', '-']))
+        .or_else(|| lower.strip_prefix('s'))?;
+    if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    number.parse().ok()
+}
+
+/// Show title guessed from the text before the episode marker, e.g. `The.Office.S01E02.mkv`
+fn title_from_filename(filename: &str) -> Option<String> {
+    let (marker, _, _) = find_episode_marker(filename)?;
+    let title = filename[..marker]
+        .replace(['.', '_'], " ")
+        .trim_matches([' ', '-'])
+        .to_string();
+    (!title.is_empty()).then_some(title)
+}
+
+/// Text content of the first `<tag>` element, with the XML entities Kodi writes decoded
+fn nfo_tag(content: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let start = content.find(&open)? + open.len();
+    let end = start + content[start..].find(&close)?;
+    let value = content[start..end]
+        .trim()
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&");
+    (!value.is_empty()).then_some(value)
+}
+
+/// Title from a `tvshow.nfo`. Files holding only a scraper URL yield `None`.
+pub fn parse_tvshow_nfo(content: &str) -> Option<String> {
+    if !content.contains("<tvshow") {
+        return None;
+    }
+    nfo_tag(content, "title")
+}
+
+/// Season, episode and title from a per-episode `.nfo`, if it describes an episode
+pub fn parse_episode_nfo(content: &str) -> Option<EpisodeNfo> {
+    if !content.contains("<episodedetails") {
+        return None;
+    }
+    Some(EpisodeNfo {
+        season: nfo_tag(content, "season").and_then(|s| s.parse().ok()),
+        episode: nfo_tag(content, "episode").and_then(|s| s.parse().ok()),
+        title: nfo_tag(content, "title"),
+    })
+}
+
+fn directory_name(dir: &Path) -> Option<String> {
+    dir.file_name().map(|name| name.to_string_lossy().to_string())
+}
+
+/// Works out which show, season and episode video files belong to, caching
+/// the show-level `.nfo` lookups for each directory
+#[derive(Default)]
+pub struct EpisodeDetector {
+    /// Show title for directories holding a `tvshow.nfo`
+    shows: HashMap<PathBuf, Option<String>>,
+    movie_dirs: HashMap<PathBuf, bool>,
+}
+
+impl EpisodeDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Title of the show rooted at `dir`, if the directory holds a `tvshow.nfo`
+    fn show_at(&mut self, dir: &Path) -> Option<String> {
+        self.shows
+            .entry(dir.to_path_buf())
+            .or_insert_with(|| {
+                let nfo = dir.join(TV_SHOW_NFO);
+                if !nfo.is_file() {
+                    return None;
+                }
+                let title = std::fs::read_to_string(&nfo)
+                    .ok()
+                    .and_then(|content| parse_tvshow_nfo(&content));
+                title.or_else(|| directory_name(dir))
+            })
+            .clone()
+    }
+
+    fn is_movie_dir(&mut self, dir: &Path) -> bool {
+        *self
+            .movie_dirs
+            .entry(dir.to_path_buf())
+            .or_insert_with(|| dir.join(MOVIE_NFO).is_file())
+    }
+
+    /// Detect the episode a video file is. A `tvshow.nfo` in the file's directory or
+    /// the one above marks the show; without one the filename must carry an `SxxExx`
+    /// marker or the file must have an episode `.nfo`.
+    pub fn detect(&mut self, path: &Path) -> Option<EpisodeInfo> {
+        let parent = path.parent()?;
+        if self.is_movie_dir(parent) {
+            return None;
+        }
+
+        let filename = path.file_name()?.to_string_lossy().to_string();
+        let episode_nfo = path
+            .file_stem()
+            .map(|stem| parent.join(format!("{}.nfo", stem.to_string_lossy())))
+            .and_then(|nfo| std::fs::read_to_string(nfo).ok())
+            .and_then(|content| parse_episode_nfo(&content));
+        let numbers = parse_episode_numbers(&filename);
+        let season_folder = directory_name(parent).and_then(|name| parse_season_folder(&name));
+
+        let show = [Some(parent), parent.parent()]
+            .into_iter()
+            .flatten()
+            .find_map(|dir| self.show_at(dir).map(|title| (dir.to_path_buf(), title)));
+        let (show_path, show_title) = match show {
+            Some(show) => show,
+            None => {
+                if numbers.is_none() && episode_nfo.is_none() {
+                    return None;
+                }
+                let show_path = match season_folder {
+                    Some(_) => parent.parent().unwrap_or(parent),
+                    None => parent,
+                };
+                let title = title_from_filename(&filename).or_else(|| directory_name(show_path))?;
+                (show_path.to_path_buf(), title)
+            }
+        };
+
+        let nfo = episode_nfo.unwrap_or_default();
+        Some(EpisodeInfo {
+            show_title,
+            show_path,
+            season: nfo.season.or(numbers.map(|(season, _)| season)).or(season_folder)?,
+            episode: nfo.episode.or(numbers.map(|(_, episode)| episode))?,
+            title: nfo.title,
+        })
+    }
+}
+
+/// Detect the episodes among indexed video files, keyed by media file id.
+/// Reads `.nfo` files from disk, so call it off the async runtime.
+pub fn detect_episodes(files: &[MediaFile]) -> Vec<(i64, EpisodeInfo)> {
+    let mut detector = EpisodeDetector::new();
+    files
+        .iter()
+        .filter(|file| file.mime_type.starts_with("video/"))
+        .filter_map(|file| Some((file.id?, detector.detect(&file.path)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_episode_markers() {
+        assert_eq!(parse_episode_numbers("Show.Name.S01E02.720p.mkv"), Some((1, 2)));
+        assert_eq!(parse_episode_numbers("show s10e103.mp4"), Some((10, 103)));
+        assert_eq!(parse_episode_numbers("Show - 3x07 - Title.avi"), Some((3, 7)));
+        assert_eq!(parse_episode_numbers("Movie.1920x1080.mkv"), None);
+        assert_eq!(parse_episode_numbers("Brooklyn.mkv"), None);
+        assert_eq!(parse_episode_numbers("Mass01E02.mkv"), None);
+
+        assert_eq!(parse_season_folder("Season 1"), Some(1));
+        assert_eq!(parse_season_folder("season.02"), Some(2));
+        assert_eq!(parse_season_folder("S03"), Some(3));
+        assert_eq!(parse_season_folder("Specials"), Some(0));
+        assert_eq!(parse_season_folder("Extras"), None);
+
+        assert_eq!(title_from_filename("The.Office.S01E02.mkv").as_deref(), Some("The Office"));
+        assert_eq!(title_from_filename("S01E02.mkv"), None);
+    }
+
+    #[test]
+    fn test_parse_nfo_files() {
+        let show = "<?xml version=\"1.0\"?>\n<tvshow>\n  <title>Law &amp; Order</title>\n  <plot>...</plot>\n</tvshow>";
+        assert_eq!(parse_tvshow_nfo(show).as_deref(), Some("Law & Order"));
+        assert_eq!(parse_tvshow_nfo("https://thetvdb.com/?tab=series&id=1"), None);
+
+        let episode = "<episodedetails><title>Pilot</title><season>1</season><episode>1</episode></episodedetails>";
+        assert_eq!(
+            parse_episode_nfo(episode),
+            Some(EpisodeNfo { season: Some(1), episode: Some(1), title: Some("Pilot".to_string()) })
+        );
+        assert_eq!(parse_episode_nfo("<movie><title>Heat</title></movie>"), None);
+    }
+
+    #[test]
+    fn test_detect_episodes_from_nfo_and_filenames() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path();
+
+        // A show described by tvshow.nfo, with an episode .nfo overriding the filename
+        let show_dir = root.join("lo");
+        let season_dir = show_dir.join("Season 2");
+        fs::create_dir_all(&season_dir).unwrap();
+        fs::write(show_dir.join(TV_SHOW_NFO), "<tvshow><title>Law &amp; Order</title></tvshow>").unwrap();
+        fs::write(
+            season_dir.join("episode-a.nfo"),
+            "<episodedetails><title>Prescription for Death</title><season>2</season><episode>5</episode></episodedetails>",
+        )
+        .unwrap();
+
+        // No .nfo at all: the SxxExx filename is the fallback
+        let plain_dir = root.join("Downloads");
+        fs::create_dir_all(&plain_dir).unwrap();
+
+        // Movies are left alone even when their names look like episodes
+        let movie_dir = root.join("Movies").join("S01E01 The Movie");
+        fs::create_dir_all(&movie_dir).unwrap();
+        fs::write(movie_dir.join(MOVIE_NFO), "<movie/>").unwrap();
+
+        let mut detector = EpisodeDetector::new();
+        assert_eq!(
+            detector.detect(&season_dir.join("episode-a.mkv")),
+            Some(EpisodeInfo {
+                show_title: "Law & Order".to_string(),
+                show_path: show_dir.clone(),
+                season: 2,
+                episode: 5,
+                title: Some("Prescription for Death".to_string()),
+            })
+        );
+        assert_eq!(
+            detector.detect(&season_dir.join("Law.and.Order.S02E06.mkv")).map(|e| (e.show_path, e.season, e.episode)),
+            Some((show_dir.clone(), 2, 6))
+        );
+
+        let fallback = detector.detect(&plain_dir.join("The.Office.S03E04.mkv")).unwrap();
+        assert_eq!(fallback.show_title, "The Office");
+        assert_eq!((fallback.season, fallback.episode), (3, 4));
+
+        assert_eq!(detector.detect(&plain_dir.join("holiday.mp4")), None);
+        assert_eq!(detector.detect(&movie_dir.join("S01E01.mkv")), None);
+    }
+}
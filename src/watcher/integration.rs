@@ -9,7 +9,8 @@ use tracing::{debug, error, info, warn};
 use crate::database::{DatabaseManager, MediaFile};
 use crate::error::Result;
 use crate::media;
-use crate::watcher::{FileSystemEvent, FileSystemWatcher};
+use crate::platform::filesystem::MediaTypes;
+use crate::watcher::{FileSystemEvent, FileSystemWatcher, RecursiveMode};
 
 /// Service that integrates file system watching with database updates
 pub struct WatcherDatabaseIntegration<D, W>
@@ -52,7 +53,7 @@ where
     }
 
     /// Start the integration service
-    pub async fn start(&mut self, monitored_directories: &[PathBuf]) -> Result<()> {
+    pub async fn start(&mut self, monitored_directories: &[(PathBuf, RecursiveMode)]) -> Result<()> {
         info!("Starting watcher-database integration service");
 
         // Start watching directories
@@ -134,6 +135,14 @@ where
                     info!("Media file renamed: {:?} -> {:?}", from, to);
                     Some(QueuedOperation::Move { from, to })
                 }
+                FileSystemEvent::ResyncNeeded(directories) => {
+                    info!("Resyncing {} director(ies) after dropped events", directories.len());
+                    let mut queue = processing_queue.write().await;
+                    for dir in directories {
+                        queue.insert(dir.clone(), QueuedOperation::Add(dir));
+                    }
+                    None
+                }
             };
 
             if let Some(op) = operation {
@@ -425,7 +434,7 @@ where
         let modified = metadata.modified().unwrap_or(SystemTime::now());
 
         // Get MIME type
-        let mime_type = media::get_mime_type(path);
+        let mime_type = media::get_mime_type(path, &MediaTypes::default()).await;
 
         let mut media_file = MediaFile::new(path.to_path_buf(), size, mime_type);
         media_file.modified = modified;
@@ -598,7 +607,8 @@ where
 mod tests {
     use super::*;
     use async_trait::async_trait;
-    use crate::database::MediaFile;
+    use crate::database::{MediaFile, Playlist, Subtitle, TvEpisode};
+    use crate::tvshows::EpisodeInfo;
     use crate::watcher::CrossPlatformWatcher;
     use std::collections::HashMap;
     use std::sync::Arc;
@@ -646,6 +656,17 @@ mod tests {
             Ok(())
         }
 
+        async fn rename_media_file(&self, from: &Path, to: &Path) -> anyhow::Result<bool> {
+            let mut files = self.files.write().await;
+            let Some(mut file) = files.remove(from) else {
+                return Ok(false);
+            };
+            file.filename = to.file_name().unwrap_or_default().to_string_lossy().to_string();
+            file.path = to.to_path_buf();
+            files.insert(file.path.clone(), file);
+            Ok(true)
+        }
+
         async fn get_files_in_directory(&self, _dir: &Path) -> anyhow::Result<Vec<MediaFile>> {
             let files = self.files.read().await;
             Ok(files.values().cloned().collect())
@@ -691,6 +712,54 @@ mod tests {
         async fn vacuum(&self) -> anyhow::Result<()> {
             Ok(())
         }
+
+        async fn cleanup_invalid_records(&self) -> anyhow::Result<usize> {
+            Ok(0)
+        }
+
+        async fn remove_duplicates(&self) -> anyhow::Result<usize> {
+            Ok(0)
+        }
+
+        async fn replace_tv_episodes(&self, _episodes: &[(i64, EpisodeInfo)]) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn get_tv_episodes(&self) -> anyhow::Result<Vec<TvEpisode>> {
+            Ok(Vec::new())
+        }
+
+        async fn replace_subtitles(&self, _media_file_id: i64, _subtitles: &[Subtitle]) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn get_all_subtitles(&self) -> anyhow::Result<Vec<Subtitle>> {
+            Ok(Vec::new())
+        }
+
+        async fn store_playlist(&self, _playlist: &Playlist) -> anyhow::Result<i64> {
+            Ok(0)
+        }
+
+        async fn remove_playlist(&self, _path: &Path) -> anyhow::Result<bool> {
+            Ok(false)
+        }
+
+        async fn get_playlists(&self) -> anyhow::Result<Vec<Playlist>> {
+            Ok(Vec::new())
+        }
+
+        async fn get_system_update_id(&self) -> anyhow::Result<Option<u32>> {
+            Ok(None)
+        }
+
+        async fn set_system_update_id(&self, _update_id: u32) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn next_boot_id(&self) -> anyhow::Result<u32> {
+            Ok(1)
+        }
     }
 
     #[tokio::test]
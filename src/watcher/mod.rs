@@ -1,17 +1,30 @@
 use async_trait::async_trait;
-use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use notify::{RecommendedWatcher, Watcher};
+pub use notify::RecursiveMode;
 use notify_debouncer_full::{new_debouncer, DebounceEventResult, DebouncedEvent, Debouncer, FileIdMap};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::{mpsc, RwLock};
 use tracing::{debug, error, info, warn};
 
+use crate::config::{AppConfig, MediaConfig};
 use crate::error::Result;
+use crate::platform::filesystem::{
+    create_platform_filesystem_manager, is_hidden_path, strip_extended_length_prefix, MediaTypes,
+};
 
 pub mod integration;
 
+/// Default number of events buffered between the watcher and its consumer
+pub const EVENT_CHANNEL_CAPACITY: usize = 1000;
+
+/// How long the event channel must stay free of overflows before dropped
+/// events are replaced by a resync of the affected directories
+const RESYNC_SETTLE_DURATION: Duration = Duration::from_secs(1);
+
 /// Events that can occur in the file system for media files
 #[derive(Debug, Clone)]
 pub enum FileSystemEvent {
@@ -23,13 +36,178 @@ pub enum FileSystemEvent {
     Deleted(PathBuf),
     /// A file was renamed/moved
     Renamed { from: PathBuf, to: PathBuf },
+    /// Events for these directories were dropped while the event channel was
+    /// full, so they must be rescanned to catch up
+    ResyncNeeded(Vec<PathBuf>),
+}
+
+impl FileSystemEvent {
+    /// Files this event names; a resync names none
+    pub fn paths(&self) -> Vec<&PathBuf> {
+        match self {
+            FileSystemEvent::Created(path) | FileSystemEvent::Modified(path) | FileSystemEvent::Deleted(path) => vec![path],
+            FileSystemEvent::Renamed { from, to } => vec![from, to],
+            FileSystemEvent::ResyncNeeded(_) => Vec::new(),
+        }
+    }
+
+    /// Directories whose contents this event changes
+    fn affected_directories(&self) -> Vec<PathBuf> {
+        let parent = |path: &Path| path.parent().unwrap_or(path).to_path_buf();
+        match self {
+            FileSystemEvent::Created(path) | FileSystemEvent::Modified(path) | FileSystemEvent::Deleted(path) => {
+                vec![parent(path)]
+            }
+            FileSystemEvent::Renamed { from, to } => vec![parent(from), parent(to)],
+            FileSystemEvent::ResyncNeeded(directories) => directories.clone(),
+        }
+    }
+}
+
+/// Tracks directories whose events were dropped because the event channel was full
+#[derive(Default)]
+struct OverflowTracker {
+    state: std::sync::Mutex<OverflowState>,
+    resync_task_started: AtomicBool,
+}
+
+#[derive(Default)]
+struct OverflowState {
+    directories: HashSet<PathBuf>,
+    dropped_events: usize,
+    last_overflow: Option<Instant>,
+}
+
+impl OverflowTracker {
+    /// Record a dropped event, returning true if it starts a new overflow episode
+    fn record(&self, event: &FileSystemEvent) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let first = state.dropped_events == 0;
+        state.directories.extend(event.affected_directories());
+        state.dropped_events += 1;
+        state.last_overflow = Some(Instant::now());
+        first
+    }
+
+    fn is_pending(&self) -> bool {
+        self.state.lock().unwrap().dropped_events > 0
+    }
+
+    /// Take the directories to resync once no overflow has happened for `settle`
+    fn take_settled(&self, settle: Duration) -> Option<(Vec<PathBuf>, usize)> {
+        let mut state = self.state.lock().unwrap();
+        if state.dropped_events == 0 || state.last_overflow.is_some_and(|at| at.elapsed() < settle) {
+            return None;
+        }
+        let dropped = std::mem::take(&mut state.dropped_events);
+        let mut directories: Vec<_> = state.directories.drain().collect();
+        directories.sort();
+        state.last_overflow = None;
+        Some((directories, dropped))
+    }
+}
+
+/// Queue events for the consumer without blocking the notify thread, recording
+/// any that do not fit so they can be recovered by a resync
+fn dispatch_events(
+    event_sender: &mpsc::Sender<FileSystemEvent>,
+    overflow: &OverflowTracker,
+    events: Vec<FileSystemEvent>,
+) {
+    for fs_event in events {
+        match event_sender.try_send(fs_event) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(event)) => {
+                if overflow.record(&event) {
+                    warn!(
+                        "File system event channel is full; dropping events and scheduling a resync of the affected directories"
+                    );
+                }
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                error!("Failed to send file system event: receiver closed");
+            }
+        }
+    }
+}
+
+
+/// How a directory is watched. Backends such as FSEvents report canonical
+/// paths, so the canonical form of the directory is kept for matching events.
+#[derive(Debug, Clone)]
+struct WatchedDirectory {
+    mode: RecursiveMode,
+    canonical: PathBuf,
+}
+
+impl WatchedDirectory {
+    fn new(path: &Path, mode: RecursiveMode) -> Self {
+        Self {
+            mode,
+            canonical: path.canonicalize().unwrap_or_else(|_| path.to_path_buf()),
+        }
+    }
+
+    /// Whether `path` is `root` itself or, for recursive watches, anywhere below
+    /// it; non-recursive watches only cover direct children
+    fn covers(&self, root: &Path, path: &Path) -> bool {
+        [root, self.canonical.as_path()].into_iter().any(|root| match self.mode {
+            RecursiveMode::Recursive => path.starts_with(root),
+            RecursiveMode::NonRecursive => path == root || path.parent() == Some(root),
+        })
+    }
+}
+
+/// Symlinked directories below `root` as `(link, canonical target)` pairs,
+/// descending through the links themselves. Canonical paths already visited are
+/// not entered again, so a symlink cycle ends the walk instead of looping.
+async fn symlinked_directories(root: &Path) -> Vec<(PathBuf, PathBuf)> {
+    let filesystem = create_platform_filesystem_manager();
+    let canonical = |path: PathBuf| {
+        let filesystem = &filesystem;
+        async move { filesystem.canonicalize_path(&path).await.unwrap_or(path) }
+    };
+
+    let mut links = Vec::new();
+    let mut visited = HashSet::from([canonical(root.to_path_buf()).await]);
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(directory) = pending.pop() {
+        let Ok(mut entries) = tokio::fs::read_dir(&directory).await else {
+            continue;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            let Ok(file_type) = entry.file_type().await else {
+                continue;
+            };
+            let is_directory = if file_type.is_symlink() {
+                tokio::fs::metadata(&path).await.is_ok_and(|metadata| metadata.is_dir())
+            } else {
+                file_type.is_dir()
+            };
+            if !is_directory {
+                continue;
+            }
+            let target = canonical(path.clone()).await;
+            if !visited.insert(target.clone()) {
+                debug!("Not following {:?} again: {:?} is already watched", path, target);
+                continue;
+            }
+            if file_type.is_symlink() {
+                links.push((path.clone(), target));
+            }
+            pending.push(path);
+        }
+    }
+    links
 }
 
 /// Trait for cross-platform file system watching
 #[async_trait]
 pub trait FileSystemWatcher: Send + Sync {
-    /// Start watching the specified directories for changes
-    async fn start_watching(&self, directories: &[PathBuf]) -> Result<()>;
+    /// Start watching the specified directories for changes, descending into
+    /// subdirectories only for those watched with [`RecursiveMode::Recursive`]
+    async fn start_watching(&self, directories: &[(PathBuf, RecursiveMode)]) -> Result<()>;
     
     /// Stop watching all directories
     async fn stop_watching(&self) -> Result<()>;
@@ -38,13 +216,85 @@ pub trait FileSystemWatcher: Send + Sync {
     fn get_event_receiver(&self) -> mpsc::Receiver<FileSystemEvent>;
     
     /// Add a new path to watch
-    async fn add_watch_path(&self, path: &Path) -> Result<()>;
+    async fn add_watch_path(&self, path: &Path, mode: RecursiveMode) -> Result<()>;
     
     /// Remove a path from watching
     async fn remove_watch_path(&self, path: &Path) -> Result<()>;
     
     /// Check if a path is currently being watched
     async fn is_watching(&self, path: &Path) -> bool;
+
+    /// Filter events by the hidden file, MIME type and exclude pattern
+    /// settings of `media` from now on, after a configuration reload
+    fn update_filters(&self, media: &MediaConfig);
+}
+
+/// Directories of `media` to watch, descending into subdirectories only for
+/// those scanned recursively; none when `watch_for_changes` is off
+pub fn watched_directories(media: &MediaConfig) -> Vec<(PathBuf, RecursiveMode)> {
+    if !media.watch_for_changes {
+        return Vec::new();
+    }
+    media
+        .effective_directories()
+        .iter()
+        .map(|dir| {
+            let mode = if dir.recursive { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
+            (PathBuf::from(&dir.path), mode)
+        })
+        .collect()
+}
+
+/// Bring the watched directories in line with a reloaded media configuration:
+/// removed directories are unwatched and added ones watched, and a directory
+/// whose `recursive` setting changed is watched again in its new mode. Events
+/// are filtered by the new exclude patterns and media types from then on.
+pub async fn apply_directory_changes(
+    watcher: &dyn FileSystemWatcher,
+    old_media: &MediaConfig,
+    new_media: &MediaConfig,
+) -> Result<()> {
+    watcher.update_filters(new_media);
+    let old_directories = watched_directories(old_media);
+    let new_directories = watched_directories(new_media);
+
+    for entry in old_directories.iter().filter(|entry| !new_directories.contains(entry)) {
+        watcher.remove_watch_path(&entry.0).await?;
+    }
+    for (path, mode) in new_directories.iter().filter(|entry| !old_directories.contains(entry)) {
+        watcher.add_watch_path(path, *mode).await?;
+    }
+    Ok(())
+}
+
+/// Whether `path` is a media file or playlist by its extension, including the
+/// configured mappings, or a file whose content is sniffed
+fn has_media_type(media_extensions: &HashSet<String>, media_types: &MediaTypes, path: &Path) -> bool {
+    let known_extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| media_extensions.contains(&extension.to_lowercase()));
+    known_extension || media_types.is_media_file(path) || media_types.sniffs(path)
+}
+
+/// Settings of the media configuration that decide which events are reported,
+/// kept in line with the scanner across configuration reloads
+#[derive(Default)]
+struct EventFilters {
+    include_hidden: bool,
+    /// Configured MIME type mappings and content sniffing, as the scanner uses them
+    media_types: MediaTypes,
+    /// Exclude patterns of each media directory, as in `MonitoredDirectoryConfig`
+    exclude_patterns: HashMap<PathBuf, Vec<String>>,
+}
+
+/// Exclude patterns of each monitored directory of `media`
+pub fn exclude_patterns(media: &MediaConfig) -> HashMap<PathBuf, Vec<String>> {
+    media
+        .directories
+        .iter()
+        .map(|dir| (PathBuf::from(&dir.path), dir.exclude_patterns.clone().unwrap_or_default()))
+        .collect()
 }
 
 /// Cross-platform file system watcher implementation
@@ -52,15 +302,23 @@ pub struct CrossPlatformWatcher {
     debouncer: Arc<RwLock<Option<Debouncer<RecommendedWatcher, FileIdMap>>>>,
     event_sender: mpsc::Sender<FileSystemEvent>,
     event_receiver: Arc<RwLock<Option<mpsc::Receiver<FileSystemEvent>>>>,
-    watched_paths: Arc<RwLock<HashSet<PathBuf>>>,
+    /// Watched directories and whether their subdirectories are watched too
+    watched_paths: Arc<std::sync::RwLock<HashMap<PathBuf, WatchedDirectory>>>,
     media_extensions: HashSet<String>,
+    /// Media configuration settings events are filtered by, replaced on reload
+    filters: Arc<std::sync::RwLock<EventFilters>>,
     debounce_duration: Duration,
+    overflow: Arc<OverflowTracker>,
+    follow_symlinks: bool,
+    /// Link path of each followed symlinked directory, keyed by its canonical
+    /// target, so events reported for the target are attributed to the link
+    symlink_targets: Arc<std::sync::RwLock<HashMap<PathBuf, PathBuf>>>,
 }
 
 impl CrossPlatformWatcher {
     /// Create a new cross-platform file system watcher
     pub fn new() -> Self {
-        let (event_sender, event_receiver) = mpsc::channel(1000);
+        let (event_sender, event_receiver) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
         
         // Define supported media file extensions
         let media_extensions = [
@@ -70,6 +328,8 @@ impl CrossPlatformWatcher {
             "mp3", "flac", "wav", "aac", "ogg", "wma", "m4a", "opus", "aiff",
             // Image formats
             "jpg", "jpeg", "png", "gif", "bmp", "tiff", "webp", "svg",
+            // Playlists
+            "m3u", "m3u8", "pls",
         ]
         .iter()
         .map(|ext| ext.to_lowercase())
@@ -79,31 +339,171 @@ impl CrossPlatformWatcher {
             debouncer: Arc::new(RwLock::new(None)),
             event_sender,
             event_receiver: Arc::new(RwLock::new(Some(event_receiver))),
-            watched_paths: Arc::new(RwLock::new(HashSet::new())),
+            watched_paths: Default::default(),
             media_extensions,
+            filters: Default::default(),
             debounce_duration: Duration::from_millis(100), // 100ms debounce
+            overflow: Arc::new(OverflowTracker::default()),
+            follow_symlinks: false,
+            symlink_targets: Default::default(),
         }
     }
 
-    /// Check if a file is a supported media file based on its extension
-    fn is_media_file(&self, path: &Path) -> bool {
-        if let Some(extension) = path.extension() {
-            if let Some(ext_str) = extension.to_str() {
-                return self.media_extensions.contains(&ext_str.to_lowercase());
+    /// Buffer at most `capacity` events before falling back to a directory resync
+    pub fn with_event_channel_capacity(mut self, capacity: usize) -> Self {
+        let (event_sender, event_receiver) = mpsc::channel(capacity.max(1));
+        self.event_sender = event_sender;
+        self.event_receiver = Arc::new(RwLock::new(Some(event_receiver)));
+        self
+    }
+
+    /// Whether dropped events are waiting to be replaced by a resync
+    pub fn resync_pending(&self) -> bool {
+        self.overflow.is_pending()
+    }
+
+    /// Report events for hidden files and directories instead of ignoring them
+    pub fn with_hidden_files(self, include_hidden: bool) -> Self {
+        self.filters.write().unwrap().include_hidden = include_hidden;
+        self
+    }
+
+    /// Ignore files matching the exclude patterns of the media directory they
+    /// are in, so live events agree with [`AppConfig::should_exclude_file`]
+    pub fn with_exclude_patterns(self, patterns: HashMap<PathBuf, Vec<String>>) -> Self {
+        self.filters.write().unwrap().exclude_patterns = patterns;
+        self
+    }
+
+    /// Report events for files with configured `custom_mime_types` extensions,
+    /// and for those whose content is sniffed, like the scanner indexes them
+    pub fn with_media_types(self, media_types: MediaTypes) -> Self {
+        self.filters.write().unwrap().media_types = media_types;
+        self
+    }
+
+    /// Watch symlinked directories below recursive watches and report their
+    /// events. Without this, events under a symlinked directory are ignored.
+    pub fn with_follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// `path` with a followed symlink target prefix replaced by the link's path
+    fn resolve_symlink_target(&self, path: &Path) -> PathBuf {
+        let targets = self.symlink_targets.read().unwrap();
+        targets
+            .iter()
+            .find_map(|(target, link)| Some(link.join(path.strip_prefix(target).ok()?)))
+            .unwrap_or_else(|| path.to_path_buf())
+    }
+
+    /// Whether `path` lies inside a symlinked directory below the watched
+    /// directory containing it, or is one. Called from the notify callback
+    /// thread, outside the runtime, so the blocking lookups are fine here.
+    fn through_symlink(&self, path: &Path) -> bool {
+        let watched = self.watched_paths.read().unwrap();
+        let Some(root) = watched
+            .iter()
+            .filter(|(root, directory)| directory.covers(root, path))
+            .map(|(root, directory)| if path.starts_with(root) { root.as_path() } else { directory.canonical.as_path() })
+            .next()
+        else {
+            return false;
+        };
+        let is_symlink = |p: &Path| std::fs::symlink_metadata(p).is_ok_and(|m| m.file_type().is_symlink());
+        path.ancestors()
+            .take_while(|ancestor| *ancestor != root)
+            .enumerate()
+            .any(|(depth, ancestor)| is_symlink(ancestor) && (depth > 0 || ancestor.is_dir()))
+    }
+
+    /// Start watching the targets of symlinked directories below `root`
+    async fn watch_symlink_targets(&self, root: &Path) {
+        let root_canonical = tokio::fs::canonicalize(root).await.unwrap_or_else(|_| root.to_path_buf());
+        for (link, target) in symlinked_directories(root).await {
+            if target.starts_with(&root_canonical) {
+                continue; // Already inside the watched tree
+            }
+            let mut debouncer_guard = self.debouncer.write().await;
+            let Some(debouncer) = debouncer_guard.as_mut() else {
+                return;
+            };
+            match debouncer.watcher().watch(&target, RecursiveMode::Recursive) {
+                Ok(()) => {
+                    info!("Following symlinked directory {:?} -> {:?}", link, target);
+                    self.symlink_targets.write().unwrap().insert(target, link);
+                }
+                Err(e) => warn!("Failed to watch symlink target {:?} of {:?}: {}", target, link, e),
             }
         }
-        false
+    }
+
+    /// Whether a file name matches an exclude pattern of a watched directory containing it
+    fn is_excluded(&self, path: &Path) -> bool {
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            return false;
+        };
+        let watched = self.watched_paths.read().unwrap();
+        let filters = self.filters.read().unwrap();
+        filters.exclude_patterns.iter().any(|(root, patterns)| {
+            let contains = watched
+                .get(root)
+                .map_or_else(|| path.starts_with(root), |directory| directory.covers(root, path));
+            contains && patterns.iter().any(|pattern| AppConfig::matches_pattern(file_name, pattern))
+        })
+    }
+
+    /// Whether a path should be ignored because it is hidden
+    fn skips_hidden(&self, path: &Path) -> bool {
+        !self.filters.read().unwrap().include_hidden && is_hidden_path(path)
+    }
+
+    /// Whether events for `path` belong to a watched directory: anywhere below a
+    /// recursive one, or directly inside a non-recursive one. Backends may
+    /// report deeper events for non-recursive watches, so they are filtered here.
+    fn in_watch_scope(&self, path: &Path) -> bool {
+        let watched = self.watched_paths.read().unwrap();
+        watched.is_empty() || watched.iter().any(|(root, directory)| directory.covers(root, path))
+    }
+
+    /// Whether subdirectories of `path` are indexed, i.e. it lies below a recursively watched directory
+    fn watches_subdirectories(&self, path: &Path) -> bool {
+        let watched = self.watched_paths.read().unwrap();
+        watched.is_empty()
+            || watched
+                .iter()
+                .any(|(root, directory)| directory.mode == RecursiveMode::Recursive && directory.covers(root, path))
+    }
+
+    /// Check if a file is a supported media file based on its extension and is
+    /// neither hidden nor excluded
+    fn is_media_file(&self, path: &Path) -> bool {
+        if self.skips_hidden(path) || self.is_excluded(path) {
+            return false;
+        }
+        has_media_type(&self.media_extensions, &self.filters.read().unwrap().media_types, path)
     }
 
     /// Convert notify events to our FileSystemEvent enum
     fn convert_events(&self, events: Vec<DebouncedEvent>) -> Vec<FileSystemEvent> {
         let mut fs_events = Vec::new();
         
-        for event in events {
+        for mut event in events {
+            event.event.paths = std::mem::take(&mut event.event.paths)
+                .into_iter()
+                .map(|path| self.resolve_symlink_target(&path))
+                .filter(|path| self.in_watch_scope(path))
+                .filter(|path| self.follow_symlinks || !self.through_symlink(path))
+                .collect();
             match event.event.kind {
                 notify::EventKind::Create(_) => {
                     for path in &event.event.paths {
-                        if path.is_dir() {
+                        if path.is_dir() && self.skips_hidden(path) {
+                            debug!("Hidden directory created, ignoring: {:?}", path);
+                        } else if path.is_dir() && !self.watches_subdirectories(path) {
+                            debug!("Directory created in a non-recursive media directory, ignoring: {:?}", path);
+                        } else if path.is_dir() {
                             // Handle directory creation - scan for media files
                             info!("Directory created (detected by watcher): {:?}", path);
                             fs_events.push(FileSystemEvent::Created(path.clone()));
@@ -159,13 +559,63 @@ impl CrossPlatformWatcher {
             }
         }
         
-        fs_events
+        // Report paths in the same form the scanner and database use
+        fs_events.into_iter().map(Self::normalize_event_paths).collect()
+    }
+
+    /// Strip Windows extended-length prefixes from the paths of an event
+    fn normalize_event_paths(event: FileSystemEvent) -> FileSystemEvent {
+        match event {
+            FileSystemEvent::Created(path) => FileSystemEvent::Created(strip_extended_length_prefix(&path)),
+            FileSystemEvent::Modified(path) => FileSystemEvent::Modified(strip_extended_length_prefix(&path)),
+            FileSystemEvent::Deleted(path) => FileSystemEvent::Deleted(strip_extended_length_prefix(&path)),
+            FileSystemEvent::Renamed { from, to } => FileSystemEvent::Renamed {
+                from: strip_extended_length_prefix(&from),
+                to: strip_extended_length_prefix(&to),
+            },
+            FileSystemEvent::ResyncNeeded(directories) => FileSystemEvent::ResyncNeeded(
+                directories.iter().map(|dir| strip_extended_length_prefix(dir)).collect(),
+            ),
+        }
+    }
+
+    /// Start the task that turns dropped events into a directory resync once
+    /// the burst subsides. Only one task runs per watcher.
+    fn spawn_resync_task(&self) {
+        if self.overflow.resync_task_started.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let overflow = self.overflow.clone();
+        let event_sender = self.event_sender.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(RESYNC_SETTLE_DURATION / 4);
+            while !event_sender.is_closed() {
+                ticker.tick().await;
+                if let Some((directories, dropped)) = overflow.take_settled(RESYNC_SETTLE_DURATION) {
+                    info!(
+                        "Scheduling resync of {} director(ies) after {} file system event(s) were dropped",
+                        directories.len(),
+                        dropped
+                    );
+                    // Wait for room rather than dropping the resync itself
+                    if event_sender.send(FileSystemEvent::ResyncNeeded(directories)).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        });
     }
 
     /// Initialize the debounced watcher
     async fn initialize_watcher(&self) -> Result<()> {
         let event_sender = self.event_sender.clone();
         let media_extensions = self.media_extensions.clone();
+        let filters = self.filters.clone();
+        let overflow = self.overflow.clone();
+        let watched_paths = self.watched_paths.clone();
+        let follow_symlinks = self.follow_symlinks;
+        let symlink_targets = self.symlink_targets.clone();
+        self.spawn_resync_task();
         
         let debouncer = new_debouncer(
             self.debounce_duration,
@@ -198,13 +648,9 @@ impl CrossPlatformWatcher {
                                     }
                                     
                                     // Include media files
-                                    if let Some(extension) = path.extension() {
-                                        if let Some(ext_str) = extension.to_str() {
-                                            if media_extensions.contains(&ext_str.to_lowercase()) {
-                                                info!("Including media file event for path: {:?}", path);
-                                                return true;
-                                            }
-                                        }
+                                    if has_media_type(&media_extensions, &filters.read().unwrap().media_types, path) {
+                                        info!("Including media file event for path: {:?}", path);
+                                        return true;
                                     }
                                     
                                     debug!("Excluding non-media file event for path: {:?}", path);
@@ -219,17 +665,17 @@ impl CrossPlatformWatcher {
                                 debouncer: Arc::new(RwLock::new(None)),
                                 event_sender: event_sender.clone(),
                                 event_receiver: Arc::new(RwLock::new(None)),
-                                watched_paths: Arc::new(RwLock::new(HashSet::new())),
+                                watched_paths: watched_paths.clone(),
                                 media_extensions: media_extensions.clone(),
+                                filters: filters.clone(),
                                 debounce_duration: Duration::from_millis(100),
+                                overflow: overflow.clone(),
+                                follow_symlinks,
+                                symlink_targets: symlink_targets.clone(),
                             };
                             
                             let fs_events = watcher.convert_events(relevant_events);
-                            for fs_event in fs_events {
-                                if let Err(e) = event_sender.try_send(fs_event) {
-                                    error!("Failed to send file system event: {}", e);
-                                }
-                            }
+                            dispatch_events(&event_sender, &overflow, fs_events);
                         }
                     }
                     Err(errors) => {
@@ -252,7 +698,7 @@ impl CrossPlatformWatcher {
 
 #[async_trait]
 impl FileSystemWatcher for CrossPlatformWatcher {
-    async fn start_watching(&self, directories: &[PathBuf]) -> Result<()> {
+    async fn start_watching(&self, directories: &[(PathBuf, RecursiveMode)]) -> Result<()> {
         info!("Starting file system watcher for {} directories", directories.len());
         
         // Initialize the watcher if not already done
@@ -262,9 +708,7 @@ impl FileSystemWatcher for CrossPlatformWatcher {
 
         let mut debouncer_guard = self.debouncer.write().await;
         if let Some(ref mut debouncer) = *debouncer_guard {
-            let mut watched_paths = self.watched_paths.write().await;
-            
-            for directory in directories {
+            for (directory, mode) in directories {
                 if !directory.exists() {
                     warn!("Directory does not exist, skipping: {:?}", directory);
                     continue;
@@ -275,10 +719,10 @@ impl FileSystemWatcher for CrossPlatformWatcher {
                     continue;
                 }
 
-                match debouncer.watcher().watch(directory, RecursiveMode::Recursive) {
+                match debouncer.watcher().watch(directory, *mode) {
                     Ok(()) => {
-                        watched_paths.insert(directory.clone());
-                        info!("Started watching directory: {:?}", directory);
+                        self.watched_paths.write().unwrap().insert(directory.clone(), WatchedDirectory::new(directory, *mode));
+                        info!("Started watching directory: {:?} ({:?})", directory, mode);
                         
                         // Test if directory is accessible
                         if directory.exists() && directory.is_dir() {
@@ -294,6 +738,13 @@ impl FileSystemWatcher for CrossPlatformWatcher {
                 }
             }
         }
+        drop(debouncer_guard);
+
+        if self.follow_symlinks {
+            for (directory, _) in directories.iter().filter(|(_, mode)| *mode == RecursiveMode::Recursive) {
+                self.watch_symlink_targets(directory).await;
+            }
+        }
 
         Ok(())
     }
@@ -307,8 +758,7 @@ impl FileSystemWatcher for CrossPlatformWatcher {
             drop(debouncer);
         }
         
-        let mut watched_paths = self.watched_paths.write().await;
-        watched_paths.clear();
+        self.watched_paths.write().unwrap().clear();
         
         info!("File system watcher stopped");
         Ok(())
@@ -327,11 +777,11 @@ impl FileSystemWatcher for CrossPlatformWatcher {
         // If we can't get the original receiver, create a new channel
         // This shouldn't happen in normal usage
         warn!("Creating new event receiver - original may have been consumed");
-        let (_, receiver) = mpsc::channel(1000);
+        let (_, receiver) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
         receiver
     }
 
-    async fn add_watch_path(&self, path: &Path) -> Result<()> {
+    async fn add_watch_path(&self, path: &Path, mode: RecursiveMode) -> Result<()> {
         if !path.exists() {
             warn!("Path does not exist, cannot watch: {:?}", path);
             return Ok(());
@@ -339,17 +789,19 @@ impl FileSystemWatcher for CrossPlatformWatcher {
 
         let mut debouncer_guard = self.debouncer.write().await;
         if let Some(ref mut debouncer) = *debouncer_guard {
-            let mut watched_paths = self.watched_paths.write().await;
-            
-            if watched_paths.contains(path) {
+            if self.watched_paths.read().unwrap().contains_key(path) {
                 debug!("Path already being watched: {:?}", path);
                 return Ok(());
             }
 
-            match debouncer.watcher().watch(path, RecursiveMode::Recursive) {
+            match debouncer.watcher().watch(path, mode) {
                 Ok(()) => {
-                    watched_paths.insert(path.to_path_buf());
-                    info!("Added watch path: {:?}", path);
+                    self.watched_paths.write().unwrap().insert(path.to_path_buf(), WatchedDirectory::new(path, mode));
+                    info!("Added watch path: {:?} ({:?})", path, mode);
+                    drop(debouncer_guard);
+                    if self.follow_symlinks && mode == RecursiveMode::Recursive {
+                        self.watch_symlink_targets(path).await;
+                    }
                     Ok(())
                 }
                 Err(e) => {
@@ -366,16 +818,14 @@ impl FileSystemWatcher for CrossPlatformWatcher {
     async fn remove_watch_path(&self, path: &Path) -> Result<()> {
         let mut debouncer_guard = self.debouncer.write().await;
         if let Some(ref mut debouncer) = *debouncer_guard {
-            let mut watched_paths = self.watched_paths.write().await;
-            
-            if !watched_paths.contains(path) {
+            if !self.watched_paths.read().unwrap().contains_key(path) {
                 debug!("Path not being watched: {:?}", path);
                 return Ok(());
             }
 
             match debouncer.watcher().unwatch(path) {
                 Ok(()) => {
-                    watched_paths.remove(path);
+                    self.watched_paths.write().unwrap().remove(path);
                     info!("Removed watch path: {:?}", path);
                     Ok(())
                 }
@@ -391,8 +841,15 @@ impl FileSystemWatcher for CrossPlatformWatcher {
     }
 
     async fn is_watching(&self, path: &Path) -> bool {
-        let watched_paths = self.watched_paths.read().await;
-        watched_paths.contains(path)
+        self.watched_paths.read().unwrap().contains_key(path)
+    }
+
+    fn update_filters(&self, media: &MediaConfig) {
+        *self.filters.write().unwrap() = EventFilters {
+            include_hidden: media.include_hidden,
+            media_types: media.media_types(),
+            exclude_patterns: exclude_patterns(media),
+        };
     }
 }
 
@@ -424,12 +881,32 @@ mod tests {
         assert!(watcher.is_media_file(Path::new("test.jpg")));
         assert!(!watcher.is_media_file(Path::new("test.txt")));
         assert!(!watcher.is_media_file(Path::new("test")));
+        assert!(!watcher.is_media_file(Path::new("test.ts")));
+
+        // Configured extensions, and files identified by their content, are media too
+        let media_types = MediaTypes::new(Some(&HashMap::from([("ts".to_string(), "video/mp2t".to_string())])))
+            .with_content_sniffing(true, &[]);
+        let watcher = CrossPlatformWatcher::new().with_media_types(media_types);
+        assert!(watcher.is_media_file(Path::new("test.ts")));
+        assert!(watcher.is_media_file(Path::new("test")));
+        assert!(watcher.is_media_file(Path::new("test.dat")));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_hidden_media_files_ignored_unless_included() {
+        let watcher = CrossPlatformWatcher::new();
+        assert!(!watcher.is_media_file(Path::new("/media/.preview.mp4")));
+        assert!(watcher.is_media_file(Path::new("/media/preview.mp4")));
+
+        let watcher = CrossPlatformWatcher::new().with_hidden_files(true);
+        assert!(watcher.is_media_file(Path::new("/media/.preview.mp4")));
     }
 
     #[tokio::test]
     async fn test_watch_nonexistent_directory() {
         let watcher = CrossPlatformWatcher::new();
-        let result = watcher.start_watching(&[PathBuf::from("/nonexistent/path")]).await;
+        let result = watcher.start_watching(&[(PathBuf::from("/nonexistent/path"), RecursiveMode::Recursive)]).await;
         // Should not fail, just log a warning
         assert!(result.is_ok());
     }
@@ -440,7 +917,7 @@ mod tests {
         let watcher = CrossPlatformWatcher::new();
         
         // Start watching
-        let result = watcher.start_watching(&[temp_dir.path().to_path_buf()]).await;
+        let result = watcher.start_watching(&[(temp_dir.path().to_path_buf(), RecursiveMode::Recursive)]).await;
         assert!(result.is_ok());
         
         // Check if watching
@@ -463,7 +940,7 @@ mod tests {
         let mut receiver = watcher.get_event_receiver();
         
         // Start watching
-        watcher.start_watching(&[temp_dir.path().to_path_buf()]).await.unwrap();
+        watcher.start_watching(&[(temp_dir.path().to_path_buf(), RecursiveMode::Recursive)]).await.unwrap();
         
         // Give the watcher time to initialize
         sleep(Duration::from_millis(200)).await;
@@ -518,4 +995,171 @@ mod tests {
         
         watcher.stop_watching().await.unwrap();
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_event_flood_schedules_resync() {
+        let temp_dir = TempDir::new().unwrap();
+        let watcher = CrossPlatformWatcher::new().with_event_channel_capacity(8);
+        let mut receiver = watcher.get_event_receiver();
+        watcher.start_watching(&[(temp_dir.path().to_path_buf(), RecursiveMode::Recursive)]).await.unwrap();
+
+        let flood: Vec<_> = (0..50)
+            .map(|i| FileSystemEvent::Created(temp_dir.path().join(format!("clip{}.mp4", i))))
+            .collect();
+        dispatch_events(&watcher.event_sender, &watcher.overflow, flood);
+        assert!(watcher.resync_pending());
+
+        let mut created = 0;
+        let resynced = timeout(Duration::from_secs(5), async {
+            loop {
+                match receiver.recv().await {
+                    Some(FileSystemEvent::Created(_)) => created += 1,
+                    Some(FileSystemEvent::ResyncNeeded(directories)) => return directories,
+                    other => panic!("unexpected event: {:?}", other),
+                }
+            }
+        })
+        .await
+        .expect("a resync should be scheduled once the burst subsides");
+
+        assert_eq!(created, 8);
+        assert_eq!(resynced, vec![temp_dir.path().to_path_buf()]);
+        assert!(!watcher.resync_pending());
+
+        watcher.stop_watching().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_non_recursive_watch_ignores_subdirectories() {
+        let temp_dir = TempDir::new().unwrap();
+        let subdir = temp_dir.path().join("extras");
+        fs::create_dir(&subdir).unwrap();
+        let watcher = CrossPlatformWatcher::new();
+        let mut receiver = watcher.get_event_receiver();
+        watcher
+            .start_watching(&[(temp_dir.path().to_path_buf(), RecursiveMode::NonRecursive)])
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(200)).await;
+
+        fs::write(subdir.join("bonus.mp4"), b"test content").unwrap();
+        fs::create_dir(temp_dir.path().join("new_folder")).unwrap();
+        // A file directly inside the directory is still reported
+        let top_level = temp_dir.path().join("movie.mp4");
+        fs::write(&top_level, b"test content").unwrap();
+
+        let mut events = Vec::new();
+        let _ = timeout(Duration::from_secs(2), async {
+            while let Some(event) = receiver.recv().await {
+                events.push(event);
+            }
+        })
+        .await;
+
+        let paths: Vec<PathBuf> = events
+            .iter()
+            .flat_map(|event| match event {
+                FileSystemEvent::Created(path) | FileSystemEvent::Modified(path) | FileSystemEvent::Deleted(path) => {
+                    vec![path.clone()]
+                }
+                FileSystemEvent::Renamed { from, to } => vec![from.clone(), to.clone()],
+                FileSystemEvent::ResyncNeeded(directories) => directories.clone(),
+            })
+            .collect();
+        assert!(paths.iter().all(|path| !path.starts_with(&subdir)), "{:?}", events);
+        assert!(paths.iter().all(|path| !path.ends_with("new_folder")), "{:?}", events);
+        assert!(paths.iter().any(|path| path.ends_with("movie.mp4")), "{:?}", events);
+    }
+
+    #[tokio::test]
+    async fn test_excluded_files_produce_no_events() {
+        let temp_dir = TempDir::new().unwrap();
+        let patterns = HashMap::from([(
+            temp_dir.path().to_path_buf(),
+            vec!["*.part.mp4".to_string(), "sample.mkv".to_string(), ".*".to_string()],
+        )]);
+        let watcher = CrossPlatformWatcher::new()
+            .with_hidden_files(true)
+            .with_exclude_patterns(patterns);
+        assert!(watcher.is_excluded(&temp_dir.path().join("movie.part.mp4")));
+        assert!(!watcher.is_excluded(Path::new("/elsewhere/sample.mkv")));
+
+        let mut receiver = watcher.get_event_receiver();
+        watcher
+            .start_watching(&[(temp_dir.path().to_path_buf(), RecursiveMode::Recursive)])
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(200)).await;
+
+        for name in ["movie.part.mp4", "sample.mkv", ".preview.mp4", "movie.mp4"] {
+            fs::write(temp_dir.path().join(name), b"test content").unwrap();
+        }
+
+        let mut events = Vec::new();
+        let _ = timeout(Duration::from_secs(2), async {
+            while let Some(event) = receiver.recv().await {
+                events.push(event);
+            }
+        })
+        .await;
+
+        let reported: Vec<&PathBuf> = events
+            .iter()
+            .filter_map(|event| match event {
+                FileSystemEvent::Created(path) | FileSystemEvent::Modified(path) => Some(path),
+                _ => None,
+            })
+            .collect();
+        assert!(reported.iter().any(|path| path.ends_with("movie.mp4")), "{:?}", events);
+        for name in ["movie.part.mp4", "sample.mkv", ".preview.mp4"] {
+            assert!(reported.iter().all(|path| !path.ends_with(name)), "{:?}", events);
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_symlinked_directory_events_follow_the_flag() {
+        let temp_dir = TempDir::new().unwrap();
+        let media_dir = temp_dir.path().join("media");
+        let drive = temp_dir.path().join("drive");
+        fs::create_dir_all(&media_dir).unwrap();
+        fs::create_dir_all(&drive).unwrap();
+        std::os::unix::fs::symlink(&drive, media_dir.join("drive")).unwrap();
+        std::os::unix::fs::symlink(&media_dir, media_dir.join("loop")).unwrap();
+
+        let created_under_link = |follow: bool| {
+            let media_dir = media_dir.clone();
+            let drive = drive.clone();
+            async move {
+                let watcher = CrossPlatformWatcher::new().with_follow_symlinks(follow);
+                let mut receiver = watcher.get_event_receiver();
+                timeout(
+                    Duration::from_secs(10),
+                    watcher.start_watching(&[(media_dir.clone(), RecursiveMode::Recursive)]),
+                )
+                .await
+                .expect("symlink cycle was not broken")
+                .unwrap();
+                sleep(Duration::from_millis(200)).await;
+
+                let name = format!("linked-{}.mp4", follow);
+                fs::write(drive.join(&name), b"test content").unwrap();
+                let mut events = Vec::new();
+                let _ = timeout(Duration::from_secs(2), async {
+                    while let Some(event) = receiver.recv().await {
+                        events.push(event);
+                    }
+                })
+                .await;
+                watcher.stop_watching().await.unwrap();
+                events.iter().any(|event| {
+                    matches!(event, FileSystemEvent::Created(path) | FileSystemEvent::Modified(path)
+                        if *path == media_dir.join("drive").join(&name))
+                })
+            }
+        };
+
+        assert!(!created_under_link(false).await);
+        assert!(created_under_link(true).await);
+    }
+}
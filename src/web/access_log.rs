@@ -0,0 +1,82 @@
+use axum::{
+    body::HttpBody,
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use std::net::SocketAddr;
+use tracing::info;
+
+use crate::state::AppState;
+
+/// Path prefixes of the requests renderers make for content: DIDL browsing and media bytes
+const ACCESS_LOGGED_PREFIXES: &[&str] = &["/media/", "/download/", "/thumbnail/", "/albumart/", "/subtitle/", "/control/ContentDirectory"];
+
+/// Whether a request path is covered by the access log
+pub fn is_access_logged(path: &str) -> bool {
+    ACCESS_LOGGED_PREFIXES.iter().any(|prefix| path.starts_with(prefix))
+}
+
+fn header_str(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// Logs media and DIDL requests with the client address and User-Agent when
+/// `logging.access_log` is enabled, so quirky renderers can be told apart.
+pub async fn log_access(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if !state.config.logging.access_log || !is_access_logged(request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    let client = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0.ip().to_string())
+        .unwrap_or_else(|| "-".to_string());
+    let user_agent = header_str(request.headers(), header::USER_AGENT).unwrap_or("-").to_string();
+    let soap_action = request
+        .headers()
+        .get("soapaction")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|action| action.trim_matches('"').rsplit('#').next())
+        .unwrap_or_default()
+        .to_string();
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+
+    let response = next.run(request).await;
+
+    // Streams report their length in the header; buffered bodies know their exact size
+    let bytes = header_str(response.headers(), header::CONTENT_LENGTH)
+        .and_then(|length| length.parse::<u64>().ok())
+        .or_else(|| response.body().size_hint().exact());
+    info!(
+        target: "vuio::access",
+        "{} \"{} {}{}\" {} {} \"{}\"",
+        client,
+        method,
+        path,
+        if soap_action.is_empty() { String::new() } else { format!(" {}", soap_action) },
+        response.status().as_u16(),
+        bytes.map(|bytes| bytes.to_string()).unwrap_or_else(|| "-".to_string()),
+        user_agent,
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_content_requests_are_logged() {
+        assert!(is_access_logged("/media/42"));
+        assert!(is_access_logged("/media/by-path/Music/song.mp3"));
+        assert!(is_access_logged("/thumbnail/7"));
+        assert!(is_access_logged("/control/ContentDirectory"));
+        assert!(!is_access_logged("/description.xml"));
+        assert!(!is_access_logged("/health"));
+        assert!(!is_access_logged("/api/stats"));
+    }
+}
@@ -0,0 +1,232 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use tracing::{info, trace, warn};
+
+use crate::{
+    config::{ApiToken, AuthMode, ServerAuthConfig, ServerConfig},
+    state::AppState,
+    web::error::WebError,
+};
+
+/// Label under which the single `server.api_token` shortcut is accepted
+pub const DEFAULT_TOKEN_LABEL: &str = "default";
+
+/// SHA-256 hex digest of an API token, the form stored in `server.api_tokens`
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Compare two digests without exiting early on the first differing byte
+fn digests_match(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Label of the authenticated token, added to the request extensions of privileged calls
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenLabel(pub String);
+
+/// The API tokens currently accepted. Shared by all requests and replaced when
+/// the configuration is reloaded, so tokens can be issued and revoked without a restart.
+#[derive(Clone, Default)]
+pub struct ApiTokenStore {
+    tokens: Arc<RwLock<Vec<ApiToken>>>,
+}
+
+impl ApiTokenStore {
+    pub fn from_config(server: &ServerConfig) -> Self {
+        let store = Self::default();
+        store.reload(server);
+        store
+    }
+
+    /// Replace the accepted tokens with those in `server`
+    pub fn reload(&self, server: &ServerConfig) {
+        let mut tokens = server.api_tokens.clone();
+        if let Some(token) = &server.api_token {
+            tokens.push(ApiToken {
+                label: DEFAULT_TOKEN_LABEL.to_string(),
+                token_sha256: hash_token(token),
+            });
+        }
+        for token in &mut tokens {
+            token.token_sha256.make_ascii_lowercase();
+        }
+        *self.tokens.write().unwrap() = tokens;
+    }
+
+    /// Whether any token is configured; without one the API is left open,
+    /// apart from the routes [`require_configured_credentials`] refuses
+    pub fn is_enabled(&self) -> bool {
+        !self.tokens.read().unwrap().is_empty()
+    }
+
+    /// Label of the configured token matching `token`
+    pub fn authenticate(&self, token: &str) -> Option<String> {
+        let digest = hash_token(token);
+        self.tokens
+            .read()
+            .unwrap()
+            .iter()
+            .find(|t| digests_match(&t.token_sha256, &digest))
+            .map(|t| t.label.clone())
+    }
+
+    /// Labels of the configured tokens, never their digests
+    pub fn labels(&self) -> Vec<String> {
+        self.tokens.read().unwrap().iter().map(|t| t.label.clone()).collect()
+    }
+}
+
+/// Require a configured API token as `Authorization: Bearer <token>` and record
+/// which labeled token made each call. The API stays open while no token is configured.
+pub async fn require_api_token(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, WebError> {
+    if !state.api_tokens.is_enabled() {
+        return Ok(next.run(request).await);
+    }
+
+    let presented = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim);
+    let Some(label) = presented.and_then(|token| state.api_tokens.authenticate(token)) else {
+        warn!(target: "vuio::audit", "Rejected {} {}: missing or unknown API token", request.method(), request.uri().path());
+        return Err(WebError::Unauthorized);
+    };
+
+    info!(target: "vuio::audit", "{} {} by API token '{}'", request.method(), request.uri().path(), label);
+    request.extensions_mut().insert(TokenLabel(label));
+    Ok(next.run(request).await)
+}
+
+/// Refuse privileged API calls, which reveal the configuration or change the
+/// library and database, while neither API tokens nor `server.auth` are
+/// configured. The server listens on all interfaces by default, so these would
+/// otherwise be open to anyone on the network.
+pub async fn require_configured_credentials(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, WebError> {
+    if state.api_tokens.is_enabled() || state.config.server.auth.mode != AuthMode::Disabled {
+        return Ok(next.run(request).await);
+    }
+
+    warn!(
+        target: "vuio::audit",
+        "Rejected {} {}: configure an API token or server.auth to use this endpoint",
+        request.method(),
+        request.uri().path()
+    );
+    Err(WebError::Forbidden)
+}
+
+/// Paths renderers fetch without credentials: device and service descriptions,
+/// the device icon, SOAP control, eventing, and the media, thumbnails, album art and subtitles they play
+fn is_dlna_path(path: &str) -> bool {
+    matches!(
+        path,
+        "/description.xml" | "/ContentDirectory.xml" | "/ConnectionManager.xml" | "/icon.png" | "/icon.jpg"
+    )
+        || ["/control/", "/event/", "/media/", "/thumbnail/", "/albumart/", "/subtitle/"]
+            .iter()
+            .any(|prefix| path.starts_with(prefix))
+}
+
+/// Whether the `Authorization` header satisfies the configured `server.auth`
+fn credentials_match(auth: &ServerAuthConfig, authorization: Option<&str>) -> bool {
+    let Some(authorization) = authorization else {
+        return false;
+    };
+    match auth.mode {
+        AuthMode::Disabled => true,
+        AuthMode::Basic => {
+            let Some(decoded) = authorization
+                .strip_prefix("Basic ")
+                .and_then(|encoded| STANDARD.decode(encoded.trim()).ok())
+                .and_then(|bytes| String::from_utf8(bytes).ok())
+            else {
+                return false;
+            };
+            let expected = format!(
+                "{}:{}",
+                auth.username.as_deref().unwrap_or_default(),
+                auth.password.as_deref().unwrap_or_default()
+            );
+            digests_match(&hash_token(&decoded), &hash_token(&expected))
+        }
+        AuthMode::Token => authorization
+            .strip_prefix("Bearer ")
+            .zip(auth.token.as_deref())
+            .is_some_and(|(presented, token)| digests_match(&hash_token(presented.trim()), &hash_token(token))),
+    }
+}
+
+/// Require the credentials configured in `server.auth` for every request.
+/// `/health` is always open for liveness probes, and DLNA paths stay open when `exempt_dlna` is set; `/api/*` calls are left to
+/// [`require_api_token`] when API tokens are configured, since a request carries
+/// a single `Authorization` header.
+pub async fn require_server_auth(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let auth = &state.config.server.auth;
+    let path = request.uri().path();
+    let exempt = auth.mode == AuthMode::Disabled
+        || path == "/health"
+        || (auth.exempt_dlna && is_dlna_path(path))
+        || (path.starts_with("/api/") && state.api_tokens.is_enabled());
+    let authorization = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+    if exempt || credentials_match(auth, authorization) {
+        return next.run(request).await;
+    }
+
+    warn!(target: "vuio::audit", "Rejected {} {}: missing or invalid server credentials", request.method(), path);
+    match auth.mode {
+        AuthMode::Basic => (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Basic realm=\"VuIO\", charset=\"UTF-8\"")],
+            "Unauthorized",
+        )
+            .into_response(),
+        _ => (StatusCode::UNAUTHORIZED, [(header::WWW_AUTHENTICATE, "Bearer realm=\"VuIO\"")], "Unauthorized")
+            .into_response(),
+    }
+}
+
+/// Refuse clients outside the configured client subnets. Rejections are not
+/// logged above trace level, so a misbehaving device cannot flood the logs.
+pub async fn require_allowed_client(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    // Without connection info (e.g. when the router is driven directly) there is nothing to filter on
+    let client = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|info| info.0);
+    if let Some(client) = client {
+        if !state.client_filter.permits(client.ip()) {
+            trace!("Refusing {} {} from filtered client {}", request.method(), request.uri().path(), client);
+            return StatusCode::FORBIDDEN.into_response();
+        }
+    }
+    next.run(request).await
+}
+
+/// Lists the labels of the configured API tokens
+pub async fn api_tokens_handler(State(state): State<AppState>) -> Json<serde_json::Value> {
+    Json(json!({
+        "auth_required": state.api_tokens.is_enabled(),
+        "labels": state.api_tokens.labels(),
+    }))
+}
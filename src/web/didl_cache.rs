@@ -0,0 +1,208 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+/// Capacity used when no configuration is available
+pub const DEFAULT_DIDL_CACHE_BYTES: u64 = 8 * 1024 * 1024;
+
+/// The arguments that determine a Browse response
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BrowseKey {
+    pub object_id: String,
+    pub browse_flag: String,
+    pub starting_index: String,
+    pub requested_count: String,
+    pub sort_criteria: String,
+    /// Host of the media URLs, which follows the client's address family
+    pub server_host: String,
+}
+
+impl BrowseKey {
+    fn size(&self) -> u64 {
+        (self.object_id.len()
+            + self.browse_flag.len()
+            + self.starting_index.len()
+            + self.requested_count.len()
+            + self.sort_criteria.len()
+            + self.server_host.len()) as u64
+    }
+}
+
+struct CachedResponse {
+    body: Arc<str>,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct CacheEntries {
+    responses: HashMap<BrowseKey, CachedResponse>,
+    /// Keys ordered by last use, oldest first
+    recency: BTreeMap<u64, BrowseKey>,
+    total_bytes: u64,
+    clock: u64,
+    /// SystemUpdateID the cached responses were rendered for
+    update_id: u32,
+}
+
+impl CacheEntries {
+    fn remove(&mut self, key: &BrowseKey) {
+        if let Some(response) = self.responses.remove(key) {
+            self.recency.remove(&response.last_used);
+            self.total_bytes -= key.size() + response.body.len() as u64;
+        }
+    }
+
+    fn touch(&mut self, key: &BrowseKey) {
+        self.clock += 1;
+        if let Some(response) = self.responses.get_mut(key) {
+            self.recency.remove(&response.last_used);
+            response.last_used = self.clock;
+            self.recency.insert(self.clock, key.clone());
+        }
+    }
+
+    /// Forget every response once the library has changed
+    fn sync_update_id(&mut self, update_id: u32) {
+        if self.update_id != update_id {
+            self.responses.clear();
+            self.recency.clear();
+            self.total_bytes = 0;
+            self.update_id = update_id;
+        }
+    }
+}
+
+/// Byte-bounded LRU cache of rendered Browse responses. Renderers re-browse the
+/// same containers constantly, so responses are reused until the SystemUpdateID
+/// changes.
+#[derive(Clone)]
+pub struct DidlCache {
+    entries: Arc<Mutex<CacheEntries>>,
+    capacity_bytes: u64,
+}
+
+impl Default for DidlCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_DIDL_CACHE_BYTES)
+    }
+}
+
+impl DidlCache {
+    /// Create a cache holding at most `capacity_bytes`; 0 disables caching
+    pub fn new(capacity_bytes: u64) -> Self {
+        Self {
+            entries: Arc::default(),
+            capacity_bytes,
+        }
+    }
+
+    /// Response cached for `key`, if it was rendered at the current `update_id`
+    pub fn get(&self, key: &BrowseKey, update_id: u32) -> Option<Arc<str>> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.sync_update_id(update_id);
+        let body = entries.responses.get(key)?.body.clone();
+        entries.touch(key);
+        Some(body)
+    }
+
+    /// Cache `body` for `key` after a miss. `update_id` is the SystemUpdateID read
+    /// before the response was rendered, so a change made meanwhile discards it.
+    pub fn insert(&self, key: BrowseKey, update_id: u32, body: Arc<str>) {
+        let size = key.size() + body.len() as u64;
+        if size > self.capacity_bytes {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.update_id != update_id {
+            return;
+        }
+        entries.remove(&key);
+        while entries.total_bytes + size > self.capacity_bytes {
+            let Some((_, oldest)) = entries.recency.pop_first() else {
+                break;
+            };
+            entries.remove(&oldest);
+        }
+        entries.total_bytes += size;
+        entries.responses.insert(key.clone(), CachedResponse { body, last_used: 0 });
+        entries.touch(&key);
+    }
+
+    /// Forget every response, for changes the SystemUpdateID does not cover such
+    /// as a configuration reload or a new server address
+    pub fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.responses.clear();
+        entries.recency.clear();
+        entries.total_bytes = 0;
+    }
+
+    /// Total bytes currently cached
+    pub fn cached_bytes(&self) -> u64 {
+        self.entries.lock().unwrap().total_bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(object_id: &str) -> BrowseKey {
+        BrowseKey {
+            object_id: object_id.to_string(),
+            browse_flag: "BrowseDirectChildren".to_string(),
+            starting_index: "0".to_string(),
+            requested_count: "0".to_string(),
+            sort_criteria: String::new(),
+            server_host: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used_within_capacity() {
+        // Each entry costs its key (23 bytes for a one-letter ObjectID) plus the body
+        let cache = DidlCache::new(200);
+        assert!(cache.get(&key("a"), 1).is_none());
+        cache.insert(key("a"), 1, "x".repeat(57).into());
+        cache.insert(key("b"), 1, "y".repeat(57).into());
+        // Using `a` again makes `b` the eviction candidate
+        assert!(cache.get(&key("a"), 1).is_some());
+        cache.insert(key("c"), 1, "z".repeat(57).into());
+        assert_eq!(cache.cached_bytes(), 160);
+        assert!(cache.get(&key("a"), 1).is_some());
+        assert!(cache.get(&key("b"), 1).is_none());
+
+        // Responses larger than the whole cache are not kept
+        cache.insert(key("d"), 1, "w".repeat(500).into());
+        assert!(cache.get(&key("d"), 1).is_none());
+        let disabled = DidlCache::new(0);
+        assert!(disabled.get(&key("a"), 1).is_none());
+        disabled.insert(key("a"), 1, "x".into());
+        assert_eq!(disabled.cached_bytes(), 0);
+    }
+
+    #[test]
+    fn test_update_id_change_discards_responses() {
+        let cache = DidlCache::default();
+        assert!(cache.get(&key("video"), 3).is_none());
+        cache.insert(key("video"), 3, "<DIDL-Lite/>".into());
+        assert_eq!(cache.get(&key("video"), 3).as_deref(), Some("<DIDL-Lite/>"));
+        assert!(cache.get(&key("video"), 4).is_none());
+        assert_eq!(cache.cached_bytes(), 0);
+
+        // A response rendered before the change arrives too late to be cached
+        cache.insert(key("video"), 3, "<DIDL-Lite/>".into());
+        assert!(cache.get(&key("video"), 4).is_none());
+    }
+
+    #[test]
+    fn test_clear_discards_responses_at_same_update_id() {
+        let cache = DidlCache::default();
+        cache.insert(key("video"), 3, "<DIDL-Lite/>".into());
+        cache.clear();
+        assert!(cache.get(&key("video"), 3).is_none());
+        assert_eq!(cache.cached_bytes(), 0);
+        cache.insert(key("video"), 3, "<DIDL-Lite/>".into());
+        assert!(cache.get(&key("video"), 3).is_some());
+    }
+}
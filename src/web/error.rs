@@ -0,0 +1,193 @@
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use thiserror::Error;
+use tracing::{error, warn};
+
+use crate::error::AppError;
+
+/// Errors returned by HTTP handlers, each mapped to a distinct status code
+#[derive(Error, Debug)]
+pub enum WebError {
+    #[error("Not Found")]
+    NotFound,
+
+    #[error("Bad Request: {0}")]
+    BadRequest(String),
+
+    /// The requested byte range lies outside a file of the given size
+    #[error("Range Not Satisfiable")]
+    RangeNotSatisfiable(u64),
+
+    #[error("Unauthorized")]
+    Unauthorized,
+
+    #[error("Forbidden")]
+    Forbidden,
+
+    /// The resource cannot be delivered as the request demands, e.g. an image
+    /// requested with the DLNA `Streaming` transfer mode
+    #[error("Not Acceptable: {0}")]
+    NotAcceptable(String),
+
+    /// The server is at capacity, e.g. every transcoder is busy
+    #[error("Service Unavailable: {0}")]
+    ServiceUnavailable(String),
+
+    /// Details are logged but never sent to the client
+    #[error("Internal Server Error: {0}")]
+    Internal(String),
+}
+
+impl WebError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            WebError::NotFound => StatusCode::NOT_FOUND,
+            WebError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            WebError::RangeNotSatisfiable(_) => StatusCode::RANGE_NOT_SATISFIABLE,
+            WebError::Unauthorized => StatusCode::UNAUTHORIZED,
+            WebError::Forbidden => StatusCode::FORBIDDEN,
+            WebError::NotAcceptable(_) => StatusCode::NOT_ACCEPTABLE,
+            WebError::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            WebError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// UPnP error code and description used when reporting this error as a SOAP fault
+    fn upnp_error(&self) -> (u16, &'static str) {
+        match self {
+            WebError::NotFound => (701, "No such object"),
+            WebError::BadRequest(_) | WebError::RangeNotSatisfiable(_) | WebError::NotAcceptable(_) => {
+                (402, "Invalid Args")
+            }
+            WebError::Unauthorized | WebError::Forbidden => (606, "Action not authorized"),
+            WebError::ServiceUnavailable(_) | WebError::Internal(_) => (501, "Action Failed"),
+        }
+    }
+
+    /// Render the error as a UPnP SOAP fault for control endpoints
+    pub fn into_soap_fault(self) -> Response {
+        self.log();
+        let (code, description) = self.upnp_error();
+        let body = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/">
+    <s:Body>
+        <s:Fault>
+            <faultcode>s:Client</faultcode>
+            <faultstring>UPnPError</faultstring>
+            <detail>
+                <UPnPError xmlns="urn:schemas-upnp-org:control-1-0">
+                    <errorCode>{}</errorCode>
+                    <errorDescription>{}</errorDescription>
+                </UPnPError>
+            </detail>
+        </s:Fault>
+    </s:Body>
+</s:Envelope>"#,
+            code, description
+        );
+
+        // UPnP requires faults to be sent with 500 regardless of the cause
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(header::CONTENT_TYPE, "text/xml; charset=utf-8")],
+            body,
+        )
+            .into_response()
+    }
+
+    fn log(&self) {
+        match self {
+            WebError::Internal(details) => error!("Request failed: {}", details),
+            WebError::Forbidden | WebError::Unauthorized | WebError::ServiceUnavailable(_) => {
+                warn!("Request rejected: {}", self)
+            }
+            _ => {}
+        }
+    }
+}
+
+impl IntoResponse for WebError {
+    fn into_response(self) -> Response {
+        self.log();
+        let status = self.status_code();
+        let body = match &self {
+            WebError::BadRequest(reason) => format!("Bad Request: {}", reason),
+            WebError::Internal(_) => "Internal Server Error".to_string(),
+            other => other.to_string(),
+        };
+
+        match self {
+            WebError::RangeNotSatisfiable(file_size) => (
+                status,
+                [(header::CONTENT_RANGE, format!("bytes */{}", file_size))],
+                body,
+            )
+                .into_response(),
+            _ => (status, body).into_response(),
+        }
+    }
+}
+
+impl From<AppError> for WebError {
+    fn from(err: AppError) -> Self {
+        match err {
+            AppError::NotFound => WebError::NotFound,
+            AppError::InvalidRange => WebError::BadRequest("invalid Range header".to_string()),
+            AppError::Io(io_err) => io_err.into(),
+            other => WebError::Internal(other.to_string()),
+        }
+    }
+}
+
+impl From<anyhow::Error> for WebError {
+    fn from(err: anyhow::Error) -> Self {
+        WebError::Internal(format!("{:#}", err))
+    }
+}
+
+impl From<std::io::Error> for WebError {
+    fn from(err: std::io::Error) -> Self {
+        match err.kind() {
+            // An indexed file that has since disappeared from disk
+            std::io::ErrorKind::NotFound => WebError::NotFound,
+            std::io::ErrorKind::PermissionDenied => WebError::Forbidden,
+            std::io::ErrorKind::ResourceBusy => WebError::ServiceUnavailable(err.to_string()),
+            _ => WebError::Internal(err.to_string()),
+        }
+    }
+}
+
+impl From<axum::http::Error> for WebError {
+    fn from(err: axum::http::Error) -> Self {
+        WebError::Internal(err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_status_codes_and_soap_fault() {
+        assert_eq!(WebError::NotFound.into_response().status(), StatusCode::NOT_FOUND);
+        assert_eq!(WebError::Unauthorized.into_response().status(), StatusCode::UNAUTHORIZED);
+
+        let range = WebError::RangeNotSatisfiable(11).into_response();
+        assert_eq!(range.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(range.headers()[header::CONTENT_RANGE], "bytes */11");
+
+        // Internal details are not leaked to clients
+        let internal = WebError::Internal("disk I/O error at /secret/path".to_string()).into_response();
+        let body = axum::body::to_bytes(internal.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"Internal Server Error");
+
+        let fault = WebError::NotFound.into_soap_fault();
+        assert_eq!(fault.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = axum::body::to_bytes(fault.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("<errorCode>701</errorCode>"));
+    }
+}
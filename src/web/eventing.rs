@@ -0,0 +1,681 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc, Mutex,
+};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, broadcast::error::RecvError, mpsc};
+use tracing::{debug, info, warn};
+
+use crate::database::{DatabaseManager, MediaFile};
+use crate::media::ScanResult;
+use crate::web::xml::xml_escape;
+
+/// Subscription duration granted when the subscriber asks for none or for "infinite"
+pub const DEFAULT_SUBSCRIPTION_TIMEOUT: Duration = Duration::from_secs(1800);
+
+/// Longest subscription duration granted, so forgotten subscribers expire
+const MAX_SUBSCRIPTION_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Object changes listed in one `LastChange` event; a larger batch is reported
+/// as a change of the root container instead
+const MAX_LAST_CHANGE_OBJECTS: usize = 256;
+
+/// How a ContentDirectory object changed, as reported in `LastChange`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ObjectChangeKind {
+    Added,
+    Modified,
+    Removed,
+}
+
+impl ObjectChangeKind {
+    /// Element name of the change in a `LastChange` state event
+    fn element(self) -> &'static str {
+        match self {
+            ObjectChangeKind::Added => "objAdd",
+            ObjectChangeKind::Modified => "objMod",
+            ObjectChangeKind::Removed => "objDel",
+        }
+    }
+}
+
+/// A change to one ContentDirectory object, identified by its object ID
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ContentChange {
+    pub kind: ObjectChangeKind,
+    pub object_id: String,
+}
+
+impl ContentChange {
+    pub fn added(id: i64) -> Self {
+        Self { kind: ObjectChangeKind::Added, object_id: id.to_string() }
+    }
+
+    pub fn modified(id: i64) -> Self {
+        Self { kind: ObjectChangeKind::Modified, object_id: id.to_string() }
+    }
+
+    pub fn removed(id: i64) -> Self {
+        Self { kind: ObjectChangeKind::Removed, object_id: id.to_string() }
+    }
+
+    /// A change somewhere below the root container, standing for too many to list
+    pub fn root_modified() -> Self {
+        Self { kind: ObjectChangeKind::Modified, object_id: "0".to_string() }
+    }
+}
+
+/// Object changes recorded for the next update, in order and without repeats
+#[derive(Default)]
+struct PendingObjects {
+    changes: Vec<ContentChange>,
+    seen: HashSet<ContentChange>,
+    /// Set once the batch outgrew `MAX_LAST_CHANGE_OBJECTS`
+    collapsed: bool,
+}
+
+impl PendingObjects {
+    fn record(&mut self, change: ContentChange) {
+        if self.collapsed || !self.seen.insert(change.clone()) {
+            return;
+        }
+        if self.changes.len() == MAX_LAST_CHANGE_OBJECTS {
+            self.changes = vec![ContentChange::root_modified()];
+            self.seen = HashSet::new();
+            self.collapsed = true;
+        } else {
+            self.changes.push(change);
+        }
+    }
+
+    fn take(&mut self) -> Vec<ContentChange> {
+        std::mem::take(self).changes
+    }
+}
+
+/// Object changes of a scan: its new, updated and removed files
+pub fn scan_changes(result: &ScanResult) -> Vec<ContentChange> {
+    let ids = |files: &[MediaFile]| files.iter().filter_map(|file| file.id).collect::<Vec<_>>();
+    ids(&result.new_files)
+        .into_iter()
+        .map(ContentChange::added)
+        .chain(ids(&result.updated_files).into_iter().map(ContentChange::modified))
+        .chain(ids(&result.removed_files).into_iter().map(ContentChange::removed))
+        .collect()
+}
+
+/// A published SystemUpdateID and the object changes batched into it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentUpdate {
+    pub update_id: u32,
+    pub changes: Arc<Vec<ContentChange>>,
+}
+
+/// SystemUpdateIDs reserved in the database ahead of the published one. The
+/// stored value stays above every id published so far, so the id keeps
+/// increasing across restarts, even after a crash, with one write per batch of ids.
+pub const UPDATE_ID_RESERVATION: u32 = 64;
+
+/// Load the SystemUpdateID to resume from after a restart and reserve the ids after it
+pub async fn restore_system_update_id(database: &dyn DatabaseManager) -> anyhow::Result<u32> {
+    let resumed = database.get_system_update_id().await?.unwrap_or(1).max(1);
+    database.set_system_update_id(resumed.saturating_add(UPDATE_ID_RESERVATION)).await?;
+    Ok(resumed)
+}
+
+/// A batch is published at the latest this many quiet windows after its first
+/// change, so a long scan still reaches clients at a moderated rate
+const MAX_BATCH_WINDOWS: u32 = 5;
+
+/// Coalesces bursts of content changes into a single SystemUpdateID bump.
+///
+/// Every change is counted internally, but the externally observed update ID is
+/// only incremented (and subscribers notified) once no further change has arrived
+/// for the configured debounce window, or once a burst has lasted
+/// `MAX_BATCH_WINDOWS` windows. The object changes recorded meanwhile are
+/// published with the update for the `LastChange` event.
+#[derive(Clone)]
+pub struct ContentUpdateNotifier {
+    system_update_id: Arc<AtomicU32>,
+    pending_changes: Arc<AtomicU32>,
+    pending_objects: Arc<Mutex<PendingObjects>>,
+    trigger: mpsc::UnboundedSender<()>,
+    updates: broadcast::Sender<ContentUpdate>,
+}
+
+impl ContentUpdateNotifier {
+    /// Create a notifier publishing to `system_update_id` and start its debounce task.
+    pub fn new(system_update_id: Arc<AtomicU32>, debounce: Duration) -> Self {
+        let (trigger, mut trigger_rx) = mpsc::unbounded_channel();
+        let (updates, _) = broadcast::channel(16);
+        let pending_changes = Arc::new(AtomicU32::new(0));
+        let pending_objects = Arc::new(Mutex::new(PendingObjects::default()));
+
+        let task_update_id = system_update_id.clone();
+        let task_pending = pending_changes.clone();
+        let task_objects = pending_objects.clone();
+        let task_updates = updates.clone();
+        tokio::spawn(async move {
+            while trigger_rx.recv().await.is_some() {
+                // Keep waiting while changes keep arriving within the window,
+                // but no longer than the batch limit
+                let deadline = tokio::time::Instant::now() + debounce * MAX_BATCH_WINDOWS;
+                loop {
+                    let quiet = (tokio::time::Instant::now() + debounce).min(deadline);
+                    match tokio::time::timeout_at(quiet, trigger_rx.recv()).await {
+                        Ok(Some(())) if tokio::time::Instant::now() < deadline => {}
+                        _ => break,
+                    }
+                }
+
+                let changes = task_pending.swap(0, Ordering::SeqCst);
+                let objects = task_objects.lock().unwrap().take();
+                if changes > 0 {
+                    let new_id = task_update_id.fetch_add(1, Ordering::SeqCst) + 1;
+                    debug!(
+                        "Publishing SystemUpdateID {} for {} batched change(s), {} object change(s)",
+                        new_id,
+                        changes,
+                        objects.len()
+                    );
+                    // No subscribers is not an error
+                    let _ = task_updates.send(ContentUpdate { update_id: new_id, changes: Arc::new(objects) });
+                }
+            }
+        });
+
+        Self {
+            system_update_id,
+            pending_changes,
+            pending_objects,
+            trigger,
+            updates,
+        }
+    }
+
+    /// Record a content change; the published update follows after the debounce window.
+    pub fn record_change(&self) {
+        self.pending_changes.fetch_add(1, Ordering::SeqCst);
+        let _ = self.trigger.send(());
+    }
+
+    /// Record a content change affecting `changes`, which are listed in the
+    /// `LastChange` event of the published update. Repeats within a batch are
+    /// reported once, and a batch of more than `MAX_LAST_CHANGE_OBJECTS`
+    /// objects as a change of the root container.
+    pub fn record_object_changes(&self, changes: impl IntoIterator<Item = ContentChange>) {
+        {
+            let mut pending = self.pending_objects.lock().unwrap();
+            for change in changes {
+                pending.record(change);
+            }
+        }
+        self.record_change();
+    }
+
+    /// Number of changes recorded since the last published update
+    pub fn pending_changes(&self) -> u32 {
+        self.pending_changes.load(Ordering::SeqCst)
+    }
+
+    /// The currently published SystemUpdateID
+    pub fn current_update_id(&self) -> u32 {
+        self.system_update_id.load(Ordering::SeqCst)
+    }
+
+    /// Subscribe to published SystemUpdateID changes
+    pub fn subscribe(&self) -> broadcast::Receiver<ContentUpdate> {
+        self.updates.subscribe()
+    }
+
+    /// Keep the reservation made by `restore_system_update_id` ahead of the
+    /// published SystemUpdateID, renewing it once half of it is used up
+    pub fn persist_to(&self, database: Arc<dyn DatabaseManager>) {
+        let mut updates = self.subscribe();
+        let system_update_id = self.system_update_id.clone();
+        let mut reserved = self.current_update_id().saturating_add(UPDATE_ID_RESERVATION);
+        tokio::spawn(async move {
+            loop {
+                match updates.recv().await {
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                }
+                let current = system_update_id.load(Ordering::SeqCst);
+                if current.saturating_add(UPDATE_ID_RESERVATION / 2) < reserved {
+                    continue;
+                }
+                let next = current.saturating_add(UPDATE_ID_RESERVATION);
+                match database.set_system_update_id(next).await {
+                    Ok(()) => reserved = next,
+                    Err(e) => warn!("Failed to persist SystemUpdateID {}: {}", next, e),
+                }
+            }
+        });
+    }
+}
+
+/// Parse a GENA `TIMEOUT` header such as `Second-1800`, capped to a day
+pub fn parse_subscription_timeout(value: Option<&str>) -> Duration {
+    value
+        .and_then(|v| v.trim().strip_prefix("Second-"))
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .map(|secs| Duration::from_secs(secs).min(MAX_SUBSCRIPTION_TIMEOUT))
+        .unwrap_or(DEFAULT_SUBSCRIPTION_TIMEOUT)
+}
+
+/// Parse a GENA `CALLBACK` header: one or more URLs, each in angle brackets
+pub fn parse_callback_urls(value: &str) -> Vec<String> {
+    value
+        .split('<')
+        .filter_map(|part| part.split_once('>').map(|(url, _)| url.trim()))
+        .filter(|url| url.starts_with("http://"))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Event body announcing a new ContentDirectory SystemUpdateID, with the
+/// object changes behind it as an escaped `LastChange` state event
+pub fn system_update_propertyset(update_id: u32, changes: &[ContentChange]) -> String {
+    let last_change = if changes.is_empty() {
+        String::new()
+    } else {
+        let events: String = changes
+            .iter()
+            .map(|change| {
+                format!(
+                    r#"<{} objID="{}" updateID="{}"/>"#,
+                    change.kind.element(),
+                    xml_escape(&change.object_id),
+                    update_id
+                )
+            })
+            .collect();
+        xml_escape(&format!(
+            r#"<StateEvent xmlns="urn:schemas-upnp-org:av:cds-event">{}</StateEvent>"#,
+            events
+        ))
+    };
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<e:propertyset xmlns:e="urn:schemas-upnp-org:event-1-0">
+    <e:property>
+        <SystemUpdateID>{}</SystemUpdateID>
+    </e:property>
+    <e:property>
+        <ContainerUpdateIDs></ContainerUpdateIDs>
+    </e:property>
+    <e:property>
+        <LastChange>{}</LastChange>
+    </e:property>
+</e:propertyset>"#,
+        update_id, last_change
+    )
+}
+
+/// Initial event body of the ConnectionManager: its protocols and the single
+/// default connection
+pub fn connection_manager_propertyset(source_protocol_info: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<e:propertyset xmlns:e="urn:schemas-upnp-org:event-1-0">
+    <e:property>
+        <SourceProtocolInfo>{}</SourceProtocolInfo>
+    </e:property>
+    <e:property>
+        <SinkProtocolInfo></SinkProtocolInfo>
+    </e:property>
+    <e:property>
+        <CurrentConnectionIDs>0</CurrentConnectionIDs>
+    </e:property>
+</e:propertyset>"#,
+        xml_escape(source_protocol_info)
+    )
+}
+
+struct Subscription {
+    callbacks: Vec<String>,
+    expires: Instant,
+    /// Event key of the next NOTIFY; 0 is reserved for the initial event
+    next_seq: u32,
+    /// Events waiting for delivery; dropping the subscription ends its delivery task
+    queue: mpsc::UnboundedSender<(PendingEvent, String)>,
+}
+
+/// Deliver the events queued for one subscriber one at a time, so they
+/// arrive in SEQ order as GENA requires
+fn spawn_delivery() -> mpsc::UnboundedSender<(PendingEvent, String)> {
+    let (queue, mut events) = mpsc::unbounded_channel::<(PendingEvent, String)>();
+    tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        while let Some((event, body)) = events.recv().await {
+            send_event(client.clone(), event, body).await;
+        }
+    });
+    queue
+}
+
+/// A NOTIFY due to one subscriber
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingEvent {
+    pub sid: String,
+    pub callbacks: Vec<String>,
+    pub seq: u32,
+}
+
+/// GENA subscribers to ContentDirectory events, by SID
+#[derive(Clone, Default)]
+pub struct EventSubscriptions {
+    subscriptions: Arc<Mutex<HashMap<String, Subscription>>>,
+}
+
+impl EventSubscriptions {
+    /// Register a subscriber and return its SID. `initial_event` is queued as
+    /// event 0, ahead of any update sent to the new subscriber.
+    pub fn subscribe(&self, callbacks: Vec<String>, timeout: Duration, initial_event: String) -> String {
+        let sid = format!("uuid:{}", uuid::Uuid::new_v4());
+        let queue = spawn_delivery();
+        let event = PendingEvent { sid: sid.clone(), callbacks: callbacks.clone(), seq: 0 };
+        let _ = queue.send((event, initial_event));
+
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        prune(&mut subscriptions);
+        subscriptions.insert(
+            sid.clone(),
+            Subscription {
+                callbacks,
+                expires: Instant::now() + timeout,
+                next_seq: 1,
+                queue,
+            },
+        );
+        sid
+    }
+
+    /// Extend a subscription; false when the SID is unknown or already expired
+    pub fn renew(&self, sid: &str, timeout: Duration) -> bool {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        prune(&mut subscriptions);
+        match subscriptions.get_mut(sid) {
+            Some(subscription) => {
+                subscription.expires = Instant::now() + timeout;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cancel a subscription; false when the SID is unknown
+    pub fn unsubscribe(&self, sid: &str) -> bool {
+        self.subscriptions.lock().unwrap().remove(sid).is_some()
+    }
+
+    /// Number of subscriptions that have not expired
+    pub fn active_count(&self) -> usize {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        prune(&mut subscriptions);
+        subscriptions.len()
+    }
+
+    /// Drop expired subscriptions and queue `body` for each remaining one under
+    /// its next event key, returning the events queued
+    pub fn queue_events(&self, body: &str) -> Vec<PendingEvent> {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        prune(&mut subscriptions);
+        subscriptions
+            .iter_mut()
+            .map(|(sid, subscription)| {
+                let seq = subscription.next_seq;
+                // The key wraps to 1, never back to the initial 0
+                subscription.next_seq = subscription.next_seq.checked_add(1).unwrap_or(1);
+                let event = PendingEvent {
+                    sid: sid.clone(),
+                    callbacks: subscription.callbacks.clone(),
+                    seq,
+                };
+                let _ = subscription.queue.send((event.clone(), body.to_string()));
+                event
+            })
+            .collect()
+    }
+
+    /// Send each published SystemUpdateID and its `LastChange` to every live subscriber
+    pub fn start(&self, notifier: &ContentUpdateNotifier) -> tokio::task::JoinHandle<()> {
+        let subscriptions = self.clone();
+        let mut updates = notifier.subscribe();
+        tokio::spawn(async move {
+            loop {
+                let update = match updates.recv().await {
+                    Ok(update) => update,
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let body = system_update_propertyset(update.update_id, &update.changes);
+                subscriptions.queue_events(&body);
+            }
+        })
+    }
+}
+
+fn prune(subscriptions: &mut HashMap<String, Subscription>) {
+    let now = Instant::now();
+    subscriptions.retain(|sid, subscription| {
+        let live = subscription.expires > now;
+        if !live {
+            info!("UPnP subscription {} expired", sid);
+        }
+        live
+    });
+}
+
+/// Deliver an event, trying the subscriber's callback URLs in order until one accepts it
+pub async fn send_event(client: reqwest::Client, event: PendingEvent, body: String) {
+    for url in &event.callbacks {
+        let result = client
+            .request(reqwest::Method::from_bytes(b"NOTIFY").unwrap(), url)
+            .header("CONTENT-TYPE", "text/xml; charset=\"utf-8\"")
+            .header("NT", "upnp:event")
+            .header("NTS", "upnp:propchange")
+            .header("SID", &event.sid)
+            .header("SEQ", event.seq.to_string())
+            .body(body.clone())
+            .timeout(Duration::from_secs(5))
+            .send()
+            .await;
+        match result {
+            Ok(response) => {
+                debug!("Event {} for {} sent to {}, status: {}", event.seq, event.sid, url, response.status());
+                return;
+            }
+            Err(e) => warn!("Failed to send event notification to {}: {}", url, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_system_update_id_never_decreases_across_restarts() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db: Arc<dyn DatabaseManager> =
+            Arc::new(crate::database::SqliteDatabase::new(temp_dir.path().join("test.db")).await.unwrap());
+        db.initialize().await.unwrap();
+
+        assert_eq!(restore_system_update_id(db.as_ref()).await.unwrap(), 1);
+        let notifier = ContentUpdateNotifier::new(Arc::new(AtomicU32::new(1)), Duration::from_millis(5));
+        notifier.persist_to(db.clone());
+        let mut updates = notifier.subscribe();
+        for _ in 0..UPDATE_ID_RESERVATION * 2 {
+            notifier.record_change();
+            updates.recv().await.unwrap();
+        }
+        let published = notifier.current_update_id();
+        assert_eq!(published, UPDATE_ID_RESERVATION * 2 + 1);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // Restarting, even without a clean shutdown, resumes above every published id
+        let resumed = restore_system_update_id(db.as_ref()).await.unwrap();
+        assert!(resumed > published, "{} should exceed {}", resumed, published);
+        let resumed_again = restore_system_update_id(db.as_ref()).await.unwrap();
+        assert!(resumed_again > resumed);
+
+        db.set_system_update_id(5000).await.unwrap();
+        assert_eq!(restore_system_update_id(db.as_ref()).await.unwrap(), 5000);
+        assert_eq!(db.get_system_update_id().await.unwrap(), Some(5000 + UPDATE_ID_RESERVATION));
+    }
+
+    #[tokio::test]
+    async fn test_rapid_changes_publish_single_update() {
+        let update_id = Arc::new(AtomicU32::new(1));
+        let notifier = ContentUpdateNotifier::new(update_id.clone(), Duration::from_millis(100));
+        let mut updates = notifier.subscribe();
+
+        for _ in 0..500 {
+            notifier.record_change();
+        }
+        assert_eq!(notifier.current_update_id(), 1);
+
+        let published = tokio::time::timeout(Duration::from_secs(2), updates.recv())
+            .await
+            .expect("update should be published after the burst settles")
+            .unwrap();
+        assert_eq!(published.update_id, 2);
+        assert_eq!(notifier.current_update_id(), 2);
+        assert_eq!(notifier.pending_changes(), 0);
+
+        // Nothing else is published for the same burst
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(updates.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rapid_object_changes_coalesce_into_one_last_change() {
+        let notifier = ContentUpdateNotifier::new(Arc::new(AtomicU32::new(1)), Duration::from_millis(100));
+        let mut updates = notifier.subscribe();
+
+        for id in 0..200 {
+            notifier.record_object_changes([ContentChange::added(id)]);
+        }
+        notifier.record_object_changes([ContentChange::modified(3), ContentChange::added(3), ContentChange::removed(7)]);
+
+        let published = tokio::time::timeout(Duration::from_secs(2), updates.recv())
+            .await
+            .expect("one update should cover the whole burst")
+            .unwrap();
+        assert_eq!(published.update_id, 2);
+        assert_eq!(published.changes.len(), 202);
+        assert_eq!(published.changes[200], ContentChange::modified(3));
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(updates.try_recv().is_err());
+
+        // A batch too large to list is reported as a change of the root container
+        for id in 0..1000 {
+            notifier.record_object_changes([ContentChange::added(id)]);
+        }
+        let collapsed = tokio::time::timeout(Duration::from_secs(2), updates.recv()).await.unwrap().unwrap();
+        assert_eq!(collapsed.update_id, 3);
+        assert_eq!(*collapsed.changes, vec![ContentChange::root_modified()]);
+
+        let body = system_update_propertyset(published.update_id, &published.changes[..2]);
+        assert!(body.contains("<SystemUpdateID>2</SystemUpdateID>"));
+        assert!(body.contains(
+            "<LastChange>&lt;StateEvent xmlns=&quot;urn:schemas-upnp-org:av:cds-event&quot;&gt;\
+             &lt;objAdd objID=&quot;0&quot; updateID=&quot;2&quot;/&gt;\
+             &lt;objAdd objID=&quot;1&quot; updateID=&quot;2&quot;/&gt;&lt;/StateEvent&gt;</LastChange>"
+        ));
+        assert!(system_update_propertyset(1, &[]).contains("<LastChange></LastChange>"));
+    }
+
+    #[tokio::test]
+    async fn test_sustained_changes_publish_at_moderated_rate() {
+        let notifier = ContentUpdateNotifier::new(Arc::new(AtomicU32::new(1)), Duration::from_millis(50));
+        let mut updates = notifier.subscribe();
+
+        // Changes every 10ms never leave a quiet window, yet updates still go out
+        let started = Instant::now();
+        while started.elapsed() < Duration::from_millis(700) {
+            notifier.record_object_changes([ContentChange::modified(1)]);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let mut published = 0;
+        while let Ok(Ok(update)) = tokio::time::timeout(Duration::from_millis(300), updates.recv()).await {
+            assert_eq!(*update.changes, vec![ContentChange::modified(1)]);
+            published += 1;
+        }
+        assert!((2..=5).contains(&published), "{} updates published", published);
+    }
+
+    #[tokio::test]
+    async fn test_subscriptions_expire_and_sequence_events() {
+        let subscriptions = EventSubscriptions::default();
+        let sid = subscriptions.subscribe(
+            vec!["http://10.0.0.2:1400/events".to_string()],
+            Duration::from_secs(60),
+            String::new(),
+        );
+        let short = subscriptions.subscribe(vec!["http://10.0.0.3/".to_string()], Duration::ZERO, String::new());
+        assert!(subscriptions.renew(&sid, Duration::from_secs(120)));
+
+        // The zero-length subscription is pruned before any event is sent
+        let events = subscriptions.queue_events("");
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].sid.as_str(), events[0].seq), (sid.as_str(), 1));
+        assert_eq!(subscriptions.queue_events("")[0].seq, 2);
+        assert!(!subscriptions.renew(&short, Duration::from_secs(60)));
+
+        assert!(subscriptions.unsubscribe(&sid));
+        assert!(!subscriptions.unsubscribe(&sid));
+        assert_eq!(subscriptions.active_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_events_reach_a_subscriber_in_seq_order() {
+        use axum::http::HeaderMap;
+
+        // The callback answers the first NOTIFY slowly, so later ones would overtake it if sent concurrently
+        let (seqs_tx, mut seqs_rx) = mpsc::unbounded_channel();
+        let callback = axum::Router::new().route(
+            "/events",
+            axum::routing::any(move |headers: HeaderMap| {
+                let seqs_tx = seqs_tx.clone();
+                async move {
+                    let seq: u32 = headers["seq"].to_str().unwrap().parse().unwrap();
+                    if seq == 0 {
+                        tokio::time::sleep(Duration::from_millis(200)).await;
+                    }
+                    seqs_tx.send(seq).unwrap();
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let callback_url = format!("http://{}/events", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, callback).await.unwrap() });
+
+        let subscriptions = EventSubscriptions::default();
+        subscriptions.subscribe(vec![callback_url], Duration::from_secs(60), "initial".to_string());
+        for _ in 0..3 {
+            subscriptions.queue_events("update");
+        }
+
+        let mut seqs = Vec::new();
+        while seqs.len() < 4 {
+            seqs.push(tokio::time::timeout(Duration::from_secs(5), seqs_rx.recv()).await.unwrap().unwrap());
+        }
+        assert_eq!(seqs, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_parse_gena_headers() {
+        assert_eq!(
+            parse_callback_urls("<http://10.0.0.2:1400/a> <http://10.0.0.2:1401/b><ftp://x>"),
+            vec!["http://10.0.0.2:1400/a", "http://10.0.0.2:1401/b"]
+        );
+        assert!(parse_callback_urls("http://no-brackets/").is_empty());
+        assert_eq!(parse_subscription_timeout(Some("Second-300")), Duration::from_secs(300));
+        assert_eq!(parse_subscription_timeout(Some("Second-infinite")), DEFAULT_SUBSCRIPTION_TIMEOUT);
+        assert_eq!(parse_subscription_timeout(Some("Second-999999999")), MAX_SUBSCRIPTION_TIMEOUT);
+        assert_eq!(parse_subscription_timeout(None), DEFAULT_SUBSCRIPTION_TIMEOUT);
+    }
+}
@@ -0,0 +1,216 @@
+use axum::body::Bytes;
+use futures_util::future::{BoxFuture, FutureExt};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tracing::debug;
+
+use crate::database::MediaFile;
+use crate::watcher::FileSystemEvent;
+
+/// Files up to this size are served from memory; larger media always streams from disk
+pub const SMALL_FILE_MAX_BYTES: u64 = 2 * 1024 * 1024;
+
+/// Capacity used when no configuration is available
+pub const DEFAULT_SMALL_FILE_CACHE_BYTES: u64 = 32 * 1024 * 1024;
+
+/// Reads a whole file from disk on a cache miss
+pub type FileLoader = Arc<dyn Fn(PathBuf) -> BoxFuture<'static, std::io::Result<Vec<u8>>> + Send + Sync>;
+
+struct CachedFile {
+    path: PathBuf,
+    modified: SystemTime,
+    data: Bytes,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct CacheEntries {
+    files: HashMap<i64, CachedFile>,
+    /// Object ids ordered by last use, oldest first
+    recency: BTreeMap<u64, i64>,
+    total_bytes: u64,
+    clock: u64,
+}
+
+impl CacheEntries {
+    fn remove(&mut self, id: i64) {
+        if let Some(file) = self.files.remove(&id) {
+            self.recency.remove(&file.last_used);
+            self.total_bytes -= file.data.len() as u64;
+        }
+    }
+
+    fn touch(&mut self, id: i64) {
+        self.clock += 1;
+        if let Some(file) = self.files.get_mut(&id) {
+            self.recency.remove(&file.last_used);
+            file.last_used = self.clock;
+            self.recency.insert(self.clock, id);
+        }
+    }
+}
+
+/// Byte-bounded LRU cache of small media files such as photos and album art,
+/// keyed by object id and modification time
+#[derive(Clone)]
+pub struct SmallFileCache {
+    entries: Arc<Mutex<CacheEntries>>,
+    capacity_bytes: u64,
+    loader: FileLoader,
+}
+
+impl Default for SmallFileCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_SMALL_FILE_CACHE_BYTES)
+    }
+}
+
+impl SmallFileCache {
+    /// Create a cache holding at most `capacity_bytes`; 0 disables caching
+    pub fn new(capacity_bytes: u64) -> Self {
+        Self {
+            entries: Arc::default(),
+            capacity_bytes,
+            loader: Arc::new(|path| async move { tokio::fs::read(path).await }.boxed()),
+        }
+    }
+
+    /// Replace how files are read from disk on a miss
+    pub fn with_loader(mut self, loader: FileLoader) -> Self {
+        self.loader = loader;
+        self
+    }
+
+    /// Whether `file` is small enough to be served from the cache
+    pub fn is_cacheable(&self, file: &MediaFile) -> bool {
+        file.id.is_some() && file.size <= SMALL_FILE_MAX_BYTES.min(self.capacity_bytes)
+    }
+
+    /// Contents of `file` from memory, reading and caching it on a miss. Returns
+    /// `None` when the file on disk no longer matches the indexed size, so the
+    /// caller streams it instead.
+    pub async fn get_or_load(&self, file: &MediaFile) -> std::io::Result<Option<Bytes>> {
+        let Some(id) = file.id else {
+            return Ok(None);
+        };
+
+        {
+            let mut entries = self.entries.lock().unwrap();
+            match entries.files.get(&id) {
+                Some(cached) if cached.modified == file.modified => {
+                    let data = cached.data.clone();
+                    entries.touch(id);
+                    return Ok(Some(data));
+                }
+                Some(_) => entries.remove(id),
+                None => {}
+            }
+        }
+
+        let data = Bytes::from((self.loader)(file.path.clone()).await?);
+        if data.len() as u64 != file.size {
+            debug!("Not caching {}: size changed since it was indexed", file.path.display());
+            return Ok(None);
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.remove(id);
+        while entries.total_bytes + file.size > self.capacity_bytes {
+            let Some((_, oldest)) = entries.recency.pop_first() else {
+                break;
+            };
+            entries.remove(oldest);
+        }
+        entries.total_bytes += file.size;
+        entries.files.insert(
+            id,
+            CachedFile {
+                path: file.path.clone(),
+                modified: file.modified,
+                data: data.clone(),
+                last_used: 0,
+            },
+        );
+        entries.touch(id);
+        Ok(Some(data))
+    }
+
+    /// Drop cached files at or below `path`
+    pub fn invalidate_path(&self, path: &Path) {
+        let mut entries = self.entries.lock().unwrap();
+        let stale: Vec<i64> = entries
+            .files
+            .iter()
+            .filter(|(_, file)| file.path.starts_with(path))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in stale {
+            entries.remove(id);
+        }
+    }
+
+    /// Drop cached files changed by a file system event
+    pub fn handle_event(&self, event: &FileSystemEvent) {
+        match event {
+            FileSystemEvent::Created(_) => {}
+            FileSystemEvent::Modified(path) | FileSystemEvent::Deleted(path) => self.invalidate_path(path),
+            FileSystemEvent::Renamed { from, to } => {
+                self.invalidate_path(from);
+                self.invalidate_path(to);
+            }
+            FileSystemEvent::ResyncNeeded(directories) => {
+                directories.iter().for_each(|dir| self.invalidate_path(dir));
+            }
+        }
+    }
+
+    /// Total bytes currently cached
+    pub fn cached_bytes(&self) -> u64 {
+        self.entries.lock().unwrap().total_bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn small_file(id: i64, size: u64) -> MediaFile {
+        let mut file = MediaFile::new(PathBuf::from(format!("/photos/{}.jpg", id)), size, "image/jpeg".to_string());
+        file.id = Some(id);
+        file
+    }
+
+    #[tokio::test]
+    async fn test_evicts_least_recently_used_within_capacity() {
+        let cache = SmallFileCache::new(250)
+            .with_loader(Arc::new(|path| {
+                async move {
+                    let id: usize = path.file_stem().unwrap().to_string_lossy().parse().unwrap();
+                    Ok(vec![0u8; id * 10])
+                }
+                .boxed()
+            }));
+        let (a, b, c) = (small_file(10, 100), small_file(11, 110), small_file(12, 120));
+
+        cache.get_or_load(&a).await.unwrap().unwrap();
+        cache.get_or_load(&b).await.unwrap().unwrap();
+        // Using `a` again makes `b` the eviction candidate
+        cache.get_or_load(&a).await.unwrap().unwrap();
+        cache.get_or_load(&c).await.unwrap().unwrap();
+        assert_eq!(cache.cached_bytes(), 220);
+        assert!(cache.entries.lock().unwrap().files.contains_key(&10));
+        assert!(!cache.entries.lock().unwrap().files.contains_key(&11));
+
+        // A newer mtime is a miss that replaces the entry
+        let mut touched = a.clone();
+        touched.modified += Duration::from_secs(1);
+        cache.get_or_load(&touched).await.unwrap().unwrap();
+        assert_eq!(cache.cached_bytes(), 220);
+
+        assert!(!SmallFileCache::new(0).is_cacheable(&a));
+        assert!(!cache.is_cacheable(&small_file(13, SMALL_FILE_MAX_BYTES + 1)));
+    }
+}
@@ -0,0 +1,91 @@
+//! Device icon listed in the `<iconList>` of `description.xml`.
+//!
+//! Clients show it next to the server name. `server.icon_path` may point at a
+//! PNG or JPEG; without one, a small built-in icon is served.
+
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use axum::body::Bytes;
+use tracing::warn;
+
+use crate::config::ServerConfig;
+use crate::metadata::image_resolution;
+
+/// Icon served when no `server.icon_path` is configured
+const DEFAULT_ICON: &[u8] = include_bytes!("icon.png");
+
+/// Largest icon accepted; renderers expect small thumbnails, and the image is
+/// held in memory for the life of the server
+pub const MAX_ICON_BYTES: usize = 256 * 1024;
+
+/// An icon image with the attributes advertised for it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceIcon {
+    pub data: Bytes,
+    pub mime_type: &'static str,
+    pub width: u32,
+    pub height: u32,
+    /// Color depth in bits per pixel
+    pub depth: u8,
+}
+
+impl DeviceIcon {
+    /// Recognize a PNG or JPEG image and read its size and color depth
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        if data.len() > MAX_ICON_BYTES {
+            return Err(anyhow!("larger than {} bytes", MAX_ICON_BYTES));
+        }
+        let (width, height) = image_resolution(&data).ok_or_else(|| anyhow!("not a PNG or JPEG image"))?;
+        let (mime_type, depth) = if data.starts_with(b"\x89PNG") {
+            // Bit depth and color type follow the size in the IHDR chunk
+            let channels = match data.get(25) {
+                Some(2) => 3,
+                Some(4) => 2,
+                Some(6) => 4,
+                _ => 1,
+            };
+            ("image/png", data[24].saturating_mul(channels))
+        } else {
+            ("image/jpeg", 24)
+        };
+        Ok(Self { data: data.into(), mime_type, width, height, depth })
+    }
+
+    /// Read the icon image at `path`, without reading past the size limit
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut data = Vec::new();
+        std::fs::File::open(path)
+            .and_then(|file| file.take(MAX_ICON_BYTES as u64 + 1).read_to_end(&mut data))
+            .with_context(|| format!("Cannot read icon {}", path.display()))?;
+        Self::from_bytes(data).with_context(|| format!("Invalid icon {}", path.display()))
+    }
+
+    /// The configured `server.icon_path`, or the built-in icon when none is
+    /// set or the configured one cannot be used
+    pub fn from_config(server: &ServerConfig) -> Self {
+        let Some(path) = &server.icon_path else {
+            return Self::default();
+        };
+        Self::load(Path::new(path)).unwrap_or_else(|e| {
+            warn!("Using the built-in device icon: {:#}", e);
+            Self::default()
+        })
+    }
+
+    /// URL path the icon is served at, with an extension matching its type
+    pub fn url(&self) -> &'static str {
+        if self.mime_type == "image/png" {
+            "/icon.png"
+        } else {
+            "/icon.jpg"
+        }
+    }
+}
+
+impl Default for DeviceIcon {
+    fn default() -> Self {
+        Self::from_bytes(DEFAULT_ICON.to_vec()).expect("built-in icon is a valid PNG")
+    }
+}
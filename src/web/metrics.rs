@@ -0,0 +1,160 @@
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures_util::{Stream, StreamExt};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::state::AppState;
+use crate::web::handlers::HEALTH_CHECK_TIMEOUT;
+
+/// Content type of the Prometheus text exposition format
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+#[derive(Default)]
+struct Counters {
+    bytes_served: AtomicU64,
+    active_streams: AtomicU64,
+    ssdp_responses: AtomicU64,
+    scans: AtomicU64,
+    scan_micros: AtomicU64,
+}
+
+/// Counters updated while serving media and answering SSDP searches,
+/// exposed in Prometheus format at `GET /metrics`
+#[derive(Clone, Default)]
+pub struct ServerMetrics {
+    counters: Arc<Counters>,
+}
+
+/// Keeps a stream counted as active until it is dropped
+struct ActiveStream(Arc<Counters>);
+
+impl Drop for ActiveStream {
+    fn drop(&mut self) {
+        self.0.active_streams.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl ServerMetrics {
+    /// Count bytes sent from memory rather than a stream
+    pub fn record_bytes_served(&self, bytes: u64) {
+        self.counters.bytes_served.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Count `stream` as active until the response body is dropped, adding
+    /// every chunk it yields to the bytes served
+    pub fn track_stream<S, B, E>(&self, stream: S) -> impl Stream<Item = Result<B, E>> + Send + 'static
+    where
+        S: Stream<Item = Result<B, E>> + Send + 'static,
+        B: AsRef<[u8]>,
+    {
+        self.counters.active_streams.fetch_add(1, Ordering::Relaxed);
+        let active = ActiveStream(self.counters.clone());
+        stream.map(move |chunk| {
+            if let Ok(bytes) = &chunk {
+                active.0.bytes_served.fetch_add(bytes.as_ref().len() as u64, Ordering::Relaxed);
+            }
+            chunk
+        })
+    }
+
+    pub fn record_ssdp_response(&self) {
+        self.counters.ssdp_responses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record how long a full library scan took
+    pub fn record_scan(&self, duration: Duration) {
+        self.counters.scans.fetch_add(1, Ordering::Relaxed);
+        self.counters.scan_micros.fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn bytes_served(&self) -> u64 {
+        self.counters.bytes_served.load(Ordering::Relaxed)
+    }
+
+    pub fn active_streams(&self) -> u64 {
+        self.counters.active_streams.load(Ordering::Relaxed)
+    }
+
+    pub fn ssdp_responses(&self) -> u64 {
+        self.counters.ssdp_responses.load(Ordering::Relaxed)
+    }
+}
+
+/// Append one metric with its help and type lines
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str, value: impl std::fmt::Display) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+/// Render the metrics in the Prometheus text format. Library figures come from
+/// the database; `database_up` is false when it failed to answer.
+pub fn render_metrics(metrics: &ServerMetrics, total_files: usize, total_size: u64, database_up: bool) -> String {
+    let counters = &metrics.counters;
+    let mut out = String::new();
+    write_metric(&mut out, "vuio_media_files_total", "gauge", "Media files in the library", total_files);
+    write_metric(&mut out, "vuio_media_size_bytes", "gauge", "Total size of the media files in the library", total_size);
+    write_metric(&mut out, "vuio_bytes_served_total", "counter", "Media bytes sent to clients", metrics.bytes_served());
+    write_metric(&mut out, "vuio_active_streams", "gauge", "Media streams currently being sent", metrics.active_streams());
+    write_metric(&mut out, "vuio_ssdp_responses_total", "counter", "SSDP search responses sent", metrics.ssdp_responses());
+
+    let _ = writeln!(out, "# HELP vuio_scan_duration_seconds Duration of full library scans");
+    let _ = writeln!(out, "# TYPE vuio_scan_duration_seconds summary");
+    let _ = writeln!(
+        out,
+        "vuio_scan_duration_seconds_sum {}",
+        counters.scan_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0
+    );
+    let _ = writeln!(out, "vuio_scan_duration_seconds_count {}", counters.scans.load(Ordering::Relaxed));
+
+    write_metric(&mut out, "vuio_database_up", "gauge", "Whether the database answered the last health check", u8::from(database_up));
+    out
+}
+
+/// `GET /metrics`, registered when `server.metrics_enabled` is set
+pub async fn metrics_handler(State(state): State<AppState>) -> Response {
+    let (total_files, total_size, database_up) =
+        match tokio::time::timeout(HEALTH_CHECK_TIMEOUT, state.database.get_stats()).await {
+            Ok(Ok(stats)) => (stats.total_files, stats.total_size, true),
+            _ => (0, 0, false),
+        };
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)],
+        render_metrics(&state.metrics, total_files, total_size, database_up),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Bytes;
+
+    #[tokio::test]
+    async fn test_tracked_streams_count_bytes_until_dropped() {
+        let metrics = ServerMetrics::default();
+        let chunks = vec![Ok::<_, std::io::Error>(Bytes::from_static(b"abcd")), Ok(Bytes::from_static(b"ef"))];
+        let mut stream = Box::pin(metrics.track_stream(futures_util::stream::iter(chunks)));
+        assert_eq!(metrics.active_streams(), 1);
+
+        stream.next().await.unwrap().unwrap();
+        assert_eq!(metrics.bytes_served(), 4);
+        drop(stream);
+        assert_eq!(metrics.active_streams(), 0);
+
+        metrics.record_bytes_served(10);
+        metrics.record_scan(Duration::from_millis(1500));
+        let text = render_metrics(&metrics, 3, 42, true);
+        assert!(text.contains("vuio_bytes_served_total 14\n"));
+        assert!(text.contains("vuio_scan_duration_seconds_sum 1.5\n"));
+        assert!(text.contains("vuio_scan_duration_seconds_count 1\n"));
+        assert!(text.contains("vuio_database_up 1\n"));
+    }
+}
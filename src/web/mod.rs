@@ -1,13 +1,90 @@
+pub mod access_log;
+pub mod auth;
+pub mod didl_cache;
+pub mod error;
+pub mod eventing;
+pub mod file_cache;
 pub mod handlers;
+pub mod icon;
+pub mod metrics;
+pub mod throttle;
+pub mod upnp;
 pub mod xml;
 
 use crate::state::AppState;
-use axum::{routing::get, Router};
+use axum::{
+    body::HttpBody,
+    http::{header, Response},
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use tower_http::compression::{
+    predicate::{DefaultPredicate, Predicate},
+    CompressionLayer,
+};
 
-pub fn create_router(state: AppState) -> Router {
+/// Compression predicate that leaves media byte streams untouched. Media is
+/// usually compressed already, and encoding it would break byte-range requests.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SkipMediaStreams;
+
+impl Predicate for SkipMediaStreams {
+    fn should_compress<B>(&self, response: &Response<B>) -> bool
+    where
+        B: HttpBody,
+    {
+        let headers = response.headers();
+        if headers.contains_key(header::ACCEPT_RANGES) || headers.contains_key(header::CONTENT_RANGE) {
+            return false;
+        }
+
+        let content_type = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        !["video/", "audio/", "image/", "application/octet-stream", "text/vtt", "application/x-subrip"]
+            .iter()
+            .any(|prefix| content_type.starts_with(prefix))
+    }
+}
+
+/// Management API routes that reveal the configuration or change the library
+/// and database, refused until API tokens or `server.auth` are configured
+fn privileged_api_router(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/api/config", get(handlers::api_config_handler))
+        .route("/api/config/preview", post(handlers::api_config_preview_handler))
+        .route("/api/rescan", post(handlers::api_rescan_handler))
+        .route("/api/admin/vacuum", post(handlers::api_admin_vacuum_handler))
+        .route("/api/admin/dedupe", post(handlers::api_admin_dedupe_handler))
+        .route("/api/admin/cleanup", post(handlers::api_admin_cleanup_handler))
+        .route_layer(middleware::from_fn_with_state(state, auth::require_configured_credentials))
+}
+
+/// Management API routes, guarded by the configured API tokens
+fn api_router(state: AppState) -> Router<AppState> {
     Router::new()
+        .route("/api/stats", get(handlers::api_stats_handler))
+        .route("/api/diagnostics", get(handlers::api_diagnostics_handler))
+        .route("/api/files", get(handlers::api_files_handler))
+        .route("/api/files/{id}", get(handlers::api_file_handler))
+        .route("/api/scan/errors", get(handlers::api_scan_errors_handler))
+        .route("/api/tokens", get(auth::api_tokens_handler))
+        .merge(privileged_api_router(state.clone()))
+        .route_layer(middleware::from_fn_with_state(state, auth::require_api_token))
+}
+
+pub fn create_router(state: AppState) -> Router {
+    let mut router = Router::new();
+    if state.config.server.metrics_enabled {
+        router = router.route("/metrics", get(metrics::metrics_handler));
+    }
+    router
         .route("/", get(handlers::root_handler))
+        .route("/health", get(handlers::health_handler))
         .route("/description.xml", get(handlers::description_handler))
+        .route(state.icon.url(), get(handlers::serve_icon))
         .route(
             "/ContentDirectory.xml",
             get(handlers::content_directory_scpd),
@@ -16,11 +93,30 @@ pub fn create_router(state: AppState) -> Router {
             "/control/ContentDirectory",
             get(handlers::content_directory_control).post(handlers::content_directory_control),
         )
+        .route("/ConnectionManager.xml", get(handlers::connection_manager_scpd))
+        .route(
+            "/control/ConnectionManager",
+            get(handlers::connection_manager_control).post(handlers::connection_manager_control),
+        )
         .route(
             "/event/ContentDirectory",
             axum::routing::any(handlers::content_directory_subscribe),
         )
+        .route(
+            "/event/ConnectionManager",
+            axum::routing::any(handlers::connection_manager_subscribe),
+        )
         // Corrected route syntax from "/media/:id" to "/media/{id}"
         .route("/media/{id}", get(handlers::serve_media))
+        .route("/media/by-path/{*path}", get(handlers::serve_media_by_path))
+        .route("/download/{id}", get(handlers::download_media))
+        .route("/thumbnail/{id}", get(handlers::serve_thumbnail))
+        .route("/albumart/{id}", get(handlers::serve_album_art))
+        .route("/subtitle/{id}", get(handlers::serve_subtitle))
+        .merge(api_router(state.clone()))
+        .layer(middleware::from_fn_with_state(state.clone(), auth::require_server_auth))
+        .layer(middleware::from_fn_with_state(state.clone(), auth::require_allowed_client))
+        .layer(middleware::from_fn_with_state(state.clone(), access_log::log_access))
+        .layer(CompressionLayer::new().compress_when(DefaultPredicate::new().and(SkipMediaStreams)))
         .with_state(state)
-}
\ No newline at end of file
+}
@@ -0,0 +1,127 @@
+//! Per-stream bandwidth limit for media responses, `server.max_stream_bandwidth_kbps`.
+//!
+//! Each response body gets its own token bucket, so the cap applies per
+//! connection rather than to the server as a whole. Chunks are cut into slices
+//! of about a tenth of a second's worth of bytes before pacing, which keeps the
+//! stream smooth even at low caps; the renderer just buffers less ahead.
+
+use axum::body::Bytes;
+use futures_util::{stream, Stream, StreamExt};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Lowest accepted cap. Slices are at least 1 KiB, so lower caps would stall
+/// for seconds between slices.
+pub const MIN_STREAM_BANDWIDTH_KBPS: u64 = 64;
+
+/// Bytes accrued per second, spent as data is sent. At most `burst` bytes go
+/// out without waiting after the stream has been idle.
+struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(bytes_per_second: u64, burst: usize) -> Self {
+        Self {
+            rate: bytes_per_second as f64,
+            burst: burst as f64,
+            tokens: burst as f64,
+            updated: Instant::now(),
+        }
+    }
+
+    /// Spend `bytes` tokens, waiting until the bucket has refilled enough to cover them
+    async fn take(&mut self, bytes: usize) {
+        let now = Instant::now();
+        let refilled = now.duration_since(self.updated).as_secs_f64() * self.rate;
+        self.tokens = (self.tokens + refilled).min(self.burst);
+        self.updated = now;
+        self.tokens -= bytes as f64;
+        if self.tokens < 0.0 {
+            tokio::time::sleep(Duration::from_secs_f64(-self.tokens / self.rate)).await;
+        }
+    }
+}
+
+/// Bytes per second allowed by a cap of `kbps` kilobits per second
+pub fn bytes_per_second(kbps: u64) -> u64 {
+    kbps.saturating_mul(1000) / 8
+}
+
+/// Split a chunk into slices of at most `max_len` bytes
+fn split_chunk<E>(chunk: Result<Bytes, E>, max_len: usize) -> Vec<Result<Bytes, E>> {
+    match chunk {
+        Ok(mut bytes) => {
+            let mut slices = Vec::with_capacity(bytes.len() / max_len + 1);
+            while bytes.len() > max_len {
+                slices.push(Ok(bytes.split_to(max_len)));
+            }
+            slices.push(Ok(bytes));
+            slices
+        }
+        Err(e) => vec![Err(e)],
+    }
+}
+
+/// Pace `stream` to at most `bytes_per_second`
+pub fn throttle<S, B, E>(stream: S, bytes_per_second: u64) -> impl Stream<Item = Result<Bytes, E>> + Send + 'static
+where
+    S: Stream<Item = Result<B, E>> + Send + 'static,
+    B: Into<Bytes>,
+    E: Send + 'static,
+{
+    let slice_len = (bytes_per_second / 10).clamp(1024, 64 * 1024) as usize;
+    let slices = stream.flat_map(move |chunk| stream::iter(split_chunk(chunk.map(Into::into), slice_len)));
+    let bucket = TokenBucket::new(bytes_per_second.max(1), slice_len);
+    stream::unfold((Box::pin(slices), bucket), |(mut slices, mut bucket)| async move {
+        let slice = slices.next().await?;
+        if let Ok(bytes) = &slice {
+            bucket.take(bytes.len()).await;
+        }
+        Some((slice, (slices, bucket)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(elapsed: Duration, expected: Duration) {
+        let difference = elapsed.abs_diff(expected);
+        assert!(difference < Duration::from_millis(5), "took {:?}, expected {:?}", elapsed, expected);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_token_bucket_paces_to_rate() {
+        let mut bucket = TokenBucket::new(10_000, 1_000);
+        let started = Instant::now();
+        bucket.take(1_000).await;
+        assert_eq!(started.elapsed(), Duration::ZERO);
+        for _ in 0..10 {
+            bucket.take(1_000).await;
+        }
+        assert_near(started.elapsed(), Duration::from_secs(1));
+
+        // An idle stream saves up no more than the burst
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        let resumed = Instant::now();
+        bucket.take(1_000).await;
+        assert_eq!(resumed.elapsed(), Duration::ZERO);
+        bucket.take(1_000).await;
+        assert_near(resumed.elapsed(), Duration::from_millis(100));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_throttled_stream_stays_at_cap() {
+        // 4 Mbit/s is 500 KB/s; the first 50 KB slice goes out as the burst
+        let chunks = (0..10).map(|_| Ok::<_, std::io::Error>(vec![7u8; 100_000]));
+        let started = Instant::now();
+        let slices: Vec<_> = throttle(stream::iter(chunks), bytes_per_second(4000)).collect().await;
+        let sent: usize = slices.iter().map(|slice| slice.as_ref().unwrap().len()).sum();
+        assert_eq!(sent, 1_000_000);
+        assert_near(started.elapsed(), Duration::from_millis(1900));
+    }
+}
@@ -0,0 +1,2 @@
+//! UPnP ContentDirectory features beyond Browse
+pub mod search;
@@ -0,0 +1,402 @@
+//! Parsing of ContentDirectory `SearchCriteria` into a [`MediaFilter`].
+//!
+//! Supports the subset control points send in practice: `*`, the `=`, `!=`,
+//! `contains`, `doesNotContain`, `startsWith`, `derivedfrom` and `exists`
+//! operators, and `and`/`or` with parentheses.
+
+use thiserror::Error;
+
+use crate::config::MediaClass;
+use crate::database::{MediaField, MediaFilter};
+
+/// Properties accepted in search criteria, advertised by GetSearchCapabilities
+pub const SEARCH_CAPABILITIES: &str = "dc:title,dc:creator,upnp:artist,upnp:album,upnp:class";
+
+/// Item classes used in DIDL-Lite and the MIME type prefix each one covers
+const ITEM_CLASSES: [(&str, &str); 3] = [
+    ("object.item.videoItem", "video/"),
+    ("object.item.audioItem", "audio/"),
+    ("object.item.imageItem", "image/"),
+];
+
+/// Deepest parenthesis nesting accepted, so hostile criteria cannot exhaust the stack
+const MAX_NESTING_DEPTH: usize = 32;
+/// Most relations accepted in one criteria string, bounding the filter and its SQL
+const MAX_TERMS: usize = 256;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum SearchError {
+    #[error("unexpected end of search criteria")]
+    UnexpectedEnd,
+
+    #[error("unexpected '{0}' in search criteria")]
+    Unexpected(String),
+
+    #[error("unterminated string in search criteria")]
+    UnterminatedString,
+
+    #[error("unsupported search property '{0}'")]
+    UnsupportedProperty(String),
+
+    #[error("unsupported search operator '{0}'")]
+    UnsupportedOperator(String),
+
+    #[error("search criteria nested deeper than {MAX_NESTING_DEPTH} levels")]
+    TooDeeplyNested,
+
+    #[error("search criteria has more than {MAX_TERMS} terms")]
+    TooManyTerms,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Open,
+    Close,
+}
+
+fn tokenize(criteria: &str) -> Result<Vec<Token>, SearchError> {
+    let mut tokens = Vec::new();
+    let mut chars = criteria.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '"' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') => text.push(chars.next().ok_or(SearchError::UnterminatedString)?),
+                        Some('"') => break,
+                        Some(c) => text.push(c),
+                        None => return Err(SearchError::UnterminatedString),
+                    }
+                }
+                tokens.push(Token::Quoted(text));
+            }
+            '=' => {
+                chars.next();
+                tokens.push(Token::Word("=".to_string()));
+            }
+            '!' => {
+                chars.next();
+                if chars.next_if_eq(&'=').is_none() {
+                    return Err(SearchError::Unexpected("!".to_string()));
+                }
+                tokens.push(Token::Word("!=".to_string()));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '(' | ')' | '"' | '=' | '!') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Parentheses currently open
+    depth: usize,
+    /// Relations parsed so far
+    terms: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Result<Token, SearchError> {
+        let token = self.tokens.get(self.pos).cloned().ok_or(SearchError::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn next_is_word(&mut self, word: &str) -> bool {
+        match self.tokens.get(self.pos) {
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case(word) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn or_expression(&mut self) -> Result<MediaFilter, SearchError> {
+        let mut filter = self.and_expression()?;
+        while self.next_is_word("or") {
+            filter = MediaFilter::Or(Box::new(filter), Box::new(self.and_expression()?));
+        }
+        Ok(filter)
+    }
+
+    fn and_expression(&mut self) -> Result<MediaFilter, SearchError> {
+        let mut filter = self.relation()?;
+        while self.next_is_word("and") {
+            filter = MediaFilter::And(Box::new(filter), Box::new(self.relation()?));
+        }
+        Ok(filter)
+    }
+
+    fn relation(&mut self) -> Result<MediaFilter, SearchError> {
+        let property = match self.next()? {
+            Token::Open => {
+                self.depth += 1;
+                if self.depth > MAX_NESTING_DEPTH {
+                    return Err(SearchError::TooDeeplyNested);
+                }
+                let filter = self.or_expression()?;
+                self.depth -= 1;
+                return match self.next()? {
+                    Token::Close => Ok(filter),
+                    other => Err(unexpected(other)),
+                };
+            }
+            Token::Word(word) => word,
+            other => return Err(unexpected(other)),
+        };
+        self.terms += 1;
+        if self.terms > MAX_TERMS {
+            return Err(SearchError::TooManyTerms);
+        }
+        let operator = match self.next()? {
+            Token::Word(word) => word,
+            other => return Err(unexpected(other)),
+        };
+
+        if operator.eq_ignore_ascii_case("exists") {
+            let exists = match self.next()? {
+                Token::Word(w) if w.eq_ignore_ascii_case("true") => true,
+                Token::Word(w) if w.eq_ignore_ascii_case("false") => false,
+                other => return Err(unexpected(other)),
+            };
+            return Ok(exists_filter(&property, exists));
+        }
+
+        let value = match self.next()? {
+            Token::Quoted(value) => value,
+            other => return Err(unexpected(other)),
+        };
+        relation_filter(&property, &operator, value)
+    }
+}
+
+fn unexpected(token: Token) -> SearchError {
+    SearchError::Unexpected(match token {
+        Token::Word(word) => word,
+        Token::Quoted(text) => format!("\"{}\"", text),
+        Token::Open => "(".to_string(),
+        Token::Close => ")".to_string(),
+    })
+}
+
+/// Media columns holding a text property; `dc:title` matches the displayed
+/// filename as well as the tag title
+fn property_fields(property: &str) -> Option<&'static [MediaField]> {
+    match property {
+        "dc:title" => Some(&[MediaField::Filename, MediaField::Title]),
+        "dc:creator" | "upnp:artist" => Some(&[MediaField::Artist]),
+        "upnp:album" => Some(&[MediaField::Album]),
+        _ => None,
+    }
+}
+
+fn any_of(filters: impl IntoIterator<Item = MediaFilter>) -> MediaFilter {
+    filters
+        .into_iter()
+        .reduce(|a, b| MediaFilter::Or(Box::new(a), Box::new(b)))
+        .unwrap_or(MediaFilter::Nothing)
+}
+
+fn not(filter: MediaFilter) -> MediaFilter {
+    MediaFilter::Not(Box::new(filter))
+}
+
+fn exists_filter(property: &str, exists: bool) -> MediaFilter {
+    let present = match property {
+        "upnp:class" | "dc:title" => MediaFilter::All,
+        _ => match property_fields(property) {
+            Some(fields) => any_of(fields.iter().map(|f| not(MediaFilter::Equals(*f, String::new())))),
+            // Properties such as @refID are never set on our items
+            None => MediaFilter::Nothing,
+        },
+    };
+    match (exists, present) {
+        (true, present) => present,
+        (false, MediaFilter::All) => MediaFilter::Nothing,
+        (false, MediaFilter::Nothing) => MediaFilter::All,
+        (false, present) => not(present),
+    }
+}
+
+/// Items whose class equals `class`, or derives from it when `derived` is set
+fn class_filter(class: &str, derived: bool) -> MediaFilter {
+    let class = class.to_ascii_lowercase();
+    let covers = |candidate: &str| {
+        let candidate = candidate.to_ascii_lowercase();
+        candidate == class || (derived && candidate.starts_with(&format!("{}.", class)))
+    };
+
+    if derived && (class == "object" || class == "object.item") {
+        return MediaFilter::All;
+    }
+    let mime_prefix = |prefix: &str| MediaFilter::StartsWith(MediaField::MimeType, prefix.to_string());
+    if class == "object.item" {
+        // Plain items are files outside the video, audio and image classes
+        return not(any_of(ITEM_CLASSES.iter().map(|(_, prefix)| mime_prefix(prefix))));
+    }
+    any_of(
+        ITEM_CLASSES
+            .iter()
+            .filter(|(item_class, _)| covers(item_class))
+            .map(|(_, prefix)| mime_prefix(prefix)),
+    )
+}
+
+fn relation_filter(property: &str, operator: &str, value: String) -> Result<MediaFilter, SearchError> {
+    if property == "upnp:class" {
+        return match operator.to_ascii_lowercase().as_str() {
+            "derivedfrom" => Ok(class_filter(&value, true)),
+            "=" => Ok(class_filter(&value, false)),
+            "!=" => Ok(not(class_filter(&value, false))),
+            _ => Err(SearchError::UnsupportedOperator(operator.to_string())),
+        };
+    }
+
+    let fields = property_fields(property).ok_or_else(|| SearchError::UnsupportedProperty(property.to_string()))?;
+    let each = |make: fn(MediaField, String) -> MediaFilter| any_of(fields.iter().map(|f| make(*f, value.clone())));
+    match operator.to_ascii_lowercase().as_str() {
+        "contains" => Ok(each(MediaFilter::Contains)),
+        "doesnotcontain" => Ok(not(each(MediaFilter::Contains))),
+        "=" => Ok(each(MediaFilter::Equals)),
+        "!=" => Ok(not(each(MediaFilter::Equals))),
+        "startswith" => Ok(each(MediaFilter::StartsWith)),
+        _ => Err(SearchError::UnsupportedOperator(operator.to_string())),
+    }
+}
+
+/// Parse UPnP `SearchCriteria` into a filter over indexed media files
+pub fn parse_search_criteria(criteria: &str) -> Result<MediaFilter, SearchError> {
+    let criteria = criteria.trim();
+    if criteria.is_empty() || criteria == "*" {
+        return Ok(MediaFilter::All);
+    }
+
+    let mut parser = Parser {
+        tokens: tokenize(criteria)?,
+        pos: 0,
+        depth: 0,
+        terms: 0,
+    };
+    let filter = parser.or_expression()?;
+    match parser.tokens.get(parser.pos) {
+        None => Ok(filter),
+        Some(token) => Err(unexpected(token.clone())),
+    }
+}
+
+/// Limit a search to the advertised media classes, and to a single class when
+/// `container_id` is below one of the class roots
+pub fn scope_search(container_id: &str, advertised: &[MediaClass], filter: MediaFilter) -> MediaFilter {
+    let container_class = MediaClass::ALL
+        .into_iter()
+        .find(|class| container_id.split('/').next() == Some(class.mime_prefix().trim_end_matches('/')));
+    let classes = advertised
+        .iter()
+        .filter(|class| container_class.is_none_or(|c| c == **class))
+        .map(|class| MediaFilter::StartsWith(MediaField::MimeType, class.mime_prefix().to_string()));
+    MediaFilter::And(Box::new(any_of(classes)), Box::new(filter))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::MediaFile;
+    use std::path::PathBuf;
+
+    fn file(name: &str, mime: &str, artist: Option<&str>) -> MediaFile {
+        let mut file = MediaFile::new(PathBuf::from(format!("/media/{}", name)), 1, mime.to_string());
+        file.artist = artist.map(str::to_string);
+        file
+    }
+
+    fn matching<'a>(criteria: &str, files: &'a [MediaFile]) -> Vec<&'a str> {
+        let filter = parse_search_criteria(criteria).unwrap();
+        files.iter().filter(|f| filter.matches(f)).map(|f| f.filename.as_str()).collect()
+    }
+
+    #[test]
+    fn test_title_and_class_criteria() {
+        let files = [
+            file("Beatles - Help.mp3", "audio/mpeg", Some("The Beatles")),
+            file("beatles documentary.mp4", "video/mp4", None),
+            file("Stones.flac", "audio/flac", Some("Rolling Stones")),
+            file("notes.txt", "text/plain", None),
+        ];
+
+        assert_eq!(
+            matching(r#"upnp:class derivedfrom "object.item.audioItem" and dc:title contains "beatles""#, &files),
+            vec!["Beatles - Help.mp3"]
+        );
+        assert_eq!(
+            matching(r#"dc:title contains "BEATLES""#, &files),
+            vec!["Beatles - Help.mp3", "beatles documentary.mp4"]
+        );
+        assert_eq!(matching(r#"upnp:class = "object.item.videoItem""#, &files), vec!["beatles documentary.mp4"]);
+        assert_eq!(matching(r#"upnp:class derivedfrom "object.item""#, &files).len(), 4);
+        assert_eq!(matching(r#"upnp:class = "object.item""#, &files), vec!["notes.txt"]);
+        assert!(matching(r#"upnp:class derivedfrom "object.container""#, &files).is_empty());
+        assert_eq!(
+            matching(r#"(upnp:artist = "rolling stones" or dc:creator contains "beat") and @refID exists false"#, &files),
+            vec!["Beatles - Help.mp3", "Stones.flac"]
+        );
+        assert_eq!(matching(r#"upnp:artist exists true and dc:title doesNotContain "help""#, &files), vec!["Stones.flac"]);
+        assert_eq!(matching("*", &files).len(), 4);
+    }
+
+    #[test]
+    fn test_invalid_criteria_are_rejected() {
+        assert_eq!(
+            parse_search_criteria(r#"dc:date > "2020""#),
+            Err(SearchError::UnsupportedProperty("dc:date".to_string()))
+        );
+        assert_eq!(
+            parse_search_criteria(r#"dc:title like "x""#),
+            Err(SearchError::UnsupportedOperator("like".to_string()))
+        );
+        assert_eq!(parse_search_criteria(r#"dc:title contains "x"#), Err(SearchError::UnterminatedString));
+        assert_eq!(parse_search_criteria(r#"(dc:title contains "x""#), Err(SearchError::UnexpectedEnd));
+        assert_eq!(
+            parse_search_criteria(r#"dc:title contains "x" dc:title"#),
+            Err(SearchError::Unexpected("dc:title".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_nesting_and_term_limits() {
+        let nested = |depth: usize| format!("{}dc:title contains \"x\"{}", "(".repeat(depth), ")".repeat(depth));
+        assert!(parse_search_criteria(&nested(MAX_NESTING_DEPTH)).is_ok());
+        assert_eq!(parse_search_criteria(&nested(MAX_NESTING_DEPTH + 1)), Err(SearchError::TooDeeplyNested));
+        assert_eq!(parse_search_criteria(&nested(10_000)), Err(SearchError::TooDeeplyNested));
+
+        let chain = |terms: usize| vec![r#"dc:title contains "x""#; terms].join(" or ");
+        assert!(parse_search_criteria(&chain(MAX_TERMS)).is_ok());
+        assert_eq!(parse_search_criteria(&chain(MAX_TERMS + 1)), Err(SearchError::TooManyTerms));
+    }
+}
@@ -1,40 +1,116 @@
 // src\web\xml.rs
-use crate::{database::MediaFile, state::AppState};
+use crate::{
+    config::{AppConfig, MediaClass, MonitoredDirectoryConfig},
+    database::{compare_by_criteria, containers_descending, AlbumSummary, ArtistSummary, DirectoryPage, MediaFile, TvEpisode},
+    media::{dlna_profile, playlist::ResolvedPlaylist},
+    platform::network::url_host,
+    ssdp::location_host,
+    state::AppState,
+    web::{handlers::content_features, upnp::search::SEARCH_CAPABILITIES},
+};
+use sha2::{Digest, Sha256};
 use std::{
-    collections::HashSet,
+    net::SocketAddr,
+    collections::{BTreeMap, BTreeSet, HashSet},
     path::{Component, Path, PathBuf},
 };
 use tracing::warn;
 
-/// XML escape helper
-fn xml_escape(s: &str) -> String {
-    s.replace('&', "&amp;")
-        .replace('<', "&lt;")
-        .replace('>', "&gt;")
-        .replace('"', "&quot;")
-        .replace('\'', "&#39;")
+pub use crate::database::{SortCriterion, SortKey};
+
+/// Whether `c` may appear in an XML 1.0 document
+fn is_xml_char(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\r' | '\u{20}'..='\u{D7FF}' | '\u{E000}'..='\u{FFFD}' | '\u{10000}'..)
 }
 
-/// Get the server's IP address for use in URLs from the application state.
+/// Escape text for XML element content and attribute values. Control
+/// characters XML cannot represent, as found in some tags, are dropped.
+pub(crate) fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c if is_xml_char(c) => escaped.push(c),
+            _ => {}
+        }
+    }
+    escaped
+}
+
+/// Decode the predefined entities and character references in XML text;
+/// anything else starting with `&` is kept as written
+pub(crate) fn xml_unescape(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest.find(';').and_then(|end| {
+            let c = match &rest[1..end] {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                reference => {
+                    let code = match reference.strip_prefix("#x").or_else(|| reference.strip_prefix("#X")) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => reference.strip_prefix('#')?.parse().ok()?,
+                    };
+                    char::from_u32(code)?
+                }
+            };
+            Some((c, end + 1))
+        });
+        match decoded {
+            Some((c, len)) => {
+                unescaped.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+/// Host of a configured or detected server address for URLs
+fn address_url_host(address: &str) -> String {
+    match address.parse::<std::net::IpAddr>() {
+        Ok(ip) => url_host(ip, None),
+        Err(_) => address.to_string(),
+    }
+}
+
+/// Get the server's address for use in URLs from the application state,
+/// bracketed when it is IPv6.
 fn get_server_ip(state: &AppState) -> String {
     // 1. Use the primary interface detected at startup.
-    if let Some(iface) = state.platform_info.get_primary_interface() {
-        return iface.ip_address.to_string();
+    if let Some(iface) = state.platform_info.get().get_primary_interface() {
+        return url_host(iface.ip_address, None);
     }
 
     // 2. Fallback to the configured server interface if it's not a wildcard.
-    if state.config.server.interface != "0.0.0.0" && !state.config.server.interface.is_empty() {
-        return state.config.server.interface.clone();
+    if !matches!(state.config.server.interface.as_str(), "" | "0.0.0.0" | "::") {
+        return address_url_host(&state.config.server.interface);
     }
 
     // 3. Fallback to trying to find any usable interface from the list.
     if let Some(iface) = state
         .platform_info
+        .get()
         .network_interfaces
         .iter()
         .find(|i| !i.is_loopback && i.is_up)
     {
-        return iface.ip_address.to_string();
+        return url_host(iface.ip_address, None);
     }
 
     // 4. Final fallback.
@@ -42,6 +118,38 @@ fn get_server_ip(state: &AppState) -> String {
     "127.0.0.1".to_string()
 }
 
+/// Base URL control points reach the server at, as `http://host:port`.
+///
+/// The request's `Host` header names the address the client connected to, so
+/// it is reachable from the client's subnet, and in its address family, even
+/// when the server has several interfaces. Without a usable header the
+/// configured server interface is used, or the detected one when the server
+/// listens on all interfaces.
+pub fn server_base_url(state: &AppState, request_host: Option<&str>) -> String {
+    let port = state.config.server.port;
+    if let Some(authority) = request_host.and_then(|host| host.parse::<axum::http::uri::Authority>().ok()) {
+        return format!("http://{}:{}", authority.host(), authority.port_u16().unwrap_or(port));
+    }
+
+    let interface = state.config.server.interface.as_str();
+    let host = if interface.is_empty() || interface == "0.0.0.0" || interface == "::" {
+        get_server_ip(state)
+    } else {
+        address_url_host(interface)
+    };
+    format!("http://{}:{}", host, port)
+}
+
+/// Host of the media URLs in responses to `peer`, in the address family of its
+/// connection as SSDP `LOCATION` URLs are; the detected server address without a peer
+pub async fn media_host(state: &AppState, peer: Option<SocketAddr>) -> String {
+    match peer {
+        // IPv4 clients of a dual-stack listener show up as mapped IPv6 addresses
+        Some(peer) => location_host(state, SocketAddr::new(peer.ip().to_canonical(), peer.port())).await,
+        None => get_server_ip(state),
+    }
+}
+
 /// Get the appropriate UPnP class for a given MIME type.
 fn get_upnp_class(mime_type: &str) -> &str {
     if mime_type.starts_with("video/") {
@@ -55,30 +163,139 @@ fn get_upnp_class(mime_type: &str) -> &str {
     }
 }
 
-pub fn generate_description_xml(state: &AppState) -> String {
+/// Root container id and title for a media class.
+fn root_container(class: MediaClass) -> (&'static str, &'static str) {
+    match class {
+        MediaClass::Video => ("video", "Video"),
+        MediaClass::Audio => ("audio", "Music"),
+        MediaClass::Image => ("image", "Pictures"),
+    }
+}
+
+/// Container listed at the root for a media class: videos by folder, music by
+/// artist and album, pictures by date
+fn root_view(class: MediaClass) -> (&'static str, &'static str) {
+    match class {
+        MediaClass::Video => ("video", "Video"),
+        MediaClass::Audio => (MUSIC_CONTAINER, "Music"),
+        MediaClass::Image => (PHOTOS_CONTAINER, "Pictures"),
+    }
+}
+
+/// Build the source protocolInfo list for the advertised media classes, from
+/// the built-in and custom MIME types plus the transcoder's output format.
+pub fn generate_source_protocol_info(state: &AppState) -> String {
+    let mut entries: Vec<String> = state
+        .config
+        .media
+        .media_types()
+        .supported_mime_types()
+        .iter()
+        .filter(|mime| state.config.server.advertises(mime))
+        .flat_map(|mime| {
+            // A configured profile replaces the detected ones; an empty one announces none
+            let profiles: Vec<&str> = match state.config.media.dlna_profiles.as_ref().and_then(|p| p.get(mime)) {
+                Some(profile) => Some(profile.as_str()).filter(|p| !p.is_empty()).into_iter().collect(),
+                None => dlna_profile::profiles_for_mime(mime).to_vec(),
+            };
+            if profiles.is_empty() {
+                vec![format!("http-get:*:{}:*", mime)]
+            } else {
+                profiles.iter().map(|profile| format!("http-get:*:{}:DLNA.ORG_PN={}", mime, profile)).collect()
+            }
+        })
+        .collect();
+    if let Some(transcoder) = state.transcoder.as_ref().filter(|t| state.config.server.advertises(t.mime_type())) {
+        let entry = format!("http-get:*:{}:DLNA.ORG_PN={}", transcoder.mime_type(), transcoder.dlna_profile());
+        if !entries.contains(&entry) {
+            entries.push(entry);
+        }
+    }
+    entries.join(",")
+}
+
+/// UPnP `CONFIGID.UPNP.ORG` of the device and service descriptions served: a
+/// 24-bit hash that changes whenever they do. The base URL is left out, so the
+/// value is the same on every interface.
+pub fn description_config_id(state: &AppState) -> u32 {
+    let mut hasher = Sha256::new();
+    hasher.update(generate_description_xml(state, ""));
+    hasher.update(generate_scpd_xml());
+    hasher.update(generate_connection_manager_scpd_xml());
+    let digest = hasher.finalize();
+    u32::from_be_bytes([0, digest[0], digest[1], digest[2]])
+}
+
+/// Root device description. `URLBase` and the service URLs are absolute,
+/// built from `base_url` as given by [`server_base_url`].
+pub fn generate_description_xml(state: &AppState, base_url: &str) -> String {
+    let server = &state.config.server;
+    let base_url = xml_escape(base_url);
+    let advertised = state
+        .config
+        .server
+        .advertised_content
+        .iter()
+        .map(|class| root_container(*class).0)
+        .collect::<Vec<_>>()
+        .join(", ");
     format!(
         r#"<?xml version="1.0" encoding="UTF-8"?>
 <root xmlns="urn:schemas-upnp-org:device-1-0">
     <specVersion><major>1</major><minor>0</minor></specVersion>
+    <URLBase>{base_url}/</URLBase>
     <device>
         <deviceType>urn:schemas-upnp-org:device:MediaServer:1</deviceType>
         <friendlyName>{}</friendlyName>
-        <manufacturer>VuIO</manufacturer>
-        <modelName>VuIO Server</modelName>
+        <manufacturer>{}</manufacturer>
+        <manufacturerURL>{}</manufacturerURL>
+        <modelDescription>VuIO media server ({})</modelDescription>
+        <modelName>{}</modelName>
+        <modelNumber>{}</modelNumber>
+        <modelURL>{}</modelURL>
+        <serialNumber>{}</serialNumber>
         <UDN>uuid:{}</UDN>
+        <iconList>
+            <icon>
+                <mimetype>{}</mimetype>
+                <width>{}</width>
+                <height>{}</height>
+                <depth>{}</depth>
+                <url>{}</url>
+            </icon>
+        </iconList>
         <serviceList>
             <service>
                 <serviceType>urn:schemas-upnp-org:service:ContentDirectory:1</serviceType>
                 <serviceId>urn:upnp-org:serviceId:ContentDirectory</serviceId>
-                <SCPDURL>/ContentDirectory.xml</SCPDURL>
-                <controlURL>/control/ContentDirectory</controlURL>
-                <eventSubURL>/event/ContentDirectory</eventSubURL>
+                <SCPDURL>{base_url}/ContentDirectory.xml</SCPDURL>
+                <controlURL>{base_url}/control/ContentDirectory</controlURL>
+                <eventSubURL>{base_url}/event/ContentDirectory</eventSubURL>
+            </service>
+            <service>
+                <serviceType>urn:schemas-upnp-org:service:ConnectionManager:1</serviceType>
+                <serviceId>urn:upnp-org:serviceId:ConnectionManager</serviceId>
+                <SCPDURL>{base_url}/ConnectionManager.xml</SCPDURL>
+                <controlURL>{base_url}/control/ConnectionManager</controlURL>
+                <eventSubURL>{base_url}/event/ConnectionManager</eventSubURL>
             </service>
         </serviceList>
     </device>
 </root>"#,
-        xml_escape(&state.config.server.name),
-        state.config.server.uuid
+        xml_escape(&server.name),
+        xml_escape(&server.manufacturer),
+        xml_escape(&server.manufacturer_url),
+        advertised,
+        xml_escape(&server.model_name),
+        xml_escape(&server.model_number),
+        xml_escape(&server.model_url),
+        xml_escape(&server.serial_number),
+        server.uuid,
+        state.icon.mime_type,
+        state.icon.width,
+        state.icon.height,
+        state.icon.depth,
+        state.icon.url()
     )
 }
 
@@ -103,6 +320,33 @@ pub fn generate_scpd_xml() -> String {
                 <argument><name>UpdateID</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_UpdateID</relatedStateVariable></argument>
             </argumentList>
         </action>
+        <action>
+            <name>GetSortCapabilities</name>
+            <argumentList>
+                <argument><name>SortCaps</name><direction>out</direction><relatedStateVariable>SortCapabilities</relatedStateVariable></argument>
+            </argumentList>
+        </action>
+        <action>
+            <name>Search</name>
+            <argumentList>
+                <argument><name>ContainerID</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_ObjectID</relatedStateVariable></argument>
+                <argument><name>SearchCriteria</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_SearchCriteria</relatedStateVariable></argument>
+                <argument><name>Filter</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_Filter</relatedStateVariable></argument>
+                <argument><name>StartingIndex</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_Index</relatedStateVariable></argument>
+                <argument><name>RequestedCount</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_Count</relatedStateVariable></argument>
+                <argument><name>SortCriteria</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_SortCriteria</relatedStateVariable></argument>
+                <argument><name>Result</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_Result</relatedStateVariable></argument>
+                <argument><name>NumberReturned</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_Count</relatedStateVariable></argument>
+                <argument><name>TotalMatches</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_Count</relatedStateVariable></argument>
+                <argument><name>UpdateID</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_UpdateID</relatedStateVariable></argument>
+            </argumentList>
+        </action>
+        <action>
+            <name>GetSearchCapabilities</name>
+            <argumentList>
+                <argument><name>SearchCaps</name><direction>out</direction><relatedStateVariable>SearchCapabilities</relatedStateVariable></argument>
+            </argumentList>
+        </action>
     </actionList>
     <serviceStateTable>
         <stateVariable sendEvents="no"><name>A_ARG_TYPE_ObjectID</name><dataType>string</dataType></stateVariable>
@@ -113,27 +357,471 @@ pub fn generate_scpd_xml() -> String {
         <stateVariable sendEvents="no"><name>A_ARG_TYPE_SortCriteria</name><dataType>string</dataType></stateVariable>
         <stateVariable sendEvents="no"><name>A_ARG_TYPE_Result</name><dataType>string</dataType></stateVariable>
         <stateVariable sendEvents="no"><name>A_ARG_TYPE_UpdateID</name><dataType>ui4</dataType></stateVariable>
+        <stateVariable sendEvents="no"><name>A_ARG_TYPE_SearchCriteria</name><dataType>string</dataType></stateVariable>
+        <stateVariable sendEvents="no"><name>SortCapabilities</name><dataType>string</dataType></stateVariable>
+        <stateVariable sendEvents="no"><name>SearchCapabilities</name><dataType>string</dataType></stateVariable>
         <stateVariable sendEvents="yes"><name>SystemUpdateID</name><dataType>ui4</dataType></stateVariable>
         <stateVariable sendEvents="yes"><name>ContainerUpdateIDs</name><dataType>string</dataType></stateVariable>
+        <stateVariable sendEvents="yes"><name>LastChange</name><dataType>string</dataType></stateVariable>
+    </serviceStateTable>
+</scpd>"#.to_string()
+}
+
+/// Service description of the ConnectionManager. Only the default connection 0
+/// exists, as media is served over plain HTTP GET.
+pub fn generate_connection_manager_scpd_xml() -> String {
+    r#"<?xml version="1.0" encoding="UTF-8"?>
+<scpd xmlns="urn:schemas-upnp-org:service-1-0">
+    <specVersion><major>1</major><minor>0</minor></specVersion>
+    <actionList>
+        <action>
+            <name>GetProtocolInfo</name>
+            <argumentList>
+                <argument><name>Source</name><direction>out</direction><relatedStateVariable>SourceProtocolInfo</relatedStateVariable></argument>
+                <argument><name>Sink</name><direction>out</direction><relatedStateVariable>SinkProtocolInfo</relatedStateVariable></argument>
+            </argumentList>
+        </action>
+        <action>
+            <name>GetCurrentConnectionIDs</name>
+            <argumentList>
+                <argument><name>ConnectionIDs</name><direction>out</direction><relatedStateVariable>CurrentConnectionIDs</relatedStateVariable></argument>
+            </argumentList>
+        </action>
+        <action>
+            <name>GetCurrentConnectionInfo</name>
+            <argumentList>
+                <argument><name>ConnectionID</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_ConnectionID</relatedStateVariable></argument>
+                <argument><name>RcsID</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_RcsID</relatedStateVariable></argument>
+                <argument><name>AVTransportID</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_AVTransportID</relatedStateVariable></argument>
+                <argument><name>ProtocolInfo</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_ProtocolInfo</relatedStateVariable></argument>
+                <argument><name>PeerConnectionManager</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_ConnectionManager</relatedStateVariable></argument>
+                <argument><name>PeerConnectionID</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_ConnectionID</relatedStateVariable></argument>
+                <argument><name>Direction</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_Direction</relatedStateVariable></argument>
+                <argument><name>Status</name><direction>out</direction><relatedStateVariable>A_ARG_TYPE_ConnectionStatus</relatedStateVariable></argument>
+            </argumentList>
+        </action>
+    </actionList>
+    <serviceStateTable>
+        <stateVariable sendEvents="yes"><name>SourceProtocolInfo</name><dataType>string</dataType></stateVariable>
+        <stateVariable sendEvents="yes"><name>SinkProtocolInfo</name><dataType>string</dataType></stateVariable>
+        <stateVariable sendEvents="yes"><name>CurrentConnectionIDs</name><dataType>string</dataType></stateVariable>
+        <stateVariable sendEvents="no"><name>A_ARG_TYPE_ConnectionStatus</name><dataType>string</dataType>
+            <allowedValueList><allowedValue>OK</allowedValue><allowedValue>ContentFormatMismatch</allowedValue><allowedValue>InsufficientBandwidth</allowedValue><allowedValue>UnreliableChannel</allowedValue><allowedValue>Unknown</allowedValue></allowedValueList>
+        </stateVariable>
+        <stateVariable sendEvents="no"><name>A_ARG_TYPE_ConnectionManager</name><dataType>string</dataType></stateVariable>
+        <stateVariable sendEvents="no"><name>A_ARG_TYPE_Direction</name><dataType>string</dataType>
+            <allowedValueList><allowedValue>Input</allowedValue><allowedValue>Output</allowedValue></allowedValueList>
+        </stateVariable>
+        <stateVariable sendEvents="no"><name>A_ARG_TYPE_ProtocolInfo</name><dataType>string</dataType></stateVariable>
+        <stateVariable sendEvents="no"><name>A_ARG_TYPE_ConnectionID</name><dataType>i4</dataType></stateVariable>
+        <stateVariable sendEvents="no"><name>A_ARG_TYPE_AVTransportID</name><dataType>i4</dataType></stateVariable>
+        <stateVariable sendEvents="no"><name>A_ARG_TYPE_RcsID</name><dataType>i4</dataType></stateVariable>
     </serviceStateTable>
 </scpd>"#.to_string()
 }
 
+/// SOAP response to the ConnectionManager GetProtocolInfo action; the sink is
+/// empty because the server never renders media itself
+pub fn generate_protocol_info_response(state: &AppState) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/">
+    <s:Body>
+        <u:GetProtocolInfoResponse xmlns:u="urn:schemas-upnp-org:service:ConnectionManager:1">
+            <Source>{}</Source>
+            <Sink></Sink>
+        </u:GetProtocolInfoResponse>
+    </s:Body>
+</s:Envelope>"#,
+        xml_escape(&generate_source_protocol_info(state))
+    )
+}
+
+/// SOAP response to the ConnectionManager GetCurrentConnectionIDs action
+pub fn generate_current_connection_ids_response() -> String {
+    r#"<?xml version="1.0" encoding="UTF-8"?>
+<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/">
+    <s:Body>
+        <u:GetCurrentConnectionIDsResponse xmlns:u="urn:schemas-upnp-org:service:ConnectionManager:1">
+            <ConnectionIDs>0</ConnectionIDs>
+        </u:GetCurrentConnectionIDsResponse>
+    </s:Body>
+</s:Envelope>"#
+        .to_string()
+}
+
+/// SOAP response to the ConnectionManager GetCurrentConnectionInfo action for
+/// the default connection
+pub fn generate_current_connection_info_response() -> String {
+    r#"<?xml version="1.0" encoding="UTF-8"?>
+<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/">
+    <s:Body>
+        <u:GetCurrentConnectionInfoResponse xmlns:u="urn:schemas-upnp-org:service:ConnectionManager:1">
+            <RcsID>-1</RcsID>
+            <AVTransportID>-1</AVTransportID>
+            <ProtocolInfo></ProtocolInfo>
+            <PeerConnectionManager></PeerConnectionManager>
+            <PeerConnectionID>-1</PeerConnectionID>
+            <Direction>Output</Direction>
+            <Status>OK</Status>
+        </u:GetCurrentConnectionInfoResponse>
+    </s:Body>
+</s:Envelope>"#
+        .to_string()
+}
+
+/// SOAP response to the ContentDirectory GetSortCapabilities action
+pub fn generate_sort_capabilities_response() -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/">
+    <s:Body>
+        <u:GetSortCapabilitiesResponse xmlns:u="urn:schemas-upnp-org:service:ContentDirectory:1">
+            <SortCaps>{}</SortCaps>
+        </u:GetSortCapabilitiesResponse>
+    </s:Body>
+</s:Envelope>"#,
+        SORT_CAPABILITIES
+    )
+}
+
+/// SOAP response to the ContentDirectory GetSearchCapabilities action
+pub fn generate_search_capabilities_response() -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/">
+    <s:Body>
+        <u:GetSearchCapabilitiesResponse xmlns:u="urn:schemas-upnp-org:service:ContentDirectory:1">
+            <SearchCaps>{}</SearchCaps>
+        </u:GetSearchCapabilitiesResponse>
+    </s:Body>
+</s:Envelope>"#,
+        SEARCH_CAPABILITIES
+    )
+}
+
+/// DIDL-Lite properties Browse results can be sorted by, reported by GetSortCapabilities
+pub const SORT_CAPABILITIES: &str = "dc:title,dc:date,upnp:album,res@size";
+
+/// Parse a `SortCriteria` argument like `+dc:title,-dc:date`. Properties that
+/// are not in `SORT_CAPABILITIES` are skipped instead of failing the request.
+pub fn parse_sort_criteria(criteria: &str) -> Vec<SortCriterion> {
+    criteria
+        .split(',')
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+        .filter_map(|token| {
+            let (descending, property) = match token.as_bytes()[0] {
+                b'-' => (true, &token[1..]),
+                b'+' => (false, &token[1..]),
+                _ => (false, token),
+            };
+            let key = match property {
+                "dc:title" => SortKey::Title,
+                "dc:date" => SortKey::Date,
+                "upnp:album" => SortKey::Album,
+                "res@size" => SortKey::Size,
+                _ => {
+                    tracing::debug!("Ignoring unsupported sort property: {}", property);
+                    return None;
+                }
+            };
+            Some(SortCriterion { key, descending })
+        })
+        .collect()
+}
+
+/// ObjectID of the container grouping episodes by show and season
+pub const TV_SHOWS_CONTAINER: &str = "tv";
+
+/// Whether the ObjectID lies within the TV show grouping
+pub fn is_tv_object(object_id: &str) -> bool {
+    object_id == TV_SHOWS_CONTAINER || object_id.starts_with("tv/")
+}
+
+fn didl_container(id: &str, parent_id: &str, title: &str) -> String {
+    didl_counted_container(id, parent_id, title, None)
+}
+
+fn didl_counted_container(id: &str, parent_id: &str, title: &str, child_count: Option<usize>) -> String {
+    let child_count = child_count.map(|n| format!(r#" childCount="{}""#, n)).unwrap_or_default();
+    format!(
+        r#"<container id="{}" parentID="{}"{} restricted="1"><dc:title>{}</dc:title><upnp:class>object.container</upnp:class></container>"#,
+        xml_escape(id),
+        xml_escape(parent_id),
+        child_count,
+        xml_escape(title)
+    )
+}
+
+fn didl_item(file: &MediaFile, parent_id: &str, title: &str, state: &AppState, server_ip: &str) -> String {
+    let file_id = file.id.unwrap_or(0);
+    let url = format!("http://{}:{}/media/{}", server_ip, state.config.server.port, file_id);
+    let mut tags = String::new();
+    if let Some(artist) = &file.artist {
+        tags.push_str(&format!("\n                    <upnp:artist>{}</upnp:artist>", xml_escape(artist)));
+    }
+    if let Some(album) = &file.album {
+        tags.push_str(&format!("\n                    <upnp:album>{}</upnp:album>", xml_escape(album)));
+    }
+    let mut thumbnail_res = String::new();
+    if let Some(art) = state.album_art.lookup(file) {
+        // Pictures too large for any profile are listed without one
+        let profile = art
+            .profile
+            .map(|profile| format!(r#" xmlns:dlna="urn:schemas-dlna-org:metadata-1-0/" dlna:profileID="{}""#, profile))
+            .unwrap_or_default();
+        tags.push_str(&format!(
+            r#"
+                    <upnp:albumArtURI{}>{}</upnp:albumArtURI>"#,
+            profile,
+            xml_escape(&format!("http://{}:{}/albumart/{}", server_ip, state.config.server.port, file_id))
+        ));
+    } else if state.config.media.thumbnails_enabled && crate::media::thumbnail::supports(&file.mime_type) {
+        let thumbnail_url = xml_escape(&format!("http://{}:{}/thumbnail/{}", server_ip, state.config.server.port, file_id));
+        tags.push_str(&format!(
+            r#"
+                    <upnp:albumArtURI xmlns:dlna="urn:schemas-dlna-org:metadata-1-0/" dlna:profileID="JPEG_TN">{}</upnp:albumArtURI>"#,
+            thumbnail_url
+        ));
+        thumbnail_res = format!(
+            r#"
+                    <res protocolInfo="http-get:*:image/jpeg:DLNA.ORG_PN=JPEG_TN">{}</res>"#,
+            thumbnail_url
+        );
+    }
+    // External subtitles as plain resources and as the Samsung caption extension
+    let mut subtitle_res = String::new();
+    if file.mime_type.starts_with("video/") {
+        for subtitle in state.subtitles.for_file(file_id) {
+            let Some(subtitle_id) = subtitle.id else { continue };
+            let subtitle_url = xml_escape(&format!("http://{}:{}/subtitle/{}", server_ip, state.config.server.port, subtitle_id));
+            let caption_type = subtitle.path.extension().and_then(|ext| ext.to_str()).unwrap_or("srt").to_ascii_lowercase();
+            subtitle_res.push_str(&format!(
+                r#"
+                    <res protocolInfo="http-get:*:{}:*">{}</res>"#,
+                xml_escape(&subtitle.mime_type),
+                subtitle_url
+            ));
+            tags.push_str(&format!(
+                r#"
+                    <sec:CaptionInfoEx xmlns:sec="http://www.sec.co.kr/" sec:type="{}">{}</sec:CaptionInfoEx>"#,
+                xml_escape(&caption_type),
+                subtitle_url
+            ));
+        }
+    }
+    let duration = file
+        .duration
+        .map(|d| format!(r#" duration="{}""#, format_duration(d)))
+        .unwrap_or_default();
+    let resolution = match (file.width, file.height) {
+        (Some(width), Some(height)) => format!(r#" resolution="{}x{}""#, width, height),
+        _ => String::new(),
+    };
+    // Transcoded streams have no size known in advance
+    let (protocol_info, size) = match state.transcoder.as_ref().filter(|t| t.needs_transcoding(&file.mime_type)) {
+        Some(transcoder) => (
+            format!(
+                "http-get:*:{}:DLNA.ORG_PN={};DLNA.ORG_OP=00;DLNA.ORG_CI=1",
+                transcoder.mime_type(),
+                transcoder.dlna_profile()
+            ),
+            String::new(),
+        ),
+        None => {
+            let features = match dlna_profile::profile_for(file, state.config.media.dlna_profiles.as_ref()) {
+                Some(profile) => content_features(&file.mime_type, Some(profile), false),
+                None => "*".to_string(),
+            };
+            (format!("http-get:*:{}:{}", file.mime_type, features), format!(r#" size="{}""#, file.size))
+        }
+    };
+    format!(
+        r#"<item id="{id}" parentID="{parent_id}" restricted="1">
+                    <dc:title>{title}</dc:title>
+                    <upnp:class>{upnp_class}</upnp:class>{tags}
+                    <res protocolInfo="{protocol_info}"{size}{duration}{resolution}>{url}</res>{subtitle_res}{thumbnail_res}
+                </item>"#,
+        id = file_id,
+        parent_id = xml_escape(parent_id),
+        title = xml_escape(title),
+        upnp_class = get_upnp_class(&file.mime_type),
+        protocol_info = xml_escape(&protocol_info),
+        url = xml_escape(&url)
+    )
+}
+
+/// Format a duration as `H+:MM:SS.FFF`, the form DIDL-Lite expects for `res@duration`
+fn format_duration(duration: std::time::Duration) -> String {
+    let millis = duration.as_millis();
+    format!(
+        "{}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+fn new_didl() -> String {
+    String::from(r#"<DIDL-Lite xmlns="urn:schemas-upnp-org:metadata-1-0/DIDL-Lite/" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:upnp="urn:schemas-upnp-org:metadata-1-0/upnp/">"#)
+}
+
+/// Wrap a DIDL-Lite document in a Browse SOAP response
+fn browse_envelope(didl: String, number_returned: usize, state: &AppState) -> String {
+    result_envelope("BrowseResponse", didl, number_returned, number_returned, state)
+}
+
+/// SOAP response to the ContentDirectory Search action for one page of `total_matches` results
+pub fn generate_search_response(files: &[MediaFile], total_matches: usize, server_ip: &str, state: &AppState) -> String {
+    let mut didl = new_didl();
+    for file in files {
+        let parent_id = MediaClass::ALL
+            .into_iter()
+            .map(root_container)
+            .find(|(id, _)| file.mime_type.starts_with(&format!("{}/", id)))
+            .map_or("0", |(id, _)| id);
+        didl.push_str(&didl_item(file, parent_id, &file.filename, state, server_ip));
+    }
+    result_envelope("SearchResponse", didl, files.len(), total_matches, state)
+}
+
+fn result_envelope(action: &str, mut didl: String, number_returned: usize, total_matches: usize, state: &AppState) -> String {
+    didl.push_str("</DIDL-Lite>");
+
+    let update_id = state.content_update_id.load(std::sync::atomic::Ordering::Relaxed);
+    
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/">
+    <s:Body>
+        <u:{action} xmlns:u="urn:schemas-upnp-org:service:ContentDirectory:1">
+            <Result>{}</Result>
+            <NumberReturned>{}</NumberReturned>
+            <TotalMatches>{}</TotalMatches>
+            <UpdateID>{}</UpdateID>
+        </u:{action}>
+    </s:Body>
+</s:Envelope>"#,
+        xml_escape(&didl),
+        number_returned,
+        total_matches,
+        update_id
+    )
+}
+
+/// Whether the ObjectID names one of the media class containers below the root
+/// MIME type prefix of the files a Browse of `object_id` can list, or `None`
+/// when the object is not limited to one media class
+pub fn browse_mime_prefix(object_id: &str) -> Option<&'static str> {
+    if is_photos_object(object_id) {
+        return Some(MediaClass::Image.mime_prefix());
+    }
+    MediaClass::ALL
+        .into_iter()
+        .map(|class| class.mime_prefix())
+        .find(|prefix| object_id.starts_with(prefix.trim_end_matches('/')))
+}
+
+fn is_class_root(object_id: &str) -> bool {
+    matches!(object_id.trim_end_matches('/'), "video" | "audio" | "image")
+}
+
+/// ObjectID segment for each monitored directory: its basename, with the
+/// directory's position appended when two directories share a basename
+fn directory_keys(directories: &[MonitoredDirectoryConfig]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    directories
+        .iter()
+        .enumerate()
+        .map(|(index, dir)| {
+            let basename = dir.basename();
+            if seen.insert(basename.clone()) {
+                basename
+            } else {
+                format!("{}~{}", basename, index + 1)
+            }
+        })
+        .collect()
+}
+
+/// Whether the class roots list a container for each monitored directory: with
+/// several directories, or with a single one that has a `display_name`
+fn has_directory_containers(config: &AppConfig) -> bool {
+    let directories = &config.media.directories;
+    directories.len() > 1 || directories.iter().any(|dir| dir.display_name.is_some())
+}
+
+/// Resolve the directory a Browse path is relative to and the path within it.
+/// With directory containers the first path segment selects the directory.
+fn resolve_browse_root<'a>(config: &AppConfig, path_prefix: &'a str) -> (PathBuf, &'a Path) {
+    let directories = &config.media.directories;
+    if has_directory_containers(config) {
+        let (key, rest) = path_prefix.split_once('/').unwrap_or((path_prefix, ""));
+        if let Some(index) = directory_keys(directories).iter().position(|k| k == key) {
+            return (PathBuf::from(&directories[index].path), Path::new(rest));
+        }
+    }
+    (config.get_primary_media_dir(), Path::new(path_prefix))
+}
+
 pub fn generate_browse_response(
     object_id: &str,
     files: &[MediaFile],
+    sort: &[SortCriterion],
     state: &AppState,
 ) -> String {
-    let server_ip = get_server_ip(state);
-    let mut didl = String::from(r#"<DIDL-Lite xmlns="urn:schemas-upnp-org:metadata-1-0/DIDL-Lite/" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:upnp="urn:schemas-upnp-org:metadata-1-0/upnp/">"#);
-    let number_returned;
-
-    if object_id == "0" {
-        // Root directory: show containers for media types
-        didl.push_str(r#"<container id="video" parentID="0" restricted="1"><dc:title>Video</dc:title><upnp:class>object.container</upnp:class></container>"#);
-        didl.push_str(r#"<container id="audio" parentID="0" restricted="1"><dc:title>Music</dc:title><upnp:class>object.container</upnp:class></container>"#);
-        didl.push_str(r#"<container id="image" parentID="0" restricted="1"><dc:title>Pictures</dc:title><upnp:class>object.container</upnp:class></container>"#);
-        number_returned = 3;
+    generate_browse_page(object_id, files, sort, 0, None, &get_server_ip(state), state)
+}
+
+/// Browse response for `object_id` listing `files`. Folder listings are paged
+/// to the `limit` entries after `offset`, as `StartingIndex` and `RequestedCount` ask.
+pub fn generate_browse_page(
+    object_id: &str,
+    files: &[MediaFile],
+    sort: &[SortCriterion],
+    offset: usize,
+    limit: Option<usize>,
+    server_ip: &str,
+    state: &AppState,
+) -> String {
+    let mut didl = new_didl();
+    let mut total_matches = None;
+    let number_returned = if object_id == "0" {
+        // Root directory: show containers for the advertised media types
+        let advertised = &state.config.server.advertised_content;
+        let root_classes: Vec<_> = MediaClass::ALL
+            .into_iter()
+            .filter(|class| advertised.contains(class))
+            .collect();
+        for class in &root_classes {
+            let (id, title) = root_view(*class);
+            didl.push_str(&didl_container(id, "0", title));
+        }
+        let show_tv = state.config.media.tv_show_grouping && advertised.contains(&MediaClass::Video);
+        if show_tv {
+            didl.push_str(&didl_container(TV_SHOWS_CONTAINER, "0", "TV Shows"));
+        }
+        let show_playlists = !state.playlists.is_empty();
+        if show_playlists {
+            didl.push_str(&didl_container(PLAYLISTS_CONTAINER, "0", "Playlists"));
+        }
+        root_classes.len() + usize::from(show_tv) + usize::from(show_playlists)
+    } else if is_photos_object(object_id) {
+        if state.config.server.advertised_content.contains(&MediaClass::Image) {
+            photos_by_date(object_id, files, sort, state, server_ip, &mut didl)
+        } else {
+            0
+        }
+    } else if is_class_root(object_id) && has_directory_containers(&state.config) {
+        // Each monitored directory gets its own container under the class root
+        let directories = &state.config.media.directories;
+        for (dir, key) in directories.iter().zip(directory_keys(directories)) {
+            let container_id = format!("{}/{}", object_id.trim_end_matches('/'), key);
+            didl.push_str(&didl_container(&container_id, object_id, &dir.container_title()));
+        }
+        directories.len()
     } else {
         let mut sub_containers = HashSet::new();
         let mut items = Vec::new();
@@ -148,14 +836,18 @@ pub fn generate_browse_response(
             ("", "")
         };
         
-        let media_root = state.config.get_primary_media_dir();
         // Create a Path from the ObjectID's path part for reliable comparison
-        let browse_path = Path::new(path_prefix_str);
+        let (media_root, browse_path) = resolve_browse_root(&state.config, path_prefix_str);
 
         tracing::info!("Browse request - media_root: {:?}, browse_path: {:?}, media_type_filter: {}", media_root, browse_path, media_type_filter);
         tracing::info!("Total files to filter: {}", files.len());
 
-        for file in files.iter().filter(|f| f.mime_type.starts_with(media_type_filter)) {
+        // Files outside the advertised media classes are hidden from clients
+        let visible_files = files.iter().filter(|f| {
+            f.mime_type.starts_with(media_type_filter) && state.config.server.advertises(&f.mime_type)
+        });
+
+        for file in visible_files {
             tracing::debug!("Processing file: {:?} with mime_type: {}", file.path, file.mime_type);
             
             // Try to get relative path from media_root, handling case sensitivity and path normalization
@@ -204,64 +896,806 @@ pub fn generate_browse_response(
             }
         }
         
-        // Add containers to DIDL
-        let mut sorted_containers: Vec<_> = sub_containers.into_iter().collect();
-        sorted_containers.sort_by_key(|a| a.to_lowercase());
-        for container_name in &sorted_containers {
-            let container_id = format!("{}/{}", object_id.trim_end_matches('/'), container_name);
-            didl.push_str(&format!(
-                r#"<container id="{}" parentID="{}" restricted="1"><dc:title>{}</dc:title><upnp:class>object.container</upnp:class></container>"#,
-                xml_escape(&container_id),
-                xml_escape(object_id),
-                xml_escape(container_name)
-            ));
+        let page = DirectoryPage::from_listing(sub_containers.into_iter().collect(), items, sort, offset, limit);
+        total_matches = Some(page.total);
+        push_directory_page(&mut didl, object_id, &page, state, server_ip)
+    };
+
+    result_envelope("BrowseResponse", didl, number_returned, total_matches.unwrap_or(number_returned), state)
+}
+
+/// Directory and MIME type prefix a Browse of `object_id` lists as a folder, or
+/// `None` for the root, class roots listing directory containers and other views
+pub fn browse_folder(object_id: &str, state: &AppState) -> Option<(PathBuf, &'static str)> {
+    if is_photos_object(object_id) || (is_class_root(object_id) && has_directory_containers(&state.config)) {
+        return None;
+    }
+    let class = MediaClass::ALL.into_iter().find(|class| {
+        let root = class.mime_prefix().trim_end_matches('/');
+        object_id == root || object_id.strip_prefix(root).is_some_and(|rest| rest.starts_with('/'))
+    })?;
+    let class_root = class.mime_prefix().trim_end_matches('/');
+    let path_prefix = object_id[class_root.len()..].trim_start_matches('/');
+    let (media_root, browse_path) = resolve_browse_root(&state.config, path_prefix);
+    Some((media_root.join(browse_path), class.mime_prefix()))
+}
+
+/// Browse response for a folder page read from the database
+pub fn generate_directory_page_response(object_id: &str, page: &DirectoryPage, server_ip: &str, state: &AppState) -> String {
+    let mut didl = new_didl();
+    let number_returned = push_directory_page(&mut didl, object_id, page, state, server_ip);
+    result_envelope("BrowseResponse", didl, number_returned, page.total, state)
+}
+
+/// Append the subdirectories and files of a folder page, returning how many were added
+fn push_directory_page(didl: &mut String, object_id: &str, page: &DirectoryPage, state: &AppState, server_ip: &str) -> usize {
+    for name in &page.subdirectories {
+        let container_id = format!("{}/{}", object_id.trim_end_matches('/'), name);
+        didl.push_str(&didl_container(&container_id, object_id, name));
+    }
+    for file in &page.files {
+        didl.push_str(&didl_item(file, object_id, &file.filename, state, server_ip));
+    }
+    page.subdirectories.len() + page.files.len()
+}
+
+/// Month an image belongs to in the Pictures view, from its modification time
+fn photo_month(file: &MediaFile) -> String {
+    chrono::DateTime::<chrono::Utc>::from(file.modified).format("%Y-%m").to_string()
+}
+
+/// Browse the Pictures view: `photos` lists one container per month, newest
+/// first, plus the folder view; `photos/date/<YYYY-MM>` the images of that month
+fn photos_by_date(
+    object_id: &str,
+    files: &[MediaFile],
+    sort: &[SortCriterion],
+    state: &AppState,
+    server_ip: &str,
+    didl: &mut String,
+) -> usize {
+    let images = files.iter().filter(|f| f.mime_type.starts_with("image/"));
+    let object_id = object_id.trim_end_matches('/');
+    match object_id.strip_prefix(PHOTOS_CONTAINER).unwrap_or_default() {
+        "" | "/date" => {
+            let mut months: BTreeMap<String, usize> = BTreeMap::new();
+            for file in images {
+                *months.entry(photo_month(file)).or_default() += 1;
+            }
+            for (month, count) in months.iter().rev() {
+                let id = format!("{}/date/{}", PHOTOS_CONTAINER, month);
+                didl.push_str(&didl_counted_container(&id, PHOTOS_CONTAINER, month, Some(*count)));
+            }
+            didl.push_str(&didl_container("image", PHOTOS_CONTAINER, "Folders"));
+            months.len() + 1
         }
+        rest => {
+            let Some(month) = rest.strip_prefix("/date/") else {
+                return 0;
+            };
+            let mut items: Vec<&MediaFile> = images.filter(|f| photo_month(f) == month).collect();
+            items.sort_by_key(|f| f.filename.to_lowercase());
+            items.sort_by(|a, b| compare_by_criteria(a, b, sort));
+            for file in &items {
+                didl.push_str(&didl_item(file, object_id, &file.filename, state, server_ip));
+            }
+            items.len()
+        }
+    }
+}
 
-        // Add items to DIDL
-        items.sort_by_key(|f| f.filename.to_lowercase());
-        for file in &items {
-            let file_id = file.id.unwrap_or(0);
-            let url = format!("http://{}:{}/media/{}", server_ip, state.config.server.port, file_id);
-            let upnp_class = get_upnp_class(&file.mime_type);
-            didl.push_str(&format!(
-                r#"<item id="{id}" parentID="{parent_id}" restricted="1">
-                    <dc:title>{title}</dc:title>
-                    <upnp:class>{upnp_class}</upnp:class>
-                    <res protocolInfo="http-get:*:{mime}:*" size="{size}">{url}</res>
-                </item>"#,
-                id = file_id,
-                parent_id = xml_escape(object_id),
-                title = xml_escape(&file.filename),
-                upnp_class = upnp_class,
-                mime = &file.mime_type,
-                size = file.size,
-                url = xml_escape(&url)
-            ));
+/// Root of the Pictures view
+pub const PHOTOS_CONTAINER: &str = "photos";
+
+/// Whether the ObjectID lies within the Pictures view
+pub fn is_photos_object(object_id: &str) -> bool {
+    object_id == PHOTOS_CONTAINER || object_id.starts_with("photos/")
+}
+
+/// Root of the Music view
+pub const MUSIC_CONTAINER: &str = "music";
+
+/// Key standing for a missing artist or album tag; literal `%` is always escaped
+/// in keys, so no tag value maps to it
+const UNTAGGED_KEY: &str = "%";
+
+/// A level of the Music view, decoded from its ObjectID
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MusicObject {
+    /// `music`: all artists
+    Artists,
+    /// `music/artist/<artist>`: the albums of an artist
+    Albums { artist: Option<String> },
+    /// `music/artist/<artist>/<album>`: the tracks of an album
+    Tracks { artist: Option<String>, album: Option<String> },
+}
+
+/// Stable ObjectID segment for an artist or album name
+fn music_key(name: Option<&str>) -> String {
+    match name {
+        Some(name) => name.replace('%', "%25").replace('/', "%2F"),
+        None => UNTAGGED_KEY.to_string(),
+    }
+}
+
+fn music_name(key: &str) -> Option<String> {
+    (key != UNTAGGED_KEY).then(|| key.replace("%2F", "/").replace("%25", "%"))
+}
+
+/// Decode an ObjectID of the Music view; `None` for ObjectIDs outside it
+pub fn parse_music_object(object_id: &str) -> Option<MusicObject> {
+    let object_id = object_id.trim_end_matches('/');
+    let rest = object_id.strip_prefix(MUSIC_CONTAINER)?;
+    if rest.is_empty() || rest == "/artist" {
+        return Some(MusicObject::Artists);
+    }
+    let mut segments = rest.strip_prefix("/artist/")?.split('/');
+    match (segments.next(), segments.next(), segments.next()) {
+        (Some(artist), None, _) => Some(MusicObject::Albums { artist: music_name(artist) }),
+        (Some(artist), Some(album), None) => Some(MusicObject::Tracks {
+            artist: music_name(artist),
+            album: music_name(album),
+        }),
+        _ => None,
+    }
+}
+
+/// Browse the Music view's artist list, followed by the folder view
+pub fn generate_music_artists_response(artists: &[ArtistSummary], sort: &[SortCriterion], state: &AppState) -> String {
+    let mut didl = new_didl();
+    let mut ordered: Vec<&ArtistSummary> = artists.iter().collect();
+    if containers_descending(sort) {
+        ordered.reverse();
+    }
+    for artist in &ordered {
+        let id = format!("{}/artist/{}", MUSIC_CONTAINER, music_key(artist.name.as_deref()));
+        let title = artist.name.as_deref().unwrap_or("Unknown Artist");
+        didl.push_str(&didl_counted_container(&id, MUSIC_CONTAINER, title, Some(artist.album_count)));
+    }
+    didl.push_str(&didl_container("audio", MUSIC_CONTAINER, "Folders"));
+    browse_envelope(didl, ordered.len() + 1, state)
+}
+
+/// Browse the albums of one artist in the Music view
+pub fn generate_music_albums_response(
+    object_id: &str,
+    albums: &[AlbumSummary],
+    sort: &[SortCriterion],
+    state: &AppState,
+) -> String {
+    let object_id = object_id.trim_end_matches('/');
+    let mut didl = new_didl();
+    let mut ordered: Vec<&AlbumSummary> = albums.iter().collect();
+    if containers_descending(sort) {
+        ordered.reverse();
+    }
+    for album in &ordered {
+        let id = format!("{}/{}", object_id, music_key(album.name.as_deref()));
+        let title = album.name.as_deref().unwrap_or("Unknown Album");
+        didl.push_str(&didl_counted_container(&id, object_id, title, Some(album.track_count)));
+    }
+    browse_envelope(didl, ordered.len(), state)
+}
+
+/// Browse the tracks of one album in the Music view
+pub fn generate_music_tracks_response(
+    object_id: &str,
+    tracks: &[MediaFile],
+    sort: &[SortCriterion],
+    server_ip: &str,
+    state: &AppState,
+) -> String {
+    let object_id = object_id.trim_end_matches('/');
+    let mut didl = new_didl();
+    let mut items: Vec<&MediaFile> = tracks.iter().collect();
+    items.sort_by(|a, b| compare_by_criteria(a, b, sort));
+    for file in &items {
+        let title = file.title.as_deref().unwrap_or(&file.filename);
+        didl.push_str(&didl_item(file, object_id, title, state, server_ip));
+    }
+    browse_envelope(didl, items.len(), state)
+}
+
+/// ObjectID of the container listing the M3U and PLS playlists
+pub const PLAYLISTS_CONTAINER: &str = "playlists";
+
+/// Whether the ObjectID lies within the playlists container
+pub fn is_playlists_object(object_id: &str) -> bool {
+    object_id == PLAYLISTS_CONTAINER || object_id.starts_with("playlists/")
+}
+
+/// Database id of the playlist an ObjectID such as `playlists/3` names
+pub fn parse_playlist_id(object_id: &str) -> Option<i64> {
+    object_id.trim_end_matches('/').strip_prefix("playlists/")?.parse().ok()
+}
+
+/// Browse the `playlists` container, one child container per playlist
+pub fn generate_playlists_response(playlists: &[ResolvedPlaylist], sort: &[SortCriterion], state: &AppState) -> String {
+    let mut didl = new_didl();
+    let mut ordered: Vec<&ResolvedPlaylist> = playlists.iter().collect();
+    if containers_descending(sort) {
+        ordered.reverse();
+    }
+    for playlist in &ordered {
+        let id = format!("{}/{}", PLAYLISTS_CONTAINER, playlist.id);
+        didl.push_str(&didl_counted_container(&id, PLAYLISTS_CONTAINER, &playlist.title, Some(playlist.file_ids.len())));
+    }
+    browse_envelope(didl, ordered.len(), state)
+}
+
+/// Browse the items of one playlist, in playlist order unless a sort is requested
+pub fn generate_playlist_items_response(
+    object_id: &str,
+    files: &[MediaFile],
+    sort: &[SortCriterion],
+    server_ip: &str,
+    state: &AppState,
+) -> String {
+    let object_id = object_id.trim_end_matches('/');
+    let mut didl = new_didl();
+    let mut items: Vec<&MediaFile> = files
+        .iter()
+        .filter(|file| state.config.server.advertises(&file.mime_type))
+        .collect();
+    items.sort_by(|a, b| compare_by_criteria(a, b, sort));
+    for file in &items {
+        let title = file.title.as_deref().unwrap_or(&file.filename);
+        didl.push_str(&didl_item(file, object_id, title, state, server_ip));
+    }
+    browse_envelope(didl, items.len(), state)
+}
+
+/// Browse the TV show grouping: `tv` lists shows, `tv/<show>` its seasons and
+/// `tv/<show>/<season>` the episodes in order
+pub fn generate_tv_browse_response(
+    object_id: &str,
+    episodes: &[TvEpisode],
+    sort: &[SortCriterion],
+    server_ip: &str,
+    state: &AppState,
+) -> String {
+    let mut didl = new_didl();
+    let object_id = object_id.trim_end_matches('/');
+    let mut segments = object_id.split('/').skip(1).map(|s| s.parse::<i64>().ok());
+
+    let number_returned = match (segments.next(), segments.next(), segments.next()) {
+        (None, _, _) => {
+            let shows: BTreeMap<i64, &str> = episodes
+                .iter()
+                .map(|e| (e.show_id, e.info.show_title.as_str()))
+                .collect();
+            let mut shows: Vec<_> = shows.into_iter().collect();
+            shows.sort_by_key(|(_, title)| title.to_lowercase());
+            for (show_id, title) in &shows {
+                didl.push_str(&didl_container(&format!("{}/{}", object_id, show_id), object_id, title));
+            }
+            shows.len()
         }
-        
-        number_returned = sorted_containers.len() + items.len();
+        (Some(Some(show_id)), None, _) => {
+            let seasons: BTreeSet<u32> = episodes
+                .iter()
+                .filter(|e| e.show_id == show_id)
+                .map(|e| e.info.season)
+                .collect();
+            for season in &seasons {
+                let title = match season {
+                    0 => "Specials".to_string(),
+                    n => format!("Season {}", n),
+                };
+                didl.push_str(&didl_container(&format!("{}/{}", object_id, season), object_id, &title));
+            }
+            seasons.len()
+        }
+        (Some(Some(show_id)), Some(Some(season)), None) => {
+            let mut items: Vec<_> = episodes
+                .iter()
+                .filter(|e| e.show_id == show_id && i64::from(e.info.season) == season)
+                .filter(|e| state.config.server.advertises(&e.file.mime_type))
+                .collect();
+            items.sort_by_key(|e| (e.info.episode, e.file.filename.to_lowercase()));
+            items.sort_by(|a, b| compare_by_criteria(&a.file, &b.file, sort));
+            for episode in &items {
+                let name = episode.info.title.as_deref().unwrap_or(&episode.file.filename);
+                let title = format!("{:02}. {}", episode.info.episode, name);
+                didl.push_str(&didl_item(&episode.file, object_id, &title, state, server_ip));
+            }
+            items.len()
+        }
+        _ => 0,
+    };
+
+    browse_envelope(didl, number_returned, state)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::AppConfig,
+        database::SqliteDatabase,
+        platform::{OsType, PlatformCapabilities, PlatformInfo},
+        state::{SharedConfig, SharedPlatformInfo},
+        web::eventing::ContentUpdateNotifier,
+    };
+    use std::{
+        collections::HashMap,
+        sync::{atomic::AtomicU32, Arc},
+        time::Duration,
+    };
+    use tempfile::tempdir;
+    use tokio::sync::RwLock;
+
+    async fn create_test_state(media_root: &Path, db_path: PathBuf, files: Vec<MediaFile>) -> AppState {
+        let mut config = AppConfig::default_for_platform();
+        config.media.directories[0].path = media_root.to_string_lossy().to_string();
+        let content_update_id = Arc::new(AtomicU32::new(1));
+
+        let client_filter = crate::platform::network::ClientFilter::from_config(&config.network);
+        let config = Arc::new(config);
+        let mut state = AppState {
+            config: config.clone(),
+            applied_config: SharedConfig::new(config),
+            media_files: Arc::new(RwLock::new(files)),
+            database: Arc::new(SqliteDatabase::new(db_path).await.unwrap()),
+            platform_info: SharedPlatformInfo::new(PlatformInfo {
+                os_type: OsType::current(),
+                version: "test".to_string(),
+                capabilities: PlatformCapabilities::for_current_platform(),
+                network_interfaces: Vec::new(),
+                metadata: HashMap::new(),
+            }),
+            content_updates: ContentUpdateNotifier::new(content_update_id.clone(), Duration::from_millis(10)),
+            scan_lock: Arc::new(tokio::sync::Mutex::new(())),
+            media_lookups: Default::default(),
+            library_snapshot: Default::default(),
+            small_files: Default::default(),
+            didl_cache: Default::default(),
+            metrics: Default::default(),
+            api_tokens: Default::default(),
+            client_filter: Arc::new(client_filter),
+            thumbnails: Default::default(),
+            album_art: Default::default(),
+            event_subscriptions: Default::default(),
+            connection_manager_subscriptions: Default::default(),
+            transcoder: None,
+            subtitles: Default::default(),
+            playlists: Default::default(),
+            icon: Default::default(),
+            started_at: std::time::Instant::now(),
+            boot_id: 1,
+            config_id: 0,
+            scan_errors: Default::default(),
+            content_update_id,
+        };
+        state.update_config_id();
+        state
     }
 
-    didl.push_str("</DIDL-Lite>");
-    let total_matches = number_returned;
+    fn test_file(media_root: &Path, name: &str, mime: &str, id: i64) -> MediaFile {
+        let mut file = MediaFile::new(media_root.join(name), 1024, mime.to_string());
+        file.id = Some(id);
+        file
+    }
 
-    let update_id = state.content_update_id.load(std::sync::atomic::Ordering::Relaxed);
-    
-    format!(
-        r#"<?xml version="1.0" encoding="UTF-8"?>
-<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/">
-    <s:Body>
-        <u:BrowseResponse xmlns:u="urn:schemas-upnp-org:service:ContentDirectory:1">
-            <Result>{}</Result>
-            <NumberReturned>{}</NumberReturned>
-            <TotalMatches>{}</TotalMatches>
-            <UpdateID>{}</UpdateID>
-        </u:BrowseResponse>
-    </s:Body>
-</s:Envelope>"#,
-        xml_escape(&didl),
-        number_returned,
-        total_matches,
-        update_id
-    )
-}
\ No newline at end of file
+    #[tokio::test]
+    async fn test_audio_only_config_hides_video() {
+        let temp_dir = tempdir().unwrap();
+        let media_root = temp_dir.path().join("media");
+        let files = vec![
+            test_file(&media_root, "movie.mp4", "video/mp4", 1),
+            test_file(&media_root, "song.mp3", "audio/mpeg", 2),
+        ];
+        let mut state = create_test_state(&media_root, temp_dir.path().join("test.db"), files.clone()).await;
+        let mut config = (*state.config).clone();
+        config.server.advertised_content = vec![MediaClass::Audio];
+        state.config = Arc::new(config);
+
+        let root = generate_browse_response("0", &files, &[], &state);
+        assert!(root.contains("id=&quot;music&quot;"));
+        assert!(!root.contains("id=&quot;video&quot;"));
+        assert!(!root.contains("id=&quot;photos&quot;"));
+        assert!(root.contains("<NumberReturned>1</NumberReturned>"));
+
+        let video = generate_browse_response("video", &files, &[], &state);
+        assert!(!video.contains("movie.mp4"));
+        assert!(video.contains("<NumberReturned>0</NumberReturned>"));
+
+        let audio = generate_browse_response("audio", &files, &[], &state);
+        assert!(audio.contains("song.mp3"));
+
+        let protocol_info = generate_source_protocol_info(&state);
+        assert!(protocol_info.contains("audio/mpeg"));
+        assert!(!protocol_info.contains("video/"));
+    }
+
+    #[tokio::test]
+    async fn test_protocol_info_lists_source_formats() {
+        let temp_dir = tempdir().unwrap();
+        let media_root = temp_dir.path().join("media");
+        let state = create_test_state(&media_root, temp_dir.path().join("test.db"), Vec::new()).await;
+
+        let protocol_info = generate_source_protocol_info(&state);
+        let entries: Vec<&str> = protocol_info.split(',').collect();
+        assert!(entries.contains(&"http-get:*:video/mp4:DLNA.ORG_PN=AVC_MP4_MP_SD_AAC_MTL"));
+        assert!(entries.contains(&"http-get:*:audio/mpeg:DLNA.ORG_PN=MP3"));
+        assert!(entries.contains(&"http-get:*:video/x-matroska:*"));
+        assert!(entries.iter().all(|entry| entry.starts_with("http-get:*:")));
+
+        let response = generate_protocol_info_response(&state);
+        assert!(response.contains("http-get:*:audio/mpeg:DLNA.ORG_PN=MP3"));
+        assert!(response.contains("<Sink></Sink>"));
+        assert!(generate_description_xml(&state, &server_base_url(&state, None)).contains("urn:schemas-upnp-org:service:ConnectionManager:1"));
+
+        // Configured profiles replace the detected ones
+        let mut config = (*state.config).clone();
+        config.media.dlna_profiles = Some(
+            [
+                ("video/x-matroska".to_string(), "AVC_MKV_HP_HD_AAC_MULT5".to_string()),
+                ("audio/mpeg".to_string(), String::new()),
+            ]
+            .into(),
+        );
+        let state = AppState { config: Arc::new(config), ..state };
+        let protocol_info = generate_source_protocol_info(&state);
+        let entries: Vec<&str> = protocol_info.split(',').collect();
+        assert!(entries.contains(&"http-get:*:video/x-matroska:DLNA.ORG_PN=AVC_MKV_HP_HD_AAC_MULT5"));
+        assert!(entries.contains(&"http-get:*:audio/mpeg:*"));
+    }
+
+    #[tokio::test]
+    async fn test_description_renders_device_identity() {
+        let temp_dir = tempdir().unwrap();
+        let media_root = temp_dir.path().join("media");
+        let mut state = create_test_state(&media_root, temp_dir.path().join("test.db"), Vec::new()).await;
+        let mut config = (*state.config).clone();
+        config.server.manufacturer = "Acme & Sons".to_string();
+        config.server.manufacturer_url = "https://acme.example/".to_string();
+        config.server.model_name = "Acme Media Hub".to_string();
+        config.server.model_number = "MH-200".to_string();
+        config.server.model_url = "https://acme.example/hub".to_string();
+        config.server.serial_number = "SN-12345".to_string();
+        state.config = Arc::new(config);
+
+        let xml = generate_description_xml(&state, &server_base_url(&state, None));
+        assert!(xml.contains("<manufacturer>Acme &amp; Sons</manufacturer>"));
+        assert!(xml.contains("<manufacturerURL>https://acme.example/</manufacturerURL>"));
+        assert!(xml.contains("<modelName>Acme Media Hub</modelName>"));
+        assert!(xml.contains("<modelNumber>MH-200</modelNumber>"));
+        assert!(xml.contains("<modelURL>https://acme.example/hub</modelURL>"));
+        assert!(xml.contains("<serialNumber>SN-12345</serialNumber>"));
+    }
+
+    #[tokio::test]
+    async fn test_description_uses_absolute_service_urls() {
+        let temp_dir = tempdir().unwrap();
+        let media_root = temp_dir.path().join("media");
+        let mut state = create_test_state(&media_root, temp_dir.path().join("test.db"), Vec::new()).await;
+        let mut config = (*state.config).clone();
+        config.server.name = "Living Room".to_string();
+        config.server.interface = "10.0.0.5".to_string();
+        config.server.port = 8200;
+        state.config = Arc::new(config);
+
+        // The address the request arrived on
+        let xml = generate_description_xml(&state, &server_base_url(&state, Some("192.168.1.20:8200")));
+        assert!(xml.contains("<URLBase>http://192.168.1.20:8200/</URLBase>"));
+        assert!(xml.contains("<friendlyName>Living Room</friendlyName>"));
+        assert!(xml.contains(&format!("<UDN>uuid:{}</UDN>", state.config.server.uuid)));
+        for service in ["ContentDirectory", "ConnectionManager"] {
+            assert!(xml.contains(&format!("<serviceId>urn:upnp-org:serviceId:{}</serviceId>", service)));
+            assert!(xml.contains(&format!("<SCPDURL>http://192.168.1.20:8200/{}.xml</SCPDURL>", service)));
+            assert!(xml.contains(&format!("<controlURL>http://192.168.1.20:8200/control/{}</controlURL>", service)));
+            assert!(xml.contains(&format!("<eventSubURL>http://192.168.1.20:8200/event/{}</eventSubURL>", service)));
+        }
+
+        // Without a usable Host header the configured interface and port are used
+        assert_eq!(server_base_url(&state, None), "http://10.0.0.5:8200");
+        assert_eq!(server_base_url(&state, Some("bad\"><host")), "http://10.0.0.5:8200");
+        assert_eq!(server_base_url(&state, Some("media.local")), "http://media.local:8200");
+        assert_eq!(server_base_url(&state, Some("[fe80::1]:8200")), "http://[fe80::1]:8200");
+        let mut config = (*state.config).clone();
+        config.server.interface = "fd00::5".to_string();
+        state.config = Arc::new(config);
+        assert_eq!(server_base_url(&state, None), "http://[fd00::5]:8200");
+        // Item URLs in browse results use the same bracketed form
+        assert_eq!(get_server_ip(&state), "[fd00::5]");
+    }
+
+    #[tokio::test]
+    async fn test_description_lists_device_icon() {
+        let temp_dir = tempdir().unwrap();
+        let media_root = temp_dir.path().join("media");
+        let mut state = create_test_state(&media_root, temp_dir.path().join("test.db"), Vec::new()).await;
+
+        let xml = generate_description_xml(&state, &server_base_url(&state, None));
+        assert!(xml.contains("<mimetype>image/png</mimetype>"));
+        assert!(xml.contains("<width>48</width>"));
+        assert!(xml.contains("<url>/icon.png</url>"));
+
+        // Start of image, then a baseline frame header for 160x120 with three components
+        let jpeg = [0xFF, 0xD8, 0xFF, 0xC0, 0x00, 0x11, 0x08, 0x00, 0x78, 0x00, 0xA0, 0x03];
+        let icon_path = temp_dir.path().join("logo.jpg");
+        std::fs::write(&icon_path, jpeg).unwrap();
+        let mut config = (*state.config).clone();
+        config.server.icon_path = Some(icon_path.to_string_lossy().to_string());
+        state.icon = crate::web::icon::DeviceIcon::from_config(&config.server);
+
+        let xml = generate_description_xml(&state, &server_base_url(&state, None));
+        let icon = xml.split_once("<iconList>").unwrap().1.split_once("</iconList>").unwrap().0;
+        assert!(icon.contains("<mimetype>image/jpeg</mimetype>"));
+        assert!(icon.contains("<width>160</width>"));
+        assert!(icon.contains("<height>120</height>"));
+        assert!(icon.contains("<depth>24</depth>"));
+        assert!(icon.contains("<url>/icon.jpg</url>"));
+    }
+
+    #[tokio::test]
+    async fn test_directory_display_names() {
+        let temp_dir = tempdir().unwrap();
+        let movies = temp_dir.path().join("disk1").join("movies");
+        let shows = temp_dir.path().join("disk2").join("shows");
+        let files = vec![
+            test_file(&movies, "film.mp4", "video/mp4", 1),
+            test_file(&shows, "episode.mkv", "video/x-matroska", 2),
+        ];
+        let mut state = create_test_state(&movies, temp_dir.path().join("test.db"), files.clone()).await;
+
+        let base_config = (*state.config).clone();
+        let with_display_name = |display_name: Option<&str>| {
+            let mut config = base_config.clone();
+            let mut second = config.media.directories[0].clone();
+            second.path = shows.to_string_lossy().to_string();
+            config.media.directories[0].display_name = display_name.map(str::to_string);
+            config.media.directories.push(second);
+            Arc::new(config)
+        };
+
+        state.config = with_display_name(Some("Movies"));
+        let video = generate_browse_response("video", &files, &[], &state);
+        assert!(video.contains("id=&quot;video/movies&quot;"));
+        assert!(video.contains("&lt;dc:title&gt;Movies&lt;/dc:title&gt;"));
+        assert!(video.contains("&lt;dc:title&gt;shows&lt;/dc:title&gt;"));
+        assert!(video.contains("<NumberReturned>2</NumberReturned>"));
+
+        let movies_container = generate_browse_response("video/movies", &files, &[], &state);
+        assert!(movies_container.contains("film.mp4"));
+        assert!(!movies_container.contains("episode.mkv"));
+
+        // The ObjectID comes from the path, so renaming the container keeps it stable
+        state.config = with_display_name(None);
+        let video = generate_browse_response("video", &files, &[], &state);
+        assert!(video.contains("id=&quot;video/movies&quot;"));
+        assert!(video.contains("&lt;dc:title&gt;movies&lt;/dc:title&gt;"));
+
+        // A single directory is listed as its own container once it is given a name
+        let mut config = base_config.clone();
+        config.media.directories[0].display_name = Some("Movies".to_string());
+        state.config = Arc::new(config);
+        let video = generate_browse_response("video", &files, &[], &state);
+        assert!(video.contains("id=&quot;video/movies&quot;"));
+        assert!(video.contains("&lt;dc:title&gt;Movies&lt;/dc:title&gt;"));
+        assert!(video.contains("<NumberReturned>1</NumberReturned>"));
+        assert!(browse_folder("video", &state).is_none());
+        assert_eq!(browse_folder("video/movies", &state), Some((movies.clone(), "video/")));
+        let movies_container = generate_browse_response("video/movies", &files, &[], &state);
+        assert!(movies_container.contains("film.mp4"));
+
+        // Without one its files stay directly under the class root
+        state.config = Arc::new(base_config.clone());
+        let video = generate_browse_response("video", &files, &[], &state);
+        assert!(video.contains("film.mp4"));
+        assert!(!video.contains("id=&quot;video/movies&quot;"));
+    }
+
+    #[tokio::test]
+    async fn test_tv_show_browse_hierarchy() {
+        use crate::tvshows::EpisodeInfo;
+
+        let temp_dir = tempdir().unwrap();
+        let show_dir = temp_dir.path().join("media").join("Show");
+        let mut state = create_test_state(&show_dir, temp_dir.path().join("test.db"), Vec::new()).await;
+        let mut config = (*state.config).clone();
+        config.media.tv_show_grouping = true;
+        state.config = Arc::new(config);
+
+        let episode = |id, season, episode, title: Option<&str>| TvEpisode {
+            show_id: 7,
+            info: EpisodeInfo {
+                show_title: "Law & Order".to_string(),
+                show_path: show_dir.clone(),
+                season,
+                episode,
+                title: title.map(str::to_string),
+            },
+            file: test_file(&show_dir, &format!("s{:02}e{:02}.mkv", season, episode), "video/x-matroska", id),
+        };
+        let episodes = vec![
+            episode(1, 1, 2, None),
+            episode(2, 1, 1, Some("Pilot")),
+            episode(3, 0, 1, None),
+        ];
+
+        let root = generate_browse_response("0", &[], &[], &state);
+        assert!(root.contains("id=&quot;tv&quot;"));
+        assert!(root.contains("<NumberReturned>4</NumberReturned>"));
+
+        let shows = generate_tv_browse_response("tv", &episodes, &[], "127.0.0.1", &state);
+        assert!(shows.contains("id=&quot;tv/7&quot;"));
+        assert!(shows.contains("Law &amp;amp; Order"));
+        assert!(shows.contains("<NumberReturned>1</NumberReturned>"));
+
+        let seasons = generate_tv_browse_response("tv/7", &episodes, &[], "127.0.0.1", &state);
+        assert!(seasons.contains("&lt;dc:title&gt;Specials&lt;/dc:title&gt;"));
+        assert!(seasons.contains("id=&quot;tv/7/1&quot;"));
+        assert!(seasons.contains("<NumberReturned>2</NumberReturned>"));
+
+        let season_one = generate_tv_browse_response("tv/7/1", &episodes, &[], "127.0.0.1", &state);
+        let pilot = season_one.find("01. Pilot").unwrap();
+        let second = season_one.find("02. s01e02.mkv").unwrap();
+        assert!(pilot < second);
+        assert!(season_one.contains("<NumberReturned>2</NumberReturned>"));
+
+        assert!(generate_tv_browse_response("tv/8/1", &episodes, &[], "127.0.0.1", &state).contains("<NumberReturned>0</NumberReturned>"));
+        assert!(generate_tv_browse_response("tv/nope", &episodes, &[], "127.0.0.1", &state).contains("<NumberReturned>0</NumberReturned>"));
+    }
+
+    #[test]
+    fn test_parse_sort_criteria() {
+        assert_eq!(
+            parse_sort_criteria("+dc:title, -dc:date,upnp:album,-res@size"),
+            vec![
+                SortCriterion { key: SortKey::Title, descending: false },
+                SortCriterion { key: SortKey::Date, descending: true },
+                SortCriterion { key: SortKey::Album, descending: false },
+                SortCriterion { key: SortKey::Size, descending: true },
+            ]
+        );
+        // Unsupported properties are dropped, the rest still apply
+        assert_eq!(
+            parse_sort_criteria("-upnp:rating,+dc:title,,-"),
+            vec![SortCriterion { key: SortKey::Title, descending: false }]
+        );
+        assert!(parse_sort_criteria("").is_empty());
+        assert!(generate_sort_capabilities_response().contains(&format!("<SortCaps>{}</SortCaps>", SORT_CAPABILITIES)));
+    }
+
+    #[tokio::test]
+    async fn test_items_report_probed_duration_and_tags() {
+        let temp_dir = tempdir().unwrap();
+        let media_root = temp_dir.path().join("media");
+        let mut song = test_file(&media_root, "song.mp3", "audio/mpeg", 1);
+        song.duration = Some(Duration::from_millis(3_723_045));
+        song.artist = Some("Band & Co".to_string());
+        song.album = Some("Live".to_string());
+        let files = vec![song, test_file(&media_root, "other.mp3", "audio/mpeg", 2)];
+        let state = create_test_state(&media_root, temp_dir.path().join("test.db"), files.clone()).await;
+
+        let response = generate_browse_response("audio", &files, &[], &state);
+        let xml = response.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&amp;", "&");
+        assert!(xml.contains(r#"size="1024" duration="1:02:03.045">"#));
+        assert!(xml.contains("<upnp:artist>Band &amp; Co</upnp:artist>"));
+        assert!(xml.contains("<upnp:album>Live</upnp:album>"));
+        assert_eq!(xml.matches("duration=").count(), 1);
+        assert_eq!(format_duration(Duration::from_secs(59)), "0:00:59.000");
+    }
+
+    #[tokio::test]
+    async fn test_special_characters_in_metadata_produce_well_formed_xml() {
+        let temp_dir = tempdir().unwrap();
+        let media_root = temp_dir.path().join("media");
+        let mut movie = test_file(&media_root, "A & B <test>.mp4", "video/mp4", 1);
+        movie.artist = Some(r#"The "Quoted" Band's"#.to_string());
+        movie.album = Some("Greatest > Hits\u{1}\u{FFFE}".to_string());
+        let clip = test_file(&media_root.join(r#"Tom's "Cuts" <&>"#), "clip.mkv", "video/x-matroska", 2);
+        let files = vec![movie, clip];
+        let state = create_test_state(&media_root, temp_dir.path().join("test.db"), files.clone()).await;
+
+        for response in [generate_browse_response("video", &files, &[], &state), generate_search_response(&files, 2, "127.0.0.1", &state)] {
+            let envelope = roxmltree::Document::parse(&response).unwrap();
+            let result = envelope.descendants().find(|node| node.has_tag_name("Result")).unwrap().text().unwrap();
+            let didl = roxmltree::Document::parse(result).unwrap();
+            let texts = |tag: &str| {
+                didl.descendants()
+                    .filter(|node| node.tag_name().name() == tag)
+                    .filter_map(|node| node.text())
+                    .collect::<Vec<_>>()
+            };
+            assert!(texts("title").contains(&"A & B <test>.mp4"));
+            assert_eq!(texts("artist"), vec![r#"The "Quoted" Band's"#]);
+            assert_eq!(texts("album"), vec!["Greatest > Hits"]);
+        }
+
+        let browse = generate_browse_response("video", &files, &[], &state);
+        let result = roxmltree::Document::parse(&browse).unwrap();
+        let didl = result.descendants().find(|node| node.has_tag_name("Result")).unwrap().text().unwrap().to_string();
+        let didl = roxmltree::Document::parse(&didl).unwrap();
+        let container = didl.descendants().find(|node| node.has_tag_name("container")).unwrap();
+        assert_eq!(container.attribute("id"), Some(r#"video/Tom's "Cuts" <&>"#));
+        assert_eq!(container.descendants().find(|node| node.has_tag_name("title")).unwrap().text(), Some(r#"Tom's "Cuts" <&>"#));
+
+        // Object IDs sent back by clients decode to the same text
+        for text in [r#"Tom's "Cuts" <&>"#, "A & B", "&unknown; & &#xZZ;"] {
+            assert_eq!(xml_unescape(&xml_escape(text)), text);
+        }
+        assert_eq!(xml_unescape("&#65;&#x42;&apos;&amp;lt;"), "AB'&lt;");
+    }
+
+    #[tokio::test]
+    async fn test_items_report_resolution_when_known() {
+        let temp_dir = tempdir().unwrap();
+        let media_root = temp_dir.path().join("media");
+        let mut photo = test_file(&media_root, "photo.jpg", "image/jpeg", 1);
+        photo.width = Some(4032);
+        photo.height = Some(3024);
+        let mut clip = test_file(&media_root, "clip.mp4", "video/mp4", 2);
+        clip.width = Some(1920);
+        let state = create_test_state(&media_root, temp_dir.path().join("test.db"), Vec::new()).await;
+
+        let item = didl_item(&photo, "image", "photo", &state, "127.0.0.1");
+        assert!(item.contains(r#"size="1024" resolution="4032x3024">"#), "{}", item);
+        assert!(item.contains(r#"protocolInfo="http-get:*:image/jpeg:DLNA.ORG_PN=JPEG_LRG;DLNA.ORG_OP=01;DLNA.ORG_CI=0;"#));
+
+        // A missing dimension omits the attribute and the profile
+        let item = didl_item(&clip, "video", "clip", &state, "127.0.0.1");
+        assert!(item.contains(r#"protocolInfo="http-get:*:video/mp4:*" size="1024">"#), "{}", item);
+        assert!(!item.contains("resolution="));
+    }
+
+    #[test]
+    fn test_browse_mime_prefix_follows_object_class() {
+        assert_eq!(browse_mime_prefix("video/movies/2020"), Some("video/"));
+        assert_eq!(browse_mime_prefix("audio"), Some("audio/"));
+        assert_eq!(browse_mime_prefix(PHOTOS_CONTAINER), Some("image/"));
+        assert_eq!(browse_mime_prefix("photos/2024-05"), Some("image/"));
+        assert_eq!(browse_mime_prefix("0"), None);
+    }
+
+    #[tokio::test]
+    async fn test_photos_grouped_by_month() {
+        let temp_dir = tempdir().unwrap();
+        let media_root = temp_dir.path().join("media");
+        let month = |file: &mut MediaFile, secs: u64| file.modified = std::time::UNIX_EPOCH + Duration::from_secs(secs);
+        let mut files = vec![
+            test_file(&media_root, "beach.jpg", "image/jpeg", 1),
+            test_file(&media_root, "party.jpg", "image/jpeg", 2),
+            test_file(&media_root, "snow.png", "image/png", 3),
+            test_file(&media_root, "song.mp3", "audio/mpeg", 4),
+        ];
+        // July 2024, July 2024 and January 2025
+        month(&mut files[0], 1_720_000_000);
+        month(&mut files[1], 1_721_000_000);
+        month(&mut files[2], 1_736_000_000);
+        let state = create_test_state(&media_root, temp_dir.path().join("test.db"), files.clone()).await;
+
+        let photos = generate_browse_response("photos", &files, &[], &state);
+        let newest = photos.find("photos/date/2025-01").unwrap();
+        let older = photos.find("photos/date/2024-07").unwrap();
+        assert!(newest < older);
+        assert!(photos.contains("childCount=&quot;2&quot;"));
+        assert!(photos.contains("id=&quot;image&quot;"));
+        assert!(photos.contains("<NumberReturned>3</NumberReturned>"));
+
+        let july = generate_browse_response("photos/date/2024-07", &files, &[], &state);
+        assert!(july.contains("beach.jpg") && july.contains("party.jpg"));
+        assert!(!july.contains("snow.png"));
+        assert!(july.contains("parentID=&quot;photos/date/2024-07&quot;"));
+    }
+
+    #[test]
+    fn test_music_object_ids_round_trip() {
+        assert_eq!(parse_music_object("music"), Some(MusicObject::Artists));
+        assert_eq!(parse_music_object("musical"), None);
+        assert_eq!(parse_music_object("video"), None);
+
+        let artist = format!("music/artist/{}", music_key(Some("AC/DC 100%")));
+        assert_eq!(artist, "music/artist/AC%2FDC 100%25");
+        assert_eq!(
+            parse_music_object(&artist),
+            Some(MusicObject::Albums { artist: Some("AC/DC 100%".to_string()) })
+        );
+        assert_eq!(
+            parse_music_object(&format!("{}/{}", artist, music_key(None))),
+            Some(MusicObject::Tracks { artist: Some("AC/DC 100%".to_string()), album: None })
+        );
+        assert_eq!(parse_music_object("music/artist/a/b/c"), None);
+    }
+}
@@ -7,7 +7,7 @@ use vuio::platform::{PlatformInfo, OsType, PlatformCapabilities};
 use vuio::platform::network::{NetworkManager, BaseNetworkManager, SsdpConfig};
 use vuio::platform::filesystem::{FileSystemManager, BaseFileSystemManager, create_platform_filesystem_manager};
 use vuio::database::{DatabaseManager, SqliteDatabase, MediaFile};
-use vuio::watcher::{FileSystemWatcher, CrossPlatformWatcher, FileSystemEvent};
+use vuio::watcher::{FileSystemWatcher, CrossPlatformWatcher, FileSystemEvent, RecursiveMode};
 
 // Platform-specific network managers
 #[cfg(target_os = "windows")]
@@ -26,7 +26,7 @@ type PlatformNetworkManager = MacOSNetworkManager;
 type PlatformNetworkManager = LinuxNetworkManager;
 use std::path::{Path, PathBuf};
 use std::time::Duration;
-use tempfile::{TempDir, NamedTempFile};
+use tempfile::TempDir;
 use tokio::time::timeout;
 
 /// Platform-specific network manager tests
@@ -293,12 +293,6 @@ mod network_tests {
         use super::*;
         use vuio::platform::network::LinuxNetworkManager;
         
-        #[tokio::test]
-        async fn test_linux_network_manager_creation() {
-            let manager = LinuxNetworkManager::new();
-            assert!(manager.is_ok());
-        }
-        
         #[tokio::test]
         async fn test_linux_network_namespaces() {
             let manager = LinuxNetworkManager::new();
@@ -1023,7 +1017,7 @@ mod watcher_tests {
         let watcher = CrossPlatformWatcher::new();
         
         // Start watching the directory
-        let result = watcher.start_watching(&[temp_dir.path().to_path_buf()]).await;
+        let result = watcher.start_watching(&[(temp_dir.path().to_path_buf(), RecursiveMode::Recursive)]).await;
         assert!(result.is_ok());
         
         // Verify directory is being watched
@@ -1046,7 +1040,7 @@ mod watcher_tests {
         let mut receiver = watcher.get_event_receiver();
         
         // Start watching
-        watcher.start_watching(&[temp_dir.path().to_path_buf()]).await.unwrap();
+        watcher.start_watching(&[(temp_dir.path().to_path_buf(), RecursiveMode::Recursive)]).await.unwrap();
         
         // Give watcher time to initialize
         sleep(Duration::from_millis(100)).await;
@@ -1094,10 +1088,10 @@ mod watcher_tests {
         let watcher = CrossPlatformWatcher::new();
         
         // Initialize watcher
-        watcher.start_watching(&[temp_dir1.path().to_path_buf()]).await.unwrap();
+        watcher.start_watching(&[(temp_dir1.path().to_path_buf(), RecursiveMode::Recursive)]).await.unwrap();
         
         // Add second directory
-        let add_result = watcher.add_watch_path(temp_dir2.path()).await;
+        let add_result = watcher.add_watch_path(temp_dir2.path(), RecursiveMode::Recursive).await;
         assert!(add_result.is_ok());
         assert!(watcher.is_watching(temp_dir2.path()).await);
         
@@ -1118,7 +1112,7 @@ mod watcher_tests {
         
         // Try to watch non-existent directory
         let nonexistent = PathBuf::from("/nonexistent/directory");
-        let result = watcher.start_watching(&[nonexistent.clone()]).await;
+        let result = watcher.start_watching(&[(nonexistent.clone(), RecursiveMode::Recursive)]).await;
         
         // Should not fail, just log warning
         assert!(result.is_ok());
@@ -1133,7 +1127,7 @@ mod watcher_tests {
         let watcher = CrossPlatformWatcher::new();
         
         let mut receiver = watcher.get_event_receiver();
-        watcher.start_watching(&[temp_dir.path().to_path_buf()]).await.unwrap();
+        watcher.start_watching(&[(temp_dir.path().to_path_buf(), RecursiveMode::Recursive)]).await.unwrap();
         
         sleep(Duration::from_millis(100)).await;
         
@@ -1188,7 +1182,7 @@ mod watcher_tests {
         let watcher = CrossPlatformWatcher::new();
         
         let mut receiver = watcher.get_event_receiver();
-        watcher.start_watching(&[temp_dir.path().to_path_buf()]).await.unwrap();
+        watcher.start_watching(&[(temp_dir.path().to_path_buf(), RecursiveMode::Recursive)]).await.unwrap();
         
         sleep(Duration::from_millis(100)).await;
         
@@ -1221,7 +1215,7 @@ mod watcher_tests {
         let watcher = CrossPlatformWatcher::new();
         
         let mut receiver = watcher.get_event_receiver();
-        watcher.start_watching(&[temp_dir.path().to_path_buf()]).await.unwrap();
+        watcher.start_watching(&[(temp_dir.path().to_path_buf(), RecursiveMode::Recursive)]).await.unwrap();
         
         sleep(Duration::from_millis(100)).await;
         
@@ -0,0 +1,341 @@
+//! Tests of the Postgres backend, built with `--features postgres`.
+//!
+//! They need a server: set `VUIO_TEST_POSTGRES_URL`, e.g.
+//! `postgres://postgres@localhost/postgres`, or they are skipped. Each test
+//! works in a schema of its own, dropped when it finishes.
+#![cfg(feature = "postgres")]
+
+use sqlx::postgres::{PgConnectOptions, PgPool};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use vuio::database::postgres::PostgresDatabase;
+use vuio::database::{
+    migrations, DatabaseManager, MediaField, MediaFile, MediaFilter, Playlist, SortCriterion, SortKey, Subtitle,
+};
+use vuio::tvshows::EpisodeInfo;
+
+/// A database in a fresh schema, removed by `drop_schema`
+struct TestDatabase {
+    db: PostgresDatabase,
+    admin: PgPool,
+    schema: String,
+}
+
+impl TestDatabase {
+    async fn drop_schema(self) {
+        sqlx::query(&format!("DROP SCHEMA {} CASCADE", self.schema))
+            .execute(&self.admin)
+            .await
+            .unwrap();
+    }
+}
+
+async fn test_database() -> Option<TestDatabase> {
+    let Ok(url) = std::env::var("VUIO_TEST_POSTGRES_URL") else {
+        eprintln!("VUIO_TEST_POSTGRES_URL not set; skipping Postgres test");
+        return None;
+    };
+    let admin = PgPool::connect(&url).await.unwrap();
+    let schema = format!("vuio_test_{}", uuid::Uuid::new_v4().simple());
+    sqlx::query(&format!("CREATE SCHEMA {}", schema)).execute(&admin).await.unwrap();
+
+    let options = PgConnectOptions::from_str(&url).unwrap().options([("search_path", schema.as_str())]);
+    let db = PostgresDatabase::connect_with(options).await.unwrap();
+    db.initialize().await.unwrap();
+    Some(TestDatabase { db, admin, schema })
+}
+
+#[tokio::test]
+async fn test_postgres_media_file_crud() {
+    let Some(test) = test_database().await else { return };
+    let db = &test.db;
+    assert_eq!(db.schema_version().await.unwrap(), migrations::latest_version());
+    // Initializing again finds nothing to do
+    db.initialize().await.unwrap();
+
+    let mut media_file = MediaFile::new(PathBuf::from("/test/video.mp4"), 1024, "video/mp4".to_string());
+    media_file.title = Some("Test Video".to_string());
+    media_file.width = Some(1920);
+    media_file.height = Some(1080);
+    let id = db.store_media_file(&media_file).await.unwrap();
+    assert!(id > 0);
+
+    let retrieved = db.get_file_by_path(Path::new("/test/video.mp4")).await.unwrap().unwrap();
+    assert_eq!(retrieved.id, Some(id));
+    assert_eq!(retrieved.filename, "video.mp4");
+    assert_eq!(retrieved.title.as_deref(), Some("Test Video"));
+    assert_eq!((retrieved.width, retrieved.height), (Some(1920), Some(1080)));
+
+    let mut updated_file = retrieved.clone();
+    updated_file.title = Some("Updated Video".to_string());
+    db.update_media_file(&updated_file).await.unwrap();
+    let updated = db.get_file_by_id(id).await.unwrap().unwrap();
+    assert_eq!(updated.title.as_deref(), Some("Updated Video"));
+
+    // Updating a row that is not there yet inserts it
+    let missing = MediaFile::new(PathBuf::from("/test/missing.mkv"), 512, "video/x-matroska".to_string());
+    db.update_media_file(&missing).await.unwrap();
+    let inserted = db.get_file_by_path(Path::new("/test/missing.mkv")).await.unwrap().unwrap();
+    assert_eq!(inserted.size, 512);
+    assert!(db.remove_media_file(Path::new("/test/missing.mkv")).await.unwrap());
+
+    assert!(db.rename_media_file(Path::new("/test/video.mp4"), Path::new("/test/sub/new.mp4")).await.unwrap());
+    let renamed = db.get_file_by_id(id).await.unwrap().unwrap();
+    assert_eq!(renamed.path, PathBuf::from("/test/sub/new.mp4"));
+    assert_eq!(renamed.filename, "new.mp4");
+
+    let stats = db.get_stats().await.unwrap();
+    assert_eq!((stats.total_files, stats.total_size), (1, 1024));
+    assert_eq!(db.get_stats_detailed().await.unwrap().video.file_count, 1);
+
+    assert!(db.remove_media_file(Path::new("/test/sub/new.mp4")).await.unwrap());
+    assert!(db.get_file_by_id(id).await.unwrap().is_none());
+    assert!(!db.remove_media_file(Path::new("/test/sub/new.mp4")).await.unwrap());
+
+    let first_boot = db.next_boot_id().await.unwrap();
+    assert_eq!(db.next_boot_id().await.unwrap(), first_boot + 1);
+
+    test.drop_schema().await;
+}
+
+#[tokio::test]
+async fn test_postgres_batch_store_and_directories() {
+    let Some(test) = test_database().await else { return };
+    let db = &test.db;
+
+    let files: Vec<MediaFile> = [
+        "/media/Movie/a.mp4",
+        "/media/Movie/sub/b.mp4",
+        "/media/MovieExtras/c.mp4",
+        "/media/100%/f.mp4",
+        "/media/1000/g.mp4",
+    ]
+    .iter()
+    .map(|path| MediaFile::new(PathBuf::from(path), 1, "video/mp4".to_string()))
+    .collect();
+    let ids = db.batch_store_media_files(&files).await.unwrap();
+    assert_eq!(ids.len(), files.len());
+
+    // A re-scan updates rows in place and keeps their ids
+    let mut rescanned = files[0].clone();
+    rescanned.size = 4096;
+    assert_eq!(db.batch_store_media_files(&[rescanned]).await.unwrap(), vec![ids[0]]);
+    assert_eq!(db.get_file_by_id(ids[0]).await.unwrap().unwrap().size, 4096);
+
+    // Batched lookups skip what is not indexed
+    let paths = vec![files[2].path.clone(), PathBuf::from("/media/missing.mp4")];
+    let by_path = db.get_file_ids_by_paths(&paths).await.unwrap();
+    assert_eq!(by_path.into_iter().collect::<Vec<_>>(), vec![(files[2].path.clone(), ids[2])]);
+    let by_id = db.get_files_by_ids(&[ids[3], -1]).await.unwrap();
+    assert_eq!(by_id.into_iter().map(|f| f.path).collect::<Vec<_>>(), vec![files[3].path.clone()]);
+
+    let names = |files: Vec<MediaFile>| files.into_iter().map(|f| f.filename).collect::<Vec<_>>();
+    assert_eq!(names(db.get_files_in_directory(Path::new("/media/Movie")).await.unwrap()), vec!["a.mp4", "b.mp4"]);
+    assert_eq!(names(db.get_files_in_directory(Path::new("/media/100%")).await.unwrap()), vec!["f.mp4"]);
+
+    assert_eq!(db.remove_files_in_directory(Path::new("/media/Movie")).await.unwrap(), 2);
+    let existing = vec![PathBuf::from("/media/MovieExtras/c.mp4")];
+    assert_eq!(db.cleanup_missing_files(&existing).await.unwrap(), 2);
+    assert_eq!(names(db.get_all_media_files().await.unwrap()), vec!["c.mp4"]);
+
+    test.drop_schema().await;
+}
+
+#[tokio::test]
+async fn test_postgres_text_search_matches_partial_words() {
+    let Some(test) = test_database().await else { return };
+    let db = &test.db;
+
+    let track = |name: &str, title: &str, artist: &str, album: &str| {
+        let mut file = MediaFile::new(PathBuf::from(format!("/music/{}", name)), 1024, "audio/mpeg".to_string());
+        file.title = Some(title.to_string());
+        file.artist = Some(artist.to_string());
+        file.album = Some(album.to_string());
+        file
+    };
+    db.store_media_file(&track("01.mp3", "Yesterday", "The Beatles", "Help!")).await.unwrap();
+    db.store_media_file(&track("02.mp3", "Help!", "The Beatles", "Help!")).await.unwrap();
+    db.store_media_file(&track("03.mp3", "Angie", "The Rolling Stones", "Goats Head Soup")).await.unwrap();
+    db.store_media_file(&MediaFile::new(PathBuf::from("/videos/holiday_beach.mp4"), 1024, "video/mp4".to_string()))
+        .await
+        .unwrap();
+
+    let titles = |files: Vec<MediaFile>| files.into_iter().map(|f| f.filename).collect::<Vec<_>>();
+    assert_eq!(titles(db.search_text("beat", 10).await.unwrap()).len(), 2);
+    assert_eq!(titles(db.search_text("help", 10).await.unwrap()), vec!["02.mp3", "01.mp3"]);
+    assert_eq!(titles(db.search_text("roll ston", 10).await.unwrap()), vec!["03.mp3"]);
+    assert_eq!(titles(db.search_text("BEACH", 10).await.unwrap()), vec!["holiday_beach.mp4"]);
+    assert!(db.search_text("beatles angie", 10).await.unwrap().is_empty());
+    assert_eq!(db.search_text("the", 1).await.unwrap().len(), 1);
+    // tsquery syntax in the query is treated as plain words
+    assert!(db.search_text("&|!", 10).await.unwrap().is_empty());
+    assert_eq!(titles(db.search_text("goat* !soup'", 10).await.unwrap()), vec!["03.mp3"]);
+
+    // The index follows updates and deletes
+    let mut angie = db.get_file_by_path(Path::new("/music/03.mp3")).await.unwrap().unwrap();
+    angie.title = Some("Wild Horses".to_string());
+    db.update_media_file(&angie).await.unwrap();
+    assert!(db.search_text("angie", 10).await.unwrap().is_empty());
+    assert_eq!(titles(db.search_text("horse", 10).await.unwrap()), vec!["03.mp3"]);
+    db.remove_media_file(Path::new("/music/03.mp3")).await.unwrap();
+    assert!(db.search_text("horse", 10).await.unwrap().is_empty());
+
+    test.drop_schema().await;
+}
+
+#[tokio::test]
+async fn test_postgres_search_and_music_queries() {
+    let Some(test) = test_database().await else { return };
+    let db = &test.db;
+
+    let track = |name: &str, artist: Option<&str>, album: Option<&str>| {
+        let mut file = MediaFile::new(PathBuf::from(format!("/music/{}", name)), 1024, "audio/mpeg".to_string());
+        file.artist = artist.map(str::to_string);
+        file.album = album.map(str::to_string);
+        file
+    };
+    for file in [
+        track("01 Help.mp3", Some("The Beatles"), Some("Help!")),
+        track("02 Yesterday.mp3", Some("The Beatles"), Some("Help!")),
+        track("03 Penny Lane.mp3", Some("The Beatles"), None),
+        track("04 Angie.mp3", Some("Rolling Stones"), Some("Goats Head Soup")),
+        track("05 100%_pure.mp3", None, None),
+        MediaFile::new(PathBuf::from("/videos/beatles.mp4"), 1024, "video/mp4".to_string()),
+    ] {
+        db.store_media_file(&file).await.unwrap();
+    }
+
+    let names = |files: Vec<MediaFile>| files.into_iter().map(|f| f.filename).collect::<Vec<_>>();
+    let beatles = MediaFilter::Contains(MediaField::Artist, "BEATLES".to_string());
+    let (page, total) = db.search_media_files(&beatles, 1, Some(1)).await.unwrap();
+    assert_eq!((names(page), total), (vec!["02 Yesterday.mp3".to_string()], 3));
+    let audio_or_help = MediaFilter::Or(
+        Box::new(MediaFilter::Equals(MediaField::Album, "help!".to_string())),
+        Box::new(MediaFilter::Not(Box::new(MediaFilter::StartsWith(MediaField::MimeType, "audio/".to_string())))),
+    );
+    let (all, total) = db.search_media_files(&audio_or_help, 0, None).await.unwrap();
+    assert_eq!(total, 3);
+    assert_eq!(names(all), vec!["01 Help.mp3", "02 Yesterday.mp3", "beatles.mp4"]);
+    // LIKE wildcards match literally
+    let literal = MediaFilter::Contains(MediaField::Filename, "0%_".to_string());
+    assert_eq!(db.search_media_files(&literal, 0, None).await.unwrap().1, 1);
+
+    let artists = db.get_music_artists().await.unwrap();
+    let summary: Vec<_> = artists.iter().map(|a| (a.name.as_deref(), a.album_count)).collect();
+    assert_eq!(summary, vec![(Some("Rolling Stones"), 1), (Some("The Beatles"), 2), (None, 1)]);
+    let albums = db.get_artist_albums(Some("The Beatles")).await.unwrap();
+    let summary: Vec<_> = albums.iter().map(|a| (a.name.as_deref(), a.track_count)).collect();
+    assert_eq!(summary, vec![(Some("Help!"), 2), (None, 1)]);
+    assert_eq!(names(db.get_album_tracks(None, None).await.unwrap()), vec!["05 100%_pure.mp3"]);
+    assert_eq!(
+        names(db.get_album_tracks(Some("The Beatles"), Some("Help!")).await.unwrap()),
+        vec!["01 Help.mp3", "02 Yesterday.mp3"]
+    );
+
+    // Unlike SQLite, ILIKE folds non-ASCII letters too
+    db.store_media_file(&track("06 La Vie en rose.mp3", Some("Édith Piaf"), None)).await.unwrap();
+    let piaf = MediaFilter::Contains(MediaField::Artist, "ÉDITH".to_string());
+    assert_eq!(db.search_media_files(&piaf, 0, None).await.unwrap().1, 1);
+
+    test.drop_schema().await;
+}
+
+#[tokio::test]
+async fn test_postgres_browse_directory_pages() {
+    let Some(test) = test_database().await else { return };
+    let db = &test.db;
+
+    for (path, mime, size) in [
+        ("/media/b.mp4", "video/mp4", 30),
+        ("/media/A.mkv", "video/x-matroska", 10),
+        ("/media/c.mp4", "video/mp4", 20),
+        ("/media/Shows/s1/e1.mp4", "video/mp4", 5),
+        ("/media/extras/clip.mp4", "video/mp4", 5),
+        ("/media/song.mp3", "audio/mpeg", 5),
+        ("/media-other/d.mp4", "video/mp4", 5),
+    ] {
+        db.store_media_file(&MediaFile::new(PathBuf::from(path), size, mime.to_string())).await.unwrap();
+    }
+    let dir = Path::new("/media");
+    let browse = |offset: usize, limit: Option<usize>, criteria: Vec<SortCriterion>| async move {
+        let page = db.browse_directory(dir, "video/", &criteria, offset, limit).await.unwrap();
+        let mut names = page.subdirectories.clone();
+        names.extend(page.files.iter().map(|f| f.filename.clone()));
+        (names, page.total)
+    };
+
+    let (all, total) = browse(0, None, Vec::new()).await;
+    assert_eq!(all, vec!["extras", "Shows", "A.mkv", "b.mp4", "c.mp4"]);
+    assert_eq!(total, 5);
+    assert_eq!(browse(1, Some(2), Vec::new()).await.0, vec!["Shows", "A.mkv"]);
+    assert_eq!(browse(4, Some(2), Vec::new()).await.0, vec!["c.mp4"]);
+    let by_size = vec![SortCriterion { key: SortKey::Size, descending: true }];
+    assert_eq!(browse(1, Some(3), by_size).await.0, vec!["Shows", "b.mp4", "c.mp4"]);
+
+    test.drop_schema().await;
+}
+
+#[tokio::test]
+async fn test_postgres_related_records_follow_their_files() {
+    let Some(test) = test_database().await else { return };
+    let db = &test.db;
+
+    let video = MediaFile::new(PathBuf::from("/tv/Show/S01E02.mkv"), 1, "video/x-matroska".to_string());
+    let video_id = db.store_media_file(&video).await.unwrap();
+    let episode = EpisodeInfo {
+        show_title: "Show".to_string(),
+        show_path: PathBuf::from("/tv/Show"),
+        season: 1,
+        episode: 2,
+        title: Some("Second".to_string()),
+    };
+    db.replace_tv_episodes(&[(video_id, episode.clone()), (video_id + 100, episode)]).await.unwrap();
+    let episodes = db.get_tv_episodes().await.unwrap();
+    assert_eq!(episodes.len(), 1);
+    assert_eq!(episodes[0].file.id, Some(video_id));
+    assert_eq!(episodes[0].info.episode, 2);
+
+    let subtitle = Subtitle {
+        id: None,
+        media_file_id: video_id,
+        path: PathBuf::from("/tv/Show/S01E02.en.srt"),
+        language: Some("en".to_string()),
+        mime_type: "text/srt".to_string(),
+    };
+    db.replace_subtitles(video_id, &[subtitle]).await.unwrap();
+    let stored = db.get_subtitles(video_id).await.unwrap().remove(0);
+    assert_eq!(stored.language.as_deref(), Some("en"));
+    let by_id = db.get_subtitle_by_id(stored.id.unwrap()).await.unwrap().unwrap();
+    assert_eq!(by_id.path, stored.path);
+    assert!(db.get_subtitle_by_id(stored.id.unwrap() + 1).await.unwrap().is_none());
+
+    let playlist = Playlist {
+        id: None,
+        path: PathBuf::from("/tv/list.m3u"),
+        title: "List".to_string(),
+        entries: vec![PathBuf::from("/tv/Show/S01E02.mkv"), PathBuf::from("/tv/missing.mkv")],
+    };
+    let playlist_id = db.store_playlist(&playlist).await.unwrap();
+    assert_eq!(db.store_playlist(&playlist).await.unwrap(), playlist_id);
+    assert_eq!(db.get_playlists().await.unwrap()[0].entries, playlist.entries);
+    assert!(db.remove_playlist(Path::new("/tv/list.m3u")).await.unwrap());
+
+    // Removing the video removes its episode and subtitles
+    db.remove_media_file(&video.path).await.unwrap();
+    assert!(db.get_tv_episodes().await.unwrap().is_empty());
+    assert!(db.get_all_subtitles().await.unwrap().is_empty());
+
+    assert_eq!(db.get_system_update_id().await.unwrap(), None);
+    db.set_system_update_id(65).await.unwrap();
+    db.set_system_update_id(129).await.unwrap();
+    assert_eq!(db.get_system_update_id().await.unwrap(), Some(129));
+
+    let health = db.check_and_repair().await.unwrap();
+    assert!(health.is_healthy);
+    assert!(db.create_backup(Path::new("/tmp/vuio.db")).await.is_err());
+    assert_eq!(db.cleanup_invalid_records().await.unwrap(), 0);
+    assert_eq!(db.remove_duplicates().await.unwrap(), 0);
+    db.vacuum().await.unwrap();
+
+    test.drop_schema().await;
+}